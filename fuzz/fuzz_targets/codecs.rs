@@ -0,0 +1,127 @@
+//! Decodes random bytes with every codec of heed-types, the bytes read from a database can
+//! be anything and decoding them must never panic. The items that are decoded are encoded
+//! again and must decode to the same item.
+
+#![no_main]
+
+use std::fmt::Debug;
+
+use heed::byteorder::BigEndian;
+use heed::types::*;
+use heed::zerocopy::{U32, U64};
+use heed::{BytesDecode, BytesEncode};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Color {
+    Red = 1,
+    Green = 2,
+    Blue = 300,
+}
+
+impl Discriminant for Color {
+    type Repr = u16;
+
+    fn discriminant(&self) -> u16 {
+        *self as u16
+    }
+
+    fn from_discriminant(discriminant: u16) -> Option<Color> {
+        match discriminant {
+            1 => Some(Color::Red),
+            2 => Some(Color::Green),
+            300 => Some(Color::Blue),
+            _ => None,
+        }
+    }
+}
+
+fuzz_target!(|bytes: &[u8]| {
+    roundtrip::<Unit>(bytes);
+    roundtrip::<OwnedType<u64>>(bytes);
+    roundtrip::<OwnedType<[u16; 3]>>(bytes);
+    roundtrip::<UnalignedType<U64<BigEndian>>>(bytes);
+    roundtrip::<FixedSlice<u8, 4>>(bytes);
+    roundtrip::<FixedSlice<u64, 3>>(bytes);
+    roundtrip::<DiscriminantOf<Color>>(bytes);
+    roundtrip::<SerdeBincode<(u32, String, Vec<i64>, Option<bool>)>>(bytes);
+
+    roundtrip_str::<Str>(bytes);
+    roundtrip_str::<FixedStr<8>>(bytes);
+    roundtrip_str::<ZeroTerminatedStr>(bytes);
+    roundtrip_str::<LenPrefixedStr>(bytes);
+
+    roundtrip_slice::<ByteSlice, _>(bytes);
+    roundtrip_slice::<OwnedSlice<u32>, _>(bytes);
+    roundtrip_slice::<UnalignedSlice<U32<BigEndian>>, _>(bytes);
+
+    decode::<DecodeIgnore>(bytes);
+    decode::<CowType<u64>>(bytes);
+    decode::<CowSlice<u64>>(bytes);
+    decode::<Checksummed<Str>>(bytes);
+    decode::<Checksummed<OwnedType<u64>>>(bytes);
+    decode::<SerdeJson<serde_json::Value>>(bytes);
+
+    key_parts(bytes);
+});
+
+fn decode<C: BytesDecode>(bytes: &[u8]) {
+    let _ = C::bytes_decode(bytes);
+}
+
+fn roundtrip<C>(bytes: &[u8])
+where
+    C: BytesDecode + for<'a> BytesEncode<'a, EItem = <C as BytesDecode>::DItem>,
+    C::DItem: PartialEq + Debug,
+{
+    if let Some(item) = C::bytes_decode(bytes) {
+        let encoded = C::bytes_encode(&item).expect("a decoded item can be encoded");
+        assert_eq!(C::bytes_decode(&encoded), Some(item));
+    }
+}
+
+fn roundtrip_str<C>(bytes: &[u8])
+where
+    C: BytesDecode<DItem = String> + for<'a> BytesEncode<'a, EItem = str>,
+{
+    if let Some(item) = C::bytes_decode(bytes) {
+        let encoded = C::bytes_encode(&item).expect("a decoded string can be encoded");
+        assert_eq!(C::bytes_decode(&encoded), Some(item));
+    }
+}
+
+fn roundtrip_slice<C, T>(bytes: &[u8])
+where
+    C: BytesDecode<DItem = Vec<T>> + for<'a> BytesEncode<'a, EItem = [T]>,
+    T: PartialEq + Debug + 'static,
+{
+    if let Some(item) = C::bytes_decode(bytes) {
+        let encoded = C::bytes_encode(&item).expect("a decoded slice can be encoded");
+        assert_eq!(C::bytes_decode(&encoded), Some(item));
+    }
+}
+
+/// Decodes the bytes as a composite key, the fields that are decoded
+/// are encoded as the exact bytes they were read from.
+fn key_parts(bytes: &[u8]) {
+    fn field<T: KeyPart + PartialEq + Debug>(rest: &mut &[u8]) -> Option<()> {
+        let before = *rest;
+        let item = T::decode_key(rest)?;
+        let mut encoded = Vec::new();
+        item.encode_key(&mut encoded);
+        assert_eq!(encoded, before[..before.len() - rest.len()]);
+        Some(())
+    }
+
+    let mut rest = bytes;
+    let _ = (|| {
+        field::<u8>(&mut rest)?;
+        field::<i32>(&mut rest)?;
+        field::<bool>(&mut rest)?;
+        field::<char>(&mut rest)?;
+        field::<String>(&mut rest)?;
+        field::<i128>(&mut rest)?;
+        field::<Vec<u8>>(&mut rest)?;
+        field::<u64>(&mut rest)
+    })();
+}
@@ -0,0 +1,172 @@
+//! Iterates over random ranges and prefixes of random keys, in both directions, and compares
+//! the keys returned by the stores with the keys the ranges contain. The keys extending the
+//! bounds, or ending with 0 or 255 bytes, are where the translation of the bounds into the
+//! keys the cursors seek can go wrong.
+
+#![no_main]
+
+use std::collections::BTreeSet;
+use std::ops::{Bound, RangeBounds};
+
+use heed::store::rck::raw::DBType;
+use heed::store::{Store, Transaction};
+use heed::types::{ByteSlice, Unit};
+use heed::{Database, Env, EnvOpenOptions};
+use libfuzzer_sys::arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use tempfile::TempDir;
+
+/// LMDB doesn't accept empty keys nor keys longer than 511 bytes.
+const MAX_LMDB_KEY_SIZE: usize = 511;
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    keys: Vec<Vec<u8>>,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    prefix: Vec<u8>,
+}
+
+struct Stores {
+    _dir: TempDir,
+    env: Env,
+    db: Database<ByteSlice, Unit>,
+    rocks: DBType,
+}
+
+impl Stores {
+    fn open() -> Stores {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path().join("lmdb")).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database(&mut wtxn, None).unwrap();
+        wtxn.commit().unwrap();
+
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let rocks = DBType::open(&opts, dir.path().join("rocks")).unwrap();
+
+        Stores { _dir: dir, env, db, rocks }
+    }
+}
+
+thread_local! {
+    static STORES: Stores = Stores::open();
+}
+
+fuzz_target!(|input: Input| {
+    let Input { keys, start, end, prefix } = input;
+    let keys: BTreeSet<_> = keys.into_iter().filter(|key| lmdb_key(key)).collect();
+    let range = (bound_as_slice(&start), bound_as_slice(&end));
+    let expected: Vec<_> = keys.iter().filter(|key| range.contains(&key[..])).cloned().collect();
+
+    STORES.with(|stores| {
+        if lmdb_bound(range.0) && lmdb_bound(range.1) {
+            check_lmdb(stores, &keys, range, &expected);
+        }
+        if lmdb_key(&prefix) {
+            check_lmdb_prefix(stores, &keys, &prefix);
+        }
+        if !inverted(range) {
+            check_rocks(stores, &keys, range, &expected);
+        }
+    });
+});
+
+fn check_lmdb(
+    stores: &Stores,
+    keys: &BTreeSet<Vec<u8>>,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+    expected: &[Vec<u8>],
+) {
+    let mut wtxn = stores.env.write_txn().unwrap();
+    stores.db.clear(&mut wtxn).unwrap();
+    for key in keys {
+        stores.db.put(&mut wtxn, key, &()).unwrap();
+    }
+
+    let found: Vec<_> =
+        stores.db.range(&wtxn, &range).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert_eq!(found, expected, "range {:?}", range);
+
+    let found: Vec<_> =
+        stores.db.rev_range(&wtxn, &range).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert!(found.iter().eq(expected.iter().rev()), "rev range {:?}", range);
+
+    wtxn.abort().unwrap();
+}
+
+fn check_lmdb_prefix(stores: &Stores, keys: &BTreeSet<Vec<u8>>, prefix: &[u8]) {
+    let mut wtxn = stores.env.write_txn().unwrap();
+    stores.db.clear(&mut wtxn).unwrap();
+    for key in keys {
+        stores.db.put(&mut wtxn, key, &()).unwrap();
+    }
+
+    let expected: Vec<_> = keys.iter().filter(|key| key.starts_with(prefix)).collect();
+
+    let found: Vec<_> =
+        stores.db.prefix_iter(&wtxn, prefix).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert!(found.iter().eq(expected.iter().copied()), "prefix {:?}", prefix);
+
+    let found: Vec<_> =
+        stores.db.rev_prefix_iter(&wtxn, prefix).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert!(found.iter().eq(expected.iter().rev().copied()), "rev prefix {:?}", prefix);
+
+    wtxn.abort().unwrap();
+}
+
+fn check_rocks(
+    stores: &Stores,
+    keys: &BTreeSet<Vec<u8>>,
+    range: (Bound<&[u8]>, Bound<&[u8]>),
+    expected: &[Vec<u8>],
+) {
+    let table = stores.rocks.typed::<ByteSlice, Unit>("keys", &Default::default()).unwrap();
+    let mut wtx = stores.rocks.wtx().unwrap();
+    table.clear(&mut wtx).unwrap();
+    for key in keys {
+        table.put(&mut wtx, key, &()).unwrap();
+    }
+    wtx.commit().unwrap();
+
+    let rtx = stores.rocks.rtx().unwrap();
+    let found: Vec<_> = table.range(&rtx, &range).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert_eq!(found, expected, "range {:?}", range);
+
+    let found: Vec<_> =
+        table.rev_range(&rtx, &range).unwrap().map(|entry| entry.unwrap().0).collect();
+    assert!(found.iter().eq(expected.iter().rev()), "rev range {:?}", range);
+}
+
+fn lmdb_key(key: &[u8]) -> bool {
+    !key.is_empty() && key.len() <= MAX_LMDB_KEY_SIZE
+}
+
+fn lmdb_bound(bound: Bound<&[u8]>) -> bool {
+    match bound {
+        Bound::Included(key) | Bound::Excluded(key) => lmdb_key(key),
+        Bound::Unbounded => true,
+    }
+}
+
+/// Whether the start of the range is after its end, RocksDB doesn't
+/// accept a lower bound greater than the upper bound.
+fn inverted((start, end): (Bound<&[u8]>, Bound<&[u8]>)) -> bool {
+    match (start, end) {
+        (Bound::Included(start), Bound::Included(end)) => start > end,
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) => start >= end,
+        _ => false,
+    }
+}
+
+fn bound_as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(bytes) => Bound::Included(bytes),
+        Bound::Excluded(bytes) => Bound::Excluded(bytes),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
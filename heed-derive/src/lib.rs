@@ -0,0 +1,154 @@
+//! Derive macros implementing `BytesEncode` and `BytesDecode` on structs, the struct is then
+//! its own codec: a `Database<MyKey, MyValue>` stores `MyKey`s and `MyValue`s.
+//!
+//! They are re-exported by heed when its `derive` feature is enabled, the generated code refers
+//! to the `heed` crate.
+//!
+//! - `#[derive(HeedKey)]` encodes the fields one after the other, in declaration order, each
+//!   with its [`KeyPart`] encoding: the keys sort by their first field, then by the second...
+//! - `#[derive(HeedValue)]` delegates to a serde format chosen with `#[heed(format = "...")]`,
+//!   `bincode`, the default, or `json`.
+//!
+//! [`KeyPart`]: https://docs.rs/heed-types/latest/heed_types/trait.KeyPart.html
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index, LitStr};
+
+/// Implement an order-preserving key codec on a struct whose fields implement `KeyPart`.
+#[proc_macro_derive(HeedKey)]
+pub fn derive_heed_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    heed_key(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implement a value codec on a struct delegating to a serde format.
+#[proc_macro_derive(HeedValue, attributes(heed))]
+pub fn derive_heed_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    heed_value(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn heed_key(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    no_generics(input)?;
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => return Err(syn::Error::new_spanned(input, "HeedKey can only be derived on structs")),
+    };
+
+    let name = &input.ident;
+    let members: Vec<_> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let ident = &field.ident;
+                quote!(#ident)
+            })
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| {
+                let index = Index::from(i);
+                quote!(#index)
+            })
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+    let types = fields.iter().map(|field| &field.ty);
+
+    let decode = match fields {
+        Fields::Named(_) => {
+            quote!(#name { #(#members: ::heed::types::KeyPart::decode_key(&mut bytes)?,)* })
+        }
+        Fields::Unnamed(_) => {
+            let reads =
+                types.map(|ty| quote!(<#ty as ::heed::types::KeyPart>::decode_key(&mut bytes)?));
+            quote!(#name(#(#reads,)*))
+        }
+        Fields::Unit => quote!(#name),
+    };
+
+    Ok(quote! {
+        impl<'a> ::heed::BytesEncode<'a> for #name {
+            type EItem = #name;
+
+            fn bytes_encode(item: &'a #name) -> ::std::option::Option<::std::borrow::Cow<'a, [u8]>> {
+                let mut bytes = ::std::vec::Vec::new();
+                #(::heed::types::KeyPart::encode_key(&item.#members, &mut bytes);)*
+                ::std::option::Option::Some(::std::borrow::Cow::Owned(bytes))
+            }
+        }
+
+        impl ::heed::BytesDecode for #name {
+            type DItem = #name;
+
+            fn bytes_decode(bytes: &[u8]) -> ::std::option::Option<#name> {
+                let mut bytes = bytes;
+                let item = #decode;
+                if bytes.is_empty() {
+                    ::std::option::Option::Some(item)
+                } else {
+                    ::std::option::Option::None
+                }
+            }
+        }
+    })
+}
+
+fn heed_value(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    no_generics(input)?;
+    if let Data::Union(_) = input.data {
+        return Err(syn::Error::new_spanned(input, "HeedValue can't be derived on unions"));
+    }
+
+    let mut format = LitStr::new("bincode", proc_macro2::Span::call_site());
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("heed")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                format = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `format`"))
+            }
+        })?;
+    }
+
+    let codec = match format.value().as_str() {
+        "bincode" => quote!(::heed::types::SerdeBincode),
+        "json" => quote!(::heed::types::SerdeJson),
+        _ => return Err(syn::Error::new_spanned(format, "expected \"bincode\" or \"json\"")),
+    };
+
+    let name = &input.ident;
+    Ok(quote! {
+        impl<'a> ::heed::BytesEncode<'a> for #name {
+            type EItem = #name;
+
+            fn bytes_encode(item: &'a #name) -> ::std::option::Option<::std::borrow::Cow<'a, [u8]>> {
+                <#codec<#name> as ::heed::BytesEncode<'a>>::bytes_encode(item)
+            }
+        }
+
+        impl ::heed::BytesDecode for #name {
+            type DItem = #name;
+
+            fn bytes_decode(bytes: &[u8]) -> ::std::option::Option<#name> {
+                <#codec<#name> as ::heed::BytesDecode>::bytes_decode(bytes)
+            }
+        }
+    })
+}
+
+/// The decoded items must be `'static` and the codecs are the types themselves,
+/// generic types are not supported.
+fn no_generics(input: &DeriveInput) -> syn::Result<()> {
+    if input.generics.params.is_empty() {
+        Ok(())
+    } else {
+        Err(syn::Error::new_spanned(
+            &input.generics,
+            "heed codecs can't be derived on generic types",
+        ))
+    }
+}
@@ -0,0 +1,378 @@
+//! A read-only gRPC server exposing the tables of a [`Store`], described by `proto/heed.proto`.
+//!
+//! It lets tools running next to an application read its live data without mapping the
+//! environment themselves. The server never opens write transactions on its own behalf,
+//! the tables are opened without being created and a missing table is answered with
+//! `NOT_FOUND`.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use heed::EnvOpenOptions;
+//! use heed_server::StoreServer;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let env = EnvOpenOptions::new().max_dbs(16).open("target/heed.mdb")?;
+//!
+//! tonic::transport::Server::builder()
+//!     .add_service(StoreServer::new(Arc::new(env)))
+//!     .serve("127.0.0.1:50051".parse()?)
+//!     .await?;
+//! # Ok(()) }
+//! ```
+
+pub mod proto;
+
+use std::convert::{Infallible, TryFrom};
+use std::fmt;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use heed::store::{Store, Table};
+use heed_types::ByteSlice;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::codegen::{empty_body, http, Body, BoxFuture, Service, StdError};
+use tonic::server::{Grpc, NamedService, ServerStreamingService, UnaryService};
+use tonic::{Code, Request, Response, Status};
+
+use crate::proto::{Entry, GetRequest, GetResponse, ScanRequest, StatRequest, StatResponse};
+
+/// The number of entries of a scan read ahead of the client.
+const SCAN_BUFFER: usize = 64;
+
+/// The gRPC `heed.Store` service, reading the tables of the store.
+pub struct StoreServer<S> {
+    store: Arc<S>,
+}
+
+impl<S: Store> StoreServer<S> {
+    pub fn new(store: Arc<S>) -> StoreServer<S> {
+        StoreServer { store }
+    }
+}
+
+impl<S> Clone for StoreServer<S> {
+    fn clone(&self) -> Self {
+        StoreServer { store: self.store.clone() }
+    }
+}
+
+impl<S> NamedService for StoreServer<S> {
+    const NAME: &'static str = "heed.Store";
+}
+
+impl<S, B> Service<http::Request<B>> for StoreServer<S>
+where
+    S: Store,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let store = self.store.clone();
+        match req.uri().path() {
+            "/heed.Store/Get" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(GetService(store), req).await)
+            }),
+            "/heed.Store/Scan" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.server_streaming(ScanService(store), req).await)
+            }),
+            "/heed.Store/Stat" => Box::pin(async move {
+                let mut grpc = Grpc::new(tonic::codec::ProstCodec::default());
+                Ok(grpc.unary(StatService(store), req).await)
+            }),
+            _ => Box::pin(async move {
+                let response = http::Response::builder()
+                    .status(200)
+                    .header("grpc-status", (Code::Unimplemented as i32).to_string())
+                    .header(http::header::CONTENT_TYPE, "application/grpc")
+                    .body(empty_body())
+                    .unwrap();
+                Ok(response)
+            }),
+        }
+    }
+}
+
+struct GetService<S>(Arc<S>);
+
+impl<S: Store> UnaryService<GetRequest> for GetService<S> {
+    type Response = GetResponse;
+    type Future = BoxFuture<Response<GetResponse>, Status>;
+
+    fn call(&mut self, request: Request<GetRequest>) -> Self::Future {
+        let store = self.0.clone();
+        let GetRequest { table, key } = request.into_inner();
+
+        Box::pin(blocking(move || -> Result<_, ReadError<S::Error>> {
+            let table = open_table(&*store, &table)?;
+            let rtx = store.rtx()?;
+            let value = table.get::<ByteSlice, ByteSlice>(&rtx, &key)?;
+            Ok(GetResponse { value })
+        }))
+    }
+}
+
+struct StatService<S>(Arc<S>);
+
+impl<S: Store> UnaryService<StatRequest> for StatService<S> {
+    type Response = StatResponse;
+    type Future = BoxFuture<Response<StatResponse>, Status>;
+
+    fn call(&mut self, request: Request<StatRequest>) -> Self::Future {
+        let store = self.0.clone();
+        let StatRequest { table } = request.into_inner();
+
+        Box::pin(blocking(move || -> Result<_, ReadError<S::Error>> {
+            let table = open_table(&*store, &table)?;
+            let rtx = store.rtx()?;
+            let entries = table.len(&rtx)? as u64;
+            let estimated_entries = table.estimated_len(&rtx)? as u64;
+            Ok(StatResponse { entries, estimated_entries })
+        }))
+    }
+}
+
+struct ScanService<S>(Arc<S>);
+
+impl<S: Store> ServerStreamingService<ScanRequest> for ScanService<S> {
+    type Response = Entry;
+    type ResponseStream = ReceiverStream<Result<Entry, Status>>;
+    type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: Request<ScanRequest>) -> Self::Future {
+        let store = self.0.clone();
+        let request = request.into_inner();
+        let (sender, receiver) = mpsc::channel(SCAN_BUFFER);
+
+        // The read transaction lives on the blocking thread for the whole scan,
+        // the entries are sent as the client consumes them.
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = scan(&*store, &request, &sender) {
+                let _ = sender.blocking_send(Err(status(e)));
+            }
+        });
+
+        Box::pin(async move { Ok(Response::new(ReceiverStream::new(receiver))) })
+    }
+}
+
+/// Send the entries of the scanned range until it ends or the client goes away.
+fn scan<S: Store>(
+    store: &S,
+    request: &ScanRequest,
+    sender: &mpsc::Sender<Result<Entry, Status>>,
+) -> Result<(), ReadError<S::Error>> {
+    let table = open_table(store, &request.table)?;
+    let rtx = store.rtx()?;
+
+    let range = scan_bounds(request);
+    let limit = match request.limit {
+        0 => usize::MAX,
+        limit => usize::try_from(limit).unwrap_or(usize::MAX),
+    };
+
+    let send = |entry: Result<(Vec<u8>, Vec<u8>), S::Error>| -> Result<bool, S::Error> {
+        let (key, value) = entry?;
+        Ok(sender.blocking_send(Ok(Entry { key, value })).is_ok())
+    };
+
+    if request.reverse {
+        for entry in table.rev_range::<ByteSlice, ByteSlice, _>(&rtx, &range)?.take(limit) {
+            if !send(entry)? {
+                break;
+            }
+        }
+    } else {
+        for entry in table.range::<ByteSlice, ByteSlice, _>(&rtx, &range)?.take(limit) {
+            if !send(entry)? {
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn scan_bounds(request: &ScanRequest) -> (Bound<&[u8]>, Bound<&[u8]>) {
+    let start = match &request.start {
+        Some(start) => Bound::Included(&start[..]),
+        None => Bound::Unbounded,
+    };
+    let end = match &request.end {
+        Some(end) => Bound::Excluded(&end[..]),
+        None => Bound::Unbounded,
+    };
+    (start, end)
+}
+
+/// The errors of a read, a missing table is told apart from the errors of the store.
+#[derive(Debug)]
+enum ReadError<E> {
+    Store(E),
+    MissingTable(String),
+}
+
+impl<E> From<E> for ReadError<E> {
+    fn from(error: E) -> ReadError<E> {
+        ReadError::Store(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ReadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Store(e) => e.fmt(f),
+            ReadError::MissingTable(name) => write!(f, "the table {:?} doesn't exist", name),
+        }
+    }
+}
+
+/// Opens the table without creating it.
+fn open_table<'s, S: Store>(store: &'s S, name: &str) -> Result<S::Table<'s>, ReadError<S::Error>> {
+    store.open_table(name)?.ok_or_else(|| ReadError::MissingTable(name.to_string()))
+}
+
+/// Run a read of the store on the blocking threads of the runtime.
+async fn blocking<T, E, F>(read: F) -> Result<Response<T>, Status>
+where
+    T: Send + 'static,
+    E: fmt::Display + Send + 'static,
+    F: FnOnce() -> Result<T, ReadError<E>> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(read).await {
+        Ok(Ok(response)) => Ok(Response::new(response)),
+        Ok(Err(e)) => Err(status(e)),
+        Err(e) => Err(Status::internal(e.to_string())),
+    }
+}
+
+fn status<E: fmt::Display>(error: ReadError<E>) -> Status {
+    match error {
+        ReadError::MissingTable(_) => Status::not_found(error.to_string()),
+        ReadError::Store(_) => Status::internal(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_bounds_of_request() {
+        let request =
+            ScanRequest { start: Some(b"a".to_vec()), end: None, ..ScanRequest::default() };
+        assert_eq!(scan_bounds(&request), (Bound::Included(&b"a"[..]), Bound::Unbounded));
+
+        let request = ScanRequest { end: Some(b"b".to_vec()), ..ScanRequest::default() };
+        assert_eq!(scan_bounds(&request), (Bound::Unbounded, Bound::Excluded(&b"b"[..])));
+    }
+
+    #[cfg(feature = "lmdb")]
+    fn store() -> (tempfile::TempDir, Arc<heed::Env>) {
+        use heed::types::Str;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = heed::EnvOpenOptions::new().max_dbs(4).open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_poly_database(&mut wtxn, Some("users")).unwrap();
+        for (key, value) in [("alice", "1"), ("bob", "2"), ("carol", "3")].iter() {
+            db.put::<_, Str, Str>(&mut wtxn, key, value).unwrap();
+        }
+        wtxn.commit().unwrap();
+        (dir, Arc::new(env))
+    }
+
+    #[cfg(feature = "lmdb")]
+    async fn scanned(
+        store: &Arc<heed::Env>,
+        request: ScanRequest,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, Status> {
+        let response = ScanService(store.clone()).call(Request::new(request)).await?;
+        let mut receiver = response.into_inner().into_inner();
+        let mut entries = Vec::new();
+        while let Some(entry) = receiver.recv().await {
+            let Entry { key, value } = entry?;
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "lmdb")]
+    async fn missing_tables_are_not_found() {
+        let (_dir, store) = store();
+
+        let request = GetRequest { table: "missing".to_string(), key: b"alice".to_vec() };
+        let error = GetService(store.clone()).call(Request::new(request)).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+
+        let request = StatRequest { table: "missing".to_string() };
+        let error = StatService(store.clone()).call(Request::new(request)).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+
+        let request = ScanRequest { table: "missing".to_string(), ..ScanRequest::default() };
+        let error = scanned(&store, request).await.unwrap_err();
+        assert_eq!(error.code(), Code::NotFound);
+
+        // the tables read aren't created
+        assert_eq!(store.table_names().unwrap(), vec!["users".to_string()]);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "lmdb")]
+    async fn get_and_stat_read_the_table() {
+        let (_dir, store) = store();
+
+        let request = GetRequest { table: "users".to_string(), key: b"bob".to_vec() };
+        let response = GetService(store.clone()).call(Request::new(request)).await.unwrap();
+        assert_eq!(response.into_inner().value, Some(b"2".to_vec()));
+
+        let request = GetRequest { table: "users".to_string(), key: b"dave".to_vec() };
+        let response = GetService(store.clone()).call(Request::new(request)).await.unwrap();
+        assert_eq!(response.into_inner().value, None);
+
+        let request = StatRequest { table: "users".to_string() };
+        let response = StatService(store.clone()).call(Request::new(request)).await.unwrap();
+        assert_eq!(response.into_inner().entries, 3);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "lmdb")]
+    async fn scans_follow_the_bounds_and_the_limit() {
+        let (_dir, store) = store();
+
+        let request = ScanRequest {
+            table: "users".to_string(),
+            start: Some(b"b".to_vec()),
+            ..ScanRequest::default()
+        };
+        let keys: Vec<_> = scanned(&store, request).await.unwrap();
+        assert_eq!(
+            keys,
+            vec![(b"bob".to_vec(), b"2".to_vec()), (b"carol".to_vec(), b"3".to_vec())]
+        );
+
+        let request = ScanRequest {
+            table: "users".to_string(),
+            end: Some(b"carol".to_vec()),
+            reverse: true,
+            limit: 1,
+            ..ScanRequest::default()
+        };
+        let keys: Vec<_> = scanned(&store, request).await.unwrap();
+        assert_eq!(keys, vec![(b"bob".to_vec(), b"2".to_vec())]);
+    }
+}
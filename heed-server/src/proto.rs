@@ -0,0 +1,57 @@
+//! The messages of `proto/heed.proto`.
+//!
+//! They are declared by hand rather than generated at build time so that building the server
+//! doesn't require `protoc`, they must be kept in sync with the `.proto` file.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetRequest {
+    #[prost(string, tag = "1")]
+    pub table: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub key: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetResponse {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub value: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ScanRequest {
+    #[prost(string, tag = "1")]
+    pub table: String,
+    /// Included, the range starts at the first key if unset.
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub start: Option<Vec<u8>>,
+    /// Excluded, the range ends after the last key if unset.
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub end: Option<Vec<u8>>,
+    #[prost(bool, tag = "4")]
+    pub reverse: bool,
+    /// The maximum number of entries, zero means no limit.
+    #[prost(uint64, tag = "5")]
+    pub limit: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Entry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatRequest {
+    #[prost(string, tag = "1")]
+    pub table: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct StatResponse {
+    #[prost(uint64, tag = "1")]
+    pub entries: u64,
+    #[prost(uint64, tag = "2")]
+    pub estimated_entries: u64,
+}
@@ -6,6 +6,12 @@ pub trait BytesEncode<'a> {
     fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>>;
 }
 
+/// A codec that appends the bytes of an item to a buffer given by the caller, the buffer
+/// is reused between the items instead of allocating the bytes of every item.
+pub trait BytesEncodeInto<'a>: BytesEncode<'a> {
+    fn bytes_encode_into(item: &'a Self::EItem, out: &mut Vec<u8>) -> Option<()>;
+}
+
 pub trait BytesDecode {
     type DItem: 'static;
 
@@ -0,0 +1,105 @@
+use std::borrow::Cow;
+use std::{error, fmt};
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+/// Describes a type encoded by `C` followed by the CRC-32C of the encoded bytes.
+///
+/// The checksum is verified on decoding, a value whose bytes were altered on disk is decoded
+/// as an `Err(Corrupted)` rather than failing like a value that can't be decoded by `C`.
+///
+/// ```
+/// use heed_traits::{BytesDecode, BytesEncode};
+/// use heed_types::{Checksummed, Corrupted, Str};
+///
+/// // The checksum is the CRC-32C of the encoded bytes, in little endian.
+/// let bytes = Checksummed::<Str>::bytes_encode("123456789").unwrap();
+/// assert_eq!(bytes[9..], 0xE306_9283u32.to_le_bytes());
+/// assert_eq!(Checksummed::<Str>::bytes_decode(&bytes), Some(Ok("123456789".to_string())));
+///
+/// // A flipped bit or a truncated value is corrupted.
+/// let mut flipped = bytes.to_vec();
+/// flipped[3] ^= 0x10;
+/// assert_eq!(Checksummed::<Str>::bytes_decode(&flipped), Some(Err(Corrupted)));
+/// assert_eq!(Checksummed::<Str>::bytes_decode(&bytes[..12]), Some(Err(Corrupted)));
+/// assert_eq!(Checksummed::<Str>::bytes_decode(&bytes[..3]), Some(Err(Corrupted)));
+/// ```
+pub struct Checksummed<C>(std::marker::PhantomData<C>);
+
+/// The checksum of a value doesn't match its bytes, they were altered after being written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corrupted;
+
+impl fmt::Display for Corrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the checksum of the value doesn't match, it is corrupted")
+    }
+}
+
+impl error::Error for Corrupted {}
+
+impl<'a, C> BytesEncode<'a> for Checksummed<C>
+where
+    C: BytesEncode<'a>,
+{
+    type EItem = C::EItem;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = C::bytes_encode(item)?.into_owned();
+        let checksum = crc32c(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl<C> BytesDecode for Checksummed<C>
+where
+    C: BytesDecode,
+{
+    type DItem = Result<C::DItem, Corrupted>;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        if bytes.len() < 4 {
+            return Some(Err(Corrupted));
+        }
+
+        let (payload, checksum) = bytes.split_at(bytes.len() - 4);
+        let mut stored = [0; 4];
+        stored.copy_from_slice(checksum);
+        if u32::from_le_bytes(stored) != crc32c(payload) {
+            return Some(Err(Corrupted));
+        }
+
+        C::bytes_decode(payload).map(Ok)
+    }
+}
+
+unsafe impl<C> Send for Checksummed<C> {}
+
+unsafe impl<C> Sync for Checksummed<C> {}
+
+/// The lookup table of the reflected Castagnoli polynomial.
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82F6_3B78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32c(bytes: &[u8]) -> u32 {
+    let crc = bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    });
+    !crc
+}
@@ -0,0 +1,39 @@
+use std::borrow::Cow;
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::KeyPart;
+
+/// Describes a key made of fields implementing [`KeyPart`], like a tuple, encoded one after
+/// the other: the keys sort by their first field, then by the second...
+///
+/// The keys whose first fields are given are bounded by [`prefix_range`](crate::prefix_range).
+pub struct Composite<T>(std::marker::PhantomData<T>);
+
+impl<'a, T: KeyPart + 'a> BytesEncode<'a> for Composite<T> {
+    type EItem = T;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = Vec::new();
+        item.encode_key(&mut bytes);
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl<T: KeyPart + 'static> BytesDecode for Composite<T> {
+    type DItem = T;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        let mut bytes = bytes;
+        let item = T::decode_key(&mut bytes)?;
+        if bytes.is_empty() {
+            Some(item)
+        } else {
+            None
+        }
+    }
+}
+
+unsafe impl<T> Send for Composite<T> {}
+
+unsafe impl<T> Sync for Composite<T> {}
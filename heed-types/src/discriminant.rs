@@ -0,0 +1,76 @@
+use std::borrow::Cow;
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::KeyPart;
+
+/// A fieldless enum whose variants are mapped to stable numbers, to be stored with
+/// [`DiscriminantOf`].
+///
+/// The numbers are what is stored, they must never be reused for other variants.
+///
+/// ```
+/// use heed_types::Discriminant;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Color {
+///     Red = 1,
+///     Green = 2,
+/// }
+///
+/// impl Discriminant for Color {
+///     type Repr = u8;
+///
+///     fn discriminant(&self) -> u8 {
+///         *self as u8
+///     }
+///
+///     fn from_discriminant(discriminant: u8) -> Option<Color> {
+///         match discriminant {
+///             1 => Some(Color::Red),
+///             2 => Some(Color::Green),
+///             _ => None,
+///         }
+///     }
+/// }
+/// ```
+pub trait Discriminant: Sized {
+    /// The integer the variants are stored as, usually `u8` or `u16`.
+    type Repr: KeyPart;
+
+    fn discriminant(&self) -> Self::Repr;
+
+    /// The variant of a stored number, `None` if there is none.
+    fn from_discriminant(discriminant: Self::Repr) -> Option<Self>;
+}
+
+/// Describes a fieldless enum stored as the big-endian number of its variant,
+/// the keys sort like the numbers.
+pub struct DiscriminantOf<E>(std::marker::PhantomData<E>);
+
+impl<'a, E: Discriminant + 'a> BytesEncode<'a> for DiscriminantOf<E> {
+    type EItem = E;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = Vec::new();
+        item.discriminant().encode_key(&mut bytes);
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl<E: Discriminant + 'static> BytesDecode for DiscriminantOf<E> {
+    type DItem = E;
+
+    fn bytes_decode(mut bytes: &[u8]) -> Option<Self::DItem> {
+        let discriminant = E::Repr::decode_key(&mut bytes)?;
+        if bytes.is_empty() {
+            E::from_discriminant(discriminant)
+        } else {
+            None
+        }
+    }
+}
+
+unsafe impl<E> Send for DiscriminantOf<E> {}
+
+unsafe impl<E> Sync for DiscriminantOf<E> {}
@@ -1,9 +1,34 @@
 use std::borrow::Cow;
-use std::ptr;
+use std::{mem, ptr};
 
 use heed_traits::{BytesDecode, BytesEncode};
 use zerocopy::{AsBytes, FromBytes, LayoutVerified};
 
+/// Describes an array of `N` elements stored as exactly `N` times the size of `T` bytes.
+///
+/// Decoding bytes of any other length returns `None`. The bytes that are not
+/// [memory aligned] for `T` are copied into an aligned array.
+///
+/// ```
+/// use heed_types::FixedSlice;
+/// use heed_traits::{BytesDecode, BytesEncode};
+///
+/// use zerocopy::AsBytes;
+///
+/// let bytes = FixedSlice::<u64, 2>::bytes_encode(&[1, 2]).unwrap();
+/// assert_eq!(FixedSlice::<u64, 2>::bytes_decode(&bytes), Some([1, 2]));
+///
+/// // The same bytes one byte after an aligned address, misaligned for an `u64`.
+/// let mut buffer = [0u64; 3];
+/// let shifted = &mut buffer.as_bytes_mut()[1..17];
+/// shifted.copy_from_slice(&bytes);
+/// assert_eq!(FixedSlice::<u64, 2>::bytes_decode(shifted), Some([1, 2]));
+///
+/// assert_eq!(FixedSlice::<u64, 2>::bytes_decode(&bytes[..8]), None);
+/// assert_eq!(FixedSlice::<u64, 2>::bytes_decode(&[0; 17]), None);
+/// ```
+///
+/// [memory aligned]: std::mem::align_of()
 pub struct FixedSlice<T, const N: usize>(std::marker::PhantomData<T>);
 
 impl<'a, T: 'a, const N: usize> BytesEncode<'a> for FixedSlice<T, N>
@@ -19,22 +44,26 @@ where
 
 impl<T: 'static, const N: usize> BytesDecode for FixedSlice<T, N>
 where
-    [T; N]: FromBytes + Default + Copy,
+    [T; N]: FromBytes + Copy,
 {
     type DItem = [T; N];
 
     fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        if bytes.len() != mem::size_of::<[T; N]>() {
+            return None;
+        }
+
         match LayoutVerified::<_, [T; N]>::new(bytes) {
-            Some(v) => Some(v.into_ref().clone()),
+            Some(layout) => Some(*layout.into_ref()),
             None => {
-                assert_eq!(bytes.len(), std::mem::size_of::<[T; N]>());
-                let mut out = <[T; N] as Default>::default();
+                // The length is valid, it is the alignment that is wrong.
+                let mut data = mem::MaybeUninit::<[T; N]>::uninit();
 
                 unsafe {
-                    let dst = &mut out as *mut [T; N] as *mut u8;
+                    let dst = data.as_mut_ptr() as *mut u8;
                     ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+                    Some(data.assume_init())
                 }
-                Some(out)
             }
         }
     }
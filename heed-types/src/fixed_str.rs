@@ -0,0 +1,77 @@
+use std::borrow::Cow;
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+/// Describes an [`str`] stored in exactly `N` bytes, padded with nul bytes.
+///
+/// The keys are fixed-width and sort like the strings, e.g. ticker symbols or country codes.
+/// A string longer than `N` bytes can't be encoded, the trailing nul bytes are trimmed on
+/// decoding: a string that ends with nul bytes doesn't roundtrip.
+pub struct FixedStr<const N: usize>;
+
+impl<const N: usize> BytesEncode<'_> for FixedStr<N> {
+    type EItem = str;
+
+    fn bytes_encode(item: &Self::EItem) -> Option<Cow<[u8]>> {
+        if item.len() > N {
+            return None;
+        }
+
+        let mut bytes = vec![0; N];
+        bytes[..item.len()].copy_from_slice(item.as_bytes());
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl<const N: usize> BytesDecode for FixedStr<N> {
+    type DItem = String;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        if bytes.len() != N {
+            return None;
+        }
+        let len = bytes.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        std::str::from_utf8(&bytes[..len]).ok().map(|v| v.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_strings_are_padded() {
+        let bytes = FixedStr::<4>::bytes_encode("ab").unwrap();
+        assert_eq!(&bytes[..], b"ab\0\0");
+        assert_eq!(FixedStr::<4>::bytes_decode(&bytes).as_deref(), Some("ab"));
+    }
+
+    #[test]
+    fn strings_of_n_bytes_fit() {
+        let bytes = FixedStr::<4>::bytes_encode("abcd").unwrap();
+        assert_eq!(&bytes[..], b"abcd");
+        assert_eq!(FixedStr::<4>::bytes_decode(&bytes).as_deref(), Some("abcd"));
+
+        let bytes = FixedStr::<4>::bytes_encode("\u{e9}\u{e8}").unwrap();
+        assert_eq!(FixedStr::<4>::bytes_decode(&bytes).as_deref(), Some("\u{e9}\u{e8}"));
+    }
+
+    #[test]
+    fn longer_strings_arent_encoded() {
+        assert!(FixedStr::<4>::bytes_encode("abcde").is_none());
+        assert!(FixedStr::<4>::bytes_encode("ab\u{e9}\u{e8}").is_none());
+    }
+
+    #[test]
+    fn encoded_strings_sort_like_the_strings() {
+        let mut strings = vec!["b", "", "abcd", "ba", "a", "ab", "\u{e9}", "abc", "B"];
+        let mut encoded: Vec<_> =
+            strings.iter().map(|s| FixedStr::<4>::bytes_encode(s).unwrap().into_owned()).collect();
+        strings.sort_unstable();
+        encoded.sort_unstable();
+
+        let decoded: Vec<_> =
+            encoded.iter().map(|bytes| FixedStr::<4>::bytes_decode(bytes).unwrap()).collect();
+        assert_eq!(decoded, strings);
+    }
+}
@@ -0,0 +1,133 @@
+use std::convert::TryInto;
+use std::mem::size_of;
+
+use crate::LenPrefixedStr;
+
+/// A field of a composite key, encoded so that the keys sort like their fields.
+///
+/// The integers are big-endian, with the sign bit flipped for the signed ones, the strings
+/// and byte vectors are prefixed by their length as a big-endian `u32`, like with
+/// [`LenPrefixedStr`]: they sort by length first. It is what the fields of a
+/// `#[derive(HeedKey)]` struct must implement, the tuples of fields are stored
+/// with [`Composite`](crate::Composite).
+pub trait KeyPart: Sized {
+    /// Append the encoded field to `out`.
+    fn encode_key(&self, out: &mut Vec<u8>);
+
+    /// Read the field from the front of `bytes` and advance it past the field.
+    fn decode_key(bytes: &mut &[u8]) -> Option<Self>;
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if bytes.len() < len {
+        return None;
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Some(head)
+}
+
+macro_rules! impl_key_part_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl KeyPart for $t {
+                fn encode_key(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+                    take(bytes, size_of::<$t>())?.try_into().ok().map(<$t>::from_be_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_key_part_unsigned!(u8, u16, u32, u64, u128);
+
+macro_rules! impl_key_part_signed {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl KeyPart for $t {
+                fn encode_key(&self, out: &mut Vec<u8>) {
+                    (*self as $u ^ !(<$u>::MAX >> 1)).encode_key(out);
+                }
+
+                fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+                    <$u>::decode_key(bytes).map(|u| (u ^ !(<$u>::MAX >> 1)) as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_key_part_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+
+impl KeyPart for bool {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+        match u8::decode_key(bytes)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl KeyPart for char {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        u32::from(*self).encode_key(out);
+    }
+
+    fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+        u32::decode_key(bytes).and_then(std::char::from_u32)
+    }
+}
+
+impl KeyPart for Vec<u8> {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode_key(out);
+        out.extend_from_slice(self);
+    }
+
+    fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+        let len = u32::decode_key(bytes)? as usize;
+        take(bytes, len).map(<[u8]>::to_vec)
+    }
+}
+
+impl KeyPart for String {
+    fn encode_key(&self, out: &mut Vec<u8>) {
+        LenPrefixedStr::encode_into(self, out).expect("keys are smaller than 4 GiB");
+    }
+
+    fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+        let (item, rest) = LenPrefixedStr::decode_prefix(bytes)?;
+        *bytes = rest;
+        Some(item.to_string())
+    }
+}
+
+macro_rules! impl_key_part_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: KeyPart),+> KeyPart for ($($t,)+) {
+            #[allow(non_snake_case)]
+            fn encode_key(&self, out: &mut Vec<u8>) {
+                let ($($t,)+) = self;
+                $($t.encode_key(out);)+
+            }
+
+            fn decode_key(bytes: &mut &[u8]) -> Option<Self> {
+                Some(($($t::decode_key(bytes)?,)+))
+            }
+        }
+    };
+}
+
+impl_key_part_tuple!(A);
+impl_key_part_tuple!(A, B);
+impl_key_part_tuple!(A, B, C);
+impl_key_part_tuple!(A, B, C, D);
@@ -0,0 +1,78 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::KeyPart;
+
+/// The range of the encoded keys starting with some bytes, to iterate over a database
+/// whose key type is remapped to [`ByteSlice`](crate::ByteSlice).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRange {
+    /// The first key of the range, included.
+    pub start: Vec<u8>,
+    /// The smallest key greater than the keys of the range, excluded, `None` when there is
+    /// none and the range is unbounded.
+    pub end: Option<Vec<u8>>,
+}
+
+impl KeyRange {
+    /// The keys starting with `prefix`.
+    ///
+    /// The end is the prefix whose last byte is incremented, the trailing `0xFF` bytes
+    /// that can't be incremented are removed first: `[1, 0xFF]` ends at `[2]`.
+    pub fn prefix(prefix: &[u8]) -> KeyRange {
+        let mut end = prefix.to_vec();
+        while let Some(last) = end.pop() {
+            if last < u8::MAX {
+                end.push(last + 1);
+                return KeyRange { start: prefix.to_vec(), end: Some(end) };
+            }
+        }
+        KeyRange { start: prefix.to_vec(), end: None }
+    }
+}
+
+impl RangeBounds<[u8]> for KeyRange {
+    fn start_bound(&self) -> Bound<&[u8]> {
+        // Some stores, like LMDB, don't accept empty keys, even as bounds.
+        if self.start.is_empty() {
+            Bound::Unbounded
+        } else {
+            Bound::Included(&self.start)
+        }
+    }
+
+    fn end_bound(&self) -> Bound<&[u8]> {
+        match &self.end {
+            Some(end) => Bound::Excluded(end),
+            None => Bound::Unbounded,
+        }
+    }
+}
+
+/// A composite key of which `P` are the first fields: the first field of a tuple,
+/// or a tuple of its first fields.
+pub trait KeyPrefix<P: KeyPart>: KeyPart {}
+
+impl<A: KeyPart, B: KeyPart> KeyPrefix<A> for (A, B) {}
+impl<A: KeyPart, B: KeyPart, C: KeyPart> KeyPrefix<A> for (A, B, C) {}
+impl<A: KeyPart, B: KeyPart, C: KeyPart> KeyPrefix<(A, B)> for (A, B, C) {}
+impl<A: KeyPart, B: KeyPart, C: KeyPart, D: KeyPart> KeyPrefix<A> for (A, B, C, D) {}
+impl<A: KeyPart, B: KeyPart, C: KeyPart, D: KeyPart> KeyPrefix<(A, B)> for (A, B, C, D) {}
+impl<A: KeyPart, B: KeyPart, C: KeyPart, D: KeyPart> KeyPrefix<(A, B, C)> for (A, B, C, D) {}
+
+/// The range of the [`Composite`](crate::Composite) keys `K` whose first fields are `prefix`.
+///
+/// ```
+/// use heed_types::{prefix_range, KeyRange};
+///
+/// let range = prefix_range::<(u16, String), _>(&0x01FF);
+/// assert_eq!(range, KeyRange { start: vec![0x01, 0xFF], end: Some(vec![0x02]) });
+///
+/// // No key is greater than the keys starting with `0xFFFF`.
+/// let range = prefix_range::<(u16, u8, String), _>(&(0xFFFF, 0xFF));
+/// assert_eq!(range, KeyRange { start: vec![0xFF, 0xFF, 0xFF], end: None });
+/// ```
+pub fn prefix_range<K: KeyPrefix<P>, P: KeyPart>(prefix: &P) -> KeyRange {
+    let mut bytes = Vec::new();
+    prefix.encode_key(&mut bytes);
+    KeyRange::prefix(&bytes)
+}
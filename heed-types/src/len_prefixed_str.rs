@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+
+/// Describes an [`str`] prefixed by its length as a big-endian `u32`, to be embedded
+/// in composite keys.
+///
+/// Any string can be encoded but **the composite keys sort by the length of the string
+/// first**, then by its bytes: `"b"` comes before `"aa"`. Use it when the keys are looked
+/// up by equality or grouped by string, use [`ZeroTerminatedStr`](crate::ZeroTerminatedStr)
+/// when the keys must be iterated in the order of the strings.
+pub struct LenPrefixedStr;
+
+impl LenPrefixedStr {
+    /// Append the length of the string and the string to the key being built,
+    /// returns `None` if the string is longer than `u32::MAX` bytes.
+    pub fn encode_into(item: &str, out: &mut Vec<u8>) -> Option<()> {
+        let len = u32::try_from(item.len()).ok()?;
+        out.extend_from_slice(&len.to_be_bytes());
+        out.extend_from_slice(item.as_bytes());
+        Some(())
+    }
+
+    /// Read the string at the front of a key, returns it with the bytes following it.
+    pub fn decode_prefix(bytes: &[u8]) -> Option<(&str, &[u8])> {
+        if bytes.len() < 4 {
+            return None;
+        }
+        let (len, rest) = bytes.split_at(4);
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        if rest.len() < len {
+            return None;
+        }
+        let (item, rest) = rest.split_at(len);
+        Some((std::str::from_utf8(item).ok()?, rest))
+    }
+}
+
+impl BytesEncode<'_> for LenPrefixedStr {
+    type EItem = str;
+
+    fn bytes_encode(item: &Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = Vec::with_capacity(item.len() + 4);
+        LenPrefixedStr::encode_into(item, &mut bytes)?;
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl BytesEncodeInto<'_> for LenPrefixedStr {
+    fn bytes_encode_into(item: &Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+        LenPrefixedStr::encode_into(item, out)
+    }
+}
+
+impl BytesDecode for LenPrefixedStr {
+    type DItem = String;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        match LenPrefixedStr::decode_prefix(bytes)? {
+            (item, []) => Some(item.to_string()),
+            _ => None,
+        }
+    }
+}
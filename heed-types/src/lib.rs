@@ -3,8 +3,15 @@
 //! How to choose the right type to store things in this database?
 //! For specific types you can choose:
 //!   - [`Str`] to store [`str`](primitive@str)s
+//!   - [`FixedStr`] to store [`str`](primitive@str)s in fixed-width keys
+//!   - [`ZeroTerminatedStr`] or [`LenPrefixedStr`] to embed [`str`](primitive@str)s in composite keys
+//!   - [`Composite`] to store tuples of [`KeyPart`]s as keys, bounded by [`prefix_range`]
 //!   - [`Unit`] to store `()` types
-//!   - [`SerdeBincode`] or [`SerdeJson`] to store [`Serialize`]/[`Deserialize`] types
+//!   - [`DiscriminantOf`] to store fieldless enums as small numbers
+//!   - [`SerdeBincode`] or [`SerdeJson`] to store [`Serialize`]/[`Deserialize`] types,
+//!     [`SerdeBincodeFix`] or [`SerdeBincodeVar`] to choose the bincode options
+//!   - [`Checksummed`] to detect the corruption of the values of any other type
+//!   - [`MapKey`] or [`MapValue`] to transform the items of any other type, like lowercasing keys
 //!
 //! But if you want to store big types that can be efficiently deserialized then
 //! here is a little table to help you in your quest:
@@ -32,35 +39,55 @@
 //! [`Deserialize`]: serde::Deserialize
 //!
 
+mod checksummed;
+mod composite;
 mod cow_slice;
 mod cow_type;
+mod discriminant;
 mod fixed_slice;
+mod fixed_str;
+mod key_part;
+mod key_range;
+mod len_prefixed_str;
+mod map;
 mod owned_slice;
 mod owned_type;
 mod str;
 mod unaligned_slice;
 mod unaligned_type;
 mod unit;
+mod zero_terminated_str;
 
 #[cfg(feature = "serde-bincode")]
 mod serde_bincode;
 
 #[cfg(feature = "protokit")]
 mod proto;
+#[cfg(feature = "serde")]
+mod projected;
 #[cfg(feature = "serde-json")]
 mod serde_json;
 #[cfg(feature = "ordcode")]
 mod serde_ordcode;
 
+pub use self::checksummed::{Checksummed, Corrupted};
+pub use self::composite::Composite;
 pub use self::cow_slice::CowSlice;
 pub use self::cow_type::CowType;
+pub use self::discriminant::{Discriminant, DiscriminantOf};
 pub use self::fixed_slice::FixedSlice;
+pub use self::fixed_str::FixedStr;
+pub use self::key_part::KeyPart;
+pub use self::key_range::{prefix_range, KeyPrefix, KeyRange};
+pub use self::len_prefixed_str::LenPrefixedStr;
+pub use self::map::{MapKey, MapValue, Mapping};
 pub use self::owned_slice::OwnedSlice;
 pub use self::owned_type::OwnedType;
 pub use self::str::Str;
 pub use self::unaligned_slice::UnalignedSlice;
 pub use self::unaligned_type::UnalignedType;
 pub use self::unit::Unit;
+pub use self::zero_terminated_str::ZeroTerminatedStr;
 
 /// Describes a slice of bytes `[u8]` that is totally
 /// borrowed and doesn't depends on any [memory alignment].
@@ -82,10 +109,16 @@ impl heed_traits::BytesDecode for DecodeIgnore {
     }
 }
 
+#[cfg(feature = "serde-json")]
+pub use self::serde_json::json_field;
+#[cfg(feature = "serde")]
+pub use self::projected::Projected;
 #[cfg(feature = "protokit")]
 pub use self::proto::Proto;
 #[cfg(feature = "serde-bincode")]
-pub use self::serde_bincode::SerdeBincode;
+pub use self::serde_bincode::{
+    BigEndian, BincodeEndian, LittleEndian, SerdeBincode, SerdeBincodeFix, SerdeBincodeVar,
+};
 #[cfg(feature = "serde-json")]
 pub use self::serde_json::SerdeJson;
 #[cfg(feature = "ordcode")]
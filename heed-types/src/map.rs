@@ -0,0 +1,134 @@
+use std::borrow::{Borrow, Cow};
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+/// The functions a [`MapKey`] or a [`MapValue`] applies to the items of a codec,
+/// before they are encoded and after they are decoded.
+///
+/// The functions that aren't implemented leave the items unchanged.
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// use heed_traits::{BytesDecode, BytesEncode};
+/// use heed_types::{MapKey, Mapping, Str};
+///
+/// /// The names are case insensitive, they are stored in lowercase.
+/// struct Lowercase;
+///
+/// impl Mapping for Lowercase {
+///     type EItem = str;
+///     type DItem = String;
+///
+///     fn encode(name: &str) -> Cow<str> {
+///         if name.chars().any(char::is_uppercase) {
+///             Cow::Owned(name.to_lowercase())
+///         } else {
+///             Cow::Borrowed(name)
+///         }
+///     }
+/// }
+///
+/// type Name = MapKey<Str, Lowercase>;
+///
+/// assert_eq!(&Name::bytes_encode("Alice").unwrap()[..], b"alice");
+/// assert_eq!(Name::bytes_decode(b"alice"), Some("alice".to_string()));
+/// ```
+pub trait Mapping {
+    /// The items encoded by the codec.
+    type EItem: ?Sized + ToOwned;
+    /// The items decoded by the codec.
+    type DItem: 'static;
+
+    /// Applied to the items before they are encoded.
+    fn encode(item: &Self::EItem) -> Cow<'_, Self::EItem> {
+        Cow::Borrowed(item)
+    }
+
+    /// Applied to the decoded items, `None` fails the decoding.
+    fn decode(item: Self::DItem) -> Option<Self::DItem> {
+        Some(item)
+    }
+}
+
+macro_rules! impl_mapped_codec {
+    ($($(#[$doc:meta])* $name:ident),*) => {
+        $(
+            $(#[$doc])*
+            pub struct $name<C, F>(std::marker::PhantomData<(C, F)>);
+
+            impl<'a, C, F> BytesEncode<'a> for $name<C, F>
+            where
+                C: for<'b> BytesEncode<'b, EItem = F::EItem>,
+                F: Mapping,
+                F::EItem: 'a,
+            {
+                type EItem = F::EItem;
+
+                fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
+                    match F::encode(item) {
+                        Cow::Borrowed(item) => C::bytes_encode(item),
+                        Cow::Owned(item) => {
+                            let bytes = C::bytes_encode(item.borrow())?;
+                            Some(Cow::Owned(bytes.into_owned()))
+                        }
+                    }
+                }
+            }
+
+            impl<C, F> BytesDecode for $name<C, F>
+            where
+                C: BytesDecode<DItem = F::DItem>,
+                F: Mapping,
+            {
+                type DItem = F::DItem;
+
+                fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+                    C::bytes_decode(bytes).and_then(F::decode)
+                }
+            }
+
+            unsafe impl<C, F> Send for $name<C, F> {}
+
+            unsafe impl<C, F> Sync for $name<C, F> {}
+        )*
+    };
+}
+
+impl_mapped_codec!(
+    /// Describes the keys encoded by `C` whose items are transformed by the [`Mapping`] `F`,
+    /// like keys stored in lowercase.
+    ///
+    /// The keys are sorted by their encoded bytes, after the mapping.
+    MapKey,
+    /// Describes the values encoded by `C` whose items are transformed by the [`Mapping`] `F`.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use heed_traits::{BytesDecode, BytesEncode};
+    /// use heed_types::{MapValue, Mapping, Str};
+    ///
+    /// /// The urls are stored without their scheme.
+    /// struct Https;
+    ///
+    /// impl Mapping for Https {
+    ///     type EItem = str;
+    ///     type DItem = String;
+    ///
+    ///     fn encode(url: &str) -> Cow<str> {
+    ///         Cow::Borrowed(url.strip_prefix("https://").unwrap_or(url))
+    ///     }
+    ///
+    ///     fn decode(url: String) -> Option<String> {
+    ///         Some(format!("https://{}", url))
+    ///     }
+    /// }
+    ///
+    /// type Url = MapValue<Str, Https>;
+    ///
+    /// assert_eq!(&Url::bytes_encode("https://example.org").unwrap()[..], b"example.org");
+    /// assert_eq!(Url::bytes_decode(b"example.org").unwrap(), "https://example.org");
+    /// ```
+    MapValue
+);
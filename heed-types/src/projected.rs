@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+use serde::de::DeserializeOwned;
+
+/// Describes the values encoded by the serde codec `C` of which only the fields of `F` are decoded.
+///
+/// The values are encoded by `C` as a whole, the decoding skips the other fields instead of
+/// building them, it saves the allocations of a large struct when only some fields are read.
+///
+/// - With [`SerdeJson`](crate::SerdeJson), `F` is a struct with any subset of the fields, the
+///   other ones are skipped as an [`IgnoredAny`].
+/// - With [`SerdeBincode`](crate::SerdeBincode), [`SerdeBincodeFix`](crate::SerdeBincodeFix)
+///   and [`SerdeBincodeVar`](crate::SerdeBincodeVar), which don't describe their fields, `F`
+///   must have the first fields of the value, with the same types and in the same order.
+///
+/// ```
+/// use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+/// use heed_types::{Projected, SerdeBincode};
+///
+/// type Entry = SerdeBincode<(u64, String, Vec<u8>)>;
+/// type EntryId = Projected<Entry, (u64,)>;
+///
+/// let entry = (42, "a large entry".to_string(), vec![0; 4096]);
+/// let bytes = EntryId::bytes_encode(&entry).unwrap();
+/// assert_eq!(EntryId::bytes_decode(&bytes), Some((42,)));
+/// ```
+pub struct Projected<C, F>(PhantomData<(C, F)>);
+
+impl<'a, C, F> BytesEncode<'a> for Projected<C, F>
+where
+    C: BytesEncode<'a>,
+{
+    type EItem = C::EItem;
+
+    fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
+        C::bytes_encode(item)
+    }
+}
+
+impl<'a, C, F> BytesEncodeInto<'a> for Projected<C, F>
+where
+    C: BytesEncodeInto<'a>,
+{
+    fn bytes_encode_into(item: &'a Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+        C::bytes_encode_into(item, out)
+    }
+}
+
+#[cfg(feature = "serde-json")]
+impl<T, F: 'static> BytesDecode for Projected<crate::SerdeJson<T>, F>
+where
+    F: DeserializeOwned,
+{
+    type DItem = F;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        serde_json::from_slice(bytes).ok()
+    }
+}
+
+#[cfg(feature = "serde-bincode")]
+impl<T, F: 'static> BytesDecode for Projected<crate::SerdeBincode<T>, F>
+where
+    F: DeserializeOwned,
+{
+    type DItem = F;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        // the trailing fields of the value are left undecoded
+        bincode::deserialize(bytes).ok()
+    }
+}
+
+unsafe impl<C, F> Send for Projected<C, F> {}
+
+unsafe impl<C, F> Sync for Projected<C, F> {}
@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 
-use heed_traits::{BytesDecode, BytesEncode};
+use bincode::Options;
+use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
@@ -20,6 +21,15 @@ where
     }
 }
 
+impl<'a, T: 'a> BytesEncodeInto<'a> for SerdeBincode<T>
+where
+    T: Serialize,
+{
+    fn bytes_encode_into(item: &'a Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+        bincode::serialize_into(out, item).ok()
+    }
+}
+
 impl<T: 'static> BytesDecode for SerdeBincode<T>
 where
     T: DeserializeOwned,
@@ -34,3 +44,216 @@ where
 unsafe impl<T> Send for SerdeBincode<T> {}
 
 unsafe impl<T> Sync for SerdeBincode<T> {}
+
+/// The options [`SerdeBincodeFix`] and [`SerdeBincodeVar`] start from, bincode's defaults
+/// with the trailing bytes allowed. These encode the integers as varints: each codec sets
+/// them to fixint or varint, and its `E` parameter sets their endianness.
+fn default_options() -> impl Options {
+    bincode::DefaultOptions::new().allow_trailing_bytes()
+}
+
+/// Serializes with the options and a limit of `limit` bytes, 0 is no limit.
+fn serialize<O: Options, T: ?Sized + Serialize>(
+    options: O,
+    limit: u64,
+    item: &T,
+) -> bincode::Result<Vec<u8>> {
+    match limit {
+        0 => options.serialize(item),
+        limit => options.with_limit(limit).serialize(item),
+    }
+}
+
+/// Serializes into `out` with the options and a limit of `limit` bytes, 0 is no limit.
+fn serialize_into<O: Options, T: ?Sized + Serialize>(
+    options: O,
+    limit: u64,
+    item: &T,
+    out: &mut Vec<u8>,
+) -> bincode::Result<()> {
+    match limit {
+        0 => options.serialize_into(out, item),
+        limit => options.with_limit(limit).serialize_into(out, item),
+    }
+}
+
+/// Deserializes with the options the bytes if they aren't longer than `limit`, 0 is no limit.
+fn deserialize<O: Options, T: DeserializeOwned>(
+    options: O,
+    limit: u64,
+    bytes: &[u8],
+) -> bincode::Result<T> {
+    // bincode only limits the reads of the readers, not of the slices
+    if limit != 0 && bytes.len() as u64 > limit {
+        return Err(Box::new(bincode::ErrorKind::SizeLimit));
+    }
+    options.deserialize(bytes)
+}
+
+/// The byte order of the integers of a [`SerdeBincodeFix`] or a [`SerdeBincodeVar`].
+pub trait BincodeEndian {
+    #[doc(hidden)]
+    fn serialize<O: Options, T: ?Sized + Serialize>(
+        options: O,
+        limit: u64,
+        item: &T,
+    ) -> bincode::Result<Vec<u8>>;
+
+    #[doc(hidden)]
+    fn serialize_into<O: Options, T: ?Sized + Serialize>(
+        options: O,
+        limit: u64,
+        item: &T,
+        out: &mut Vec<u8>,
+    ) -> bincode::Result<()>;
+
+    #[doc(hidden)]
+    fn deserialize<O: Options, T: DeserializeOwned>(
+        options: O,
+        limit: u64,
+        bytes: &[u8],
+    ) -> bincode::Result<T>;
+}
+
+/// The integers are stored in little endian, like with [`SerdeBincode`].
+pub enum LittleEndian {}
+
+/// The integers are stored in big endian.
+pub enum BigEndian {}
+
+macro_rules! impl_bincode_endian {
+    ($endian:ty, $with:ident) => {
+        impl BincodeEndian for $endian {
+            fn serialize<O: Options, T: ?Sized + Serialize>(
+                options: O,
+                limit: u64,
+                item: &T,
+            ) -> bincode::Result<Vec<u8>> {
+                serialize(options.$with(), limit, item)
+            }
+
+            fn serialize_into<O: Options, T: ?Sized + Serialize>(
+                options: O,
+                limit: u64,
+                item: &T,
+                out: &mut Vec<u8>,
+            ) -> bincode::Result<()> {
+                serialize_into(options.$with(), limit, item, out)
+            }
+
+            fn deserialize<O: Options, T: DeserializeOwned>(
+                options: O,
+                limit: u64,
+                bytes: &[u8],
+            ) -> bincode::Result<T> {
+                deserialize(options.$with(), limit, bytes)
+            }
+        }
+    };
+}
+
+impl_bincode_endian!(LittleEndian, with_little_endian);
+impl_bincode_endian!(BigEndian, with_big_endian);
+
+/// Describes a type that is [`Serialize`]/[`Deserialize`] and uses `bincode` with fixed size
+/// integers, in the `E` byte order, to do so.
+///
+/// The items that take more than `LIMIT` bytes aren't encoded, and the values longer than it
+/// aren't decoded, 0 is no limit. `SerdeBincodeFix<T>` encodes the items like [`SerdeBincode`]:
+///
+/// ```
+/// use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+/// use heed_types::{BigEndian, Projected, SerdeBincode, SerdeBincodeFix, SerdeBincodeVar};
+///
+/// let item = (42u64, "hello".to_string());
+/// let fixed = SerdeBincodeFix::<(u64, String)>::bytes_encode(&item).unwrap();
+/// assert_eq!(fixed, SerdeBincode::<(u64, String)>::bytes_encode(&item).unwrap());
+///
+/// let big = SerdeBincodeFix::<(u64, String), BigEndian>::bytes_encode(&item).unwrap();
+/// assert_eq!(big[..8], 42u64.to_be_bytes());
+///
+/// // the small integers and lengths take a single byte
+/// let varint = SerdeBincodeVar::<(u64, String)>::bytes_encode(&item).unwrap();
+/// assert_eq!(varint.len(), 1 + 1 + 5);
+///
+/// assert!(SerdeBincodeVar::<(u64, String), BigEndian, 4>::bytes_encode(&item).is_none());
+/// assert!(SerdeBincodeFix::<(u64, String), BigEndian, 8>::bytes_decode(&big).is_none());
+///
+/// let mut out = Vec::new();
+/// SerdeBincodeVar::<(u64, String)>::bytes_encode_into(&item, &mut out).unwrap();
+/// assert_eq!(out, &varint[..]);
+/// let id = Projected::<SerdeBincodeVar<(u64, String)>, (u64,)>::bytes_decode(&out);
+/// assert_eq!(id, Some((42,)));
+/// ```
+///
+/// [`Deserialize`]: serde::Deserialize
+pub struct SerdeBincodeFix<T, E = LittleEndian, const LIMIT: u64 = 0>(
+    std::marker::PhantomData<(T, E)>,
+);
+
+/// Describes a type that is [`Serialize`]/[`Deserialize`] and uses `bincode` with variable size
+/// integers, in the `E` byte order, to do so.
+///
+/// The small integers and the lengths take less bytes than with [`SerdeBincodeFix`], the
+/// `LIMIT` is the same.
+///
+/// [`Deserialize`]: serde::Deserialize
+pub struct SerdeBincodeVar<T, E = LittleEndian, const LIMIT: u64 = 0>(
+    std::marker::PhantomData<(T, E)>,
+);
+
+macro_rules! impl_bincode_codec {
+    ($codec:ident, $with:ident) => {
+        impl<'a, T: 'a, E: BincodeEndian, const LIMIT: u64> BytesEncode<'a> for $codec<T, E, LIMIT>
+        where
+            T: Serialize,
+        {
+            type EItem = T;
+
+            fn bytes_encode(item: &'a Self::EItem) -> Option<Cow<'a, [u8]>> {
+                E::serialize(default_options().$with(), LIMIT, item).map(Cow::Owned).ok()
+            }
+        }
+
+        impl<'a, T: 'a, E: BincodeEndian, const LIMIT: u64> BytesEncodeInto<'a>
+            for $codec<T, E, LIMIT>
+        where
+            T: Serialize,
+        {
+            fn bytes_encode_into(item: &'a Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+                E::serialize_into(default_options().$with(), LIMIT, item, out).ok()
+            }
+        }
+
+        impl<T: 'static, E: BincodeEndian, const LIMIT: u64> BytesDecode for $codec<T, E, LIMIT>
+        where
+            T: DeserializeOwned,
+        {
+            type DItem = T;
+
+            fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+                E::deserialize(default_options().$with(), LIMIT, bytes).ok()
+            }
+        }
+
+        // the trailing fields of the value are left undecoded
+        impl<T, E: BincodeEndian, const LIMIT: u64, F: 'static> BytesDecode
+            for crate::Projected<$codec<T, E, LIMIT>, F>
+        where
+            F: DeserializeOwned,
+        {
+            type DItem = F;
+
+            fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+                E::deserialize(default_options().$with(), LIMIT, bytes).ok()
+            }
+        }
+
+        unsafe impl<T, E, const LIMIT: u64> Send for $codec<T, E, LIMIT> {}
+
+        unsafe impl<T, E, const LIMIT: u64> Sync for $codec<T, E, LIMIT> {}
+    };
+}
+
+impl_bincode_codec!(SerdeBincodeFix, with_fixint_encoding);
+impl_bincode_codec!(SerdeBincodeVar, with_varint_encoding);
@@ -1,7 +1,9 @@
 use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
 
-use heed_traits::{BytesDecode, BytesEncode};
-use serde::de::DeserializeOwned;
+use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+use serde::de::{DeserializeOwned, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
 use serde::Serialize;
 
 /// Describes a type that is [`Serialize`]/[`Deserialize`] and uses `serde_json` to do so.
@@ -20,6 +22,15 @@ where
     }
 }
 
+impl<'a, T: 'a> BytesEncodeInto<'a> for SerdeJson<T>
+where
+    T: Serialize,
+{
+    fn bytes_encode_into(item: &Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+        serde_json::to_writer(out, item).ok()
+    }
+}
+
 impl<T: 'static> BytesDecode for SerdeJson<T>
 where
     T: DeserializeOwned,
@@ -34,3 +45,54 @@ where
 unsafe impl<T> Send for SerdeJson<T> {}
 
 unsafe impl<T> Sync for SerdeJson<T> {}
+
+/// Decodes the field `name` of a JSON object encoded by [`SerdeJson`],
+/// skipping the other fields, `None` if the object doesn't have it.
+///
+/// ```
+/// use heed_types::json_field;
+///
+/// let bytes = br#"{"id":42,"name":"Alice","friends":[1,2,3]}"#;
+/// assert_eq!(json_field::<String>(bytes, "name"), Some("Alice".to_string()));
+/// assert_eq!(json_field::<u64>(bytes, "age"), None);
+/// ```
+pub fn json_field<T: DeserializeOwned>(bytes: &[u8], name: &str) -> Option<T> {
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+    let field = Field { name, marker: PhantomData }.deserialize(&mut deserializer).ok()?;
+    deserializer.end().ok()?;
+    field
+}
+
+/// Deserializes the field `name` of a map, the other values are ignored.
+struct Field<'n, T> {
+    name: &'n str,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T: DeserializeOwned> DeserializeSeed<'de> for Field<'_, T> {
+    type Value = Option<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Visitor<'de> for Field<'_, T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map with the field {:?}", self.name)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut field = None;
+        while let Some(key) = map.next_key::<Cow<str>>()? {
+            if field.is_none() && key == self.name {
+                field = Some(map.next_value()?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(field)
+    }
+}
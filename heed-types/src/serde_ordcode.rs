@@ -12,14 +12,7 @@ impl<'ser, T: Serialize + 'ser> BytesEncode<'ser> for Ordcode<T> {
     type EItem = T;
 
     fn bytes_encode(item: &'ser Self::EItem) -> Option<Cow<'ser, [u8]>> {
-        let key: Cow<'ser, [u8]> =
-            ordcode::ser_to_vec_ordered(item, Order::Ascending).map(Cow::Owned).ok()?;
-
-        if key.len() >= 511 {
-            panic!("Key too long");
-        }
-
-        Some(key)
+        ordcode::ser_to_vec_ordered(item, Order::Ascending).map(Cow::Owned).ok()
     }
 }
 
@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+
+use heed_traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+
+/// Describes an [`str`] followed by a nul byte, to be embedded in composite keys.
+///
+/// The nul byte is lower than any other byte, the composite keys therefore sort like the
+/// strings, in lexicographic byte order, before the parts that follow. The flip side is that
+/// a string containing a nul byte can't be encoded: [`encode_into`](ZeroTerminatedStr::encode_into)
+/// and `bytes_encode` return `None`.
+pub struct ZeroTerminatedStr;
+
+impl ZeroTerminatedStr {
+    /// Append the string and its terminator to the key being built.
+    pub fn encode_into(item: &str, out: &mut Vec<u8>) -> Option<()> {
+        if item.as_bytes().contains(&0) {
+            return None;
+        }
+        out.extend_from_slice(item.as_bytes());
+        out.push(0);
+        Some(())
+    }
+
+    /// Read the string at the front of a key, returns it with the bytes following its terminator.
+    pub fn decode_prefix(bytes: &[u8]) -> Option<(&str, &[u8])> {
+        let end = bytes.iter().position(|&b| b == 0)?;
+        let item = std::str::from_utf8(&bytes[..end]).ok()?;
+        Some((item, &bytes[end + 1..]))
+    }
+}
+
+impl BytesEncode<'_> for ZeroTerminatedStr {
+    type EItem = str;
+
+    fn bytes_encode(item: &Self::EItem) -> Option<Cow<[u8]>> {
+        let mut bytes = Vec::with_capacity(item.len() + 1);
+        ZeroTerminatedStr::encode_into(item, &mut bytes)?;
+        Some(Cow::Owned(bytes))
+    }
+}
+
+impl BytesEncodeInto<'_> for ZeroTerminatedStr {
+    fn bytes_encode_into(item: &Self::EItem, out: &mut Vec<u8>) -> Option<()> {
+        ZeroTerminatedStr::encode_into(item, out)
+    }
+}
+
+impl BytesDecode for ZeroTerminatedStr {
+    type DItem = String;
+
+    fn bytes_decode(bytes: &[u8]) -> Option<Self::DItem> {
+        match ZeroTerminatedStr::decode_prefix(bytes)? {
+            (item, []) => Some(item.to_string()),
+            _ => None,
+        }
+    }
+}
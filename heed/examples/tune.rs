@@ -0,0 +1,16 @@
+use std::env;
+use std::error::Error;
+
+use heed::EnvOpenOptions;
+
+// Prints the usage of the environment at the given path and the options recommended for it.
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = env::args().nth(1).ok_or("usage: tune <env path>")?;
+
+    // The databases are opened one at a time to be read.
+    let env = EnvOpenOptions::new().max_dbs(1).open(path)?;
+    let report = heed::tune::inspect(&env)?;
+    print!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,368 @@
+//! Incremental backups of MDBX environments, made of the pages written since the last backup.
+//!
+//! MDBX stores the id of the transaction that wrote a page in its header, and never writes a
+//! page in place: the pages a transaction changes are written to free pages. An increment is
+//! made of the pages of the data file written after the transaction of the previous increment,
+//! and of the meta pages, the first increment of all the pages. Applying the increments in
+//! order to an empty file gives the data file of the environment at the transaction of the
+//! last increment.
+//!
+//! An [`IncrementalBackup`] keeps the increments in a directory, named after their
+//! transaction, the last one records the transaction the next increment starts from.
+//! [`write_increment`] and [`apply_increment`] write and apply a single increment.
+//!
+//! An increment is read while a read transaction keeps the pages of its snapshot from being
+//! reused, the writers only wait while the meta pages are copied. The pages the transactions
+//! committed meanwhile write are left to the next increment. The increments are restored to
+//! the data file of an environment in a directory, the environments opened with the
+//! `MdbNoSubDir` flag too.
+//!
+//! ```no_run
+//! # use heed::EnvOpenOptions;
+//! use heed::backup::IncrementalBackup;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let env = EnvOpenOptions::new().open("target/env.mdbx")?;
+//! let backups = IncrementalBackup::open("backups")?;
+//! let txn_id = backups.backup(&env)?;
+//! assert_eq!(backups.last_txn_id()?, Some(txn_id));
+//!
+//! // Restores the environment as of the last increment.
+//! backups.restore("restored")?;
+//! let restored = EnvOpenOptions::new().open("restored")?;
+//! # Ok(()) }
+//! ```
+
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::{ByteOrder, NativeEndian};
+
+use crate::mdb::error::mdb_result;
+use crate::mdb::ffi;
+use crate::{Env, Result};
+
+/// The first bytes of an increment.
+const MAGIC: &[u8; 8] = b"heedinc1";
+
+/// The page number ending the pages of an increment.
+const END: u64 = u64::MAX;
+
+/// The meta pages at the start of the data file, in every increment.
+const NUM_METAS: u64 = 3;
+
+/// The bytes of the header of a page read to know the transaction that wrote it.
+const PAGE_HEADER: usize = 16;
+
+/// The flag of the first page of a run of overflow pages, whose length follows the flags.
+const P_OVERFLOW: u16 = 0x04;
+
+/// The name of the data file of MDBX.
+const DATA_FILE: &str = "mdbx.dat";
+
+/// The transactions an increment goes from and to, see [`apply_increment`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Increment {
+    /// The transaction of the previous increment, 0 for the first one.
+    pub since_txn_id: u64,
+    /// The last transaction committed when the increment was written.
+    pub txn_id: u64,
+}
+
+/// Writes the pages of the environment written after the transaction `since_txn_id` to `out`,
+/// all of them if it is 0, and returns the id of the last committed transaction they hold.
+pub fn write_increment<W: Write>(env: &Env, since_txn_id: u64, out: W) -> Result<u64> {
+    let mut stat = std::mem::MaybeUninit::uninit();
+    unsafe { mdb_result(ffi::mdb_env_stat(env.env_mut_ptr(), stat.as_mut_ptr()))? };
+    let page_size = unsafe { stat.assume_init() }.ms_psize as usize;
+
+    let [data, _lock] = env.file_paths()?;
+    let mut file = File::open(data)?;
+    let mut metas = vec![0; NUM_METAS as usize * page_size];
+    let (rtxn, txn_id, file_len) = loop {
+        // The meta pages are written in place, the writers wait while they are copied.
+        let wtxn = env.write_txn()?;
+        let txn_id = env.last_txn_id()?;
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut metas)?;
+        let file_len = file.metadata()?.len();
+        drop(wtxn);
+
+        // The snapshot of the metas is kept if no transaction was committed in between.
+        let rtxn = env.read_txn()?;
+        if rtxn.id() == txn_id {
+            break (rtxn, txn_id, file_len);
+        }
+    };
+
+    let mut out = BufWriter::new(out);
+    out.write_all(MAGIC)?;
+    out.write_all(&since_txn_id.to_le_bytes())?;
+    out.write_all(&txn_id.to_le_bytes())?;
+    out.write_all(&(page_size as u32).to_le_bytes())?;
+    out.write_all(&file_len.to_le_bytes())?;
+    for (pgno, meta) in (0..NUM_METAS).zip(metas.chunks(page_size)) {
+        out.write_all(&pgno.to_le_bytes())?;
+        out.write_all(&1u64.to_le_bytes())?;
+        out.write_all(meta)?;
+    }
+
+    let pages = file_len / page_size as u64;
+    let mut page = vec![0; page_size];
+    let mut pgno = NUM_METAS;
+    while pgno < pages {
+        // Only the header of a page is read to know whether it is in the increment.
+        file.seek(SeekFrom::Start(pgno * page_size as u64))?;
+        file.read_exact(&mut page[..PAGE_HEADER])?;
+        let written = NativeEndian::read_u64(&page[0..8]);
+        if written <= since_txn_id || written > txn_id {
+            // The unchanged runs of overflow pages aren't skipped at once: the header of
+            // a freed run remains, the pages after it can be reused one by one. The pages
+            // written after the snapshot aren't part of it, the next increment has them.
+            pgno += 1;
+            continue;
+        }
+
+        let flags = NativeEndian::read_u16(&page[10..12]);
+        let count = match flags & P_OVERFLOW {
+            0 => 1,
+            _ => u64::from(NativeEndian::read_u32(&page[12..16])).clamp(1, pages - pgno),
+        };
+        file.read_exact(&mut page[PAGE_HEADER..])?;
+        out.write_all(&pgno.to_le_bytes())?;
+        out.write_all(&count.to_le_bytes())?;
+        out.write_all(&page)?;
+        for _ in 1..count {
+            file.read_exact(&mut page)?;
+            out.write_all(&page)?;
+        }
+        pgno += count;
+    }
+
+    out.write_all(&END.to_le_bytes())?;
+    out.flush()?;
+    drop(rtxn);
+    Ok(txn_id)
+}
+
+/// Writes the pages of an increment read from `input` to the data file `data`, which must be
+/// empty or hold the increments before it, and returns the transactions it goes from and to.
+pub fn apply_increment<R: Read>(data: &mut File, input: R) -> Result<Increment> {
+    let mut input = BufReader::new(input);
+    let mut magic = [0; 8];
+    input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("not an increment of a heed backup").into());
+    }
+    let since_txn_id = read_u64(&mut input)?;
+    let txn_id = read_u64(&mut input)?;
+    let mut page_size = [0; 4];
+    input.read_exact(&mut page_size)?;
+    let page_size = u64::from(u32::from_le_bytes(page_size));
+    data.set_len(read_u64(&mut input)?)?;
+
+    loop {
+        let pgno = read_u64(&mut input)?;
+        if pgno == END {
+            break;
+        }
+        let count = read_u64(&mut input)?;
+        data.seek(SeekFrom::Start(pgno * page_size))?;
+        let copied = io::copy(&mut (&mut input).take(count * page_size), data)?;
+        if copied != count * page_size {
+            return Err(invalid("the increment is truncated").into());
+        }
+    }
+
+    Ok(Increment { since_txn_id, txn_id })
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The increments of the backups of an environment, kept in a directory.
+#[derive(Debug, Clone)]
+pub struct IncrementalBackup {
+    dir: PathBuf,
+}
+
+impl IncrementalBackup {
+    /// Opens the backups kept in the directory `dir`, it is created if needed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<IncrementalBackup> {
+        fs::create_dir_all(&dir)?;
+        Ok(IncrementalBackup { dir: dir.as_ref().to_path_buf() })
+    }
+
+    /// The paths of the increments, in the order they are applied.
+    pub fn increments(&self) -> Result<Vec<PathBuf>> {
+        let mut increments = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() == Some("inc".as_ref()) {
+                increments.push(path);
+            }
+        }
+        // The names are the zero padded ids of the transactions.
+        increments.sort_unstable();
+        Ok(increments)
+    }
+
+    /// The transaction of the last increment, the next one starts after it.
+    pub fn last_txn_id(&self) -> Result<Option<u64>> {
+        let last = match self.increments()?.pop() {
+            Some(last) => last,
+            None => return Ok(None),
+        };
+        let mut input = File::open(last)?;
+        let mut header = [0; 24];
+        input.read_exact(&mut header)?;
+        Ok(Some(u64::from_le_bytes(header[16..24].try_into().unwrap())))
+    }
+
+    /// Writes an increment of the pages written since the last one, and returns its
+    /// transaction. No increment is written if no transaction was committed since.
+    pub fn backup(&self, env: &Env) -> Result<u64> {
+        let since = self.last_txn_id()?.unwrap_or(0);
+        if since != 0 && env.last_txn_id()? == since {
+            return Ok(since);
+        }
+
+        // Written aside, an increment is only seen once complete.
+        let partial = self.dir.join("partial");
+        let mut file = File::create(&partial)?;
+        let txn_id = write_increment(env, since, &mut file)?;
+        file.sync_all()?;
+        fs::rename(partial, self.dir.join(format!("{:020}.inc", txn_id)))?;
+        Ok(txn_id)
+    }
+
+    /// Applies the increments to the data file of a new environment in the directory `path`.
+    pub fn restore<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::create_dir_all(&path)?;
+        let mut data =
+            File::options().write(true).create_new(true).open(path.as_ref().join(DATA_FILE))?;
+        let mut txn_id = 0;
+        for increment in self.increments()? {
+            let applied = apply_increment(&mut data, File::open(&increment)?)?;
+            if applied.since_txn_id != txn_id {
+                return Err(invalid("an increment of the backup is missing").into());
+            }
+            txn_id = applied.txn_id;
+        }
+        data.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{OwnedType, Str};
+
+    use super::*;
+    use crate::{Database, EnvOpenOptions};
+
+    #[test]
+    fn restore_the_increments_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("env")).unwrap();
+        let env = EnvOpenOptions::new().open(dir.path().join("env")).unwrap();
+        let backups = IncrementalBackup::open(dir.path().join("backups")).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, &1, "one").unwrap();
+        wtxn.commit().unwrap();
+        let first = backups.backup(&env).unwrap();
+        assert_eq!(backups.backup(&env).unwrap(), first);
+
+        let mut wtxn = env.write_txn().unwrap();
+        db.put(&mut wtxn, &2, &"two".repeat(10_000)).unwrap();
+        db.delete(&mut wtxn, &1).unwrap();
+        wtxn.commit().unwrap();
+        let second = backups.backup(&env).unwrap();
+        assert!(second > first);
+        assert_eq!(backups.increments().unwrap().len(), 2);
+
+        backups.restore(dir.path().join("restored")).unwrap();
+        let restored = EnvOpenOptions::new().open(dir.path().join("restored")).unwrap();
+        let rtxn = restored.read_txn().unwrap();
+        let db = restored.open_database::<OwnedType<u32>, Str>(&rtxn, None).unwrap().unwrap();
+        assert_eq!(db.get(&rtxn, &1).unwrap(), None);
+        assert_eq!(db.get(&rtxn, &2).unwrap().map(|two| two.len()), Some(30_000));
+    }
+
+    /// Commits a transaction from another thread on the first write of the increment.
+    struct CommitOnWrite {
+        env: Env,
+        db: Database<OwnedType<u32>, Str>,
+        bytes: Vec<u8>,
+    }
+
+    impl Write for CommitOnWrite {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.bytes.is_empty() {
+                let (env, db) = (self.env.clone(), self.db);
+                std::thread::spawn(move || {
+                    let mut wtxn = env.write_txn().unwrap();
+                    db.put(&mut wtxn, &2, "two").unwrap();
+                    wtxn.commit().unwrap();
+                })
+                .join()
+                .unwrap();
+            }
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writers_commit_while_an_increment_is_written() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("env")).unwrap();
+        let env = EnvOpenOptions::new().open(dir.path().join("env")).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, &1, "one").unwrap();
+        wtxn.commit().unwrap();
+
+        let mut first = CommitOnWrite { env: env.clone(), db, bytes: Vec::new() };
+        let txn_id = write_increment(&env, 0, &mut first).unwrap();
+        assert!(env.last_txn_id().unwrap() > txn_id);
+        let mut second = Vec::new();
+        write_increment(&env, txn_id, &mut second).unwrap();
+
+        // The first increment is the snapshot it started from, the second adds the commit.
+        for (name, increments) in
+            [("first", vec![&first.bytes]), ("both", vec![&first.bytes, &second])]
+        {
+            let path = dir.path().join(name);
+            fs::create_dir_all(&path).unwrap();
+            let mut data =
+                File::options().write(true).create_new(true).open(path.join(DATA_FILE)).unwrap();
+            for increment in &increments {
+                apply_increment(&mut data, &increment[..]).unwrap();
+            }
+            drop(data);
+
+            let restored = EnvOpenOptions::new().open(&path).unwrap();
+            let rtxn = restored.read_txn().unwrap();
+            let db = restored.open_database::<OwnedType<u32>, Str>(&rtxn, None).unwrap().unwrap();
+            assert_eq!(db.get(&rtxn, &1).unwrap(), Some("one"));
+            let two = if increments.len() == 2 { Some("two") } else { None };
+            assert_eq!(db.get(&rtxn, &2).unwrap(), two);
+        }
+    }
+}
@@ -1,12 +1,20 @@
 use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::{marker, mem, ptr};
 
 use crate::mdb::error::mdb_result;
 use crate::mdb::ffi;
+use crate::mdb::op::MdbOp;
 use crate::*;
 
 pub struct RoCursor<'txn> {
     cursor: *mut ffi::MDB_cursor,
+    /// The database and transaction of the cursor, given to its errors.
+    op: MdbOp<'static>,
+    /// The generation of the transaction, and its value when the cursor was opened.
+    generation: Arc<AtomicU64>,
+    opened_at: u64,
     _marker: marker::PhantomData<&'txn ()>,
 }
 
@@ -14,12 +22,29 @@ impl<'txn> RoCursor<'txn> {
     pub(crate) fn new<T>(txn: &'txn RoTxn<T>, dbi: ffi::MDB_dbi) -> Result<RoCursor<'txn>> {
         let mut cursor: *mut ffi::MDB_cursor = ptr::null_mut();
 
-        unsafe { mdb_result(ffi::mdb_cursor_open(txn.txn, dbi, &mut cursor))? }
+        let env_ident = txn.env.env_mut_ptr() as usize;
+        let op = MdbOp::new("mdb_cursor_get").db(env_ident, dbi).txn(txn.kind());
 
-        Ok(RoCursor { cursor, _marker: marker::PhantomData })
+        let result = unsafe { mdb_result(ffi::mdb_cursor_open(txn.txn, dbi, &mut cursor)) };
+        op.op("mdb_cursor_open").result(result)?;
+
+        let generation = txn.generation.clone();
+        let opened_at = generation.load(Ordering::Acquire);
+        Ok(RoCursor { cursor, op, generation, opened_at, _marker: marker::PhantomData })
+    }
+
+    /// Fails with `BadTxn` once the transaction of the cursor is committed or aborted,
+    /// the cursor must not touch the memory of an ended transaction.
+    fn check_txn(&self) -> Result<()> {
+        if self.generation.load(Ordering::Acquire) == self.opened_at {
+            Ok(())
+        } else {
+            Err(self.op.error(MdbError::BadTxn))
+        }
     }
 
     pub fn current(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = mem::MaybeUninit::uninit();
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -40,11 +65,12 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.error(e)),
         }
     }
 
     pub fn move_on_first(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = mem::MaybeUninit::uninit();
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -65,11 +91,12 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.error(e)),
         }
     }
 
     pub fn move_on_last(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = mem::MaybeUninit::uninit();
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -90,7 +117,7 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.error(e)),
         }
     }
 
@@ -98,6 +125,7 @@ impl<'txn> RoCursor<'txn> {
         &mut self,
         key: &[u8],
     ) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = unsafe { crate::into_val(&key) };
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -118,11 +146,12 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.key(key).error(e)),
         }
     }
 
     pub fn move_on_prev(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = mem::MaybeUninit::uninit();
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -143,11 +172,12 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.error(e)),
         }
     }
 
     pub fn move_on_next(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        self.check_txn()?;
         let mut key_val = mem::MaybeUninit::uninit();
         let mut data_val = mem::MaybeUninit::uninit();
 
@@ -168,14 +198,18 @@ impl<'txn> RoCursor<'txn> {
                 Ok(Some((key, data)))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op.error(e)),
         }
     }
 }
 
 impl Drop for RoCursor<'_> {
     fn drop(&mut self) {
-        unsafe { ffi::mdb_cursor_close(self.cursor) }
+        // LMDB frees the cursors of a write transaction when it ends.
+        let ended = self.generation.load(Ordering::Acquire) != self.opened_at;
+        if !(ended && cfg!(not(feature = "mdbx")) && self.op.txn_kind() == Some(TxnKind::Write)) {
+            unsafe { ffi::mdb_cursor_close(self.cursor) }
+        }
     }
 }
 
@@ -202,13 +236,14 @@ impl<'txn> RwCursor<'txn> {
     ///
     /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
     pub unsafe fn del_current(&mut self) -> Result<bool> {
+        self.cursor.check_txn()?;
         // Delete the current entry
         let result = mdb_result(ffi::mdb_cursor_del(self.cursor.cursor, 0));
 
         match result {
             Ok(()) => Ok(true),
             Err(e) if e.not_found() => Ok(false),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.cursor.op.op("mdb_cursor_del").error(e)),
         }
     }
 
@@ -236,6 +271,7 @@ impl<'txn> RwCursor<'txn> {
     ///
     /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
     pub unsafe fn put_current(&mut self, key: &[u8], data: &[u8]) -> Result<bool> {
+        self.cursor.check_txn()?;
         let mut key_val = crate::into_val(&key);
         let mut data_val = crate::into_val(&data);
 
@@ -250,7 +286,7 @@ impl<'txn> RwCursor<'txn> {
         match result {
             Ok(()) => Ok(true),
             Err(e) if e.not_found() => Ok(false),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.cursor.op.op("mdb_cursor_put").key(key).error(e)),
         }
     }
 
@@ -273,6 +309,7 @@ impl<'txn> RwCursor<'txn> {
     ///
     /// [undefined behavior]: https://doc.rust-lang.org/reference/behavior-considered-undefined.html
     pub unsafe fn append(&mut self, key: &[u8], data: &[u8]) -> Result<()> {
+        self.cursor.check_txn()?;
         let mut key_val = crate::into_val(&key);
         let mut data_val = crate::into_val(&data);
 
@@ -284,7 +321,7 @@ impl<'txn> RwCursor<'txn> {
             ffi::MDB_APPEND,
         ));
 
-        result.map_err(Into::into)
+        self.cursor.op.op("mdb_cursor_put").key(key).result(result)
     }
 }
 
@@ -301,3 +338,53 @@ impl DerefMut for RwCursor<'_> {
         &mut self.cursor
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursors_fail_once_their_txn_ended() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<types::Str, types::Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, "hello", "world").unwrap();
+        wtxn.commit().unwrap();
+
+        // The transactions outlive their cursors, their generation is bumped by hand as
+        // ending them would: the cursors fail without touching the memory of an ended one.
+        let rtxn = env.clone().static_read_txn().unwrap();
+        let generation = rtxn.generation.clone();
+        let mut cursor = RoCursor::new(&rtxn, db.dyndb.dbi).unwrap();
+        assert_eq!(cursor.move_on_first().unwrap(), Some((&b"hello"[..], &b"world"[..])));
+        generation.fetch_add(1, Ordering::Release);
+        match cursor.move_on_next() {
+            Err(Error::Mdb { source: MdbError::BadTxn, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(cursor);
+
+        // Committing the transaction bumps its generation.
+        let ended_at = generation.load(Ordering::Acquire);
+        rtxn.commit().unwrap();
+        assert_ne!(generation.load(Ordering::Acquire), ended_at);
+
+        let wtxn = env.write_txn().unwrap();
+        let generation = wtxn.generation.clone();
+        let mut cursor = RwCursor::new(&wtxn, db.dyndb.dbi).unwrap();
+        assert!(cursor.move_on_first().unwrap().is_some());
+        generation.fetch_add(1, Ordering::Release);
+        match unsafe { cursor.put_current(b"hello", b"there") } {
+            Err(Error::Mdb { source: MdbError::BadTxn, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+        drop(cursor);
+
+        let ended_at = generation.load(Ordering::Acquire);
+        wtxn.commit().unwrap();
+        assert_ne!(generation.load(Ordering::Acquire), ended_at);
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(db.get(&rtxn, "hello").unwrap().as_deref(), Some("world"));
+    }
+}
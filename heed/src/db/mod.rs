@@ -1,4 +1,5 @@
 mod polymorph;
+mod scratch;
 mod uniform;
 
 pub use self::polymorph::PolyDatabase;
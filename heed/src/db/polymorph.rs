@@ -1,9 +1,10 @@
 use std::borrow::Cow;
 use std::ops::{Bound, RangeBounds};
-use std::{mem, ptr};
+use std::{fmt, mem, ptr};
 
 use crate::mdb::error::mdb_result;
 use crate::mdb::ffi;
+use crate::mdb::op::MdbOp;
 use crate::types::DecodeIgnore;
 use crate::*;
 
@@ -108,11 +109,41 @@ pub struct PolyDatabase {
     pub(crate) dbi: ffi::MDB_dbi,
 }
 
+impl fmt::Debug for PolyDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PolyDatabase")
+            .field("name", &Env::opened_database_name(self.env_ident, self.dbi))
+            .field("dbi", &self.dbi)
+            .finish()
+    }
+}
+
 impl PolyDatabase {
     pub(crate) fn new(env_ident: usize, dbi: ffi::MDB_dbi) -> PolyDatabase {
         PolyDatabase { env_ident, dbi }
     }
 
+    /// The context of an LMDB operation on this database.
+    fn op(&self, name: &'static str) -> MdbOp<'static> {
+        MdbOp::new(name).db(self.env_ident, self.dbi)
+    }
+
+    /// Encode a key and check that its size is supported by the environment,
+    /// to report the database and the size instead of an opaque `MDB_BAD_VALSIZE`.
+    fn encode_key<'a, T, KC>(&self, txn: &RoTxn<T>, key: &'a KC::EItem) -> Result<Cow<'a, [u8]>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        let bytes = KC::bytes_encode(key).ok_or(Error::Encoding)?;
+        let max = txn.env.max_key_size();
+        if bytes.len() > max {
+            let database = txn.env.database_name(self.dbi);
+            return Err(Error::KeyTooLong { database, len: bytes.len(), max });
+        }
+
+        Ok(bytes)
+    }
+
     /// Retrieve the sequence of a database.
     ///
     /// This function allows to retrieve the unique positive integer of this database.
@@ -173,7 +204,7 @@ impl PolyDatabase {
 
         match result {
             Ok(()) => unsafe { Ok(value.assume_init()) },
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op("mdbx_dbi_sequence").txn(txn.kind()).error(e)),
         }
     }
 
@@ -232,7 +263,7 @@ impl PolyDatabase {
         match result {
             Ok(()) => unsafe { Ok(Some(value.assume_init())) },
             Err(Error::Other(c)) if c == i32::max_value() => Ok(None), // MDBX_RESULT_TRUE
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op("mdbx_dbi_sequence").txn(TxnKind::Write).error(e)),
         }
     }
 
@@ -284,7 +315,7 @@ impl PolyDatabase {
     {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
 
         let mut key_val = unsafe { crate::into_val(&key_bytes) };
         let mut data_val = mem::MaybeUninit::uninit();
@@ -300,7 +331,7 @@ impl PolyDatabase {
                 Ok(Some(data))
             }
             Err(e) if e.not_found() => Ok(None),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op("mdb_get").key(&key_bytes).txn(txn.kind()).error(e)),
         }
     }
 
@@ -361,7 +392,7 @@ impl PolyDatabase {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
         let mut cursor = RoCursor::new(txn, self.dbi)?;
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         cursor.move_on_key_greater_than_or_equal_to(&key_bytes)?;
 
         match cursor.move_on_prev() {
@@ -431,7 +462,7 @@ impl PolyDatabase {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
         let mut cursor = RoCursor::new(txn, self.dbi)?;
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         let result = match cursor.move_on_key_greater_than_or_equal_to(&key_bytes) {
             Ok(Some((key, data))) if key == &key_bytes[..] => Ok(Some((key, data))),
             Ok(_) => cursor.move_on_prev(),
@@ -505,7 +536,7 @@ impl PolyDatabase {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
         let mut cursor = RoCursor::new(txn, self.dbi)?;
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         let entry = match cursor.move_on_key_greater_than_or_equal_to(&key_bytes)? {
             Some((key, data)) if key > &key_bytes[..] => Some((key, data)),
             Some((_key, _data)) => cursor.move_on_next()?,
@@ -578,7 +609,7 @@ impl PolyDatabase {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
         let mut cursor = RoCursor::new(txn, self.dbi)?;
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         match cursor.move_on_key_greater_than_or_equal_to(&key_bytes) {
             Ok(Some((key, data))) => match (KC::bytes_decode(key), DC::bytes_decode(data)) {
                 (Some(key), Some(data)) => Ok(Some((key, data))),
@@ -748,19 +779,13 @@ impl PolyDatabase {
     pub fn len<'txn, T>(&self, txn: &'txn RoTxn<T>) -> Result<usize> {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
-        let mut cursor = RoCursor::new(txn, self.dbi)?;
-        let mut count = 0;
-
-        match cursor.move_on_first()? {
-            Some(_) => count += 1,
-            None => return Ok(0),
-        }
-
-        while let Some(_) = cursor.move_on_next()? {
-            count += 1;
-        }
+        // The database statistics keep track of the number of entries.
+        let mut stat = mem::MaybeUninit::uninit();
+        let result = unsafe { mdb_result(ffi::mdb_stat(txn.txn, self.dbi, stat.as_mut_ptr())) };
+        self.op("mdb_stat").txn(txn.kind()).result(result)?;
+        let stat = unsafe { stat.assume_init() };
 
-        Ok(count)
+        Ok(stat.ms_entries as usize)
     }
 
     /// Returns `true` if and only if this database is empty.
@@ -854,7 +879,8 @@ impl PolyDatabase {
     pub fn iter<'txn, T, KC, DC>(&self, txn: &'txn RoTxn<T>) -> Result<RoIter<'txn, KC, DC>> {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
-        RoCursor::new(txn, self.dbi).map(|cursor| RoIter::new(cursor))
+        let len = self.len(txn)?;
+        RoCursor::new(txn, self.dbi).map(|cursor| RoIter::new(cursor, len))
     }
 
     /// Return a mutable lexicographically ordered iterator of all key-value pairs in this database.
@@ -961,7 +987,8 @@ impl PolyDatabase {
     ) -> Result<RoRevIter<'txn, KC, DC>> {
         assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
 
-        RoCursor::new(txn, self.dbi).map(|cursor| RoRevIter::new(cursor))
+        let len = self.len(txn)?;
+        RoCursor::new(txn, self.dbi).map(|cursor| RoRevIter::new(cursor, len))
     }
 
     /// Return a mutable reversed lexicographically ordered iterator of all key-value pairs
@@ -1101,7 +1128,71 @@ impl PolyDatabase {
             Bound::Unbounded => Bound::Unbounded,
         };
 
-        RoCursor::new(txn, self.dbi).map(|cursor| RoRange::new(cursor, start_bound, end_bound))
+        let len = self.len(txn)?;
+        RoCursor::new(txn, self.dbi).map(|cursor| RoRange::new(cursor, start_bound, end_bound, len))
+    }
+
+    /// An estimation of the number of entries in a range of this database, without reading
+    /// them. It walks down the B-tree to both bounds of the range, its cost doesn't depend
+    /// on the number of entries in the range, the bounds being included or not is ignored.
+    #[cfg(feature = "mdbx")]
+    pub fn estimate_range<'a, T, KC, R>(&self, txn: &RoTxn<T>, range: &'a R) -> Result<usize>
+    where
+        KC: BytesEncode<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
+
+        let encode = |bound: Bound<&'a KC::EItem>| match bound {
+            Bound::Included(bound) | Bound::Excluded(bound) => {
+                KC::bytes_encode(bound).map(Some).ok_or(Error::Encoding)
+            }
+            Bound::Unbounded => Ok(None),
+        };
+        let start = encode(range.start_bound())?;
+        let end = encode(range.end_bound())?;
+
+        ffi::estimate_range(txn.txn, self.dbi, start.as_deref(), end.as_deref())
+    }
+
+    /// Return an owned query over a range of key-value pairs in this database,
+    /// that can be turned into a stream with [`RangeQuery::into_stream`].
+    ///
+    /// The bounds are encoded right away, the entries are only read by the stream,
+    /// in a read transaction of its own.
+    #[cfg(feature = "async")]
+    pub fn range_query<'a, KC, DC, R>(&self, env: &Env, range: &'a R) -> Result<RangeQuery<KC, DC>>
+    where
+        KC: BytesEncode<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        assert_eq!(self.env_ident, env.env_mut_ptr() as usize);
+
+        let start_bound = match range.start_bound() {
+            Bound::Included(bound) => {
+                let bytes = KC::bytes_encode(bound).ok_or(Error::Encoding)?;
+                Bound::Included(bytes.into_owned())
+            }
+            Bound::Excluded(bound) => {
+                let bytes = KC::bytes_encode(bound).ok_or(Error::Encoding)?;
+                Bound::Excluded(bytes.into_owned())
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        let end_bound = match range.end_bound() {
+            Bound::Included(bound) => {
+                let bytes = KC::bytes_encode(bound).ok_or(Error::Encoding)?;
+                Bound::Included(bytes.into_owned())
+            }
+            Bound::Excluded(bound) => {
+                let bytes = KC::bytes_encode(bound).ok_or(Error::Encoding)?;
+                Bound::Excluded(bytes.into_owned())
+            }
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        Ok(RangeQuery::new(env.clone(), self.dbi, start_bound, end_bound))
     }
 
     /// Return a mutable lexicographically ordered iterator of a range of
@@ -1274,7 +1365,9 @@ impl PolyDatabase {
             Bound::Unbounded => Bound::Unbounded,
         };
 
-        RoCursor::new(txn, self.dbi).map(|cursor| RoRevRange::new(cursor, start_bound, end_bound))
+        let len = self.len(txn)?;
+        RoCursor::new(txn, self.dbi)
+            .map(|cursor| RoRevRange::new(cursor, start_bound, end_bound, len))
     }
 
     /// Return a mutable reversed lexicographically ordered iterator of a range of
@@ -1671,24 +1764,71 @@ impl PolyDatabase {
     {
         assert_eq!(self.env_ident, txn.txn.env.env_mut_ptr() as usize);
 
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         let data_bytes: Cow<[u8]> = DC::bytes_encode(&data).ok_or(Error::Encoding)?;
+        self.put_bytes(txn, &key_bytes, &data_bytes)
+    }
 
+    /// Insert a key-value pair in this database like [`put`](PolyDatabase::put), the data is
+    /// encoded into a buffer of the thread, reused by the calls instead of allocating the
+    /// bytes of every value, see [`BytesEncodeInto`].
+    ///
+    /// ```
+    /// # use heed::EnvOpenOptions;
+    /// use heed::types::*;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempfile::tempdir()?;
+    /// # let env = EnvOpenOptions::new().open(dir.path())?;
+    /// let mut wtxn = env.write_txn()?;
+    /// let db = env.create_poly_database(&mut wtxn, None)?;
+    ///
+    /// for i in 0..100u32 {
+    ///     let point = (i, i * 2);
+    ///     db.put_buffered::<_, OwnedType<u32>, SerdeBincode<(u32, u32)>>(&mut wtxn, &i, &point)?;
+    /// }
+    ///
+    /// let point = db.get::<_, OwnedType<u32>, SerdeBincode<(u32, u32)>>(&wtxn, &42)?;
+    /// assert_eq!(point, Some((42, 84)));
+    /// wtxn.commit()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn put_buffered<'a, T, KC, DC>(
+        &self,
+        txn: &mut RwTxn<T>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<()>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncodeInto<'a>,
+    {
+        assert_eq!(self.env_ident, txn.txn.env.env_mut_ptr() as usize);
+
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
+        super::scratch::with_scratch(|data_bytes| {
+            DC::bytes_encode_into(data, data_bytes).ok_or(Error::Encoding)?;
+            self.put_bytes(txn, &key_bytes, data_bytes)
+        })
+    }
+
+    fn put_bytes<T>(&self, txn: &mut RwTxn<T>, key_bytes: &[u8], data_bytes: &[u8]) -> Result<()> {
         let mut key_val = unsafe { crate::into_val(&key_bytes) };
         let mut data_val = unsafe { crate::into_val(&data_bytes) };
         let flags = 0;
 
-        unsafe {
-            mdb_result(ffi::mdb_put(txn.txn.txn, self.dbi, &mut key_val, &mut data_val, flags))?
-        }
+        let result = unsafe {
+            mdb_result(ffi::mdb_put(txn.txn.txn, self.dbi, &mut key_val, &mut data_val, flags))
+        };
 
-        Ok(())
+        self.op("mdb_put").key(key_bytes).txn(TxnKind::Write).result(result)
     }
 
     /// Append the given key/data pair to the end of the database.
     ///
     /// This option allows fast bulk loading when keys are already known to be in the correct order.
-    /// Loading unsorted keys will cause a MDB_KEYEXIST error.
+    /// Loading unsorted keys returns an [`Error::UnsortedAppend`] reporting the appended key
+    /// and the last key of the database.
     ///
     /// ```
     /// # use std::fs;
@@ -1733,18 +1873,32 @@ impl PolyDatabase {
     {
         assert_eq!(self.env_ident, txn.txn.env.env_mut_ptr() as usize);
 
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         let data_bytes: Cow<[u8]> = DC::bytes_encode(&data).ok_or(Error::Encoding)?;
 
         let mut key_val = unsafe { crate::into_val(&key_bytes) };
         let mut data_val = unsafe { crate::into_val(&data_bytes) };
         let flags = ffi::MDB_APPEND;
 
-        unsafe {
-            mdb_result(ffi::mdb_put(txn.txn.txn, self.dbi, &mut key_val, &mut data_val, flags))?
+        let result = unsafe {
+            mdb_result(ffi::mdb_put(txn.txn.txn, self.dbi, &mut key_val, &mut data_val, flags))
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(MdbError::KeyExist) => Err(self.unsorted_append(&txn.txn, &key_bytes)),
+            Err(e) => Err(self.op("mdb_put").key(&key_bytes).txn(TxnKind::Write).error(e)),
         }
+    }
 
-        Ok(())
+    /// The error of a key appended after a key it doesn't sort after, reporting the last key.
+    fn unsorted_append<T>(&self, txn: &RoTxn<T>, key: &[u8]) -> Error {
+        let last = match RoCursor::new(txn, self.dbi).and_then(|mut c| c.move_on_last()) {
+            Ok(last) => last.map_or_else(Vec::new, |(last, _)| last.to_vec()),
+            Err(e) => return e,
+        };
+        let database = txn.env.database_name(self.dbi);
+        Error::UnsortedAppend { database, last, key: key.to_vec() }
     }
 
     /// Deletes a key-value pairs in this database.
@@ -1796,7 +1950,7 @@ impl PolyDatabase {
     {
         assert_eq!(self.env_ident, txn.txn.env.env_mut_ptr() as usize);
 
-        let key_bytes: Cow<[u8]> = KC::bytes_encode(&key).ok_or(Error::Encoding)?;
+        let key_bytes: Cow<[u8]> = self.encode_key::<T, KC>(txn, key)?;
         let mut key_val = unsafe { crate::into_val(&key_bytes) };
 
         let result = unsafe {
@@ -1806,7 +1960,7 @@ impl PolyDatabase {
         match result {
             Ok(()) => Ok(true),
             Err(e) if e.not_found() => Ok(false),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(self.op("mdb_del").key(&key_bytes).txn(TxnKind::Write).error(e)),
         }
     }
 
@@ -1929,7 +2083,8 @@ impl PolyDatabase {
     pub fn clear<T>(&self, txn: &mut RwTxn<T>) -> Result<()> {
         assert_eq!(self.env_ident, txn.txn.env.env_mut_ptr() as usize);
 
-        unsafe { mdb_result(ffi::mdb_drop(txn.txn.txn, self.dbi, 0)).map_err(Into::into) }
+        let result = unsafe { mdb_result(ffi::mdb_drop(txn.txn.txn, self.dbi, 0)) };
+        self.op("mdb_drop").txn(TxnKind::Write).result(result)
     }
 
     /// Read this polymorphic database like a typed one, specifying the codecs.
@@ -0,0 +1,57 @@
+//! The buffers the values are encoded into by the [`BytesEncodeInto`](crate::BytesEncodeInto)
+//! codecs, one per thread, reused by the puts instead of allocating the bytes of every value.
+
+use std::cell::RefCell;
+use std::mem;
+
+/// The capacity above which a buffer is released after use, a large value isn't kept.
+const MAX_KEPT_CAPACITY: usize = 1024 * 1024;
+
+thread_local! {
+    static SCRATCH: RefCell<Vec<u8>> = RefCell::new(Vec::new());
+}
+
+/// Calls `f` with the empty buffer of the thread. The buffer is taken during the call,
+/// a codec putting values while encoding one is given a new buffer.
+pub(crate) fn with_scratch<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    let mut buffer = SCRATCH.with(|scratch| mem::take(&mut *scratch.borrow_mut()));
+    buffer.clear();
+    let result = f(&mut buffer);
+    if buffer.capacity() <= MAX_KEPT_CAPACITY {
+        SCRATCH.with(|scratch| *scratch.borrow_mut() = buffer);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let capacity = with_scratch(|buffer| {
+            buffer.extend_from_slice(&[1; 100]);
+            buffer.capacity()
+        });
+        with_scratch(|buffer| {
+            assert!(buffer.is_empty());
+            assert_eq!(buffer.capacity(), capacity);
+        });
+
+        // A large buffer isn't kept, the next call is given an empty one.
+        with_scratch(|buffer| buffer.resize(MAX_KEPT_CAPACITY + 1, 0));
+        with_scratch(|buffer| assert_eq!(buffer.capacity(), 0));
+    }
+
+    #[test]
+    fn nested_calls_are_given_another_buffer() {
+        with_scratch(|outer| {
+            outer.extend_from_slice(b"outer");
+            with_scratch(|inner| {
+                assert!(inner.is_empty());
+                inner.extend_from_slice(b"inner");
+            });
+            assert_eq!(outer, b"outer");
+        });
+    }
+}
@@ -1,4 +1,4 @@
-use std::marker;
+use std::{any, fmt, marker};
 use std::ops::RangeBounds;
 
 use crate::mdb::ffi;
@@ -823,6 +823,20 @@ impl<KC, DC> Database<KC, DC> {
         self.dyndb.range::<T, KC, DC, R>(txn, range)
     }
 
+    /// Return an owned query over a range of key-value pairs in this database,
+    /// that can be turned into a stream with [`RangeQuery::into_stream`].
+    ///
+    /// The bounds are encoded right away, the entries are only read by the stream,
+    /// in a read transaction of its own.
+    #[cfg(feature = "async")]
+    pub fn range_query<'a, R>(&self, env: &Env, range: &'a R) -> Result<RangeQuery<KC, DC>>
+    where
+        KC: BytesEncode<'a>,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.dyndb.range_query::<KC, DC, R>(env, range)
+    }
+
     /// Return a mutable lexicographically ordered iterator of a range of
     /// key-value pairs in this database.
     ///
@@ -1285,10 +1299,26 @@ impl<KC, DC> Database<KC, DC> {
         self.dyndb.put::<T, KC, DC>(txn, key, data)
     }
 
+    /// Insert a key-value pair in this database like [`put`](Database::put), the data is
+    /// encoded into a buffer reused by the calls, see [`PolyDatabase::put_buffered`].
+    pub fn put_buffered<'a, T>(
+        &self,
+        txn: &mut RwTxn<T>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<()>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncodeInto<'a>,
+    {
+        self.dyndb.put_buffered::<T, KC, DC>(txn, key, data)
+    }
+
     /// Append the given key/data pair to the end of the database.
     ///
     /// This option allows fast bulk loading when keys are already known to be in the correct order.
-    /// Loading unsorted keys will cause a MDB_KEYEXIST error.
+    /// Loading unsorted keys returns an [`Error::UnsortedAppend`] reporting the appended key
+    /// and the last key of the database.
     ///
     /// ```
     /// # use std::fs;
@@ -1604,3 +1634,14 @@ impl<KC, DC> Clone for Database<KC, DC> {
 }
 
 impl<KC, DC> Copy for Database<KC, DC> {}
+
+impl<KC, DC> fmt::Debug for Database<KC, DC> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("name", &Env::opened_database_name(self.dyndb.env_ident, self.dyndb.dbi))
+            .field("dbi", &self.dyndb.dbi)
+            .field("key", &any::type_name::<KC>())
+            .field("data", &any::type_name::<DC>())
+            .finish()
+    }
+}
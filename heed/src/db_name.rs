@@ -0,0 +1,80 @@
+use std::fmt;
+
+/// The name of a database, declared once as a constant with [`db_names!`](crate::db_names)
+/// so that a misspelled name is a compile error instead of a new empty database.
+///
+/// It is accepted wherever a database or a [`Store`](crate::store::Store) table is opened
+/// or created by name.
+///
+/// ```
+/// # use heed::EnvOpenOptions;
+/// use heed::store::Store;
+/// use heed::types::{OwnedType, Str};
+/// use heed::{db_names, Database};
+///
+/// db_names! {
+///     /// The balances of the accounts, by account name.
+///     pub ACCOUNTS = "accounts";
+///     EVENTS = "events";
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempfile::tempdir()?;
+/// # let env = EnvOpenOptions::new()
+/// #     .map_size(10 * 1024 * 1024) // 10MB
+/// #     .max_dbs(3000)
+/// #     .open(dir.path())?;
+/// let mut wtxn = env.write_txn()?;
+/// let accounts: Database<Str, OwnedType<u64>> = env.create_database(&mut wtxn, ACCOUNTS)?;
+/// accounts.put(&mut wtxn, "alice", &10)?;
+/// wtxn.commit()?;
+///
+/// let events = env.typed::<Str, Str>(EVENTS, &())?;
+/// assert!(events.is_empty(&env.rtx()?)?);
+/// assert_eq!(ACCOUNTS.as_str(), "accounts");
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DbName(&'static str);
+
+impl DbName {
+    pub const fn new(name: &'static str) -> DbName {
+        DbName(name)
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        self.0
+    }
+}
+
+impl AsRef<str> for DbName {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl<'a> From<DbName> for Option<&'a str> {
+    fn from(name: DbName) -> Option<&'a str> {
+        Some(name.0)
+    }
+}
+
+impl fmt::Display for DbName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Declares [`DbName`] constants, see its documentation.
+///
+/// Every constant is declared as `NAME = "name";`, preceded by its visibility
+/// and attributes, like its documentation.
+#[macro_export]
+macro_rules! db_names {
+    ($($(#[$attr:meta])* $vis:vis $constant:ident = $name:expr;)*) => {
+        $(
+            $(#[$attr])*
+            $vis const $constant: $crate::DbName = $crate::DbName::new($name);
+        )*
+    };
+}
@@ -1,36 +1,122 @@
 use std::any::TypeId;
 use std::collections::hash_map::{Entry, HashMap};
+use std::collections::VecDeque;
 use std::ffi::CString;
-#[cfg(windows)]
-use std::ffi::OsStr;
 use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::{Bound, Range, RangeBounds};
 #[cfg(unix)]
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
-use std::{io, ptr, sync};
+use std::time::{Duration, Instant};
+use std::{fmt, io, ptr, str, sync, thread};
 
+use byteorder::{ByteOrder, NativeEndian};
+use fs2::FileExt;
 use once_cell::sync::Lazy;
 use synchronoise::event::SignalEvent;
 
-use crate::cursor::RoCursor;
+use crate::cursor::{RoCursor, RwCursor};
 use crate::flags::Flags;
 use crate::mdb::error::mdb_result;
 use crate::mdb::ffi;
-use crate::{Database, Error, PolyDatabase, Result, RoTxn, RwTxn};
+use crate::mdb::op::MdbOp;
+use crate::{Database, Error, OwnedRoTxn, PolyDatabase, Result, RoTxn, RwTxn, TxnKind};
 
 /// The list of opened environments, the value is an optional environment, it is None
 /// when someone asks to close the environment, closing is a two-phase step, to make sure
 /// noone tries to open the same environment between these two phases.
 ///
 /// Trying to open a None marked environment returns an error to the user trying to open it.
-static OPENED_ENV: Lazy<RwLock<HashMap<PathBuf, EnvEntry>>> = Lazy::new(RwLock::default);
+///
+/// Environments are keyed by the identity of the file they live in rather than by path,
+/// this way the same environment reached through a symlink, a bind mount or a hard link
+/// is never opened twice, which LMDB forbids.
+static OPENED_ENV: Lazy<RwLock<HashMap<EnvId, EnvEntry>>> = Lazy::new(RwLock::default);
 
 struct EnvEntry {
     env: Option<Env>,
     signal_event: Arc<SignalEvent>,
     options: EnvOpenOptions,
+    /// Whether the entry must be kept once the environment is closed, so that
+    /// no one can open it until the one that reserved it removes the entry.
+    reserved: bool,
+}
+
+/// Identifies the file an environment lives in, independently of the path used to reach it.
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EnvId {
+    dev: u64,
+    ino: u64,
+}
+
+/// Identifies the file an environment lives in, independently of the path used to reach it.
+#[cfg(windows)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct EnvId {
+    volume: u32,
+    index: u64,
+}
+
+#[cfg(unix)]
+fn env_id(path: &Path) -> io::Result<EnvId> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = path.metadata()?;
+    Ok(EnvId { dev: metadata.dev(), ino: metadata.ino() })
+}
+
+/// The stable standard library doesn't expose the volume serial number and the file index,
+/// they are asked to the system for a handle opened without any access.
+#[cfg(windows)]
+fn env_id(path: &Path) -> io::Result<EnvId> {
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::{AsRawHandle, RawHandle};
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct FileTime {
+        low: u32,
+        high: u32,
+    }
+
+    // Only some of the fields are read, the other ones give the layout the system fills.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct ByHandleFileInformation {
+        attributes: u32,
+        creation_time: FileTime,
+        last_access_time: FileTime,
+        last_write_time: FileTime,
+        volume_serial_number: u32,
+        size_high: u32,
+        size_low: u32,
+        number_of_links: u32,
+        index_high: u32,
+        index_low: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetFileInformationByHandle(file: RawHandle, info: *mut ByHandleFileInformation) -> i32;
+    }
+
+    // Without it directories, where the environments usually live, can't be opened.
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    let file =
+        File::options().access_mode(0).custom_flags(FILE_FLAG_BACKUP_SEMANTICS).open(path)?;
+    let mut info = std::mem::MaybeUninit::uninit();
+    // safety: the handle is open during the call, which fills the information when it succeeds.
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), info.as_mut_ptr()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let info = unsafe { info.assume_init() };
+    let index = (info.index_high as u64) << 32 | info.index_low as u64;
+    Ok(EnvId { volume: info.volume_serial_number, index })
 }
 
 // Thanks to the mozilla/rkv project
@@ -43,52 +129,193 @@ fn canonicalize_path(path: &Path) -> io::Result<PathBuf> {
 
 #[cfg(windows)]
 fn canonicalize_path(path: &Path) -> io::Result<PathBuf> {
+    /// Paths this long can only be reached in their verbatim (`\\?\`) form.
+    const MAX_PATH: usize = 260;
+
     let canonical = path.canonicalize()?;
     let url = url::Url::from_file_path(&canonical)
         .map_err(|_e| io::Error::new(io::ErrorKind::Other, "URL passing error"))?;
-    url.to_file_path()
-        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "path canonicalization error"))
+    let simplified = url
+        .to_file_path()
+        .map_err(|_e| io::Error::new(io::ErrorKind::Other, "path canonicalization error"))?;
+
+    if simplified.as_os_str().len() >= MAX_PATH {
+        Ok(canonical)
+    } else {
+        Ok(simplified)
+    }
 }
 
-#[cfg(windows)]
-/// Adding a 'missing' trait from windows OsStrExt
-trait OsStrExtLmdb {
-    fn as_bytes(&self) -> &[u8];
+/// Converts a path into the nul-terminated string the engine expects.
+#[cfg(unix)]
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_e| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
 }
+
+/// Converts a path into the nul-terminated string the engine expects.
+///
+/// The engine converts UTF-8 paths into UTF-16 itself before calling the wide-char
+/// Windows APIs, paths that aren't valid unicode cannot be represented and are refused.
 #[cfg(windows)]
-impl OsStrExtLmdb for OsStr {
-    fn as_bytes(&self) -> &[u8] {
-        &self.to_str().unwrap().as_bytes()
-    }
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid unicode"))?;
+    CString::new(path)
+        .map_err(|_e| io::Error::new(io::ErrorKind::InvalidInput, "path contains a nul byte"))
 }
 
 #[cfg(windows)]
-fn get_file_fd(file: &File) -> std::os::windows::io::RawHandle {
-    use std::os::windows::io::AsRawHandle;
+fn get_file_fd(file: &impl std::os::windows::io::AsRawHandle) -> std::os::windows::io::RawHandle {
     file.as_raw_handle()
 }
 
 #[cfg(unix)]
-fn get_file_fd(file: &File) -> std::os::unix::io::RawFd {
-    use std::os::unix::io::AsRawFd;
+fn get_file_fd(file: &impl std::os::unix::io::AsRawFd) -> std::os::unix::io::RawFd {
     file.as_raw_fd()
 }
 
+/// Asks the kernel to read the whole file into its page cache, in the background.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn prefetch_file(file: File) -> io::Result<()> {
+    match unsafe { libc::posix_fadvise(get_file_fd(&file), 0, 0, libc::POSIX_FADV_WILLNEED) } {
+        0 => Ok(()),
+        errno => Err(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+/// Reads the whole file from a background thread, the only portable way to load it into
+/// the page cache without blocking the caller.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn prefetch_file(mut file: File) -> io::Result<()> {
+    std::thread::Builder::new()
+        .name("heed-prefetch".into())
+        .spawn(move || io::copy(&mut file, &mut io::sink()))
+        .map(drop)
+}
+
+/// The addresses of the map of a data file, which LMDB only reports when it is fixed, found
+/// among the mappings of the process by the inode and path of the file. The kernel splits
+/// the mapping in parts when they are given different advice, the parts are put together.
+#[cfg(target_os = "linux")]
+fn map_region(data: &Path) -> io::Result<Range<usize>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let inode = data.metadata()?.ino().to_string();
+    let path = canonicalize_path(data)?;
+    let maps = std::fs::read_to_string("/proc/self/maps")?;
+
+    // The lines are "<start>-<end> <perms> <offset> <dev> <inode> <path>".
+    let mut region: Option<Range<usize>> = None;
+    for line in maps.lines() {
+        let fields: Vec<_> = line.splitn(6, ' ').collect();
+        match fields[..] {
+            [addresses, _, _, _, ino, mapped]
+                if ino == inode && Path::new(mapped.trim_start()) == path =>
+            {
+                let mut addresses = addresses.split('-').map(|a| usize::from_str_radix(a, 16));
+                if let (Some(Ok(start)), Some(Ok(end))) = (addresses.next(), addresses.next()) {
+                    region = Some(match region {
+                        Some(region) => region.start.min(start)..region.end.max(end),
+                        None => start..end,
+                    });
+                }
+            }
+            _ => (),
+        }
+    }
+
+    region.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, "the map of the environment was not found")
+    })
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn map_region(_data: &Path) -> io::Result<Range<usize>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "the map of the environment can't be found"))
+}
+
+/// The sizes of the map of an environment.
+///
+/// MDBX manages the geometry itself. LMDB has a fixed map size, which heed emulates the
+/// geometry on: the map is opened with `map_size`, or `min_size`, and grows by `growth_step`
+/// up to `max_size` before write transactions begin, `shrink_step` is ignored.
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct Geometry {
-    #[cfg(feature = "mdbx")]
     min_size: Option<usize>,
-    #[cfg(feature = "mdbx")]
     max_size: Option<usize>,
-    #[cfg(feature = "mdbx")]
     growth_step: Option<usize>,
-    #[cfg(feature = "mdbx")]
     shrink_step: Option<usize>,
 
     map_size: Option<usize>,
     page_size: Option<usize>,
 }
 
+impl Geometry {
+    /// Whether an environment opened with the `opened` geometry can serve a request
+    /// for this one, the map must be at least as big and the page size the same.
+    fn is_compatible_with(&self, opened: &Geometry) -> bool {
+        let same = |requested: Option<usize>, opened: Option<usize>| {
+            requested.is_none() || requested == opened
+        };
+        if !same(self.min_size, opened.min_size)
+            || !same(self.max_size, opened.max_size)
+            || !same(self.growth_step, opened.growth_step)
+            || !same(self.shrink_step, opened.shrink_step)
+        {
+            return false;
+        }
+
+        at_most(self.map_size, opened.map_size)
+            && self.page_size.map_or(true, |r| opened.page_size == Some(r))
+    }
+}
+
+/// The largest page size MDBX supports.
+#[cfg(feature = "mdbx")]
+const MAX_PAGE_SIZE: usize = 64 * 1024;
+
+fn validate_page_size(size: usize) -> Result<()> {
+    let system = page_size::get();
+    let invalid = |msg: String| Err(Error::Io(io::Error::new(io::ErrorKind::InvalidInput, msg)));
+
+    if !size.is_power_of_two() || size % system != 0 {
+        return invalid(format!(
+            "page size ({}) must be a power of two multiple of the system page size ({})",
+            size, system
+        ));
+    }
+
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    if size != system {
+        return invalid(format!(
+            "page size ({}) is not the system page size ({}), the only one LMDB supports",
+            size, system
+        ));
+    }
+
+    #[cfg(feature = "mdbx")]
+    if size > MAX_PAGE_SIZE {
+        return invalid(format!(
+            "page size ({}) must not be greater than {} bytes",
+            size, MAX_PAGE_SIZE
+        ));
+    }
+
+    Ok(())
+}
+
+/// A requested limit is satisfied when it is unspecified or lower
+/// than or equal to the one the environment was opened with.
+fn at_most<T: PartialOrd>(requested: Option<T>, opened: Option<T>) -> bool {
+    match (requested, opened) {
+        (None, _) => true,
+        (Some(requested), Some(opened)) => requested <= opened,
+        (Some(_), None) => false,
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct EnvOpenOptions {
     geometry: Geometry,
@@ -97,41 +324,68 @@ pub struct EnvOpenOptions {
     max_dbs: Option<u32>,
 
     flags: u32, // LMDB flags
+    advisory_lock: bool,
+    permissions: u32,
+    max_idle_dbs: usize,
+    max_read_txns: Option<usize>,
 }
 
 impl EnvOpenOptions {
     pub fn new() -> EnvOpenOptions {
-        EnvOpenOptions { geometry: Geometry::default(), max_readers: None, max_dbs: None, flags: 0 }
+        EnvOpenOptions {
+            geometry: Geometry::default(),
+            max_readers: None,
+            max_dbs: None,
+            flags: 0,
+            advisory_lock: false,
+            permissions: 0o600,
+            max_idle_dbs: 0,
+            max_read_txns: None,
+        }
     }
 
     pub fn map_size(&mut self, size: usize) -> &mut Self {
         self.geometry.map_size = Some(size);
         self
     }
+    /// Set the size of the pages of the environment, it must be a power of two multiple of
+    /// the system page size. LMDB can only use the system page size, other sizes require MDBX.
+    ///
+    /// An invalid size is reported when the environment is opened.
     pub fn page_size(&mut self, size: usize) -> &mut Self {
         self.geometry.page_size = Some(size);
         self
     }
 
-    #[cfg(feature = "mdbx")]
+    /// The page size environments are created with by default, the page size of
+    /// the system: 4 KiB on most platforms, 16 KiB on Apple silicon.
+    pub fn recommended_page_size() -> usize {
+        page_size::get()
+    }
+
+    /// The size the map is opened with when no [`map_size`](EnvOpenOptions::map_size) is set.
     pub fn min_size(&mut self, size: usize) -> &mut Self {
         self.geometry.min_size = Some(size);
         self
     }
 
-    #[cfg(feature = "mdbx")]
+    /// The size the map never grows beyond.
     pub fn max_size(&mut self, size: usize) -> &mut Self {
         self.geometry.max_size = Some(size);
         self
     }
 
-    #[cfg(feature = "mdbx")]
+    /// The size the map grows by when it is almost full.
+    ///
+    /// With LMDB the map grows when a write transaction begins with less than a step left,
+    /// and only if no other transaction is alive in the process: the map can't be moved under
+    /// them. A write transaction writing more than a step can still fail with `MapFull`.
     pub fn growth_step(&mut self, size: usize) -> &mut Self {
         self.geometry.growth_step = Some(size);
         self
     }
 
-    #[cfg(feature = "mdbx")]
+    /// Only used by MDBX, LMDB never shrinks its file.
     pub fn shrink_threshold(&mut self, size: usize) -> &mut Self {
         self.geometry.shrink_step = Some(size);
         self
@@ -147,6 +401,24 @@ impl EnvOpenOptions {
         self
     }
 
+    /// The number of databases kept open once all their handles are closed, see
+    /// [`Env::close_database`], the least recently closed ones are closed first.
+    /// None are kept by default.
+    pub fn max_idle_dbs(&mut self, dbs: usize) -> &mut Self {
+        self.max_idle_dbs = dbs;
+        self
+    }
+
+    /// The maximum number of read transactions alive at the same time in the process,
+    /// a read transaction beyond it fails with a [`ReadersFull`](Error::ReadersFull) error.
+    ///
+    /// Setting it below the [`max_readers`](EnvOpenOptions::max_readers) leaves slots of
+    /// the reader table to the other processes and to the write transactions of this one.
+    pub fn max_concurrent_read_txns(&mut self, txns: usize) -> &mut Self {
+        self.max_read_txns = Some(txns);
+        self
+    }
+
     /// Set one or more LMDB flags (see http://www.lmdb.tech/doc/group__mdb__env.html).
     /// ```
     /// use std::fs;
@@ -194,44 +466,135 @@ impl EnvOpenOptions {
         self
     }
 
+    /// Open the environment as a single data file instead of a directory.
+    ///
+    /// The path given to [`open`](EnvOpenOptions::open) is then the data file itself, it is
+    /// created if missing, and the lock file is stored next to it with a `-lock` suffix
+    /// (`-lck` with MDBX). This is the safe equivalent of setting the `MdbNoSubDir` flag.
+    pub fn file_mode(&mut self) -> &mut Self {
+        self.flags |= Flags::MdbNoSubDir as u32;
+        self
+    }
+
+    /// Open the environment read-only without taking part in its locking, for a process that
+    /// can't write to the lock file, e.g. analytics reading the environment of another user.
+    ///
+    /// With LMDB the lock file isn't used at all (`MDB_NOLOCK`), with MDBX the environment
+    /// is opened in exclusive mode. Write transactions are refused with [`Error::ReadOnly`].
+    ///
+    /// **The reads are not isolated from the writers**: the processes writing to the environment
+    /// don't know about these readers and reuse the pages they are reading, a read transaction
+    /// can then see corrupted data, fail or crash. This is only sound when the environment is not
+    /// written to while it is opened this way, e.g. on a stopped service or on a copy.
+    pub fn read_only_unlocked(&mut self) -> &mut Self {
+        #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+        let unlocked = Flags::MdbNoLock as u32;
+        #[cfg(all(not(feature = "lmdb"), feature = "mdbx"))]
+        let unlocked = Flags::MdbExclusive as u32;
+
+        self.flags |= Flags::MdbRdOnly as u32 | unlocked;
+        self
+    }
+
+    /// Set the permissions the files of the environment are created with, `0o600` by default.
+    ///
+    /// The mode is applied to the files that don't exist yet and is masked by the umask of the
+    /// process, e.g. `0o640` lets the members of the group, like a backup agent, read them.
+    #[cfg(unix)]
+    pub fn file_permissions(&mut self, mode: u32) -> &mut Self {
+        self.permissions = mode;
+        self
+    }
+
+    /// Take an advisory lock on a `heed.lock` file next to the environment for as long as it
+    /// is opened, the lock file records the flags and the map size the environment was opened with.
+    ///
+    /// Another process trying to open the same environment with different options then gets
+    /// a [`BadOpenOptions`](Error::BadOpenOptions) error instead of undefined behavior. The lock
+    /// is released by the OS when the process dies, a crashed process never keeps it held.
+    ///
+    /// Only processes that enable this option check the lock.
+    pub fn advisory_lock(&mut self, enabled: bool) -> &mut Self {
+        self.advisory_lock = enabled;
+        self
+    }
+
+    /// Whether an environment opened with the `opened` options can be returned
+    /// to someone asking for these options.
+    ///
+    /// The flags must be the same but the limits only need to be satisfied,
+    /// e.g. asking for fewer databases than the opened environment supports is fine.
+    pub fn is_compatible_with(&self, opened: &EnvOpenOptions) -> bool {
+        self.flags == opened.flags
+            && at_most(self.max_readers, opened.max_readers)
+            && at_most(self.max_dbs, opened.max_dbs)
+            && self.geometry.is_compatible_with(&opened.geometry)
+    }
+
+    /// Open an environment at the given path, or return the already opened one
+    /// if its options are [compatible](EnvOpenOptions::is_compatible_with) with these.
     pub fn open<P: AsRef<Path>>(&self, path: P) -> Result<Env> {
-        let path = canonicalize_path(path.as_ref())?;
+        self.raw_open(path.as_ref(), false)
+    }
+
+    /// Open an environment at the given path, or return the already opened one
+    /// only if it was opened with exactly the same options.
+    pub fn open_exclusive<P: AsRef<Path>>(&self, path: P) -> Result<Env> {
+        self.raw_open(path.as_ref(), true)
+    }
+
+    fn raw_open(&self, path: &Path, strict: bool) -> Result<Env> {
+        // In file mode the data file must exist for us to identify it, the engine
+        // is fine with an empty file and initializes it as a new environment.
+        if self.flags & Flags::MdbNoSubDir as u32 != 0 {
+            self.create_options().write(true).truncate(false).open(path)?;
+        }
 
+        let path = canonicalize_path(path)?;
         let mut lock = OPENED_ENV.write().unwrap();
+        self.open_in(&mut lock, path, strict)
+    }
+
+    /// Opens the environment at the given canonical path and registers it in `opened`.
+    fn open_in(
+        &self,
+        opened: &mut HashMap<EnvId, EnvEntry>,
+        path: PathBuf,
+        strict: bool,
+    ) -> Result<Env> {
+        let id = env_id(&path)?;
 
-        match lock.entry(path) {
+        match opened.entry(id) {
             Entry::Occupied(entry) => {
-                if &entry.get().options != self {
+                let options = &entry.get().options;
+                let compatible =
+                    if strict { options == self } else { self.is_compatible_with(options) };
+                if !compatible {
                     return Err(Error::BadOpenOptions);
                 }
                 entry.get().env.clone().ok_or(Error::DatabaseClosing)
             }
             Entry::Vacant(entry) => {
-                let path = entry.key();
-                let path_str = CString::new(path.as_os_str().as_bytes()).unwrap();
+                let id = entry.key().clone();
+                let path_str = path_to_cstring(&path)?;
+                let lock_file = if self.advisory_lock {
+                    Some(self.acquire_advisory_lock(&path)?)
+                } else {
+                    None
+                };
+
+                if let Some(size) = self.geometry.page_size {
+                    validate_page_size(size)?;
+                }
 
                 unsafe {
                     let mut env: *mut ffi::MDB_env = ptr::null_mut();
                     mdb_result(ffi::mdb_env_create(&mut env))?;
 
-                    // if let Some(size) = self.geometry.page_size {
-                    //     if size % page_size::get() != 0 {
-                    //         let msg = format!(
-                    //             "map size ({}) must be a multiple of the system page size ({})",
-                    //             size,
-                    //             page_size::get()
-                    //         );
-                    //         return Err(Error::Io(io::Error::new(
-                    //             io::ErrorKind::InvalidInput,
-                    //             msg,
-                    //         )));
-                    //     }
-                    // }
                     #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
-                    mdb_result(ffi::mdb_env_set_mapsize(
-                        env,
-                        self.geometry.page_size.unwrap_or(page_size::get()),
-                    ))?;
+                    if let Some(size) = self.geometry.map_size.or(self.geometry.min_size) {
+                        mdb_result(ffi::mdb_env_set_mapsize(env, size))?;
+                    }
                     #[cfg(all(not(feature = "lmdb"), feature = "mdbx"))]
                     {
                         mdb_result(ffi::mdb_env_set_geometry(
@@ -253,17 +616,14 @@ impl EnvOpenOptions {
                         mdb_result(ffi::mdb_env_set_maxdbs(env, dbs))?;
                     }
 
-                    // When the `read-txn-no-tls` feature is enabled, we must force LMDB
-                    // to avoid using the thread local storage, this way we allow users
-                    // to use references of RoTxn between threads safely.
-                    let flags = if cfg!(feature = "read-txn-no-tls") {
-                        self.flags | Flags::MdbNoTls as u32
-                    } else {
-                        self.flags
-                    };
+                    let flags = self.effective_flags();
 
-                    let result =
-                        mdb_result(ffi::mdb_env_open(env, path_str.as_ptr(), flags, 0o600));
+                    let result = mdb_result(ffi::mdb_env_open(
+                        env,
+                        path_str.as_ptr(),
+                        flags,
+                        self.permissions as _,
+                    ));
 
                     match result {
                         Ok(()) => {
@@ -271,41 +631,215 @@ impl EnvOpenOptions {
                             let inner = EnvInner {
                                 env,
                                 dbi_open_mutex: sync::Mutex::default(),
-                                path: path.clone(),
+                                commit_mutex: sync::Mutex::default(),
+                                max_idle_dbs: self.max_idle_dbs,
+                                max_read_txns: self.max_read_txns,
+                                read_txns: AtomicUsize::new(0),
+                                path,
+                                id,
+                                read_only: flags & Flags::MdbRdOnly as u32 != 0,
+                                closing: AtomicBool::new(false),
+                                txns: TxnGate::default(),
+                                #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+                                growth_step: self.geometry.growth_step,
+                                #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+                                max_size: self.geometry.max_size,
+                                _lock_file: lock_file,
                             };
                             let env = Env(Arc::new(inner));
                             let cache_entry = EnvEntry {
                                 env: Some(env.clone()),
                                 options: self.clone(),
                                 signal_event,
+                                reserved: false,
                             };
                             entry.insert(cache_entry);
                             Ok(env)
                         }
                         Err(e) => {
                             ffi::mdb_env_close(env);
-                            Err(e.into())
+                            Err(MdbOp::new("mdb_env_open").error(e))
                         }
                     }
                 }
             }
         }
     }
+
+    /// The flags that are effectively given to the engine when opening the environment.
+    fn effective_flags(&self) -> u32 {
+        // When the `read-txn-no-tls` feature is enabled, we must force LMDB
+        // to avoid using the thread local storage, this way we allow users
+        // to use references of RoTxn between threads safely.
+        if cfg!(feature = "read-txn-no-tls") {
+            self.flags | Flags::MdbNoTls as u32
+        } else {
+            self.flags
+        }
+    }
+
+    /// Options creating the missing files with the permissions of the environment.
+    fn create_options(&self) -> std::fs::OpenOptions {
+        let mut options = std::fs::OpenOptions::new();
+        options.create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, self.permissions);
+        options
+    }
+
+    /// Locks the advisory lock file of the environment at `path` in shared mode and
+    /// makes sure that the other processes holding it use the same options as us.
+    fn acquire_advisory_lock(&self, path: &Path) -> Result<File> {
+        let file_mode = self.flags & Flags::MdbNoSubDir as u32 != 0;
+        let mut file = self
+            .create_options()
+            .read(true)
+            .write(true)
+            .truncate(false)
+            .open(advisory_lock_path(path, file_mode))?;
+
+        let fingerprint =
+            format!("flags={:#x} map_size={:?}\n", self.effective_flags(), self.geometry.map_size);
+
+        if FileExt::try_lock_exclusive(&file).is_ok() {
+            // We are the only process using this environment, we can record our options.
+            // Locks cannot be downgraded atomically, another process could get the exclusive
+            // lock in between and rewrite the options, this is why we always check them below.
+            file.set_len(0)?;
+            file.write_all(fingerprint.as_bytes())?;
+            FileExt::unlock(&file)?;
+        }
+        FileExt::lock_shared(&file)?;
+
+        let mut recorded = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut recorded)?;
+
+        if recorded == fingerprint {
+            Ok(file)
+        } else {
+            Err(Error::BadOpenOptions)
+        }
+    }
+}
+
+/// Reads an unsigned integer stored in the native endianness by the engine.
+fn read_native_uint(bytes: &[u8]) -> u64 {
+    NativeEndian::read_uint(bytes, bytes.len())
+}
+
+/// The path of the advisory lock file of the environment at `path`.
+fn advisory_lock_path(path: &Path, file_mode: bool) -> PathBuf {
+    if file_mode {
+        let mut lock_path = path.as_os_str().to_os_string();
+        lock_path.push("-heed.lock");
+        PathBuf::from(lock_path)
+    } else {
+        path.join("heed.lock")
+    }
 }
 
 /// Returns a struct that allows to wait for the effective closing of an environment.
 pub fn env_closing_event<P: AsRef<Path>>(path: P) -> Option<EnvClosingEvent> {
+    let id = env_id(path.as_ref()).ok()?;
     let lock = OPENED_ENV.read().unwrap();
-    lock.get(path.as_ref()).map(|e| EnvClosingEvent(e.signal_event.clone()))
+    lock.get(&id).map(|e| EnvClosingEvent(e.signal_event.clone()))
 }
 
 #[derive(Clone)]
 pub struct Env(Arc<EnvInner>);
 
+impl fmt::Debug for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Env")
+            .field("path", &self.0.path)
+            .field("read_only", &self.0.read_only)
+            .field("opened_databases", &self.opened_databases())
+            .field("read_txns", &self.read_txns())
+            .field("closing", &self.is_closing())
+            .finish()
+    }
+}
+
 struct EnvInner {
     env: *mut ffi::MDB_env,
-    dbi_open_mutex: sync::Mutex<HashMap<u32, Option<(TypeId, TypeId)>>>,
+    dbi_open_mutex: sync::Mutex<OpenedDbis>,
+    /// Held by the commits of the write transactions, the ones committed without a sync
+    /// change the flags of the environment for their commit only.
+    commit_mutex: sync::Mutex<()>,
+    max_idle_dbs: usize,
+    max_read_txns: Option<usize>,
+    /// The number of read transactions alive in the process.
+    read_txns: AtomicUsize,
     path: PathBuf,
+    id: EnvId,
+    read_only: bool,
+    /// Set once the environment is prepared for closing, no transaction can begin anymore.
+    closing: AtomicBool,
+    txns: TxnGate,
+    /// The geometry emulated on LMDB, see [`Geometry`].
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    growth_step: Option<usize>,
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    max_size: Option<usize>,
+    /// Released after the environment is closed, when the inner fields are dropped.
+    _lock_file: Option<File>,
+}
+
+/// The databases opened in an environment.
+#[derive(Default)]
+struct OpenedDbis {
+    dbis: HashMap<ffi::MDB_dbi, OpenedDbi>,
+    /// The databases whose handles are all closed, from the least recently closed.
+    idle: VecDeque<ffi::MDB_dbi>,
+}
+
+struct OpenedDbi {
+    name: Option<String>,
+    types: Option<(TypeId, TypeId)>,
+    /// The number of times the database was opened and not closed.
+    handles: usize,
+}
+
+/// Counts the transactions of an environment alive in the process, the map of an
+/// LMDB environment can only be resized, and its databases closed, when there are none.
+#[derive(Default)]
+struct TxnGate(AtomicUsize);
+
+/// Set while the map is being resized or databases closed, transactions wait for it to be cleared.
+const RESIZING: usize = !(usize::MAX >> 1);
+
+impl TxnGate {
+    fn enter(&self) {
+        loop {
+            let current = self.0.load(Ordering::Acquire);
+            if current & RESIZING == 0 {
+                let next = current + 1;
+                let swap = self.0.compare_exchange_weak(
+                    current,
+                    next,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                );
+                if swap.is_ok() {
+                    return;
+                }
+            }
+            thread::yield_now();
+        }
+    }
+
+    fn leave(&self) {
+        self.0.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Run `fun` if no transaction is alive, the ones that begin meanwhile wait for it.
+    fn exclusive<R>(&self, fun: impl FnOnce() -> R) -> Option<R> {
+        self.0.compare_exchange(0, RESIZING, Ordering::AcqRel, Ordering::Acquire).ok()?;
+        let result = fun();
+        self.0.store(0, Ordering::Release);
+        Some(result)
+    }
 }
 
 unsafe impl Send for EnvInner {}
@@ -316,9 +850,13 @@ impl Drop for EnvInner {
     fn drop(&mut self) {
         let mut lock = OPENED_ENV.write().unwrap();
 
-        match lock.remove(&self.path) {
+        match lock.get(&self.id) {
             None => panic!("It seems another env closed this env before"),
-            Some(EnvEntry { signal_event, .. }) => {
+            Some(EnvEntry { signal_event, reserved, .. }) => {
+                let signal_event = signal_event.clone();
+                if !reserved {
+                    lock.remove(&self.id);
+                }
                 unsafe {
                     let _ = ffi::mdb_env_close(self.env);
                 }
@@ -329,6 +867,80 @@ impl Drop for EnvInner {
     }
 }
 
+/// A summary of the pages that are free in an environment, see [`Env::freelist_stats`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FreelistStats {
+    /// The size of a page in bytes.
+    pub page_size: u64,
+    /// The number of pages in the freelist.
+    pub free_pages: u64,
+    /// The number of free pages that can be reused right now,
+    /// the others are still visible to lagging readers.
+    pub reclaimable_pages: u64,
+    /// The length, in pages, of the longest run of contiguous free pages.
+    pub largest_run: u64,
+}
+
+/// Numbers stored along the data of an environment and updated transactionally,
+/// like an application-level format version or the last applied journal sequence number.
+///
+/// See [`Env::set_canary`] and [`Env::get_canary`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Canary {
+    pub x: u64,
+    pub y: u64,
+    pub z: u64,
+    /// The id of the transaction that last set the canary,
+    /// the value given to [`Env::set_canary`] is ignored.
+    pub v: u64,
+}
+
+/// The database emulating the MDBX canary on LMDB.
+#[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+const CANARY_DATABASE: &str = "__heed_canary";
+
+/// How the map of an environment is going to be accessed, see [`Env::advise`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Advice {
+    /// No particular access pattern, the default.
+    Normal,
+    /// The pages are accessed in a random order, the kernel doesn't read ahead of the faults.
+    Random,
+    /// The pages are accessed in order, the kernel reads ahead more aggressively.
+    Sequential,
+    /// The pages are going to be accessed soon, the kernel reads them in the background.
+    WillNeed,
+    /// The pages are not going to be accessed soon, the kernel releases them.
+    DontNeed,
+}
+
+/// A database or a table that was created, or that already existed,
+/// see [`Env::open_or_create_database`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Opened<T> {
+    Created(T),
+    Existing(T),
+}
+
+impl<T> Opened<T> {
+    pub fn is_created(&self) -> bool {
+        matches!(self, Opened::Created(_))
+    }
+
+    pub fn into_inner(self) -> T {
+        match self {
+            Opened::Created(inner) | Opened::Existing(inner) => inner,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Opened<U> {
+        match self {
+            Opened::Created(inner) => Opened::Created(f(inner)),
+            Opened::Existing(inner) => Opened::Existing(f(inner)),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum CompactionOption {
     Enabled,
@@ -338,13 +950,93 @@ pub enum CompactionOption {
 impl Env {
     /// The real size used by this environment on disk.
     pub fn real_disk_size(&self) -> Result<u64> {
-        let path = if self.contains_flag(Flags::MdbNoSubDir)? {
-            self.path().to_path_buf()
-        } else {
-            self.path().join("data.mdb")
+        let [data, _lock] = self.file_paths()?;
+        Ok(data.metadata()?.len())
+    }
+
+    /// Loads the data file of the environment into the page cache of the operating system,
+    /// so that the first reads after a cold start don't fault the pages in one by one.
+    ///
+    /// The whole file is loaded, whatever the databases that are going to be read: their
+    /// pages are interleaved in it and can't be told apart without reading them.
+    /// [`Table::warmup`](crate::store::Table::warmup) reads the pages of a single database
+    /// instead. The file is read in the background, by the kernel on Linux and FreeBSD and
+    /// by a thread of the process on the other platforms, the errors of this read are not
+    /// reported.
+    pub fn prefetch(&self) -> Result<()> {
+        let [data, _lock] = self.file_paths()?;
+        let file = File::open(data)?;
+        Ok(prefetch_file(file)?)
+    }
+
+    /// Advises the kernel about how the map of the environment is going to be accessed.
+    ///
+    /// LMDB already advises [`Advice::Random`] when opened with the `NO_READAHEAD` flag.
+    /// The map can only be found on Linux, the other unix platforms return an unsupported
+    /// error, like [`Env::mlock`].
+    #[cfg(unix)]
+    pub fn advise(&self, advice: Advice) -> Result<()> {
+        let advice = match advice {
+            Advice::Normal => libc::MADV_NORMAL,
+            Advice::Random => libc::MADV_RANDOM,
+            Advice::Sequential => libc::MADV_SEQUENTIAL,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+            Advice::DontNeed => libc::MADV_DONTNEED,
         };
+        let map = self.file_region(..)?;
+        match unsafe { libc::madvise(map.start as *mut _, map.end - map.start, advice) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error().into()),
+        }
+    }
+
+    /// Locks the pages of the map, from the `range` of bytes of the data file, in memory: they
+    /// are read if needed and stay in memory until they are unlocked with [`Env::munlock`].
+    ///
+    /// The range is clamped to the size of the data file, a bound of `usize::MAX` that can't
+    /// be made exclusive returns an invalid input error. Locking more than the
+    /// `RLIMIT_MEMLOCK` limit of the process fails, unless it is privileged.
+    #[cfg(unix)]
+    pub fn mlock<R: RangeBounds<usize>>(&self, range: R) -> Result<()> {
+        let region = self.file_region(range)?;
+        match unsafe { libc::mlock(region.start as *const _, region.end - region.start) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error().into()),
+        }
+    }
+
+    /// Unlocks the pages of the map locked by [`Env::mlock`].
+    #[cfg(unix)]
+    pub fn munlock<R: RangeBounds<usize>>(&self, range: R) -> Result<()> {
+        let region = self.file_region(range)?;
+        match unsafe { libc::munlock(region.start as *const _, region.end - region.start) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error().into()),
+        }
+    }
 
-        Ok(path.metadata()?.len())
+    /// The addresses of the `range` of bytes of the data file in the map, starting at a page
+    /// boundary. The map is larger than the file, the pages after its end must not be touched.
+    #[cfg(unix)]
+    fn file_region<R: RangeBounds<usize>>(&self, range: R) -> Result<Range<usize>> {
+        let [data, _lock] = self.file_paths()?;
+        let map = map_region(&data)?;
+        let len = (data.metadata()?.len() as usize).min(map.end - map.start);
+
+        let overflow = || io::Error::new(io::ErrorKind::InvalidInput, "the range bound overflows");
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).ok_or_else(overflow)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).ok_or_else(overflow)?,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let start = start.min(len) / page_size * page_size;
+        Ok(map.start + start..map.start + end.min(len).max(start))
     }
 
     /// Check if a flag was specified when opening the environment.
@@ -369,6 +1061,119 @@ impl Env {
         ffi::map_size(self.env_mut_ptr())
     }
 
+    /// The snapshot id of the oldest read transaction that still lags behind the last
+    /// committed transaction, if any.
+    ///
+    /// Such readers prevent the pages freed since their snapshot from being reused,
+    /// comparing this id with the [`id`](RoTxn::id) of a fresh transaction gives the
+    /// age of the oldest snapshot in use.
+    pub fn oldest_reader_id(&self) -> Result<Option<u64>> {
+        ffi::oldest_reader_id(self.env_mut_ptr())
+    }
+
+    /// The id of the last transaction committed to the environment, by this process
+    /// or by another one, the [`id`](RoTxn::id) of a read transaction started now.
+    pub fn last_txn_id(&self) -> Result<u64> {
+        ffi::last_txn_id(self.env_mut_ptr())
+    }
+
+    /// Waits until a transaction is committed after the transaction `since_txn_id`, by this
+    /// process or by another one, and returns the id of the last committed transaction,
+    /// `None` if none was committed before the `timeout`. A timeout of [`Duration::MAX`]
+    /// waits until a transaction is committed.
+    ///
+    /// The id of the last committed transaction is read from the shared memory of the
+    /// environment, it is polled at an interval that grows from 1ms to 16ms: a change is
+    /// seen a few milliseconds after the commit without reading the databases. The write
+    /// transactions that don't write anything aren't committed to the environment.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use heed::EnvOpenOptions;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let dir = tempfile::tempdir()?;
+    /// # let env = EnvOpenOptions::new().open(dir.path())?;
+    /// # use heed::types::Str;
+    /// # let mut wtxn = env.write_txn()?;
+    /// # let db = env.create_database::<Str, Str>(&mut wtxn, None)?;
+    /// # wtxn.commit()?;
+    /// let seen = env.read_txn()?.id();
+    /// assert_eq!(env.wait_for_change(seen, Duration::from_millis(10))?, None);
+    ///
+    /// let mut wtxn = env.write_txn()?;
+    /// db.put(&mut wtxn, "hello", "world")?;
+    /// wtxn.commit()?;
+    /// assert_eq!(env.wait_for_change(seen, Duration::from_secs(1))?, Some(seen + 1));
+    /// # Ok(()) }
+    /// ```
+    pub fn wait_for_change(&self, since_txn_id: u64, timeout: Duration) -> Result<Option<u64>> {
+        const MAX_INTERVAL: Duration = Duration::from_millis(16);
+
+        // A timeout too long to be added to the clock waits without a deadline.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut interval = Duration::from_millis(1);
+        loop {
+            let last = self.last_txn_id()?;
+            if last > since_txn_id {
+                return Ok(Some(last));
+            }
+
+            let sleep = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(None);
+                    }
+                    interval.min(deadline - now)
+                }
+                None => interval,
+            };
+            thread::sleep(sleep);
+            interval = (interval * 2).min(MAX_INTERVAL);
+        }
+    }
+
+    /// Iterates over the freelist of the environment and reports how much of the file
+    /// is made of free pages, which helps deciding when a compacting copy is worth it.
+    pub fn freelist_stats(&self) -> Result<FreelistStats> {
+        /// The database in which the engines store the free pages,
+        /// keyed by the id of the transaction that freed them.
+        const FREE_DBI: ffi::MDB_dbi = 0;
+
+        let mut stat = std::mem::MaybeUninit::uninit();
+        unsafe { mdb_result(ffi::mdb_env_stat(self.env_mut_ptr(), stat.as_mut_ptr()))? };
+        let stat = unsafe { stat.assume_init() };
+
+        let rtxn = self.read_txn()?;
+        let oldest = self.oldest_reader_id()?;
+        let mut cursor = RoCursor::new(&rtxn, FREE_DBI)?;
+
+        let mut stats = FreelistStats { page_size: stat.ms_psize as u64, ..Default::default() };
+        let mut pages = Vec::new();
+        while let Some((key, value)) = cursor.move_on_next()? {
+            // The value is a list of page numbers prefixed by its length.
+            let mut pgnos = value.chunks_exact(ffi::PGNO_SIZE);
+            let count = pgnos.next().map_or(0, read_native_uint);
+            let before = pages.len();
+            pages.extend(pgnos.take(count as usize).map(read_native_uint));
+
+            let freed = (pages.len() - before) as u64;
+            stats.free_pages += freed;
+            if oldest.map_or(true, |oldest| read_native_uint(key) < oldest) {
+                stats.reclaimable_pages += freed;
+            }
+        }
+
+        pages.sort_unstable();
+        let mut run = 0;
+        for (i, pgno) in pages.iter().enumerate() {
+            run = if i > 0 && pages[i - 1] + 1 == *pgno { run + 1 } else { 1 };
+            stats.largest_run = stats.largest_run.max(run);
+        }
+
+        Ok(stats)
+    }
+
     /// Returns the size used by all the databases in the environment without the free pages.
     pub fn non_free_pages_size(&self) -> Result<u64> {
         let compute_size = |stat: ffi::MDB_stat| {
@@ -384,99 +1189,325 @@ impl Env {
         size += compute_size(stat);
 
         let rtxn = self.read_txn()?;
+        self.for_each_named_database(&rtxn, |_name, dbi| {
+            let mut stat = std::mem::MaybeUninit::uninit();
+            unsafe { mdb_result(ffi::mdb_stat(rtxn.txn, dbi, stat.as_mut_ptr()))? };
+            let stat = unsafe { stat.assume_init() };
+
+            size += compute_size(stat);
+            Ok(())
+        })?;
+
+        Ok(size)
+    }
+
+    /// The names of the named databases of this environment, in order,
+    /// opened or not by this process. The database emulating the canary isn't listed.
+    ///
+    /// The databases that aren't opened are opened for a moment, it fails with a `DbsFull`
+    /// error when all the [`max_dbs`](EnvOpenOptions::max_dbs) slots are taken.
+    pub fn database_names(&self) -> Result<Vec<String>> {
+        let rtxn = self.read_txn()?;
+        let mut names = Vec::new();
+        self.for_each_named_database(&rtxn, |name, _dbi| {
+            #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+            if name == CANARY_DATABASE {
+                return Ok(());
+            }
+            names.push(name.to_owned());
+            Ok(())
+        })?;
+
+        Ok(names)
+    }
+
+    /// Whether the named database exists in this environment, without creating it,
+    /// see [`Env::database_names`] for the slot it needs.
+    pub fn database_exists(&self, name: &str) -> Result<bool> {
+        let rtxn = self.read_txn()?;
+        let dbi_open = self.0.dbi_open_mutex.lock().unwrap();
+        match self.raw_open_dbi(rtxn.txn, Some(name), 0) {
+            Ok(dbi) => {
+                if !dbi_open.dbis.contains_key(&dbi) {
+                    unsafe { ffi::mdb_dbi_close(self.env_mut_ptr(), dbi) };
+                }
+                Ok(true)
+            }
+            // The key of the name stores a value, not a database.
+            Err(crate::MdbError::Incompatible) => Ok(false),
+            Err(e) if e.not_found() => Ok(false),
+            Err(e) => Err(MdbOp::new("mdb_dbi_open").named(Some(name)).txn(TxnKind::Read).error(e)),
+        }
+    }
+
+    /// Calls `fun` with the named databases of the environment, the keys of the unnamed
+    /// database that open as a database. The ones that weren't opened are closed after.
+    pub(crate) fn for_each_named_database(
+        &self,
+        rtxn: &RoTxn,
+        mut fun: impl FnMut(&str, ffi::MDB_dbi) -> Result<()>,
+    ) -> Result<()> {
         let dbi = self.raw_open_dbi(rtxn.txn, None, 0)?;
 
-        // we don’t want anyone to open an environment while we’re computing the stats
+        // we don’t want anyone to open a database while we’re walking them
         // thus we take a lock on the dbi
         let dbi_open = self.0.dbi_open_mutex.lock().unwrap();
 
         // We’re going to iterate on the unnamed database
-        let mut cursor = RoCursor::new(&rtxn, dbi)?;
+        let mut cursor = RoCursor::new(rtxn, dbi)?;
 
         while let Some((key, _value)) = cursor.move_on_next()? {
-            if key.contains(&0) {
-                continue;
-            }
-
-            let key = String::from_utf8(key.to_vec()).unwrap();
-            if let Ok(dbi) = self.raw_open_dbi(rtxn.txn, Some(&key), 0) {
-                let mut stat = std::mem::MaybeUninit::uninit();
-                unsafe { mdb_result(ffi::mdb_stat(rtxn.txn, dbi, stat.as_mut_ptr()))? };
-                let stat = unsafe { stat.assume_init() };
+            let name = match str::from_utf8(key) {
+                Ok(name) if !name.contains('\0') => name,
+                _ => continue,
+            };
 
-                size += compute_size(stat);
+            if let Ok(dbi) = self.raw_open_dbi(rtxn.txn, Some(name), 0) {
+                let result = fun(name, dbi);
 
                 // if the db wasn’t already opened
-                if !dbi_open.contains_key(&dbi) {
+                if !dbi_open.dbis.contains_key(&dbi) {
                     unsafe {
                         ffi::mdb_dbi_close(self.env_mut_ptr(), dbi);
                     }
                 }
+                result?;
             }
         }
 
-        Ok(size)
+        Ok(())
     }
+
     pub(crate) fn env_mut_ptr(&self) -> *mut ffi::MDB_env {
         self.0.env
     }
 
-    pub fn open_database<KC, DC>(
+    /// Keeps the other write transactions of the process from committing.
+    pub(crate) fn commit_lock(&self) -> sync::MutexGuard<()> {
+        self.0.commit_mutex.lock().unwrap()
+    }
+
+    /// Begins a transaction, it must be ended with [`end_txn`](Env::end_txn) once aborted
+    /// or committed.
+    ///
+    /// With LMDB the map is grown before a write transaction if a growth step is set,
+    /// and adopted if another process grew it.
+    pub(crate) fn begin_txn(
+        &self,
+        parent: *mut ffi::MDB_txn,
+        flags: u32,
+    ) -> Result<*mut ffi::MDB_txn> {
+        if self.is_closing() {
+            return Err(Error::DatabaseClosing);
+        }
+        if self.0.read_only && flags & ffi::MDB_RDONLY == 0 {
+            return Err(Error::ReadOnly);
+        }
+
+        #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+        if parent.is_null() && flags & ffi::MDB_RDONLY == 0 {
+            self.grow_map()?;
+        }
+
+        let result = self.raw_begin_txn(parent, flags);
+
+        #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+        let result = match result {
+            Err(Error::Mdb { source: crate::MdbError::MapResized, .. })
+                if self.resize_map(0)? =>
+            {
+                self.raw_begin_txn(parent, flags)
+            }
+            result => result,
+        };
+
+        result
+    }
+
+    /// Ends a transaction begun with `flags`.
+    pub(crate) fn end_txn(&self, flags: u32) {
+        if flags & ffi::MDB_RDONLY != 0 {
+            self.0.read_txns.fetch_sub(1, Ordering::Release);
+        }
+        self.0.txns.leave();
+    }
+
+    fn raw_begin_txn(&self, parent: *mut ffi::MDB_txn, flags: u32) -> Result<*mut ffi::MDB_txn> {
+        if flags & ffi::MDB_RDONLY != 0 {
+            self.count_read_txn()?;
+        }
+
+        let mut txn: *mut ffi::MDB_txn = ptr::null_mut();
+        self.0.txns.enter();
+        match unsafe { mdb_result(ffi::mdb_txn_begin(self.env_mut_ptr(), parent, flags, &mut txn)) }
+        {
+            Ok(()) => Ok(txn),
+            Err(e) => {
+                self.end_txn(flags);
+                let kind =
+                    if flags & ffi::MDB_RDONLY != 0 { TxnKind::Read } else { TxnKind::Write };
+                Err(MdbOp::new("mdb_txn_begin").txn(kind).error(e))
+            }
+        }
+    }
+
+    /// Counts a read transaction that begins, unless the maximum is reached.
+    fn count_read_txn(&self) -> Result<()> {
+        let max = self.0.max_read_txns.unwrap_or(usize::MAX);
+        let count = self.0.read_txns.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+            if n < max {
+                Some(n + 1)
+            } else {
+                None
+            }
+        });
+        count.map(drop).map_err(|_| Error::ReadersFull)
+    }
+
+    /// Renews a read transaction that was reset, it begins again on the last snapshot.
+    #[cfg(feature = "read-txn-no-tls")]
+    pub(crate) fn renew_txn(&self, txn: *mut ffi::MDB_txn) -> Result<()> {
+        if self.is_closing() {
+            return Err(Error::DatabaseClosing);
+        }
+        self.count_read_txn()?;
+        self.0.txns.enter();
+        match unsafe { mdb_result(ffi::mdb_txn_renew(txn)) } {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                self.end_txn(ffi::MDB_RDONLY);
+                Err(MdbOp::new("mdb_txn_renew").txn(TxnKind::Read).error(e))
+            }
+        }
+    }
+
+    /// The number of read transactions alive in the process, owned ones included.
+    ///
+    /// It can be compared to the [`max_concurrent_read_txns`] to apply backpressure
+    /// before the read transactions fail.
+    ///
+    /// [`max_concurrent_read_txns`]: EnvOpenOptions::max_concurrent_read_txns
+    pub fn read_txns(&self) -> usize {
+        self.0.read_txns.load(Ordering::Acquire)
+    }
+
+    /// Grows the map by the growth step when less than a step is left.
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn grow_map(&self) -> Result<()> {
+        let step = match self.0.growth_step {
+            Some(step) => step,
+            None => return Ok(()),
+        };
+
+        let map_size = self.map_size()?;
+        if map_size.saturating_sub(ffi::used_size(self.env_mut_ptr())?) >= step {
+            return Ok(());
+        }
+
+        let size = map_size.saturating_add(step).min(self.0.max_size.unwrap_or(usize::MAX));
+        if size > map_size {
+            self.resize_map(size)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the size of the map, zero adopts the size set by another process, returns
+    /// `false` without resizing if a transaction of this process is alive.
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn resize_map(&self, size: usize) -> Result<bool> {
+        let env = self.env_mut_ptr();
+        match self.0.txns.exclusive(|| unsafe { mdb_result(ffi::mdb_env_set_mapsize(env, size)) }) {
+            Some(result) => MdbOp::new("mdb_env_set_mapsize").result(result).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    pub fn open_database<'n, KC, DC>(
         &self,
         rtxn: &RoTxn,
-        name: Option<&str>,
+        name: impl Into<Option<&'n str>>,
     ) -> Result<Option<Database<KC, DC>>>
     where
         KC: 'static,
         DC: 'static,
     {
         let types = (TypeId::of::<KC>(), TypeId::of::<DC>());
-        match self.raw_init_database(rtxn.txn, name, Some(types), false) {
-            Ok(dbi) => Ok(Some(Database::new(self.env_mut_ptr() as _, dbi))),
-            Err(Error::Mdb(e)) if e.not_found() => Ok(None),
+        match self.raw_init_database(rtxn.txn, name.into(), Some(types), false) {
+            Ok((dbi, _)) => Ok(Some(Database::new(self.env_mut_ptr() as _, dbi))),
+            Err(Error::Mdb { source, .. }) if source.not_found() => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    pub fn open_poly_database(
+    pub fn open_poly_database<'n>(
         &self,
         rtxn: &RoTxn,
-        name: Option<&str>,
+        name: impl Into<Option<&'n str>>,
     ) -> Result<Option<PolyDatabase>> {
-        match self.raw_init_database(rtxn.txn, name, None, false) {
-            Ok(dbi) => Ok(Some(PolyDatabase::new(self.env_mut_ptr() as _, dbi))),
-            Err(Error::Mdb(e)) if e.not_found() => Ok(None),
+        match self.raw_init_database(rtxn.txn, name.into(), None, false) {
+            Ok((dbi, _)) => Ok(Some(PolyDatabase::new(self.env_mut_ptr() as _, dbi))),
+            Err(Error::Mdb { source, .. }) if source.not_found() => Ok(None),
             Err(e) => Err(e),
         }
     }
 
-    pub fn create_database<KC, DC>(
+    pub fn create_database<'n, KC, DC>(
         &self,
         wtxn: &mut RwTxn,
-        name: Option<&str>,
+        name: impl Into<Option<&'n str>>,
     ) -> Result<Database<KC, DC>>
     where
         KC: 'static,
         DC: 'static,
     {
         let types = (TypeId::of::<KC>(), TypeId::of::<DC>());
-        match self.raw_init_database(wtxn.txn.txn, name, Some(types), true) {
-            Ok(dbi) => Ok(Database::new(self.env_mut_ptr() as _, dbi)),
+        match self.raw_init_database(wtxn.txn.txn, name.into(), Some(types), true) {
+            Ok((dbi, _)) => Ok(Database::new(self.env_mut_ptr() as _, dbi)),
             Err(e) => Err(e),
         }
     }
 
-    pub fn create_poly_database(
+    /// Opens the database or creates it, like [`Env::create_database`], and tells whether it
+    /// was created, to initialize it once: seeding it or building its indexes in the same
+    /// transaction. The database only exists for the others once the transaction commits.
+    pub fn open_or_create_database<'n, KC, DC>(
         &self,
         wtxn: &mut RwTxn,
-        name: Option<&str>,
+        name: impl Into<Option<&'n str>>,
+    ) -> Result<Opened<Database<KC, DC>>>
+    where
+        KC: 'static,
+        DC: 'static,
+    {
+        let types = (TypeId::of::<KC>(), TypeId::of::<DC>());
+        let (dbi, created) =
+            self.raw_init_database(wtxn.txn.txn, name.into(), Some(types), true)?;
+        let db = Database::new(self.env_mut_ptr() as _, dbi);
+        Ok(if created { Opened::Created(db) } else { Opened::Existing(db) })
+    }
+
+    pub fn create_poly_database<'n>(
+        &self,
+        wtxn: &mut RwTxn,
+        name: impl Into<Option<&'n str>>,
     ) -> Result<PolyDatabase> {
-        match self.raw_init_database(wtxn.txn.txn, name, None, true) {
-            Ok(dbi) => Ok(PolyDatabase::new(self.env_mut_ptr() as _, dbi)),
+        match self.raw_init_database(wtxn.txn.txn, name.into(), None, true) {
+            Ok((dbi, _)) => Ok(PolyDatabase::new(self.env_mut_ptr() as _, dbi)),
             Err(e) => Err(e),
         }
     }
 
+    /// Opens the database or creates it, see [`Env::open_or_create_database`].
+    pub fn open_or_create_poly_database<'n>(
+        &self,
+        wtxn: &mut RwTxn,
+        name: impl Into<Option<&'n str>>,
+    ) -> Result<Opened<PolyDatabase>> {
+        let (dbi, created) = self.raw_init_database(wtxn.txn.txn, name.into(), None, true)?;
+        let db = PolyDatabase::new(self.env_mut_ptr() as _, dbi);
+        Ok(if created { Opened::Created(db) } else { Opened::Existing(db) })
+    }
+
     fn raw_open_dbi(
         &self,
         raw_txn: *mut ffi::MDB_txn,
@@ -503,23 +1534,122 @@ impl Env {
         name: Option<&str>,
         types: Option<(TypeId, TypeId)>,
         create: bool,
-    ) -> Result<u32> {
+    ) -> Result<(u32, bool)> {
         let mut lock = self.0.dbi_open_mutex.lock().unwrap();
 
-        let flags = if create { ffi::MDB_CREATE } else { 0 };
-        match self.raw_open_dbi(raw_txn, name, flags) {
+        // Opened before it is created, to tell whether it existed.
+        let mut created = false;
+        let result = match self.raw_open_dbi(raw_txn, name, 0) {
+            Err(e) if create && e.not_found() => {
+                created = true;
+                self.raw_open_dbi(raw_txn, name, ffi::MDB_CREATE)
+            }
+            result => result,
+        };
+        match result {
             Ok(dbi) => {
-                let old_types = lock.entry(dbi).or_insert(types);
-                if *old_types == types {
-                    Ok(dbi)
-                } else {
-                    Err(Error::InvalidDatabaseTyping)
+                let opened = lock.dbis.entry(dbi).or_insert_with(|| OpenedDbi {
+                    name: name.map(String::from),
+                    types,
+                    handles: 0,
+                });
+                if opened.types != types {
+                    return Err(Error::InvalidDatabaseTyping);
+                }
+                opened.handles += 1;
+                if opened.handles == 1 {
+                    lock.idle.retain(|&idle| idle != dbi);
                 }
+                Ok((dbi, created))
+            }
+            Err(e) => {
+                let kind = if create { TxnKind::Write } else { TxnKind::Read };
+                Err(MdbOp::new("mdb_dbi_open").named(name).txn(kind).error(e))
             }
-            Err(e) => Err(e.into()),
         }
     }
 
+    /// Closes a handle of a database, the database itself is closed once all the handles
+    /// returned when opening or creating it are closed, which releases its slot among the
+    /// [`max_dbs`](EnvOpenOptions::max_dbs) of the environment.
+    ///
+    /// The [`max_idle_dbs`](EnvOpenOptions::max_idle_dbs) least recently closed databases
+    /// are kept open. The databases are only closed when no transaction is alive in the
+    /// process, the databases a transaction wrote to can't be closed before it ends, the
+    /// ones that can't are closed by the next call.
+    ///
+    /// # Safety
+    ///
+    /// The handles are copies of each other, the closed one and its copies must not be used
+    /// anymore once all the handles are closed. LMDB gives the slot of a closed database to
+    /// the next database opened, a stale handle then reads and writes that other database
+    /// instead of failing.
+    pub unsafe fn close_database<KC, DC>(&self, db: Database<KC, DC>) {
+        self.close_poly_database(db.dyndb)
+    }
+
+    /// Closes a handle of a database, see [`Env::close_database`].
+    ///
+    /// # Safety
+    ///
+    /// The closed handle and its copies must not be used anymore once all the handles of
+    /// the database are closed, see [`Env::close_database`].
+    pub unsafe fn close_poly_database(&self, db: PolyDatabase) {
+        assert_eq!(db.env_ident, self.env_mut_ptr() as usize);
+
+        let mut lock = self.0.dbi_open_mutex.lock().unwrap();
+        let OpenedDbis { dbis, idle } = &mut *lock;
+        if let Some(opened) = dbis.get_mut(&db.dbi).filter(|opened| opened.handles > 0) {
+            opened.handles -= 1;
+            if opened.handles == 0 {
+                idle.push_back(db.dbi);
+            }
+        }
+
+        let evicted = idle.len().saturating_sub(self.0.max_idle_dbs);
+        if evicted > 0 {
+            let env = self.env_mut_ptr();
+            self.0.txns.exclusive(|| {
+                for dbi in idle.drain(..evicted) {
+                    unsafe { ffi::mdb_dbi_close(env, dbi) };
+                    dbis.remove(&dbi);
+                }
+            });
+        }
+    }
+
+    /// The number of databases opened in the environment, idle ones included.
+    pub fn opened_databases(&self) -> usize {
+        self.0.dbi_open_mutex.lock().unwrap().dbis.len()
+    }
+
+    /// The name of an opened database, `None` for the unnamed one.
+    pub(crate) fn database_name(&self, dbi: ffi::MDB_dbi) -> Option<String> {
+        let lock = self.0.dbi_open_mutex.lock().unwrap();
+        lock.dbis.get(&dbi).and_then(|opened| opened.name.clone())
+    }
+
+    /// The name of a database opened in the environment identified by `env_ident`, for the
+    /// `Debug` of the databases. It doesn't wait for the locks, `None` when one is taken.
+    pub(crate) fn opened_database_name(env_ident: usize, dbi: ffi::MDB_dbi) -> Option<String> {
+        let lock = OPENED_ENV.try_read().ok()?;
+        let mut envs = lock.values().filter_map(|entry| entry.env.as_ref());
+        let env = envs.find(|env| env.env_mut_ptr() as usize == env_ident)?;
+        let dbis = env.0.dbi_open_mutex.try_lock().ok()?;
+        dbis.dbis.get(&dbi).and_then(|opened| opened.name.clone())
+    }
+
+    /// Whether the environment is prepared for closing, see [`Env::prepare_for_closing`].
+    pub fn is_closing(&self) -> bool {
+        self.0.closing.load(Ordering::Acquire)
+    }
+
+    /// The maximum size of the keys, in bytes, that can be written in or looked up from
+    /// the databases of this environment.
+    pub fn max_key_size(&self) -> usize {
+        ffi::max_key_size(self.env_mut_ptr())
+    }
+
     pub fn write_txn(&self) -> Result<RwTxn> {
         RwTxn::new(self)
     }
@@ -543,6 +1673,16 @@ impl Env {
         RoTxn::new(self)
     }
 
+    /// Create a read transaction that owns this environment handle instead of borrowing it,
+    /// call it on a clone of the `Env` to keep using the original one.
+    pub fn static_read_txn(self) -> Result<OwnedRoTxn> {
+        OwnedRoTxn::new(self)
+    }
+
+    pub fn typed_static_read_txn<T>(self) -> Result<OwnedRoTxn<T>> {
+        OwnedRoTxn::new(self)
+    }
+
     // TODO rename into `copy_to_file` for more clarity
     pub fn copy_to_path<P: AsRef<Path>>(&self, path: P, option: CompactionOption) -> Result<File> {
         let file = File::options().create_new(true).write(true).open(&path)?;
@@ -559,6 +1699,64 @@ impl Env {
         Ok(file)
     }
 
+    /// Copies this environment like [`Env::copy_to_path`] to a file compressed with zstd at
+    /// the `level`, by convention named `*.mdb.zst`, that [`Env::restore_from_compressed`]
+    /// decompresses. A compacted copy, with [`CompactionOption::Enabled`], leaves out the
+    /// free pages and is smaller than a copy of the whole file.
+    ///
+    /// The copy is compressed while it is made, through a pipe, it is never written
+    /// uncompressed to the disk.
+    #[cfg(feature = "zstd")]
+    pub fn copy_to_path_compressed<P: AsRef<Path>>(
+        &self,
+        path: P,
+        option: CompactionOption,
+        level: i32,
+    ) -> Result<File> {
+        let output = File::options().create_new(true).write(true).open(&path)?;
+        let (reader, writer) = io::pipe()?;
+        let compressed: Result<()> = thread::scope(|scope| {
+            let copy = scope.spawn(move || {
+                // safety: the pipe is open until the end of the copy, it is closed after it
+                // for the reader to see the end of the copy.
+                let copied = unsafe { self.copy_to_fd(get_file_fd(&writer), option) };
+                drop(writer);
+                copied
+            });
+
+            // The reader is dropped when the compression fails, the copy fails with it
+            // instead of waiting for the pipe to be read.
+            let mut output = io::BufWriter::new(output);
+            let encoded = zstd::stream::copy_encode(reader, &mut output, level);
+            copy.join().expect("the copy of the environment panicked")?;
+            encoded?;
+            output.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+            Ok(())
+        });
+        if let Err(e) = compressed {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+
+        Ok(File::open(path)?)
+    }
+
+    /// Decompresses a copy made by [`Env::copy_to_path_compressed`] to the new file at `path`,
+    /// the data file of the environment restored, returns it opened for reading.
+    #[cfg(feature = "zstd")]
+    pub fn restore_from_compressed<P: AsRef<Path>, Q: AsRef<Path>>(
+        compressed: P,
+        path: Q,
+    ) -> Result<File> {
+        let input = io::BufReader::new(File::open(compressed)?);
+        let output = File::options().create_new(true).write(true).open(&path)?;
+        let mut output = io::BufWriter::new(output);
+        zstd::stream::copy_decode(input, &mut output)?;
+        output.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+
+        Ok(File::open(path)?)
+    }
+
     pub unsafe fn copy_to_fd(
         &self,
         fd: ffi::mdb_filehandle_t,
@@ -585,6 +1783,65 @@ impl Env {
         Ok(())
     }
 
+    /// Set the `x`, `y` and `z` numbers of the canary, they are visible to the transactions
+    /// started once this one is committed.
+    ///
+    /// LMDB has no canary, it is emulated with a `__heed_canary` database that counts
+    /// in the [`max_dbs`](EnvOpenOptions::max_dbs) of the environment.
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    pub fn set_canary(&self, wtxn: &mut RwTxn, canary: &Canary) -> Result<()> {
+        use crate::types::ByteSlice;
+
+        let db = self.create_poly_database(wtxn, Some(CANARY_DATABASE))?;
+        let mut bytes = [0; 32];
+        for (chunk, n) in bytes.chunks_exact_mut(8).zip(&[canary.x, canary.y, canary.z, wtxn.id()])
+        {
+            chunk.copy_from_slice(&n.to_be_bytes());
+        }
+        let result = db.put::<_, ByteSlice, ByteSlice>(wtxn, CANARY_DATABASE.as_bytes(), &bytes);
+        // safety: the handle isn't kept, the next call opens the database again.
+        unsafe { self.close_poly_database(db) };
+        result
+    }
+
+    #[cfg(all(feature = "mdbx", not(feature = "lmdb")))]
+    pub fn set_canary(&self, wtxn: &mut RwTxn, canary: &Canary) -> Result<()> {
+        ffi::canary_put(wtxn.txn.txn, [canary.x, canary.y, canary.z])
+    }
+
+    /// The canary as seen by the transaction, all zeroes if it was never set.
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    pub fn get_canary<T>(&self, rtxn: &RoTxn<T>) -> Result<Canary> {
+        use crate::types::ByteSlice;
+
+        let db = match self.raw_init_database(rtxn.txn, Some(CANARY_DATABASE), None, false) {
+            Ok((dbi, _)) => PolyDatabase::new(self.env_mut_ptr() as _, dbi),
+            Err(Error::Mdb { source, .. }) if source.not_found() => return Ok(Canary::default()),
+            Err(e) => return Err(e),
+        };
+
+        let bytes = db.get::<_, ByteSlice, ByteSlice>(rtxn, CANARY_DATABASE.as_bytes());
+        // safety: the handle isn't kept, the next call opens the database again.
+        unsafe { self.close_poly_database(db) };
+        let bytes = match bytes? {
+            Some(bytes) if bytes.len() == 32 => bytes,
+            Some(_) => return Err(Error::Decoding),
+            None => return Ok(Canary::default()),
+        };
+        let n = |i: usize| {
+            let mut n = [0; 8];
+            n.copy_from_slice(&bytes[i * 8..(i + 1) * 8]);
+            u64::from_be_bytes(n)
+        };
+        Ok(Canary { x: n(0), y: n(1), z: n(2), v: n(3) })
+    }
+
+    #[cfg(all(feature = "mdbx", not(feature = "lmdb")))]
+    pub fn get_canary<T>(&self, rtxn: &RoTxn<T>) -> Result<Canary> {
+        let [x, y, z, v] = ffi::canary_get(rtxn.txn)?;
+        Ok(Canary { x, y, z, v })
+    }
+
     /// Returns the canonicalized path where this env lives.
     pub fn path(&self) -> &Path {
         &self.0.path
@@ -595,9 +1852,12 @@ impl Env {
     ///
     /// Make sure that you drop all the copies of `Env`s you have, env closing are triggered
     /// when all references are dropped, the last one will eventually close the environment.
+    /// The copies can't begin transactions anymore, they return a
+    /// [`DatabaseClosing`](Error::DatabaseClosing) error.
     pub fn prepare_for_closing(self) -> EnvClosingEvent {
+        self.0.closing.store(true, Ordering::Release);
         let mut lock = OPENED_ENV.write().unwrap();
-        let env = lock.get_mut(&self.0.path);
+        let env = lock.get_mut(&self.0.id);
 
         match env {
             None => panic!("cannot find the env that we are trying to close"),
@@ -615,6 +1875,165 @@ impl Env {
             }
         }
     }
+
+    /// Closes this environment and removes its data and lock files from disk
+    /// once it has been effectively closed.
+    ///
+    /// This is mostly useful for tests and cache-like environments that must
+    /// not outlive the process. The directory containing the files is kept.
+    ///
+    /// # Blocking
+    ///
+    /// This blocks until every copy of this environment has been dropped,
+    /// see [`EnvClosingEvent::wait`] for the dead-lock it can cause.
+    pub fn prepare_for_closing_and_delete(self) -> Result<()> {
+        let file_mode = self.contains_flag(Flags::MdbNoSubDir)?;
+        let [data, lock] = self.file_paths()?;
+        let advisory_lock = advisory_lock_path(self.path(), file_mode);
+        self.prepare_for_closing().wait();
+
+        for file in &[data, lock, advisory_lock] {
+            match std::fs::remove_file(file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compacts this environment by copying it into `tmp_dir`, closing it, and moving the
+    /// compacted copy over the original data file. The environment is then reopened with
+    /// the same options and returned.
+    ///
+    /// `tmp_dir` must be on the same file system as the environment for the final move to
+    /// be atomic. Trying to open the environment while it is being compacted returns
+    /// a [`DatabaseClosing`](Error::DatabaseClosing) error.
+    ///
+    /// # Errors
+    ///
+    /// The data file is left untouched if the copy fails. If the compacted copy can't be
+    /// moved over the data file, the copy is removed and the original environment is
+    /// reopened and returned uncompacted, the move error is only returned when it can't be.
+    ///
+    /// # Blocking
+    ///
+    /// This blocks until every copy of this environment has been dropped,
+    /// see [`EnvClosingEvent::wait`] for the dead-lock it can cause.
+    pub fn compact_in_place<P: AsRef<Path>>(self, tmp_dir: P) -> Result<Env> {
+        let path = self.path().to_path_buf();
+        let [data, _lock] = self.file_paths()?;
+        let mut name = data.file_name().unwrap_or_default().to_os_string();
+        name.push(".compacting");
+        let compacted = tmp_dir.as_ref().join(name);
+
+        let copy = self.copy_to_path(&compacted, CompactionOption::Enabled).and_then(|_| {
+            File::options().write(true).open(&compacted)?.sync_all()?;
+            Ok(())
+        });
+        if let Err(e) = copy {
+            let _ = std::fs::remove_file(&compacted);
+            return Err(e);
+        }
+
+        // We keep the entry in the global list while the env is closed,
+        // no one must open the old data file before we replace it.
+        self.0.closing.store(true, Ordering::Release);
+        let mut lock = OPENED_ENV.write().unwrap();
+        let entry = lock.get_mut(&self.0.id).expect("cannot find the env that we are compacting");
+        let registered = entry.env.take();
+        entry.reserved = true;
+        let options = entry.options.clone();
+        let closing = EnvClosingEvent(entry.signal_event.clone());
+        let id = self.0.id.clone();
+
+        // The drop of the EnvInner also locks the OPENED_ENV global.
+        drop(lock);
+        drop(registered);
+        drop(self);
+        closing.wait();
+
+        let mut lock = OPENED_ENV.write().unwrap();
+        lock.remove(&id);
+        if let Err(e) = std::fs::rename(&compacted, &data) {
+            let _ = std::fs::remove_file(&compacted);
+            // The original data file is untouched, we give the environment back uncompacted.
+            return options.open_in(&mut lock, path, false).map_err(|_| e.into());
+        }
+        options.open_in(&mut lock, path, false)
+    }
+
+    /// Creates an environment in the empty directory `path` holding a copy of the named
+    /// databases `db_names` only, read from a snapshot of this environment.
+    ///
+    /// The entries are appended to the new environment in order: its pages are filled and
+    /// written one after the other, it is as compact as a compacting copy. The environment
+    /// is created with the map size of this one and room for the copied databases, it is
+    /// returned once the copy is committed. The databases of duplicate values can't be
+    /// copied, a name that isn't a database of this environment returns a not found error.
+    pub fn export_snapshot<P: AsRef<Path>>(&self, db_names: &[&str], path: P) -> Result<Env> {
+        let names = self.database_names()?;
+        if let Some(missing) = db_names.iter().find(|name| !names.iter().any(|n| n == *name)) {
+            let op = MdbOp::new("mdb_dbi_open").named(Some(missing)).txn(TxnKind::Read);
+            return Err(op.error(crate::mdb::error::Error::NotFound));
+        }
+
+        let path = path.as_ref();
+        std::fs::create_dir_all(path)?;
+        if std::fs::read_dir(path)?.next().is_some() {
+            let message = "the snapshot must be exported to an empty directory";
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, message).into());
+        }
+
+        let dest = EnvOpenOptions::new()
+            .map_size(self.map_size()?)
+            .max_dbs(db_names.len() as u32)
+            .open(path)?;
+        let mut wtxn = dest.write_txn()?;
+        let rtxn = self.read_txn()?;
+        let mut copies = Vec::with_capacity(db_names.len());
+        self.for_each_named_database(&rtxn, |name, dbi| {
+            if !db_names.contains(&name) {
+                return Ok(());
+            }
+
+            let db = dest.create_poly_database(&mut wtxn, Some(name))?;
+            let mut cursor = RoCursor::new(&rtxn, dbi)?;
+            let mut appender = RwCursor::new(&wtxn, db.dbi)?;
+            while let Some((key, data)) = cursor.move_on_next()? {
+                // safety: the appended entries are borrowed from another environment.
+                unsafe { appender.append(key, data)? };
+            }
+            copies.push(db);
+            Ok(())
+        })?;
+        drop(rtxn);
+
+        wtxn.commit()?;
+
+        // The copies are opened by the users, with their types.
+        for db in copies {
+            // safety: the copied handles aren't returned.
+            unsafe { dest.close_poly_database(db) };
+        }
+        Ok(dest)
+    }
+
+    /// The paths of the data and the lock files of this environment.
+    pub(crate) fn file_paths(&self) -> Result<[PathBuf; 2]> {
+        #[cfg(feature = "mdbx")]
+        let (data, lock, suffix) = ("mdbx.dat", "mdbx.lck", "-lck");
+        #[cfg(not(feature = "mdbx"))]
+        let (data, lock, suffix) = ("data.mdb", "lock.mdb", "-lock");
+
+        if self.contains_flag(Flags::MdbNoSubDir)? {
+            let mut lock_path = self.path().as_os_str().to_os_string();
+            lock_path.push(suffix);
+            Ok([self.path().to_path_buf(), PathBuf::from(lock_path)])
+        } else {
+            Ok([self.path().join(data), self.path().join(lock)])
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -623,7 +2042,7 @@ pub struct EnvClosingEvent(Arc<SignalEvent>);
 impl EnvClosingEvent {
     /// Blocks this thread until the environment is effectively closed.
     ///
-    /// # Safety
+    /// # Blocking
     ///
     /// Make sure that you don't have any copy of the environment in the thread
     /// that is waiting for a close event, if you do, you will have a dead-lock.
@@ -641,13 +2060,15 @@ impl EnvClosingEvent {
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
     use std::thread;
     use std::time::Duration;
 
     use tempfile::tempdir;
 
+    use crate::flags::Flags;
     use crate::types::*;
-    use crate::{env_closing_event, EnvOpenOptions};
+    use crate::{env_closing_event, Canary, EnvOpenOptions, Error};
 
     #[test]
     fn close_env() {
@@ -707,6 +2128,619 @@ mod tests {
 
         assert!(env.is_err());
     }
+    #[test]
+    fn reopen_env_with_compatible_options() {
+        let dir = tempdir().unwrap();
+        let path = dir.path();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .max_dbs(30)
+            .open(&path)
+            .unwrap();
+
+        let smaller = EnvOpenOptions::new()
+            .map_size(8 * 1024 * 1024) // 8MB
+            .max_dbs(10)
+            .open(&path)
+            .unwrap();
+        assert_eq!(env.env_mut_ptr(), smaller.env_mut_ptr());
+
+        let unspecified = EnvOpenOptions::new().open(&path).unwrap();
+        assert_eq!(env.env_mut_ptr(), unspecified.env_mut_ptr());
+
+        let more_dbs = EnvOpenOptions::new().max_dbs(40).open(&path);
+        assert!(more_dbs.is_err());
+
+        let exclusive = EnvOpenOptions::new().max_dbs(10).open_exclusive(&path);
+        assert!(exclusive.is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn reopen_env_through_symlink() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env");
+        let link = dir.path().join("link");
+        fs::create_dir_all(&path).unwrap();
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&path)
+            .unwrap();
+        let aliased = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&link)
+            .unwrap();
+
+        assert_eq!(env.env_mut_ptr(), aliased.env_mut_ptr());
+        assert!(env_closing_event(&link).is_some());
+    }
+
+    #[test]
+    fn reopen_env_through_hard_link() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data.mdb");
+        let link = dir.path().join("alias.mdb");
+
+        let mut options = EnvOpenOptions::new();
+        options.map_size(10 * 1024 * 1024); // 10MB
+        unsafe { options.flag(Flags::MdbNoSubDir) };
+
+        fs::File::create(&path).unwrap();
+        fs::hard_link(&path, &link).unwrap();
+        let env = options.open(&path).unwrap();
+
+        // Both paths point to the same data file, we must not open it twice.
+        let aliased = options.open(&link).unwrap();
+        assert_eq!(env.env_mut_ptr(), aliased.env_mut_ptr());
+    }
+
+    #[test]
+    fn close_and_delete_env() {
+        let dir = tempdir().unwrap();
+        let path = dir.path();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&path)
+            .unwrap();
+
+        env.prepare_for_closing_and_delete().unwrap();
+
+        assert!(env_closing_event(&path).is_none());
+        assert_eq!(fs::read_dir(&path).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn open_env_in_file_mode() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env.mdb");
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .file_mode()
+            .open(&path)
+            .unwrap();
+
+        assert!(path.is_file());
+        assert_eq!(env.real_disk_size().unwrap(), path.metadata().unwrap().len());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        env.prepare_for_closing_and_delete().unwrap();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn invalid_page_size_is_rejected() {
+        let dir = tempdir().unwrap();
+        let recommended = EnvOpenOptions::recommended_page_size();
+
+        for &size in &[recommended + 1, recommended / 2, recommended * 3] {
+            match EnvOpenOptions::new().page_size(size).open(dir.path()) {
+                Err(Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput),
+                Err(e) => panic!("unexpected error for a page size of {}: {}", size, e),
+                Ok(_) => panic!("a page size of {} was accepted", size),
+            }
+        }
+
+        EnvOpenOptions::new().page_size(recommended).open(dir.path()).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn map_grows_by_growth_step() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(1024 * 1024) // 1MB
+            .growth_step(1024 * 1024)
+            .max_size(4 * 1024 * 1024)
+            .open(dir.path())
+            .unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, ByteSlice>(&mut wtxn, None).unwrap();
+        wtxn.commit().unwrap();
+
+        // Every transaction writes less than a step, until the map can't grow anymore.
+        let value = vec![0; 256 * 1024];
+        let mut result = Ok(());
+        for i in 0..32 {
+            let mut wtxn = env.write_txn().unwrap();
+            result = db.put(&mut wtxn, &i, &value).and_then(|()| wtxn.commit());
+            if result.is_err() {
+                break;
+            }
+        }
+
+        assert!(matches!(result, Err(Error::Mdb { source: crate::MdbError::MapFull, .. })));
+        assert_eq!(env.map_size().unwrap(), 4 * 1024 * 1024);
+    }
+
+    #[test]
+    fn mdb_errors_have_the_context_of_the_operation() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(1024 * 1024) // 1MB
+            .max_dbs(1)
+            .open(dir.path())
+            .unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, ByteSlice>(&mut wtxn, Some("big")).unwrap();
+        let value = vec![0; 256 * 1024];
+        let error = (0..8).find_map(|i| db.put(&mut wtxn, &i, &value).err()).unwrap();
+
+        match &error {
+            Error::Mdb { op, db, key_len, txn, source } => {
+                assert_eq!(*op, Some("mdb_put"));
+                assert_eq!(db.as_deref(), Some("big"));
+                assert_eq!(*key_len, Some(4));
+                assert_eq!(*txn, Some(crate::TxnKind::Write));
+                assert_eq!(*source, crate::MdbError::MapFull);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+        let message = error.to_string();
+        assert!(message.starts_with("mdb_put on the \"big\" database in a write transaction"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn open_env_with_nul_in_path_is_err() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = super::path_to_cstring(OsStr::from_bytes(b"heed\0env").as_ref());
+        assert_eq!(path.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn env_files_have_the_requested_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let _env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .file_permissions(0o640)
+            .advisory_lock(true)
+            .open(dir.path())
+            .unwrap();
+
+        for name in &["data.mdb", "heed.lock"] {
+            let metadata = fs::metadata(dir.path().join(name)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o640, "{}", name);
+        }
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn open_env_with_long_path() {
+        let dir = tempdir().unwrap();
+        let mut path = dir.path().to_path_buf();
+        while path.as_os_str().len() < 300 {
+            path.push("a-rather-long-directory-name");
+        }
+        fs::create_dir_all(&path).unwrap();
+
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&path)
+            .unwrap();
+        assert!(env.path().as_os_str().len() >= 260);
+    }
+
+    #[test]
+    fn advisory_lock_rejects_other_options() {
+        use fs2::FileExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path();
+
+        // A crashed process left its options behind without holding the lock.
+        fs::write(path.join("heed.lock"), "flags=0x0 map_size=Some(1)\n").unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .advisory_lock(true)
+            .open(&path)
+            .unwrap();
+        env.prepare_for_closing().wait();
+
+        // Another process is using the environment with different options.
+        fs::write(path.join("heed.lock"), "flags=0x0 map_size=Some(1)\n").unwrap();
+        let other = fs::File::open(path.join("heed.lock")).unwrap();
+        FileExt::lock_shared(&other).unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .advisory_lock(true)
+            .open(&path);
+        assert!(matches!(env, Err(crate::Error::BadOpenOptions)));
+    }
+
+    #[test]
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn read_only_unlocked_env_refuses_writes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&path)
+            .unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, "hello", "world").unwrap();
+        wtxn.commit().unwrap();
+        env.prepare_for_closing().wait();
+
+        // The lock file isn't needed, it may not even be readable.
+        fs::remove_file(path.join("lock.mdb")).unwrap();
+        let env = EnvOpenOptions::new().read_only_unlocked().open(&path).unwrap();
+        assert!(!path.join("lock.mdb").exists());
+
+        let rtxn = env.read_txn().unwrap();
+        let db = env.open_database::<Str, Str>(&rtxn, None).unwrap().unwrap();
+        assert_eq!(db.get(&rtxn, "hello").unwrap(), Some(String::from("world")));
+        drop(rtxn);
+
+        assert!(matches!(env.write_txn(), Err(Error::ReadOnly)));
+    }
+
+    #[test]
+    fn canary_is_set_transactionally() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .max_dbs(1)
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(env.get_canary(&env.read_txn().unwrap()).unwrap(), Canary::default());
+
+        let mut wtxn = env.write_txn().unwrap();
+        let id = wtxn.id();
+        env.set_canary(&mut wtxn, &Canary { x: 1, y: 2, z: 3, v: 42 }).unwrap();
+        let rtxn = env.read_txn().unwrap();
+        wtxn.commit().unwrap();
+
+        assert_eq!(env.get_canary(&rtxn).unwrap(), Canary::default());
+        drop(rtxn);
+        let canary = env.get_canary(&env.read_txn().unwrap()).unwrap();
+        assert_eq!(canary, Canary { x: 1, y: 2, z: 3, v: id });
+
+        // The handles of the canary database are closed after each call, it isn't listed.
+        let dbis = env.0.dbi_open_mutex.lock().unwrap();
+        assert!(dbis.dbis.values().all(|opened| opened.handles == 0));
+        drop(dbis);
+        assert!(env.database_names().unwrap().is_empty());
+    }
+
+    #[test]
+    fn txn_ids_and_oldest_reader() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(dir.path())
+            .unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        let wtxn = env.write_txn().unwrap();
+        assert_eq!(wtxn.id(), rtxn.id() + 1);
+        drop(wtxn);
+
+        // A reader of the last committed snapshot doesn't block anything.
+        assert_eq!(env.oldest_reader_id().unwrap(), None);
+    }
+
+    #[test]
+    fn changes_of_another_handle_are_seen() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(dir.path())
+            .unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, Unit>(&mut wtxn, None).unwrap();
+        wtxn.commit().unwrap();
+
+        // The commits without any write don't change the environment.
+        let seen = env.last_txn_id().unwrap();
+        env.write_txn().unwrap().commit().unwrap();
+        let writer = {
+            let env = env.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                let mut wtxn = env.write_txn().unwrap();
+                db.put(&mut wtxn, &1, &()).unwrap();
+                wtxn.commit().unwrap();
+            })
+        };
+
+        let changed = env.wait_for_change(seen, Duration::MAX).unwrap();
+        assert_eq!(changed, Some(seen + 1));
+        writer.join().unwrap();
+        assert_eq!(env.wait_for_change(seen + 1, Duration::ZERO).unwrap(), None);
+    }
+
+    #[test]
+    fn too_long_key_is_reported() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(dir.path())
+            .unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        let db = env.open_database::<ByteSlice, Unit>(&rtxn, None).unwrap().unwrap();
+
+        let max = env.max_key_size();
+        let key = vec![0; max + 1];
+        match db.get(&rtxn, &key) {
+            Err(Error::KeyTooLong { database: None, len, max: m }) => {
+                assert_eq!(len, max + 1);
+                assert_eq!(m, max);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unsorted_append_reports_the_keys() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<ByteSlice, Unit>(&mut wtxn, Some("sorted")).unwrap();
+        db.append(&mut wtxn, b"b", &()).unwrap();
+        db.append(&mut wtxn, b"c", &()).unwrap();
+
+        for key in &[&b"a"[..], b"c"] {
+            match db.append(&mut wtxn, key, &()) {
+                Err(Error::UnsortedAppend { database, last, key: appended }) => {
+                    assert_eq!(database.as_deref(), Some("sorted"));
+                    assert_eq!((&last[..], &appended[..]), (&b"c"[..], *key));
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn static_read_txn_outlives_env_handle() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(dir.path())
+            .unwrap();
+
+        let id = env.read_txn().unwrap().id();
+        let rtxn = env.clone().static_read_txn().unwrap();
+        let closing = env.prepare_for_closing();
+
+        // The transaction keeps the environment open until it is dropped.
+        assert_eq!(rtxn.id(), id);
+        assert!(rtxn.env().real_disk_size().is_ok());
+        assert!(!closing.wait_timeout(Duration::from_millis(10)));
+
+        rtxn.abort().unwrap();
+        closing.wait();
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn advise_and_lock_the_map() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+
+        env.advise(crate::Advice::Random).unwrap();
+        env.advise(crate::Advice::WillNeed).unwrap();
+        // The advice splits the mapping, it must still be found as a whole.
+        env.mlock(..4096).unwrap();
+        env.munlock(..).unwrap();
+        env.advise(crate::Advice::Normal).unwrap();
+
+        assert!(matches!(env.mlock(..=usize::MAX), Err(Error::Io(_))));
+        let start = (std::ops::Bound::Excluded(usize::MAX), std::ops::Bound::Unbounded);
+        assert!(matches!(env.munlock(start), Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn freelist_stats_of_new_env() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(dir.path())
+            .unwrap();
+
+        let stats = env.freelist_stats().unwrap();
+        assert!(stats.page_size > 0);
+        assert_eq!(stats.free_pages, 0);
+        assert_eq!(stats.reclaimable_pages, 0);
+        assert_eq!(stats.largest_run, 0);
+    }
+
+    #[test]
+    fn compact_env_in_place() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env");
+        let tmp = dir.path().join("tmp");
+        fs::create_dir_all(&path).unwrap();
+        fs::create_dir_all(&tmp).unwrap();
+
+        let env = EnvOpenOptions::new()
+            .map_size(10 * 1024 * 1024) // 10MB
+            .open(&path)
+            .unwrap();
+        let env = env.compact_in_place(&tmp).unwrap();
+
+        assert_eq!(fs::read_dir(&tmp).unwrap().count(), 0);
+        let reopened = EnvOpenOptions::new().open(&path).unwrap();
+        assert_eq!(env.env_mut_ptr(), reopened.env_mut_ptr());
+        let _rtxn = env.read_txn().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn restore_a_compressed_copy() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("env");
+        fs::create_dir_all(&path).unwrap();
+
+        let env = EnvOpenOptions::new().map_size(10 * 1024 * 1024).open(&path).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, "hello", "world").unwrap();
+        wtxn.commit().unwrap();
+
+        let options = [super::CompactionOption::Enabled, super::CompactionOption::Disabled];
+        for (i, &option) in options.iter().enumerate() {
+            let compressed = dir.path().join(format!("{}.mdb.zst", i));
+            let restored = dir.path().join(format!("restored-{}", i));
+            fs::create_dir_all(&restored).unwrap();
+            env.copy_to_path_compressed(&compressed, option, 3).unwrap();
+            super::Env::restore_from_compressed(&compressed, restored.join("data.mdb")).unwrap();
+
+            let env = EnvOpenOptions::new().open(&restored).unwrap();
+            let rtxn = env.read_txn().unwrap();
+            let db = env.open_database::<Str, Str>(&rtxn, None).unwrap().unwrap();
+            assert_eq!(db.get(&rtxn, "hello").unwrap().as_deref(), Some("world"));
+        }
+        // Only the compressed copies and the environments are written.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn export_a_snapshot_of_some_databases() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).open(dir.path().join("src")).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let kept = env.create_database::<Str, Str>(&mut wtxn, Some("kept")).unwrap();
+        let other = env.create_database::<Str, Str>(&mut wtxn, Some("other")).unwrap();
+        for (key, value) in &[("b", "2"), ("a", "1"), ("c", "3")] {
+            kept.put(&mut wtxn, key, value).unwrap();
+            other.put(&mut wtxn, key, value).unwrap();
+        }
+        wtxn.commit().unwrap();
+
+        let path = dir.path().join("snapshot");
+        assert!(env.export_snapshot(&["kept", "missing"], &path).is_err());
+        let snapshot = env.export_snapshot(&["kept"], &path).unwrap();
+        assert_eq!(snapshot.database_names().unwrap(), ["kept"]);
+        let rtxn = snapshot.read_txn().unwrap();
+        let db = snapshot.open_database::<Str, Str>(&rtxn, Some("kept")).unwrap().unwrap();
+        let entries: Vec<_> = db.iter(&rtxn).unwrap().map(Result::unwrap).collect();
+        assert_eq!(
+            entries,
+            [("a".into(), "1".into()), ("b".into(), "2".into()), ("c".into(), "3".into())]
+        );
+        drop(rtxn);
+
+        assert!(env.export_snapshot(&["kept"], &path).is_err());
+    }
+
+    #[test]
+    fn close_idle_databases() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).max_idle_dbs(1).open(dir.path()).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let a = env.create_database::<Str, Str>(&mut wtxn, Some("a")).unwrap();
+        let copy = env.open_database::<Str, Str>(&wtxn, Some("a")).unwrap().unwrap();
+        let b = env.create_database::<Str, Str>(&mut wtxn, Some("b")).unwrap();
+        assert!(env.create_database::<Str, Str>(&mut wtxn, Some("c")).is_err());
+        wtxn.commit().unwrap();
+
+        // The second handle of `a` keeps it open, `b` is kept idle.
+        unsafe {
+            env.close_database(a);
+            env.close_database(b);
+        }
+        assert_eq!(env.opened_databases(), 2);
+
+        // A transaction alive keeps the least recently closed `a` open.
+        let rtxn = env.read_txn().unwrap();
+        unsafe { env.close_database(copy) };
+        assert_eq!(env.opened_databases(), 2);
+        let b = env.open_database::<Str, Str>(&rtxn, Some("b")).unwrap().unwrap();
+        drop(rtxn);
+
+        // `b` is opened again, `a` is closed and its slot released.
+        unsafe { env.close_database(b) };
+        assert_eq!(env.opened_databases(), 1);
+        let mut wtxn = env.write_txn().unwrap();
+        let c = env.create_database::<Str, Str>(&mut wtxn, Some("c")).unwrap();
+        wtxn.commit().unwrap();
+        assert_eq!(env.database_name(c.dyndb.dbi).as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn debug_shows_the_names_and_codecs() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Unit>(&mut wtxn, Some("names")).unwrap();
+        wtxn.commit().unwrap();
+
+        let debug = format!("{:?}", db);
+        assert!(debug.contains(r#"name: Some("names")"#), "{}", debug);
+        assert!(debug.contains("Str") && debug.contains("Unit"), "{}", debug);
+        assert!(format!("{:?}", env).contains("opened_databases: 1"));
+    }
+
+    #[test]
+    fn closing_env_refuses_new_txns() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let copy = env.clone();
+        let rtxn = copy.read_txn().unwrap();
+
+        let closing = env.prepare_for_closing();
+        assert!(copy.is_closing());
+        assert!(matches!(copy.read_txn(), Err(Error::DatabaseClosing)));
+        assert!(matches!(copy.write_txn(), Err(Error::DatabaseClosing)));
+
+        // The transactions begun before can still be used.
+        copy.open_poly_database(&rtxn, None).unwrap();
+        drop(rtxn);
+        drop(copy);
+        closing.wait();
+    }
+
+    #[test]
+    fn read_txns_beyond_the_limit_fail() {
+        let dir = tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_concurrent_read_txns(1).open(dir.path()).unwrap();
+
+        let rtxn = env.clone().static_read_txn().unwrap();
+        let wtxn = env.write_txn().unwrap();
+        assert_eq!(env.read_txns(), 1);
+        assert!(matches!(env.read_txn(), Err(Error::ReadersFull)));
+
+        drop((rtxn, wtxn));
+        assert_eq!(env.read_txns(), 0);
+        let _rtxn = env.read_txn().unwrap();
+    }
+
     #[test]
     fn test_geometry() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,154 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Values attached to a transaction, at most one of each type.
+///
+/// It lets the wrappers layered on the same transaction, like indexes, journals or caches,
+/// share state for as long as the transaction lives without resorting to global statics.
+/// The values are dropped with the transaction, whether it is committed or aborted.
+///
+/// ```
+/// # use heed::EnvOpenOptions;
+/// use heed::store::{Transaction, WritableStore, WtxOf};
+///
+/// /// The keys a cache must forget once the transaction is committed.
+/// #[derive(Default)]
+/// struct Invalidated(Vec<String>);
+///
+/// fn invalidate<S: WritableStore>(wtx: &mut WtxOf<S>, key: &str) {
+///     if let Some(extensions) = wtx.extensions_mut() {
+///         extensions.get_or_insert_with(Invalidated::default).0.push(key.to_string());
+///     }
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempfile::tempdir()?;
+/// # let env = EnvOpenOptions::new()
+/// #     .map_size(10 * 1024 * 1024) // 10MB
+/// #     .open(dir.path())?;
+/// let mut wtxn = env.wtx()?;
+/// invalidate::<heed::Env>(&mut wtxn, "apple");
+/// invalidate::<heed::Env>(&mut wtxn, "pear");
+///
+/// let invalidated = wtxn.extensions_mut().remove::<Invalidated>().unwrap_or_default();
+/// assert_eq!(invalidated.0, ["apple", "pear"]);
+/// wtxn.commit()?;
+/// # Ok(()) }
+/// ```
+#[derive(Default)]
+pub struct Extensions {
+    // Most transactions never use it, the map is only created on the first insertion.
+    map: Option<HashMap<TypeId, Box<dyn Any + Send>>>,
+}
+
+/// The extensions of the transactions that have none, it is never given mutably.
+struct NoExtensions(Extensions);
+
+// safety: the extensions are only given by shared references and never hold a value,
+// there is nothing to access from several threads.
+unsafe impl Sync for NoExtensions {}
+
+static NO_EXTENSIONS: NoExtensions = NoExtensions(Extensions::new());
+
+impl Extensions {
+    pub const fn new() -> Extensions {
+        Extensions { map: None }
+    }
+
+    /// Empty extensions, for the transactions that can't hold any.
+    pub(crate) fn none() -> &'static Extensions {
+        &NO_EXTENSIONS.0
+    }
+
+    /// Insert a value, returns the previous value of the same type.
+    pub fn insert<T: Any + Send>(&mut self, value: T) -> Option<T> {
+        self.map
+            .get_or_insert_with(HashMap::new)
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+    }
+
+    pub fn get<T: Any + Send>(&self) -> Option<&T> {
+        self.map.as_ref()?.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.map.as_mut()?.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    /// The value of type `T`, inserted with `default` if there is none.
+    pub fn get_or_insert_with<T: Any + Send, F: FnOnce() -> T>(&mut self, default: F) -> &mut T {
+        self.map
+            .get_or_insert_with(HashMap::new)
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(default()))
+            .downcast_mut()
+            .expect("values are keyed by their type")
+    }
+
+    pub fn remove<T: Any + Send>(&mut self) -> Option<T> {
+        let value = self.map.as_mut()?.remove(&TypeId::of::<T>())?;
+        value.downcast().ok().map(|value| *value)
+    }
+
+    pub fn contains<T: Any + Send>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.as_ref().map_or(0, |map| map.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.map = None;
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Extensions").field("len", &self.len()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_keyed_by_type() {
+        let mut extensions = Extensions::new();
+        assert!(extensions.is_empty());
+        assert_eq!(extensions.insert(1u32), None);
+        assert_eq!(extensions.insert(2u32), Some(1));
+        assert_eq!(extensions.insert("a"), None);
+
+        assert_eq!(extensions.get::<u32>(), Some(&2));
+        assert_eq!(extensions.get::<u64>(), None);
+        *extensions.get_or_insert_with(|| 0u64) += 5;
+        *extensions.get_mut::<u64>().unwrap() += 1;
+        assert_eq!(extensions.remove::<u64>(), Some(6));
+        assert!(!extensions.contains::<u64>());
+        assert_eq!(extensions.len(), 2);
+    }
+
+    #[test]
+    fn transactions_have_no_extensions_by_default() {
+        use crate::store::Transaction;
+
+        struct Bare;
+
+        impl Transaction<crate::Env> for Bare {
+            fn commit(self) -> crate::Result<()> {
+                Ok(())
+            }
+        }
+
+        assert!(Bare.extensions().is_empty());
+        assert!(Bare.extensions_mut().is_none());
+    }
+}
@@ -0,0 +1,179 @@
+//! Tests of the codecs against golden encoded bytes, to catch the encoding changes that would
+//! make the data already written unreadable.
+//!
+//! [`assert_codec_roundtrip!`](crate::assert_codec_roundtrip) checks that a codec decodes the
+//! bytes it encodes a value to, to the same value, encoded again to the same bytes. The
+//! [`codec_golden_tests!`](crate::codec_golden_tests) harness declares a test for every value
+//! it is given, that also compares the encoded bytes with the golden bytes stored in the
+//! `tests/golden` directory of the crate, in a file named after the test. A codec whose
+//! encoding changed, after a change of its configuration or of a dependency, fails them.
+//!
+//! The golden files are written by running the tests with the `HEED_UPDATE_GOLDEN`
+//! environment variable set, and committed with the tests. A missing golden file fails its
+//! test otherwise.
+//!
+//! ```
+//! use heed::types::{SerdeBincode, Str};
+//! use heed::{assert_codec_roundtrip, codec_golden_tests};
+//!
+//! let bytes = assert_codec_roundtrip!(Str, "hello");
+//! assert_eq!(bytes, b"hello");
+//! assert_codec_roundtrip!(SerdeBincode<(u32, String)>, (42, "hello".to_string()));
+//!
+//! codec_golden_tests! {
+//!     greeting: Str = "hello";
+//!     user: SerdeBincode<(u32, String)> = (42, "alice".to_string());
+//! }
+//! ```
+
+use std::borrow::Borrow;
+use std::path::Path;
+use std::{env, fmt, fs, io};
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+/// The environment variable that makes the golden tests write the golden files.
+pub const UPDATE_VAR: &str = "HEED_UPDATE_GOLDEN";
+
+/// Encodes the item with the codec, checks that the bytes are decoded to the item and
+/// encoded again to the same bytes, and returns them.
+///
+/// # Panics
+///
+/// If the codec fails to encode the item or to decode its bytes, or if they don't round trip.
+pub fn assert_roundtrip<'a, C>(item: &'a <C as BytesEncode<'a>>::EItem) -> Vec<u8>
+where
+    C: BytesDecode + for<'b> BytesEncode<'b>,
+    C::DItem: for<'b> Borrow<<C as BytesEncode<'b>>::EItem>,
+    <C as BytesEncode<'a>>::EItem: PartialEq + fmt::Debug,
+{
+    let codec = std::any::type_name::<C>();
+    let bytes = <C as BytesEncode<'a>>::bytes_encode(item)
+        .unwrap_or_else(|| panic!("{} couldn't encode {:?}", codec, item))
+        .into_owned();
+    let decoded = C::bytes_decode(&bytes).unwrap_or_else(|| {
+        panic!("{} couldn't decode {:?}, encoded from {:?}", codec, bytes, item)
+    });
+    assert_eq!(decoded.borrow(), item, "{} decoded {:?} to another item", codec, bytes);
+    let reencoded = C::bytes_encode(decoded.borrow())
+        .unwrap_or_else(|| panic!("{} couldn't encode the decoded {:?}", codec, item));
+    assert_eq!(reencoded, bytes, "{} encoded {:?} again to other bytes", codec, item);
+    bytes
+}
+
+/// Checks that the item round trips through the codec, like [`assert_roundtrip`], and that
+/// its bytes are the golden bytes stored in the file. The file is written instead when the
+/// [`UPDATE_VAR`] environment variable is set.
+///
+/// # Panics
+///
+/// If the item doesn't round trip, if the golden file is missing or if its bytes differ.
+pub fn assert_golden<'a, C>(path: &Path, item: &'a <C as BytesEncode<'a>>::EItem)
+where
+    C: BytesDecode + for<'b> BytesEncode<'b>,
+    C::DItem: for<'b> Borrow<<C as BytesEncode<'b>>::EItem>,
+    <C as BytesEncode<'a>>::EItem: PartialEq + fmt::Debug,
+{
+    let bytes = assert_roundtrip::<C>(item);
+    if env::var_os(UPDATE_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(path, &bytes).unwrap();
+        return;
+    }
+
+    let golden = match fs::read(path) {
+        Ok(golden) => golden,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            panic!("the golden file {} is missing, set {} to write it", path.display(), UPDATE_VAR)
+        }
+        Err(e) => panic!("the golden file {} can't be read: {}", path.display(), e),
+    };
+    if golden != bytes {
+        let readable = match C::bytes_decode(&golden) {
+            Some(decoded) if decoded.borrow() == item => "can still be read",
+            _ => "can't be read anymore",
+        };
+        panic!(
+            "{} encodes {:?} to {:?} instead of the golden {:?} of {}, the data written before {}, \
+             set {} to accept the new encoding",
+            std::any::type_name::<C>(),
+            item,
+            bytes,
+            golden,
+            path.display(),
+            readable,
+            UPDATE_VAR
+        );
+    }
+}
+
+/// Checks that a value round trips through a codec, and returns its bytes, see the
+/// [`golden`](crate::golden) module.
+#[macro_export]
+macro_rules! assert_codec_roundtrip {
+    ($codec:ty, $value:expr $(,)?) => {
+        $crate::golden::assert_roundtrip::<$codec>(::std::borrow::Borrow::borrow(&$value))
+    };
+}
+
+/// Declares a test for every value, checking its bytes against the golden file of the test,
+/// see the [`golden`](crate::golden) module.
+///
+/// Every value is declared as `name: Codec = value;`. The golden files are stored in the
+/// `tests/golden` directory of the crate, the directory can be given first as
+/// `dir = "path";`, relative to the crate.
+#[macro_export]
+macro_rules! codec_golden_tests {
+    (dir = $dir:expr; $($name:ident: $codec:ty = $value:expr;)*) => {
+        $(
+            #[test]
+            fn $name() {
+                let path = ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join($dir)
+                    .join(concat!(stringify!($name), ".bin"));
+                $crate::golden::assert_golden::<$codec>(
+                    &path,
+                    ::std::borrow::Borrow::borrow(&$value),
+                );
+            }
+        )*
+    };
+    ($($name:ident: $codec:ty = $value:expr;)*) => {
+        $crate::codec_golden_tests! { dir = "tests/golden"; $($name: $codec = $value;)* }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{Composite, Str};
+
+    use super::*;
+
+    crate::codec_golden_tests! {
+        str_golden: Str = "hello";
+        composite_golden: Composite<(u32, u64)> = (1, 2);
+    }
+
+    #[cfg(feature = "serde-bincode")]
+    crate::codec_golden_tests! {
+        bincode_golden: heed_types::SerdeBincode<(u32, String)> = (42, "alice".to_string());
+        bincode_var_golden: heed_types::SerdeBincodeVar<(u32, String), heed_types::BigEndian> =
+            (1000, "alice".to_string());
+    }
+
+    #[cfg(feature = "serde-json")]
+    crate::codec_golden_tests! {
+        json_golden: heed_types::SerdeJson<Vec<u32>> = vec![1, 2, 3];
+    }
+
+    #[test]
+    #[should_panic(expected = "instead of the golden")]
+    fn changed_encodings_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("str.bin");
+        fs::write(&path, b"hallo").unwrap();
+        assert_golden::<Str>(&path, "hello");
+    }
+}
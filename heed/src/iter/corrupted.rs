@@ -0,0 +1,151 @@
+use super::*;
+use crate::{Error, Result};
+
+/// What an iterator does with the entries whose key or data can't be decoded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecodePolicy {
+    /// Returns an [`Error::Decoding`] for the entry, like the iterators do by default.
+    #[default]
+    Fail,
+    /// Skips the entry and counts it, see [`SkipCorrupted::skipped`].
+    Skip,
+}
+
+/// An iterator of a database that follows a [`DecodePolicy`] for the entries it can't
+/// decode, created by the `skip_corrupted` and `with_decode_policy` methods of the iterators.
+///
+/// The entries that are skipped are still read, the other errors are still returned,
+/// the usable entries of a partially corrupted database can be recovered with it.
+///
+/// ```
+/// # use heed::EnvOpenOptions;
+/// use heed::types::{ByteSlice, Str};
+/// use heed::Database;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempfile::tempdir()?;
+/// # let env = EnvOpenOptions::new()
+/// #     .map_size(10 * 1024 * 1024) // 10MB
+/// #     .max_dbs(3000)
+/// #     .open(dir.path())?;
+/// let mut wtxn = env.write_txn()?;
+/// let db: Database<Str, Str> = env.create_database(&mut wtxn, Some("names"))?;
+/// db.put(&mut wtxn, "a", "alice")?;
+/// db.remap_data_type::<ByteSlice>().put(&mut wtxn, "b", &[0xFF, 0xFE])?;
+/// db.put(&mut wtxn, "c", "carol")?;
+///
+/// let mut iter = db.iter(&wtxn)?.skip_corrupted();
+/// let names = iter.by_ref().map(|entry| entry.map(|(_, name)| name));
+/// let names: Vec<_> = names.collect::<heed::Result<_>>()?;
+/// assert_eq!(names, ["alice", "carol"]);
+/// assert_eq!(iter.skipped(), 1);
+/// # drop(iter);
+/// wtxn.commit()?;
+/// # Ok(()) }
+/// ```
+pub struct SkipCorrupted<I> {
+    iter: I,
+    policy: DecodePolicy,
+    skipped: usize,
+}
+
+impl<I> SkipCorrupted<I> {
+    pub(crate) fn new(iter: I, policy: DecodePolicy) -> SkipCorrupted<I> {
+        SkipCorrupted { iter, policy, skipped: 0 }
+    }
+
+    /// The number of entries skipped so far.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn policy(&self) -> DecodePolicy {
+        self.policy
+    }
+
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I, T> Iterator for SkipCorrupted<I>
+where
+    I: Iterator<Item = Result<T>>,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter.next() {
+                Some(Err(Error::Decoding)) if self.policy == DecodePolicy::Skip => {
+                    self.skipped += 1
+                }
+                item => return item,
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.policy {
+            DecodePolicy::Fail => self.iter.size_hint(),
+            DecodePolicy::Skip => (0, self.iter.size_hint().1),
+        }
+    }
+}
+
+macro_rules! impl_decode_policy {
+    ($($iter:ident),*) => {
+        $(
+            impl<'txn, KC, DC> $iter<'txn, KC, DC> {
+                /// Skips the entries that can't be decoded instead of returning an error,
+                /// see [`SkipCorrupted`].
+                pub fn skip_corrupted(self) -> SkipCorrupted<Self> {
+                    self.with_decode_policy(DecodePolicy::Skip)
+                }
+
+                /// Follows the `policy` for the entries that can't be decoded,
+                /// see [`SkipCorrupted`].
+                pub fn with_decode_policy(self, policy: DecodePolicy) -> SkipCorrupted<Self> {
+                    SkipCorrupted::new(self, policy)
+                }
+            }
+        )*
+    };
+}
+
+impl_decode_policy!(
+    RoIter,
+    RoRevIter,
+    RwIter,
+    RwRevIter,
+    RoPrefix,
+    RoRevPrefix,
+    RwPrefix,
+    RwRevPrefix,
+    RoRange,
+    RoRevRange,
+    RwRange,
+    RwRevRange
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_policy_only_skips_the_decoding_errors() {
+        let entries = vec![Ok(1), Err(Error::Decoding), Err(Error::Encoding), Ok(2)];
+
+        let mut failing = SkipCorrupted::new(entries.into_iter(), DecodePolicy::Fail);
+        assert!(matches!(failing.nth(1), Some(Err(Error::Decoding))));
+        assert_eq!(failing.skipped(), 0);
+
+        let entries = vec![Ok(1), Err(Error::Decoding), Err(Error::Encoding), Ok(2)];
+        let mut skipping = SkipCorrupted::new(entries.into_iter(), DecodePolicy::Skip);
+        assert!(matches!(skipping.next(), Some(Ok(1))));
+        assert!(matches!(skipping.next(), Some(Err(Error::Encoding))));
+        assert!(matches!(skipping.next(), Some(Ok(2))));
+        assert!(skipping.next().is_none());
+        assert_eq!(skipping.skipped(), 1);
+    }
+}
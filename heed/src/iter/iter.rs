@@ -1,17 +1,20 @@
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
 use std::marker;
 
+use super::IterAction;
 use crate::*;
 
 pub struct RoIter<'txn, KC, DC> {
     cursor: RoCursor<'txn>,
     move_on_first: bool,
+    /// The number of entries that are left to be returned.
+    remaining: usize,
     _phantom: marker::PhantomData<(KC, DC)>,
 }
 
 impl<'txn, KC, DC> RoIter<'txn, KC, DC> {
-    pub(crate) fn new(cursor: RoCursor<'txn>) -> RoIter<'txn, KC, DC> {
-        RoIter { cursor, move_on_first: true, _phantom: marker::PhantomData }
+    pub(crate) fn new(cursor: RoCursor<'txn>, len: usize) -> RoIter<'txn, KC, DC> {
+        RoIter { cursor, move_on_first: true, remaining: len, _phantom: marker::PhantomData }
     }
 
     /// Change the codec types of this iterator, specifying the codecs.
@@ -19,6 +22,7 @@ impl<'txn, KC, DC> RoIter<'txn, KC, DC> {
         RoIter {
             cursor: self.cursor,
             move_on_first: self.move_on_first,
+            remaining: self.remaining,
             _phantom: marker::PhantomData::default(),
         }
     }
@@ -33,6 +37,35 @@ impl<'txn, KC, DC> RoIter<'txn, KC, DC> {
         self.remap_types::<KC, DC2>()
     }
 
+    /// The bytes of the key and data of the entry the iterator is on, whatever its codecs,
+    /// `None` before the first entry. The bytes of an entry that can't be decoded can still
+    /// be inspected, without remapping the iterator.
+    pub fn current_raw(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        if self.move_on_first {
+            Ok(None)
+        } else {
+            self.cursor.current()
+        }
+    }
+
+    /// Moves to the next entry and returns the bytes of its key and data, without decoding them.
+    pub fn next_raw(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        let result = if self.move_on_first {
+            self.move_on_first = false;
+            self.cursor.move_on_first()
+        } else {
+            self.cursor.move_on_next()
+        };
+
+        match &result {
+            Ok(Some(_)) => self.remaining = self.remaining.saturating_sub(1),
+            Ok(None) => self.remaining = 0,
+            Err(_) => (),
+        }
+
+        result
+    }
+
     // /// Wrap the data bytes into a lazy decoder.
     // pub fn lazily_decode_data(self) -> RoIter<'txn, KC, LazyDecode<DC>> {
     //     self.remap_types::<KC, LazyDecode<DC>>()
@@ -47,14 +80,7 @@ where
     type Item = Result<(KC::DItem, DC::DItem)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let result = if self.move_on_first {
-            self.move_on_first = false;
-            self.cursor.move_on_first()
-        } else {
-            self.cursor.move_on_next()
-        };
-
-        match result {
+        match self.next_raw() {
             Ok(Some((key, data))) => match (KC::bytes_decode(key), DC::bytes_decode(data)) {
                 (Some(key), Some(data)) => Some(Ok((key, data))),
                 (_, _) => Some(Err(Error::Decoding)),
@@ -64,6 +90,10 @@ where
         }
     }
 
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_first {
             self.cursor.move_on_last()
@@ -88,6 +118,13 @@ where
     }
 }
 
+impl<'txn, KC, DC> ExactSizeIterator for RoIter<'txn, KC, DC>
+where
+    KC: BytesDecode,
+    DC: BytesDecode,
+{
+}
+
 pub struct RwIter<'txn, KC, DC> {
     cursor: RwCursor<'txn>,
     move_on_first: bool,
@@ -181,6 +218,21 @@ impl<'txn, KC, DC> RwIter<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwIter<'txn, KC2, DC2> {
         RwIter {
@@ -258,12 +310,14 @@ where
 pub struct RoRevIter<'txn, KC, DC> {
     cursor: RoCursor<'txn>,
     move_on_last: bool,
+    /// The number of entries that are left to be returned.
+    remaining: usize,
     _phantom: marker::PhantomData<(KC, DC)>,
 }
 
 impl<'txn, KC, DC> RoRevIter<'txn, KC, DC> {
-    pub(crate) fn new(cursor: RoCursor<'txn>) -> RoRevIter<'txn, KC, DC> {
-        RoRevIter { cursor, move_on_last: true, _phantom: marker::PhantomData }
+    pub(crate) fn new(cursor: RoCursor<'txn>, len: usize) -> RoRevIter<'txn, KC, DC> {
+        RoRevIter { cursor, move_on_last: true, remaining: len, _phantom: marker::PhantomData }
     }
 
     /// Change the codec types of this iterator, specifying the codecs.
@@ -271,6 +325,7 @@ impl<'txn, KC, DC> RoRevIter<'txn, KC, DC> {
         RoRevIter {
             cursor: self.cursor,
             move_on_last: self.move_on_last,
+            remaining: self.remaining,
             _phantom: marker::PhantomData::default(),
         }
     }
@@ -285,6 +340,35 @@ impl<'txn, KC, DC> RoRevIter<'txn, KC, DC> {
         self.remap_types::<KC, DC2>()
     }
 
+    /// The bytes of the key and data of the entry the iterator is on, whatever its codecs,
+    /// `None` before the first entry. The bytes of an entry that can't be decoded can still
+    /// be inspected, without remapping the iterator.
+    pub fn current_raw(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        if self.move_on_last {
+            Ok(None)
+        } else {
+            self.cursor.current()
+        }
+    }
+
+    /// Moves to the previous entry and returns the bytes of its key and data, without decoding them.
+    pub fn next_raw(&mut self) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
+        let result = if self.move_on_last {
+            self.move_on_last = false;
+            self.cursor.move_on_last()
+        } else {
+            self.cursor.move_on_prev()
+        };
+
+        match &result {
+            Ok(Some(_)) => self.remaining = self.remaining.saturating_sub(1),
+            Ok(None) => self.remaining = 0,
+            Err(_) => (),
+        }
+
+        result
+    }
+
     // /// Wrap the data bytes into a lazy decoder.
     // pub fn lazily_decode_data(self) -> RoRevIter<'txn, KC, LazyDecode<DC>> {
     //     self.remap_types::<KC, LazyDecode<DC>>()
@@ -299,14 +383,7 @@ where
     type Item = Result<(KC::DItem, DC::DItem)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let result = if self.move_on_last {
-            self.move_on_last = false;
-            self.cursor.move_on_last()
-        } else {
-            self.cursor.move_on_prev()
-        };
-
-        match result {
+        match self.next_raw() {
             Ok(Some((key, data))) => match (KC::bytes_decode(key), DC::bytes_decode(data)) {
                 (Some(key), Some(data)) => Some(Ok((key, data))),
                 (_, _) => Some(Err(Error::Decoding)),
@@ -316,6 +393,10 @@ where
         }
     }
 
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_last {
             self.cursor.move_on_first()
@@ -340,6 +421,13 @@ where
     }
 }
 
+impl<'txn, KC, DC> ExactSizeIterator for RoRevIter<'txn, KC, DC>
+where
+    KC: BytesDecode,
+    DC: BytesDecode,
+{
+}
+
 pub struct RwRevIter<'txn, KC, DC> {
     cursor: RwCursor<'txn>,
     move_on_last: bool,
@@ -419,6 +507,21 @@ impl<'txn, KC, DC> RwRevIter<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwRevIter<'txn, KC2, DC2> {
         RwRevIter {
@@ -492,3 +595,40 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use byteorder::BigEndian;
+    use zerocopy::U32;
+
+    use crate::types::{OwnedType, Unit};
+    use crate::EnvOpenOptions;
+
+    type BEU32 = U32<BigEndian>;
+
+    #[test]
+    fn exact_sizes_follow_the_iteration() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<BEU32>, Unit>(&mut wtxn, None).unwrap();
+        for i in 0..5 {
+            db.put(&mut wtxn, &BEU32::new(i), &()).unwrap();
+        }
+        wtxn.commit().unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        let mut iter = db.iter(&rtxn).unwrap();
+        assert_eq!(iter.len(), 5);
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.size_hint(), (4, Some(4)));
+        assert_eq!(iter.by_ref().count(), 4);
+        assert_eq!(iter.len(), 0);
+
+        let mut iter = db.rev_iter(&rtxn).unwrap();
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.next().unwrap().unwrap().0.get(), 4);
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.collect::<Result<Vec<_>, _>>().unwrap().len(), 4);
+    }
+}
@@ -1,30 +1,93 @@
+mod corrupted;
 mod iter;
 mod prefix;
 mod range;
+#[cfg(feature = "async")]
+mod stream;
 
+use std::borrow::Borrow;
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+pub use self::corrupted::{DecodePolicy, SkipCorrupted};
 pub use self::iter::{RoIter, RoRevIter, RwIter, RwRevIter};
 pub use self::prefix::{RoPrefix, RoRevPrefix, RwPrefix, RwRevPrefix};
 pub use self::range::{RoRange, RoRevRange, RwRange, RwRevRange};
+#[cfg(feature = "async")]
+pub use self::stream::{RangeQuery, RangeStream};
 
-pub(crate) fn advance_key(bytes: &mut Vec<u8>) {
-    match bytes.last_mut() {
-        Some(&mut 255) | None => bytes.push(0),
-        Some(last) => *last += 1,
-    }
+/// The smallest key greater than every key starting with `prefix`,
+/// `None` if there is none and the range is unbounded.
+pub fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    heed_types::KeyRange::prefix(prefix).end
 }
 
-pub(crate) fn retreat_key(bytes: &mut Vec<u8>) {
-    match bytes.last_mut() {
-        Some(&mut 0) => {
-            bytes.pop();
+/// What to do with an entry of a mutable iterator, returned by the closure given to
+/// [`RwIter::update_each`] and to the same method of the other mutable iterators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterAction<V> {
+    /// Leaves the entry as it is.
+    Keep,
+    /// Replaces the value of the entry.
+    Put(V),
+    /// Deletes the entry.
+    Delete,
+    /// Leaves the entry as it is and stops the iteration.
+    Stop,
+}
+
+/// Calls `f` with the entries left in the iterator and applies the actions it returns,
+/// returns the number of entries replaced or deleted.
+pub(crate) fn update_each<'txn, I, KC, DC, F>(
+    iter: &mut I,
+    cursor: for<'i> fn(&'i mut I) -> &'i mut crate::RwCursor<'txn>,
+    mut f: F,
+) -> crate::Result<usize>
+where
+    I: Iterator<Item = crate::Result<(KC::DItem, DC::DItem)>>,
+    KC: BytesDecode,
+    DC: BytesDecode + for<'a> BytesEncode<'a>,
+    DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+    F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+{
+    let mut updated = 0;
+    while let Some(entry) = iter.next() {
+        let (key, data) = entry?;
+        // The decoded entries are owned and the key is copied, nothing
+        // borrows the database when the cursor modifies it.
+        match f(key, data) {
+            IterAction::Keep => (),
+            IterAction::Put(data) => {
+                let key = match cursor(iter).current()? {
+                    Some((key, _)) => key.to_vec(),
+                    None => break,
+                };
+                let data = DC::bytes_encode(data.borrow()).ok_or(crate::Error::Encoding)?;
+                unsafe { cursor(iter).put_current(&key, &data)? };
+                updated += 1;
+            }
+            IterAction::Delete => {
+                unsafe { cursor(iter).del_current()? };
+                updated += 1;
+            }
+            IterAction::Stop => break,
         }
-        Some(last) => *last -= 1,
-        None => panic!("Vec is empty and must not be"),
     }
+    Ok(updated)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::prefix_end;
+
+    #[test]
+    fn prefix_end_bounds_the_prefixed_keys() {
+        assert_eq!(prefix_end(b"ab"), Some(b"ac".to_vec()));
+        assert_eq!(prefix_end(&[1, 0xFF, 0xFF]), Some(vec![2]));
+        assert_eq!(prefix_end(&[0xFF]), None);
+        assert_eq!(prefix_end(&[]), None);
+    }
+
     #[test]
     fn prefix_iter_with_byte_255() {
         use std::fs;
@@ -64,6 +127,95 @@ mod tests {
         wtxn.abort().unwrap();
     }
 
+    #[test]
+    fn update_each_puts_and_deletes_safely() {
+        use crate::types::*;
+        use crate::{EnvOpenOptions, IterAction};
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        for (key, data) in &[("a", "alice"), ("b", "bob"), ("c", "carol"), ("d", "dave")] {
+            db.put(&mut wtxn, key, data).unwrap();
+        }
+
+        let mut iter = db.iter_mut(&mut wtxn).unwrap();
+        let updated = iter
+            .update_each(|key, data| match key.as_str() {
+                "a" => IterAction::Put(data.to_uppercase()),
+                "b" => IterAction::Delete,
+                "c" => IterAction::Stop,
+                _ => IterAction::Put(String::new()),
+            })
+            .unwrap();
+        assert_eq!(updated, 2);
+        drop(iter);
+        let entries: Vec<_> = db.iter(&wtxn).unwrap().map(Result::unwrap).collect();
+        let names: Vec<_> = entries.iter().map(|(_, name)| name.as_str()).collect();
+        assert_eq!(names, ["ALICE", "carol", "dave"]);
+
+        let mut iter = db.rev_iter_mut(&mut wtxn).unwrap();
+        assert_eq!(iter.update_each(|_, _| IterAction::Delete).unwrap(), 3);
+        drop(iter);
+        assert!(db.is_empty(&wtxn).unwrap());
+
+        wtxn.abort().unwrap();
+    }
+
+    #[test]
+    fn rev_prefix_iter_with_byte_255() {
+        use crate::types::*;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<ByteSlice, Unit>(&mut wtxn, None).unwrap();
+
+        // The keys extending the prefix sort after the prefix with an additional 0.
+        for key in &[&[1, 254][..], &[1, 255], &[1, 255, 0], &[1, 255, 1], &[2]] {
+            db.put(&mut wtxn, key, &()).unwrap();
+        }
+
+        let keys: Vec<_> =
+            db.rev_prefix_iter(&wtxn, &[1, 255]).unwrap().map(|e| e.unwrap().0).collect();
+        assert_eq!(keys, [vec![1, 255, 1], vec![1, 255, 0], vec![1, 255]]);
+
+        let keys: Vec<_> =
+            db.rev_prefix_iter(&wtxn, &[255]).unwrap().map(|e| e.unwrap().0).collect();
+        assert!(keys.is_empty());
+    }
+
+    #[test]
+    fn raw_entries_ignore_the_codecs() {
+        use crate::types::*;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        db.put(&mut wtxn, "a", "alice").unwrap();
+        db.remap_data_type::<ByteSlice>().put(&mut wtxn, "b", &[0xFF]).unwrap();
+
+        let mut iter = db.iter(&wtxn).unwrap();
+        assert_eq!(iter.current_raw().unwrap(), None);
+        assert_eq!(iter.next().transpose().unwrap(), Some(("a".to_string(), "alice".to_string())));
+        assert!(matches!(iter.next(), Some(Err(crate::Error::Decoding))));
+        assert_eq!(iter.current_raw().unwrap(), Some((&b"b"[..], &[0xFF][..])));
+        assert_eq!(iter.next_raw().unwrap(), None);
+        assert_eq!(iter.len(), 0);
+        drop(iter);
+
+        let mut iter = db.rev_iter(&wtxn).unwrap();
+        assert_eq!(iter.next_raw().unwrap(), Some((&b"b"[..], &[0xFF][..])));
+        assert_eq!(iter.next_raw().unwrap(), Some((&b"a"[..], &b"alice"[..])));
+        drop(iter);
+
+        wtxn.abort().unwrap();
+    }
+
     #[test]
     fn iter_last() {
         use std::fs;
@@ -1,18 +1,20 @@
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
 use std::marker;
 
-use super::{advance_key, retreat_key};
+use super::prefix_end;
+use super::IterAction;
 use crate::*;
 
 fn move_on_prefix_end<'txn>(
     cursor: &mut RoCursor<'txn>,
-    prefix: &mut Vec<u8>,
+    prefix: &[u8],
 ) -> Result<Option<(&'txn [u8], &'txn [u8])>> {
-    advance_key(prefix);
-    let result =
-        cursor.move_on_key_greater_than_or_equal_to(prefix).and_then(|_| cursor.move_on_prev());
-    retreat_key(prefix);
-    result
+    match prefix_end(prefix) {
+        Some(end) => {
+            cursor.move_on_key_greater_than_or_equal_to(&end).and_then(|_| cursor.move_on_prev())
+        }
+        None => cursor.move_on_last(),
+    }
 }
 
 pub struct RoPrefix<'txn, KC, DC> {
@@ -86,9 +88,9 @@ where
 
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_first {
-            move_on_prefix_end(&mut self.cursor, &mut self.prefix)
+            move_on_prefix_end(&mut self.cursor, &self.prefix)
         } else {
-            match (self.cursor.current(), move_on_prefix_end(&mut self.cursor, &mut self.prefix)) {
+            match (self.cursor.current(), move_on_prefix_end(&mut self.cursor, &self.prefix)) {
                 (Ok(Some((ckey, _))), Ok(Some((key, data)))) if ckey != key => {
                     Ok(Some((key, data)))
                 }
@@ -208,6 +210,21 @@ impl<'txn, KC, DC> RwPrefix<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwPrefix<'txn, KC2, DC2> {
         RwPrefix {
@@ -267,9 +284,9 @@ where
 
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_first {
-            move_on_prefix_end(&mut self.cursor, &mut self.prefix)
+            move_on_prefix_end(&mut self.cursor, &self.prefix)
         } else {
-            match (self.cursor.current(), move_on_prefix_end(&mut self.cursor, &mut self.prefix)) {
+            match (self.cursor.current(), move_on_prefix_end(&mut self.cursor, &self.prefix)) {
                 (Ok(Some((ckey, _))), Ok(Some((key, data)))) if ckey != key => {
                     Ok(Some((key, data)))
                 }
@@ -343,7 +360,7 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let result = if self.move_on_last {
             self.move_on_last = false;
-            move_on_prefix_end(&mut self.cursor, &mut self.prefix)
+            move_on_prefix_end(&mut self.cursor, &self.prefix)
         } else {
             self.cursor.move_on_prev()
         };
@@ -490,6 +507,21 @@ impl<'txn, KC, DC> RwRevPrefix<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwRevPrefix<'txn, KC2, DC2> {
         RwRevPrefix {
@@ -526,7 +558,7 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let result = if self.move_on_last {
             self.move_on_last = false;
-            move_on_prefix_end(&mut self.cursor, &mut self.prefix)
+            move_on_prefix_end(&mut self.cursor, &self.prefix)
         } else {
             self.cursor.move_on_prev()
         };
@@ -1,8 +1,8 @@
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
 use std::marker;
 use std::ops::Bound;
 
-use super::{advance_key, retreat_key};
+use super::IterAction;
 use crate::*;
 
 fn move_on_range_end<'txn>(
@@ -29,9 +29,10 @@ fn move_on_range_start<'txn>(
     match start_bound {
         Bound::Included(start) => cursor.move_on_key_greater_than_or_equal_to(start),
         Bound::Excluded(start) => {
-            advance_key(start);
+            // The smallest key greater than the start is the start followed by a zero.
+            start.push(0);
             let result = cursor.move_on_key_greater_than_or_equal_to(start);
-            retreat_key(start);
+            start.pop();
             result
         }
         Bound::Unbounded => cursor.move_on_first(),
@@ -43,6 +44,12 @@ pub struct RoRange<'txn, KC, DC> {
     move_on_start: bool,
     start_bound: Bound<Vec<u8>>,
     end_bound: Bound<Vec<u8>>,
+    /// The key given to the last `seek`, used instead of the start bound on the next move.
+    seek_key: Option<Vec<u8>>,
+    /// The number of entries in the database, seeking can move back to any of them.
+    len: usize,
+    /// An upper bound of the number of entries that are left to be returned.
+    remaining: usize,
     _phantom: marker::PhantomData<(KC, DC)>,
 }
 
@@ -51,16 +58,42 @@ impl<'txn, KC, DC> RoRange<'txn, KC, DC> {
         cursor: RoCursor<'txn>,
         start_bound: Bound<Vec<u8>>,
         end_bound: Bound<Vec<u8>>,
+        len: usize,
     ) -> RoRange<'txn, KC, DC> {
         RoRange {
             cursor,
             move_on_start: true,
             start_bound,
             end_bound,
+            seek_key: None,
+            len,
+            remaining: len,
             _phantom: marker::PhantomData,
         }
     }
 
+    /// Moves the underlying cursor so that the next entry returned is the first one
+    /// with a key greater than or equal to `key`, without recreating the iterator.
+    ///
+    /// Seeking before the start of the range moves back to the start of the range.
+    pub fn seek<'a>(&mut self, key: &'a KC::EItem) -> Result<()>
+    where
+        KC: BytesEncode<'a>,
+    {
+        let key = KC::bytes_encode(key).ok_or(Error::Encoding)?.into_owned();
+        let in_range = match &self.start_bound {
+            Bound::Included(start) => &key >= start,
+            Bound::Excluded(start) => &key > start,
+            Bound::Unbounded => true,
+        };
+
+        self.seek_key = if in_range { Some(key) } else { None };
+        self.move_on_start = true;
+        self.remaining = self.len;
+
+        Ok(())
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RoRange<'txn, KC2, DC2> {
         RoRange {
@@ -68,6 +101,9 @@ impl<'txn, KC, DC> RoRange<'txn, KC, DC> {
             move_on_start: self.move_on_start,
             start_bound: self.start_bound,
             end_bound: self.end_bound,
+            seek_key: self.seek_key,
+            len: self.len,
+            remaining: self.remaining,
             _phantom: marker::PhantomData::default(),
         }
     }
@@ -98,11 +134,20 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let result = if self.move_on_start {
             self.move_on_start = false;
-            move_on_range_start(&mut self.cursor, &mut self.start_bound)
+            match self.seek_key.take() {
+                Some(key) => self.cursor.move_on_key_greater_than_or_equal_to(&key),
+                None => move_on_range_start(&mut self.cursor, &mut self.start_bound),
+            }
         } else {
             self.cursor.move_on_next()
         };
 
+        match &result {
+            Ok(Some(_)) => self.remaining = self.remaining.saturating_sub(1),
+            Ok(None) => self.remaining = 0,
+            Err(_) => (),
+        }
+
         match result {
             Ok(Some((key, data))) => {
                 let must_be_returned = match &self.end_bound {
@@ -117,6 +162,7 @@ where
                         (_, _) => Some(Err(Error::Decoding)),
                     }
                 } else {
+                    self.remaining = 0;
                     None
                 }
             }
@@ -125,6 +171,10 @@ where
         }
     }
 
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_start {
             move_on_range_end(&mut self.cursor, &self.end_bound)
@@ -144,6 +194,9 @@ where
                     Bound::Included(start) => key >= start,
                     Bound::Excluded(start) => key > start,
                     Bound::Unbounded => true,
+                } && match &self.seek_key {
+                    Some(seek) => key >= &seek[..],
+                    None => true,
                 };
 
                 if must_be_returned {
@@ -266,6 +319,21 @@ impl<'txn, KC, DC> RwRange<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwRange<'txn, KC2, DC2> {
         RwRange {
@@ -371,6 +439,8 @@ pub struct RoRevRange<'txn, KC, DC> {
     move_on_end: bool,
     start_bound: Bound<Vec<u8>>,
     end_bound: Bound<Vec<u8>>,
+    /// An upper bound of the number of entries that are left to be returned.
+    remaining: usize,
     _phantom: marker::PhantomData<(KC, DC)>,
 }
 
@@ -379,12 +449,14 @@ impl<'txn, KC, DC> RoRevRange<'txn, KC, DC> {
         cursor: RoCursor<'txn>,
         start_bound: Bound<Vec<u8>>,
         end_bound: Bound<Vec<u8>>,
+        len: usize,
     ) -> RoRevRange<'txn, KC, DC> {
         RoRevRange {
             cursor,
             move_on_end: true,
             start_bound,
             end_bound,
+            remaining: len,
             _phantom: marker::PhantomData,
         }
     }
@@ -396,6 +468,7 @@ impl<'txn, KC, DC> RoRevRange<'txn, KC, DC> {
             move_on_end: self.move_on_end,
             start_bound: self.start_bound,
             end_bound: self.end_bound,
+            remaining: self.remaining,
             _phantom: marker::PhantomData::default(),
         }
     }
@@ -431,6 +504,12 @@ where
             self.cursor.move_on_prev()
         };
 
+        match &result {
+            Ok(Some(_)) => self.remaining = self.remaining.saturating_sub(1),
+            Ok(None) => self.remaining = 0,
+            Err(_) => (),
+        }
+
         match result {
             Ok(Some((key, data))) => {
                 let must_be_returned = match &self.start_bound {
@@ -445,6 +524,7 @@ where
                         (_, _) => Some(Err(Error::Decoding)),
                     }
                 } else {
+                    self.remaining = 0;
                     None
                 }
             }
@@ -453,6 +533,10 @@ where
         }
     }
 
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+
     fn last(mut self) -> Option<Self::Item> {
         let result = if self.move_on_end {
             move_on_range_start(&mut self.cursor, &mut self.start_bound)
@@ -596,6 +680,21 @@ impl<'txn, KC, DC> RwRevRange<'txn, KC, DC> {
         self.cursor.append(&key_bytes, &data_bytes)
     }
 
+    /// Calls `f` with the entries left to iterate and replaces or deletes them as it asks,
+    /// see [`IterAction`], returns the number of entries replaced or deleted.
+    ///
+    /// This is the safe alternative to `put_current` and `del_current`, the closure
+    /// is given decoded entries that don't borrow the database.
+    pub fn update_each<F>(&mut self, f: F) -> Result<usize>
+    where
+        KC: BytesDecode,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC::DItem: for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+        F: FnMut(KC::DItem, DC::DItem) -> IterAction<DC::DItem>,
+    {
+        super::update_each::<_, KC, DC, _>(self, |iter| &mut iter.cursor, f)
+    }
+
     /// Change the codec types of this iterator, specifying the codecs.
     pub fn remap_types<KC2, DC2>(self) -> RwRevRange<'txn, KC2, DC2> {
         RwRevRange {
@@ -697,3 +796,75 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use byteorder::BigEndian;
+    use zerocopy::U32;
+
+    use super::RoRange;
+    use crate::types::{OwnedType, Unit};
+    use crate::{Database, Env, EnvOpenOptions};
+
+    type BEU32 = U32<BigEndian>;
+
+    /// An environment with the keys 0 to 9.
+    fn ten_keys() -> (tempfile::TempDir, Env, Database<OwnedType<BEU32>, Unit>) {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<BEU32>, Unit>(&mut wtxn, None).unwrap();
+        for i in 0..10 {
+            db.put(&mut wtxn, &BEU32::new(i), &()).unwrap();
+        }
+        wtxn.commit().unwrap();
+        (dir, env, db)
+    }
+
+    fn next(iter: &mut RoRange<OwnedType<BEU32>, Unit>) -> Option<u32> {
+        iter.next().map(|entry| entry.unwrap().0.get())
+    }
+
+    #[test]
+    fn size_hints_bound_the_remaining_entries() {
+        let (_dir, env, db) = ten_keys();
+        let rtxn = env.read_txn().unwrap();
+
+        let range = BEU32::new(2)..BEU32::new(5);
+        let mut iter = db.range(&rtxn, &range).unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(10)));
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(9)));
+        assert_eq!(iter.by_ref().count(), 2);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+
+        let mut iter = db.rev_range(&rtxn, &range).unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(10)));
+        assert_eq!(iter.next().unwrap().unwrap().0.get(), 4);
+        assert_eq!(iter.size_hint(), (0, Some(9)));
+        assert_eq!(iter.by_ref().count(), 2);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn seek_stays_within_the_range() {
+        let (_dir, env, db) = ten_keys();
+        let rtxn = env.read_txn().unwrap();
+
+        let range = BEU32::new(2)..=BEU32::new(6);
+        let mut iter = db.range(&rtxn, &range).unwrap();
+        iter.seek(&BEU32::new(5)).unwrap();
+        assert_eq!(next(&mut iter), Some(5));
+        assert_eq!(next(&mut iter), Some(6));
+        assert_eq!(next(&mut iter), None);
+
+        // Seeking moves back, even once the range is exhausted, but never before its start.
+        iter.seek(&BEU32::new(0)).unwrap();
+        assert_eq!(iter.size_hint(), (0, Some(10)));
+        assert_eq!(next(&mut iter), Some(2));
+        iter.seek(&BEU32::new(4)).unwrap();
+        assert_eq!(next(&mut iter), Some(4));
+        iter.seek(&BEU32::new(8)).unwrap();
+        assert_eq!(next(&mut iter), None);
+    }
+}
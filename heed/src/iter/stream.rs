@@ -0,0 +1,301 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::marker;
+use std::ops::Bound;
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_channel::oneshot;
+use futures_core::Stream;
+
+use crate::mdb::ffi;
+use crate::*;
+
+type Chunk<K, D> = Vec<Result<(K, D)>>;
+
+/// A range of a database that owns everything it needs to be read later on, on another thread.
+///
+/// It is created by the [`Database::range_query`] method and can be turned into
+/// a [`RangeStream`] with the [`RangeQuery::into_stream`] method.
+pub struct RangeQuery<KC, DC> {
+    env: Env,
+    dbi: ffi::MDB_dbi,
+    start_bound: Bound<Vec<u8>>,
+    end_bound: Bound<Vec<u8>>,
+    chunk_size: usize,
+    _phantom: marker::PhantomData<fn() -> (KC, DC)>,
+}
+
+impl<KC, DC> RangeQuery<KC, DC> {
+    pub(crate) fn new(
+        env: Env,
+        dbi: ffi::MDB_dbi,
+        start_bound: Bound<Vec<u8>>,
+        end_bound: Bound<Vec<u8>>,
+    ) -> RangeQuery<KC, DC> {
+        RangeQuery {
+            env,
+            dbi,
+            start_bound,
+            end_bound,
+            chunk_size: 256,
+            _phantom: marker::PhantomData,
+        }
+    }
+
+    /// Set the number of entries that are read and decoded at once, defaults to 256.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given size is zero.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "the chunk size must not be zero");
+        self.chunk_size = size;
+        self
+    }
+
+    /// Turn this query into a stream of the decoded entries of the range.
+    ///
+    /// The entries are read in chunks by a dedicated thread that owns a clone of the environment
+    /// and the read transaction, every entry of the stream therefore comes from the same snapshot.
+    /// The next chunk is fetched while the previous one is being consumed.
+    ///
+    /// The environment can't be closed while the stream is alive.
+    pub fn into_stream(self) -> Result<RangeStream<KC::DItem, DC::DItem>>
+    where
+        KC: BytesDecode + 'static,
+        DC: BytesDecode + 'static,
+        KC::DItem: Send,
+        DC::DItem: Send,
+    {
+        let chunk_size = self.chunk_size;
+        let (requests, receiver) = mpsc::channel();
+
+        thread::Builder::new()
+            .name(String::from("heed-range-stream"))
+            .spawn(move || self.fetch_chunks(receiver))?;
+
+        Ok(RangeStream {
+            requests: Some(requests),
+            pending: None,
+            buffered: VecDeque::new(),
+            chunk_size,
+        })
+    }
+
+    fn fetch_chunks(self, requests: mpsc::Receiver<oneshot::Sender<Chunk<KC::DItem, DC::DItem>>>)
+    where
+        KC: BytesDecode,
+        DC: BytesDecode,
+    {
+        let RangeQuery { env, dbi, start_bound, end_bound, chunk_size, .. } = self;
+
+        let txn = match env.read_txn() {
+            Ok(txn) => txn,
+            Err(e) => return reply_with_error(requests, e),
+        };
+
+        let db = PolyDatabase::new(env.env_mut_ptr() as usize, dbi);
+        let range = db.len(&txn).and_then(|len| {
+            RoCursor::new(&txn, dbi).map(|cursor| RoRange::new(cursor, start_bound, end_bound, len))
+        });
+
+        let mut range: RoRange<KC, DC> = match range {
+            Ok(range) => range,
+            Err(e) => return reply_with_error(requests, e),
+        };
+
+        for reply in requests {
+            let chunk: Vec<_> = range.by_ref().take(chunk_size).collect();
+            let last = chunk.len() < chunk_size;
+            if reply.send(chunk).is_err() || last {
+                break;
+            }
+        }
+    }
+}
+
+fn reply_with_error<K, D>(requests: mpsc::Receiver<oneshot::Sender<Chunk<K, D>>>, error: Error) {
+    if let Ok(reply) = requests.recv() {
+        let _ = reply.send(vec![Err(error)]);
+    }
+}
+
+/// A stream over the decoded entries of a range, created by [`RangeQuery::into_stream`].
+pub struct RangeStream<K, D> {
+    requests: Option<mpsc::Sender<oneshot::Sender<Chunk<K, D>>>>,
+    pending: Option<oneshot::Receiver<Chunk<K, D>>>,
+    buffered: VecDeque<Result<(K, D)>>,
+    chunk_size: usize,
+}
+
+impl<K, D> RangeStream<K, D> {
+    /// Ask the reading thread for the next chunk, if the range isn't exhausted.
+    fn request_chunk(&mut self) {
+        if let Some(requests) = &self.requests {
+            let (reply, pending) = oneshot::channel();
+            match requests.send(reply) {
+                Ok(()) => self.pending = Some(pending),
+                Err(_) => self.requests = None,
+            }
+        }
+    }
+}
+
+impl<K, D> Unpin for RangeStream<K, D> {}
+
+impl<K, D> Stream for RangeStream<K, D> {
+    type Item = Result<(K, D)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            if let Some(pending) = &mut this.pending {
+                match Pin::new(pending).poll(cx) {
+                    Poll::Ready(Ok(chunk)) => {
+                        this.pending = None;
+                        if chunk.len() < this.chunk_size {
+                            this.requests = None;
+                        }
+                        this.buffered.extend(chunk);
+                        this.request_chunk();
+                    }
+                    // The reading thread stopped, there is nothing more to read.
+                    Poll::Ready(Err(_)) => {
+                        this.pending = None;
+                        this.requests = None;
+                    }
+                    Poll::Pending if this.buffered.is_empty() => return Poll::Pending,
+                    Poll::Pending => (),
+                }
+            }
+
+            if let Some(item) = this.buffered.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+
+            if this.pending.is_none() {
+                if this.requests.is_none() {
+                    return Poll::Ready(None);
+                }
+                this.request_chunk();
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let upper = if self.requests.is_none() && self.pending.is_none() {
+            Some(self.buffered.len())
+        } else {
+            None
+        };
+        (self.buffered.len(), upper)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::RangeBounds;
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::time::{Duration, Instant};
+
+    use byteorder::BigEndian;
+    use zerocopy::U32;
+
+    use super::*;
+    use crate::types::{OwnedType, Unit};
+
+    type BEU32 = U32<BigEndian>;
+
+    /// Wakes the thread waiting for the next entry of a stream.
+    struct Unpark(thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls the stream on this thread until its next entry is ready.
+    fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+        let waker = Arc::new(Unpark(thread::current())).into();
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut *stream).poll_next(&mut cx) {
+                Poll::Ready(item) => return item,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn streamed<R: RangeBounds<BEU32>>(
+        env: &Env,
+        db: Database<OwnedType<BEU32>, Unit>,
+        range: &R,
+        chunk_size: usize,
+    ) -> Vec<u32> {
+        let query = db.range_query(env, range).unwrap().chunk_size(chunk_size);
+        let mut stream = query.into_stream().unwrap();
+        std::iter::from_fn(|| next(&mut stream)).map(|entry| entry.unwrap().0.get()).collect()
+    }
+
+    #[test]
+    fn streams_follow_the_order_and_the_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<BEU32>, Unit>(&mut wtxn, None).unwrap();
+        // Written out of order, read in the order of the keys.
+        for i in (0..100).rev() {
+            db.put(&mut wtxn, &BEU32::new(i * 3), &()).unwrap();
+        }
+        wtxn.commit().unwrap();
+
+        let key = BEU32::new;
+        let all: Vec<_> = (0..100).map(|i| i * 3).collect();
+        assert_eq!(streamed(&env, db, &(..), 7), all);
+        assert_eq!(streamed(&env, db, &(..), 100), all);
+        assert_eq!(
+            streamed(&env, db, &(key(30)..key(60)), 4),
+            (10..20).map(|i| i * 3).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            streamed(&env, db, &(key(31)..=key(60)), 256),
+            (11..=20).map(|i| i * 3).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            streamed(&env, db, &(Bound::Excluded(key(288)), Bound::Unbounded), 1),
+            [291, 294, 297]
+        );
+        assert!(streamed(&env, db, &(key(1000)..), 7).is_empty());
+    }
+
+    #[test]
+    fn dropped_streams_end_their_read_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<BEU32>, Unit>(&mut wtxn, None).unwrap();
+        for i in 0..1000 {
+            db.put(&mut wtxn, &BEU32::new(i), &()).unwrap();
+        }
+        wtxn.commit().unwrap();
+
+        let mut stream = db.range_query(&env, &(..)).unwrap().chunk_size(10).into_stream().unwrap();
+        assert_eq!(next(&mut stream).unwrap().unwrap().0.get(), 0);
+        assert_eq!(next(&mut stream).unwrap().unwrap().0.get(), 1);
+        assert_eq!(env.read_txns(), 1);
+        drop(stream);
+
+        // The reading thread sees the stream is gone when it sends its next chunk.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while env.read_txns() != 0 {
+            assert!(Instant::now() < deadline, "the read transaction of the stream is still alive");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
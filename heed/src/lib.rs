@@ -47,14 +47,21 @@
 //! # Ok(()) }
 //! ```
 
+#[cfg(feature = "mdbx")]
+pub mod backup;
 mod cursor;
 mod db;
+mod db_name;
 mod env;
+mod extensions;
+pub mod golden;
 mod iter;
 mod lazy_decode;
 mod mdb;
 pub mod store;
+pub mod sync;
 mod txn;
+pub mod tune;
 
 use std::{error, fmt, io, result};
 
@@ -63,35 +70,141 @@ pub use {byteorder, heed_types as types, zerocopy};
 
 use self::cursor::{RoCursor, RwCursor};
 pub use self::db::{Database, PolyDatabase};
-pub use self::env::{env_closing_event, CompactionOption, Env, EnvClosingEvent, EnvOpenOptions};
+pub use self::db_name::DbName;
+pub use self::env::{
+    env_closing_event, Advice, Canary, CompactionOption, Env, EnvClosingEvent, EnvOpenOptions,
+    FreelistStats, Opened,
+};
+pub use self::extensions::Extensions;
+#[cfg(feature = "async")]
+pub use self::iter::{RangeQuery, RangeStream};
 pub use self::iter::{
-    RoIter, RoPrefix, RoRange, RoRevIter, RoRevPrefix, RoRevRange, RwIter, RwPrefix, RwRange,
-    RwRevIter, RwRevPrefix, RwRevRange,
+    DecodePolicy, IterAction, RoIter, RoPrefix, RoRange, RoRevIter, RoRevPrefix, RoRevRange,
+    RwIter, RwPrefix, RwRange, RwRevIter, RwRevPrefix, RwRevRange, SkipCorrupted,
 };
 // pub use self::lazy_decode::{Lazy, LazyDecode};
 pub use self::mdb::error::Error as MdbError;
 use self::mdb::ffi::{from_val, into_val};
 pub use self::mdb::flags;
-pub use self::traits::{BytesDecode, BytesEncode};
-pub use self::txn::{RoTxn, RwTxn};
+pub use self::traits::{BytesDecode, BytesEncode, BytesEncodeInto};
+#[cfg(feature = "mdbx")]
+pub use self::txn::TxnInfo;
+pub use self::txn::{Durability, OwnedRoTxn, RoTxn, RwTxn, TxnKind};
+#[cfg(feature = "read-txn-no-tls")]
+pub use self::txn::{PooledRoTxn, RoTxnPool};
+
+/// Derive the codecs of the keys and values stored in databases.
+///
+/// ```
+/// # use heed::EnvOpenOptions;
+/// use heed::{Database, HeedKey, HeedValue};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Debug, PartialEq, HeedKey)]
+/// struct Visit {
+///     user: u32,
+///     at: i64,
+/// }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize, HeedValue)]
+/// #[heed(format = "json")]
+/// struct Page {
+///     url: String,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// # let dir = tempfile::tempdir()?;
+/// # let env = EnvOpenOptions::new()
+/// #     .map_size(10 * 1024 * 1024) // 10MB
+/// #     .max_dbs(3000)
+/// #     .open(dir.path())?;
+/// let mut wtxn = env.write_txn()?;
+/// let visits: Database<Visit, Page> = env.create_database(&mut wtxn, Some("visits"))?;
+/// for &(user, at) in &[(2, -5), (1, 10), (1, -3)] {
+///     visits.put(&mut wtxn, &Visit { user, at }, &Page { url: format!("/{}", at) })?;
+/// }
+///
+/// // The visits are sorted by user, then by time.
+/// let mut keys = Vec::new();
+/// for entry in visits.iter(&wtxn)? {
+///     let (visit, _page) = entry?;
+///     keys.push((visit.user, visit.at));
+/// }
+/// assert_eq!(keys, [(1, -3), (1, 10), (2, -5)]);
+/// assert_eq!(visits.get(&wtxn, &Visit { user: 1, at: 10 })?, Some(Page { url: "/10".into() }));
+/// wtxn.commit()?;
+/// # Ok(()) }
+/// ```
+#[cfg(feature = "derive")]
+pub use heed_derive::{HeedKey, HeedValue};
 
 /// An error that encapsulates all possible errors in this crate.
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
-    Mdb(MdbError),
+    /// An error of LMDB, with the context of the operation that failed.
+    Mdb {
+        /// The LMDB function that failed, like `mdb_put`, `None` when it isn't known.
+        op: Option<&'static str>,
+        /// The name of the database of the operation, `None` for the unnamed database,
+        /// the operations on the environment, or when it isn't known.
+        db: Option<String>,
+        /// The length of the key of the operation.
+        key_len: Option<usize>,
+        /// The kind of the transaction of the operation.
+        txn: Option<TxnKind>,
+        source: MdbError,
+    },
     Encoding,
     Decoding,
     InvalidDatabaseTyping,
     DatabaseClosing,
     BadOpenOptions,
+    /// A write transaction was asked to an environment opened read-only.
+    ReadOnly,
+    /// The reader table of the environment is full, or the environment was opened with a
+    /// [`max_concurrent_read_txns`](EnvOpenOptions::max_concurrent_read_txns) that is reached.
+    /// A read transaction can begin once another one ends.
+    ReadersFull,
+    /// A write transaction has too many dirty pages, it must be aborted. The work must be
+    /// split in smaller transactions, with MDBX `RwTxn::info` tells when to commit.
+    TxnFull,
+    /// A key is larger than the `Env::max_key_size` of its environment.
+    KeyTooLong {
+        /// The name of the database, `None` for the unnamed one.
+        database: Option<String>,
+        len: usize,
+        max: usize,
+    },
+    /// A key appended to a database doesn't sort after its last key, appended keys
+    /// must be strictly increasing under the comparator of the database.
+    UnsortedAppend {
+        /// The name of the database, `None` for the unnamed one.
+        database: Option<String>,
+        /// The last key of the database, that the appended key doesn't sort after.
+        last: Vec<u8>,
+        key: Vec<u8>,
+    },
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Io(error) => write!(f, "{}", error),
-            Error::Mdb(error) => write!(f, "{}", error),
+            Error::Mdb { op: None, source, .. } => write!(f, "{}", source),
+            Error::Mdb { op: Some(op), db, key_len, txn, source } => {
+                write!(f, "{}", op)?;
+                if let Some(db) = db {
+                    write!(f, " on the {:?} database", db)?;
+                }
+                if let Some(txn) = txn {
+                    write!(f, " in a {} transaction", txn)?;
+                }
+                if let Some(key_len) = key_len {
+                    write!(f, " with a key of {} bytes", key_len)?;
+                }
+                write!(f, ": {}", source)
+            }
             Error::Encoding => f.write_str("error while encoding"),
             Error::Decoding => f.write_str("error while decoding"),
             Error::InvalidDatabaseTyping => {
@@ -101,19 +214,57 @@ impl fmt::Display for Error {
                 f.write_str("database is in a closing phase, you can't open it at the same time")
             }
             Error::BadOpenOptions => {
-                f.write_str("an environment is already opened with different options")
+                f.write_str("an environment is already opened with incompatible options")
+            }
+            Error::ReadOnly => {
+                f.write_str("the environment is opened read-only, it can't be written to")
+            }
+            Error::ReadersFull => {
+                f.write_str("too many read transactions are alive, the reader table is full")
             }
+            Error::TxnFull => f.write_str(
+                "the write transaction has too many dirty pages, it must be split in smaller ones",
+            ),
+            Error::KeyTooLong { database: Some(name), len, max } => write!(
+                f,
+                "key of {} bytes is too long for the {:?} database, the maximum is {} bytes",
+                len, name, max
+            ),
+            Error::KeyTooLong { database: None, len, max } => write!(
+                f,
+                "key of {} bytes is too long for the unnamed database, the maximum is {} bytes",
+                len, max
+            ),
+            Error::UnsortedAppend { database: Some(name), last, key } => write!(
+                f,
+                "key {:?} appended to the {:?} database doesn't sort after its last key {:?}",
+                key, name, last
+            ),
+            Error::UnsortedAppend { database: None, last, key } => write!(
+                f,
+                "key {:?} appended to the unnamed database doesn't sort after its last key {:?}",
+                key, last
+            ),
         }
     }
 }
 
-impl error::Error for Error {}
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Mdb { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl From<MdbError> for Error {
     fn from(error: MdbError) -> Error {
         match error {
             MdbError::Other(e) => Error::Io(io::Error::from_raw_os_error(e)),
-            _ => Error::Mdb(error),
+            MdbError::ReadersFull => Error::ReadersFull,
+            MdbError::TxnFull => Error::TxnFull,
+            _ => Error::Mdb { op: None, db: None, key_len: None, txn: None, source: error },
         }
     }
 }
@@ -38,6 +38,8 @@ pub use ffi::{
     mdb_txn_abort,
     mdb_txn_begin,
     mdb_txn_commit,
+    mdb_txn_renew,
+    mdb_txn_reset,
 
     mdb_cursor_close,
     mdb_cursor_del,
@@ -57,6 +59,36 @@ pub mod cursor_op {
     pub const MDB_GET_CURRENT: MDB_cursor_op = ffi::MDB_GET_CURRENT;
 }
 
+/// The size of the page numbers stored in the freelist database.
+pub const PGNO_SIZE: usize = std::mem::size_of::<usize>();
+
+/// The environment flags with which the commits don't wait for the disk.
+pub const NON_DURABLE_FLAGS: u32 = ffi::MDB_NOSYNC | ffi::MDB_NOMETASYNC | ffi::MDB_MAPASYNC;
+
+/// Commits a write transaction with `MDB_NOSYNC` set for its commit only, the caller keeps
+/// the other commits of the process from reading the flags meanwhile.
+pub unsafe fn txn_commit_without_sync(env: *mut MDB_env, txn: *mut MDB_txn) -> libc::c_int {
+    let mut flags = 0;
+    let rc = ffi::mdb_env_get_flags(env, &mut flags);
+    if rc != 0 {
+        ffi::mdb_txn_abort(txn);
+        return rc;
+    }
+    if flags & ffi::MDB_NOSYNC != 0 {
+        return ffi::mdb_txn_commit(txn);
+    }
+
+    let rc = ffi::mdb_env_set_flags(env, ffi::MDB_NOSYNC, 1);
+    if rc != 0 {
+        ffi::mdb_txn_abort(txn);
+        return rc;
+    }
+    let rc = ffi::mdb_txn_commit(txn);
+    // MDB_NOSYNC can always be changed, clearing it doesn't fail.
+    ffi::mdb_env_set_flags(env, ffi::MDB_NOSYNC, 0);
+    rc
+}
+
 pub fn map_size(env: *mut MDB_env) -> Result<usize, crate::Error> {
     let mut env_info = std::mem::MaybeUninit::uninit();
     unsafe { super::error::mdb_result(mdb_env_info(env, env_info.as_mut_ptr()))? };
@@ -65,6 +97,74 @@ pub fn map_size(env: *mut MDB_env) -> Result<usize, crate::Error> {
     Ok(env_info.me_mapsize as _)
 }
 
+/// The bytes of the map used by the last committed transaction, up to its last page.
+pub fn used_size(env: *mut MDB_env) -> Result<usize, crate::Error> {
+    let mut env_info = std::mem::MaybeUninit::uninit();
+    let mut stat = std::mem::MaybeUninit::uninit();
+    unsafe {
+        super::error::mdb_result(mdb_env_info(env, env_info.as_mut_ptr()))?;
+        super::error::mdb_result(mdb_env_stat(env, stat.as_mut_ptr()))?;
+    }
+    let (env_info, stat) = unsafe { (env_info.assume_init(), stat.assume_init()) };
+
+    Ok((env_info.me_last_pgno + 1) * stat.ms_psize as usize)
+}
+
+pub fn max_key_size(env: *mut MDB_env) -> usize {
+    unsafe { ffi::mdb_env_get_maxkeysize(env) as usize }
+}
+
+/// The number of overflow pages LMDB allocates for an entry, zero if it fits in its leaf page.
+pub fn overflow_pages(page_size: usize, key_len: usize, data_len: usize) -> Option<u64> {
+    // The sizes of a page header, of a node header and of a node index, from mdb.c.
+    const PAGEHDRSZ: usize = 16;
+    const NODESIZE: usize = 8;
+    const INDXSIZE: usize = 2;
+
+    let node_max = (((page_size - PAGEHDRSZ) / 2) & !1) - INDXSIZE;
+    if NODESIZE + key_len + data_len <= node_max {
+        Some(0)
+    } else {
+        Some(((PAGEHDRSZ - 1 + data_len) / page_size + 1) as u64)
+    }
+}
+
+pub fn txn_id(txn: *mut MDB_txn) -> u64 {
+    unsafe { ffi::mdb_txn_id(txn) as u64 }
+}
+
+/// The id of the last transaction committed to the environment, by any process.
+pub fn last_txn_id(env: *mut MDB_env) -> Result<u64, crate::Error> {
+    let mut env_info = std::mem::MaybeUninit::uninit();
+    unsafe { super::error::mdb_result(mdb_env_info(env, env_info.as_mut_ptr()))? };
+    let env_info = unsafe { env_info.assume_init() };
+
+    Ok(env_info.me_last_txnid as u64)
+}
+
+pub fn oldest_reader_id(env: *mut MDB_env) -> Result<Option<u64>, crate::Error> {
+    // LMDB only reports the reader table as formatted lines of the form
+    // "<pid> <thread> <txnid>", the txnid is a dash for unused slots.
+    unsafe extern "C" fn collect(msg: *const libc::c_char, ctx: *mut libc::c_void) -> libc::c_int {
+        let oldest = &mut *(ctx as *mut Option<u64>);
+        let line = std::ffi::CStr::from_ptr(msg).to_string_lossy();
+        if let Some(txnid) = line.split_whitespace().nth(2).and_then(|t| t.parse().ok()) {
+            *oldest = Some(oldest.map_or(txnid, |o: u64| o.min(txnid)));
+        }
+        0
+    }
+
+    let mut env_info = std::mem::MaybeUninit::uninit();
+    unsafe { super::error::mdb_result(mdb_env_info(env, env_info.as_mut_ptr()))? };
+    let env_info = unsafe { env_info.assume_init() };
+
+    let mut oldest = None;
+    let ctx = &mut oldest as *mut Option<u64> as *mut libc::c_void;
+    unsafe { super::error::mdb_result(ffi::mdb_reader_list(env, Some(collect), ctx))? };
+
+    Ok(oldest.filter(|&txnid| txnid < env_info.me_last_txnid as u64))
+}
+
 pub unsafe fn into_val(value: &[u8]) -> ffi::MDB_val {
     ffi::MDB_val { mv_data: value.as_ptr() as *mut libc::c_void, mv_size: value.len() }
 }
@@ -36,6 +36,8 @@ pub use ffi::{
     mdbx_txn_abort as mdb_txn_abort,
     mdbx_txn_begin as mdb_txn_begin,
     mdbx_txn_commit as mdb_txn_commit,
+    mdbx_txn_renew as mdb_txn_renew,
+    mdbx_txn_reset as mdb_txn_reset,
     mdbx_cursor_close as mdb_cursor_close,
     mdbx_cursor_del as mdb_cursor_del,
     mdbx_cursor_get as mdb_cursor_get,
@@ -63,6 +65,38 @@ pub unsafe fn mdb_stat(txn: *mut MDB_txn, dbi: MDB_dbi, stat: *mut MDB_stat) ->
     mdbx_dbi_stat(txn, dbi, stat, size_of::<MDB_stat>())
 }
 
+/// The size of the page numbers stored in the freelist database.
+pub const PGNO_SIZE: usize = size_of::<ffi::pgno_t>();
+
+/// The environment flags with which the commits don't wait for the disk.
+pub const NON_DURABLE_FLAGS: u32 =
+    ffi::MDBX_SAFE_NOSYNC | ffi::MDBX_NOMETASYNC | ffi::MDBX_MAPASYNC;
+
+/// Commits a write transaction with `MDBX_SAFE_NOSYNC` set for its commit only, the caller
+/// keeps the other commits of the process from reading the flags meanwhile. MDBX reads the
+/// flags of the environment when the transaction commits.
+pub unsafe fn txn_commit_without_sync(env: *mut MDB_env, txn: *mut MDB_txn) -> ::libc::c_int {
+    let mut flags = 0;
+    let rc = ffi::mdbx_env_get_flags(env, &mut flags);
+    if rc != 0 {
+        ffi::mdbx_txn_abort(txn);
+        return rc;
+    }
+    if flags & ffi::MDBX_SAFE_NOSYNC != 0 {
+        return ffi::mdbx_txn_commit(txn);
+    }
+
+    let rc = ffi::mdbx_env_set_flags(env, ffi::MDBX_SAFE_NOSYNC, true);
+    if rc != 0 {
+        ffi::mdbx_txn_abort(txn);
+        return rc;
+    }
+    let rc = ffi::mdbx_txn_commit(txn);
+    // MDBX_SAFE_NOSYNC can always be changed, clearing it doesn't fail.
+    ffi::mdbx_env_set_flags(env, ffi::MDBX_SAFE_NOSYNC, false);
+    rc
+}
+
 pub fn map_size(env: *mut MDB_env) -> Result<usize, crate::Error> {
     let mut env_info = std::mem::MaybeUninit::uninit();
     unsafe {
@@ -77,6 +111,101 @@ pub fn map_size(env: *mut MDB_env) -> Result<usize, crate::Error> {
     Ok(env_info.mi_mapsize as _)
 }
 
+pub fn max_key_size(env: *mut MDB_env) -> usize {
+    unsafe { ffi::mdbx_env_get_maxkeysize(env) as usize }
+}
+
+/// MDBX sizes its large pages differently, they are not reported.
+pub fn overflow_pages(_page_size: usize, _key_len: usize, _data_len: usize) -> Option<u64> {
+    None
+}
+
+pub fn txn_id(txn: *mut MDB_txn) -> u64 {
+    unsafe { ffi::mdbx_txn_id(txn) }
+}
+
+/// The id of the last transaction committed to the environment, by any process.
+pub fn last_txn_id(env: *mut MDB_env) -> Result<u64, crate::Error> {
+    let mut env_info = std::mem::MaybeUninit::uninit();
+    unsafe {
+        super::error::mdb_result(mdbx_env_info(
+            env,
+            env_info.as_mut_ptr(),
+            size_of::<MDBX_envinfo>(),
+        ))?
+    };
+    let env_info = unsafe { env_info.assume_init() };
+
+    Ok(env_info.mi_recent_txnid)
+}
+
+pub fn txn_info(txn: *mut MDB_txn) -> Result<ffi::MDBX_txn_info, crate::Error> {
+    let mut info = std::mem::MaybeUninit::uninit();
+    // Scanning the reader table is only needed for the reader lag of read transactions.
+    unsafe { super::error::mdb_result(ffi::mdbx_txn_info(txn, info.as_mut_ptr(), false))? };
+    Ok(unsafe { info.assume_init() })
+}
+
+/// An estimation of the number of entries between two keys, `None` for the first or
+/// the last key of the database.
+pub fn estimate_range(
+    txn: *mut MDB_txn,
+    dbi: MDB_dbi,
+    begin: Option<&[u8]>,
+    end: Option<&[u8]>,
+) -> Result<usize, crate::Error> {
+    let mut begin = begin.map(|key| unsafe { into_val(key) });
+    let mut end = end.map(|key| unsafe { into_val(key) });
+    let as_ptr = |val: &mut Option<ffi::MDBX_val>| {
+        val.as_mut().map_or(std::ptr::null_mut(), |val| val as *mut _)
+    };
+
+    let mut distance = 0;
+    unsafe {
+        super::error::mdb_result(ffi::mdbx_estimate_range(
+            txn,
+            dbi,
+            as_ptr(&mut begin),
+            std::ptr::null_mut(),
+            as_ptr(&mut end),
+            std::ptr::null_mut(),
+            &mut distance,
+        ))?
+    };
+
+    Ok(distance.max(0) as usize)
+}
+
+/// The `x`, `y`, `z` and `v` numbers of the canary.
+pub fn canary_get(txn: *mut MDB_txn) -> Result<[u64; 4], crate::Error> {
+    let mut canary = ffi::MDBX_canary { x: 0, y: 0, z: 0, v: 0 };
+    unsafe { super::error::mdb_result(ffi::mdbx_canary_get(txn, &mut canary))? };
+    Ok([canary.x, canary.y, canary.z, canary.v])
+}
+
+/// Set the `x`, `y` and `z` numbers of the canary, MDBX sets `v` to the id of the transaction.
+pub fn canary_put(txn: *mut MDB_txn, [x, y, z]: [u64; 3]) -> Result<(), crate::Error> {
+    let canary = ffi::MDBX_canary { x, y, z, v: 0 };
+    unsafe { super::error::mdb_result(ffi::mdbx_canary_put(txn, &canary))? };
+    Ok(())
+}
+
+pub fn oldest_reader_id(env: *mut MDB_env) -> Result<Option<u64>, crate::Error> {
+    let mut env_info = std::mem::MaybeUninit::uninit();
+    unsafe {
+        super::error::mdb_result(mdbx_env_info(
+            env,
+            env_info.as_mut_ptr(),
+            size_of::<MDBX_envinfo>(),
+        ))?
+    };
+    let env_info = unsafe { env_info.assume_init() };
+
+    // The latter reader is the last committed transaction when no reader lags behind.
+    let oldest = env_info.mi_latter_reader_txnid;
+    Ok(if oldest < env_info.mi_recent_txnid { Some(oldest) } else { None })
+}
+
 pub unsafe fn into_val(value: &[u8]) -> ffi::MDBX_val {
     ffi::MDBX_val { iov_base: value.as_ptr() as *mut libc::c_void, iov_len: value.len() }
 }
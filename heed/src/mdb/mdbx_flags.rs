@@ -9,4 +9,5 @@ pub enum Flags {
     MdbNoTls = mdbx_sys::MDBX_NOTLS,
     MdbNoRdAhead = mdbx_sys::MDBX_NORDAHEAD,
     MdbNoMemInit = mdbx_sys::MDBX_NOMEMINIT,
+    MdbExclusive = mdbx_sys::MDBX_EXCLUSIVE,
 }
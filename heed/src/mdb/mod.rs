@@ -1,3 +1,5 @@
+pub mod op;
+
 // MDBX
 
 #[cfg(feature = "mdbx")]
@@ -0,0 +1,78 @@
+use crate::mdb::error::Error as MdbError;
+use crate::mdb::ffi;
+use crate::{Env, Error, TxnKind};
+
+/// The context of an LMDB operation, given to its errors, see [`Error::Mdb`].
+///
+/// The name of the database is only looked up once the operation failed.
+#[derive(Clone, Copy)]
+pub(crate) struct MdbOp<'a> {
+    name: &'static str,
+    db: OpDb<'a>,
+    key_len: Option<usize>,
+    txn: Option<TxnKind>,
+}
+
+#[derive(Clone, Copy)]
+enum OpDb<'a> {
+    Unknown,
+    /// A database opened in the environment identified by its pointer.
+    Opened(usize, ffi::MDB_dbi),
+    Named(Option<&'a str>),
+}
+
+impl<'a> MdbOp<'a> {
+    /// The context of a call to the LMDB function `name`, like `mdb_put`.
+    pub(crate) fn new(name: &'static str) -> MdbOp<'a> {
+        MdbOp { name, db: OpDb::Unknown, key_len: None, txn: None }
+    }
+
+    /// The same context for another LMDB function.
+    pub(crate) fn op(self, name: &'static str) -> MdbOp<'a> {
+        MdbOp { name, ..self }
+    }
+
+    pub(crate) fn db(self, env_ident: usize, dbi: ffi::MDB_dbi) -> MdbOp<'a> {
+        MdbOp { db: OpDb::Opened(env_ident, dbi), ..self }
+    }
+
+    /// The database being opened, `None` for the unnamed one.
+    pub(crate) fn named(self, name: Option<&'a str>) -> MdbOp<'a> {
+        MdbOp { db: OpDb::Named(name), ..self }
+    }
+
+    pub(crate) fn key(self, key: &[u8]) -> MdbOp<'a> {
+        MdbOp { key_len: Some(key.len()), ..self }
+    }
+
+    pub(crate) fn txn(self, kind: TxnKind) -> MdbOp<'a> {
+        MdbOp { txn: Some(kind), ..self }
+    }
+
+    pub(crate) fn txn_kind(&self) -> Option<TxnKind> {
+        self.txn
+    }
+
+    /// The error of the operation, with its context.
+    pub(crate) fn error(self, error: MdbError) -> Error {
+        match Error::from(error) {
+            Error::Mdb { source, .. } => Error::Mdb {
+                op: Some(self.name),
+                db: match self.db {
+                    OpDb::Unknown => None,
+                    OpDb::Opened(env_ident, dbi) => Env::opened_database_name(env_ident, dbi),
+                    OpDb::Named(name) => name.map(String::from),
+                },
+                key_len: self.key_len,
+                txn: self.txn,
+                source,
+            },
+            error => error,
+        }
+    }
+
+    /// The result of the operation, its error is given the context.
+    pub(crate) fn result(self, result: Result<(), MdbError>) -> crate::Result<()> {
+        result.map_err(|e| self.error(e))
+    }
+}
@@ -0,0 +1,209 @@
+//! A wrapper of a [`Typed`] table checking the keys it writes, to catch the codecs that don't
+//! read back the keys they write during development.
+//!
+//! The key of every put or append of a [`CheckedTable`] must not be longer than the maximum
+//! size of the keys of the store, like [`Env::max_key_size`](crate::Env::max_key_size), and
+//! the key codec must decode it to an item encoded to the same bytes. The operation returns a
+//! [`CheckError`] otherwise, and isn't given to the table, the reads aren't checked.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::checked::{CheckError, CheckedTable};
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let users = env.typed::<Str, OwnedType<u64>>("users", &())?;
+//! let users = CheckedTable::new(users, env.max_key_size());
+//!
+//! let mut wtxn = env.wtx()?;
+//! users.put(&mut wtxn, "alice", &1)?;
+//! let long = "a".repeat(env.max_key_size() + 1);
+//! assert!(matches!(users.put(&mut wtxn, &long, &2), Err(CheckError::KeyTooLong { .. })));
+//! assert_eq!(users.get(&wtxn, "alice")?, Some(1));
+//! # Ok(()) }
+//! ```
+
+use std::borrow::Borrow;
+use std::ops::Deref;
+use std::{error, fmt};
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::store::{ErrorOf, Typed, WritableStore, WtxOf};
+
+/// An error of a [`CheckedTable`].
+#[derive(Debug)]
+pub enum CheckError<E> {
+    /// An error of the wrapped table.
+    Store(E),
+    /// The key codec couldn't encode the key.
+    Encoding,
+    /// The encoded key is longer than the maximum size of the keys.
+    KeyTooLong { len: usize, max: usize },
+    /// The key codec couldn't decode the encoded key.
+    KeyNotDecoded { key: Vec<u8> },
+    /// The key codec decoded the encoded key to an item encoded to other bytes.
+    KeyNotRoundTripped { key: Vec<u8>, reencoded: Vec<u8> },
+}
+
+impl<E: fmt::Display> fmt::Display for CheckError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckError::Store(error) => write!(f, "{}", error),
+            CheckError::Encoding => f.write_str("the key couldn't be encoded"),
+            CheckError::KeyTooLong { len, max } => {
+                write!(f, "the key is {} bytes long, the keys are at most {} bytes long", len, max)
+            }
+            CheckError::KeyNotDecoded { key } => {
+                write!(f, "the key {:?} can't be decoded by its codec", key)
+            }
+            CheckError::KeyNotRoundTripped { key, reencoded } => {
+                write!(f, "the key {:?} is decoded and encoded again to {:?}", key, reencoded)
+            }
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for CheckError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CheckError::Store(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A table checking the keys it writes, see the [module documentation](self).
+///
+/// It derefs to the wrapped table for the operations that aren't checked.
+#[derive(Debug, Clone)]
+pub struct CheckedTable<T> {
+    table: T,
+    max_key_size: usize,
+}
+
+impl<T> CheckedTable<T> {
+    pub fn new(table: T, max_key_size: usize) -> CheckedTable<T> {
+        CheckedTable { table, max_key_size }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+}
+
+impl<T> Deref for CheckedTable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.table
+    }
+}
+
+impl<'s, S: WritableStore, KC, DC> CheckedTable<Typed<'s, S, KC, DC>>
+where
+    KC: BytesDecode + for<'b> BytesEncode<'b>,
+    KC::DItem: for<'b> Borrow<<KC as BytesEncode<'b>>::EItem>,
+{
+    pub fn put<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a <KC as BytesEncode<'a>>::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), CheckError<ErrorOf<S>>>
+    where
+        DC: BytesEncode<'a>,
+    {
+        self.check_key(key)?;
+        self.table.put(txn, key, data).map_err(CheckError::Store)
+    }
+
+    pub fn append<'a>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a <KC as BytesEncode<'a>>::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), CheckError<ErrorOf<S>>>
+    where
+        DC: BytesEncode<'a>,
+    {
+        self.check_key(key)?;
+        self.table.append(txn, key, data).map_err(CheckError::Store)
+    }
+
+    fn check_key<'a>(
+        &self,
+        key: &'a <KC as BytesEncode<'a>>::EItem,
+    ) -> Result<(), CheckError<ErrorOf<S>>> {
+        let key = <KC as BytesEncode<'a>>::bytes_encode(key).ok_or(CheckError::Encoding)?;
+        if key.len() > self.max_key_size {
+            return Err(CheckError::KeyTooLong { len: key.len(), max: self.max_key_size });
+        }
+
+        let decoded = match KC::bytes_decode(&key) {
+            Some(decoded) => decoded,
+            None => return Err(CheckError::KeyNotDecoded { key: key.into_owned() }),
+        };
+        match KC::bytes_encode(decoded.borrow()) {
+            Some(reencoded) if reencoded == key => Ok(()),
+            reencoded => Err(CheckError::KeyNotRoundTripped {
+                key: key.into_owned(),
+                reencoded: reencoded.map_or_else(Vec::new, |bytes| bytes.into_owned()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use heed_types::Unit;
+
+    use super::*;
+    use crate::store::Store;
+    use crate::EnvOpenOptions;
+
+    /// Decodes only the first 4 bytes of the names.
+    struct Truncating;
+
+    impl<'a> BytesEncode<'a> for Truncating {
+        type EItem = str;
+
+        fn bytes_encode(item: &'a str) -> Option<Cow<'a, [u8]>> {
+            Some(Cow::Borrowed(item.as_bytes()))
+        }
+    }
+
+    impl BytesDecode for Truncating {
+        type DItem = String;
+
+        fn bytes_decode(bytes: &[u8]) -> Option<String> {
+            String::from_utf8(bytes.iter().take(4).copied().collect()).ok()
+        }
+    }
+
+    #[test]
+    fn asymmetric_keys_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let names = env.typed::<Truncating, Unit>("names", &()).unwrap();
+        let names = CheckedTable::new(names, 8);
+
+        let mut wtxn = env.wtx().unwrap();
+        names.put(&mut wtxn, "bob", &()).unwrap();
+        let error = names.put(&mut wtxn, "alice", &()).unwrap_err();
+        assert!(
+            matches!(error, CheckError::KeyNotRoundTripped { reencoded, .. } if reencoded == b"alic")
+        );
+        let error = names.append(&mut wtxn, "alice-and-bob", &()).unwrap_err();
+        assert!(matches!(error, CheckError::KeyTooLong { len: 13, max: 8 }));
+        assert_eq!(names.len(&wtxn).unwrap(), 1);
+    }
+}
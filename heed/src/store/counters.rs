@@ -0,0 +1,133 @@
+//! Named counters on top of any [`Store`], for metrics and quotas updated in write transactions.
+//!
+//! Every counter is a big endian `i64` stored under its name, incremented with
+//! [`Table::increment`]: a read, modify and write of the value on LMDB, a merge of the
+//! delta on RocksDB, whose tables of counters are given a merge operator by
+//! [`Store::counters_config`]. A counter that was never incremented is 0.
+//!
+//! RocksDB doesn't store the merge operator of a table, it is given again every time the
+//! database is opened. A RocksDB store with counters is reopened with
+//! [`OpenWithRecovery::open_with_recovery`](crate::store::rck::OpenWithRecovery), which
+//! gives it to every table, or with the [`Store::counters_config`] options given to the
+//! column families of the counters. Incrementing the counters of a store opened otherwise
+//! fails.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::counters::Counters;
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let quotas = Counters::open(&env, "quotas")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! assert_eq!(quotas.incr(&mut wtxn, "alice", 10)?, 10);
+//! assert_eq!(quotas.incr(&mut wtxn, "alice", -3)?, 7);
+//! assert_eq!(quotas.incr(&mut wtxn, "bob", 1)?, 1);
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! assert_eq!(quotas.get(&rtxn, "carol")?, 0);
+//! let snapshot = quotas.snapshot(&rtxn)?;
+//! assert_eq!(snapshot.into_iter().collect::<Vec<_>>(), [("alice".into(), 7), ("bob".into(), 1)]);
+//! # Ok(()) }
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::{Infallible, TryInto};
+
+use either::Either;
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+/// Named counters stored in a table of a [`Store`].
+///
+/// See the [module documentation](self).
+pub struct Counters<'s, S: Store + 's> {
+    table: TableOf<'s, S>,
+}
+
+impl<'s, S: Store> Counters<'s, S> {
+    /// Open the counters stored in the `name` table of the store,
+    /// configured by [`Store::counters_config`].
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let table = store.table(name, &store.counters_config())?;
+        Ok(Counters { table })
+    }
+
+    /// Adds `delta` to the counter and returns its new value.
+    pub fn incr(&self, txn: &mut WtxOf<S>, name: &str, delta: i64) -> Result<i64, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        self.table.increment(txn, name.as_bytes(), delta)
+    }
+
+    /// The value of the counter.
+    pub fn get(&self, txn: &RtxOf<S>, name: &str) -> Result<i64, ErrorOf<S>> {
+        let value = self.table.get::<ByteSlice, ByteSlice>(txn, name.as_bytes())?;
+        Ok(value.map_or(0, |value| decode(&value)))
+    }
+
+    /// The values of all the counters, by name.
+    pub fn snapshot(&self, txn: &RtxOf<S>) -> Result<BTreeMap<String, i64>, ErrorOf<S>> {
+        let mut counters = BTreeMap::new();
+        let visited = self.table.raw_iter::<Infallible, _>(txn, |name, value| {
+            counters.insert(String::from_utf8_lossy(name).into_owned(), decode(value));
+            Ok(())
+        });
+
+        match visited {
+            Ok(()) => Ok(counters),
+            Err(Either::Left(e)) => Err(e),
+            Err(Either::Right(never)) => match never {},
+        }
+    }
+}
+
+/// The value of a counter, a value that isn't 8 bytes long counts as 0.
+pub(crate) fn decode(bytes: &[u8]) -> i64 {
+    bytes.try_into().map_or(0, i64::from_be_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn counters_outlive_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+            let counters = Counters::open(&env, "counters").unwrap();
+            let mut wtx = env.wtx().unwrap();
+            assert_eq!(counters.incr(&mut wtx, "hits", 2).unwrap(), 2);
+            wtx.commit().unwrap();
+            env.prepare_for_closing().wait();
+        }
+
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let counters = Counters::open(&env, "counters").unwrap();
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(counters.incr(&mut wtx, "hits", -5).unwrap(), -3);
+        assert_eq!(counters.get(&wtx, "misses").unwrap(), 0);
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        let snapshot = counters.snapshot(&rtx).unwrap();
+        assert_eq!(snapshot.into_iter().collect::<Vec<_>>(), [("hits".to_string(), -3)]);
+    }
+
+    #[test]
+    fn malformed_counters_count_as_zero() {
+        assert_eq!(decode(&7i64.to_be_bytes()), 7);
+        assert_eq!(decode(b"short"), 0);
+    }
+}
@@ -0,0 +1,296 @@
+//! A harness checking that the data of a store stays consistent when the process writing
+//! it crashes, meant to be used by the tests of the applications built on heed.
+//!
+//! A [`CrashSim`] runs a workload in a child process, kills it after a random delay, then
+//! verifies the invariants of the data left on disk, like a journal being consistent or an
+//! index agreeing with its table. It does so many times on the same directory, every run
+//! resumes from what the previous ones left.
+//!
+//! The child process is the test binary itself, running the test that started the simulation:
+//! [`CrashSim::run`] runs the workload and exits when it is called in the child process. The
+//! process is killed with `SIGKILL` on Unix, the writes that reached the page cache survive,
+//! it simulates crashes of the process, not of the machine.
+//!
+//! ```no_run
+//! # use std::error::Error;
+//! # use std::path::Path;
+//! use heed::store::crashsim::CrashSim;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//! use heed::EnvOpenOptions;
+//!
+//! fn open(dir: &Path) -> heed::Result<heed::Env> {
+//!     EnvOpenOptions::new().map_size(10 * 1024 * 1024).max_dbs(2).open(dir)
+//! }
+//!
+//! // In a `#[test] fn counters_agree()` of the `tests` module of the crate.
+//! # fn main() -> Result<(), Box<dyn Error>> {
+//! let dir = tempfile::tempdir()?;
+//! let report = CrashSim::new("tests::counters_agree").runs(20).run(
+//!     dir.path(),
+//!     |dir| {
+//!         // Two counters always incremented together, forever, until the process is killed.
+//!         let env = open(dir).unwrap();
+//!         let (a, b) = (env.typed::<Str, OwnedType<u64>>("a", &()).unwrap(), env.typed::<Str, OwnedType<u64>>("b", &()).unwrap());
+//!         loop {
+//!             let mut wtxn = env.wtx().unwrap();
+//!             let n = a.get(&wtxn, "n").unwrap().unwrap_or(0) + 1;
+//!             a.put(&mut wtxn, "n", &n).unwrap();
+//!             b.put(&mut wtxn, "n", &n).unwrap();
+//!             wtxn.commit().unwrap();
+//!         }
+//!     },
+//!     |dir| -> Result<(), Box<dyn Error>> {
+//!         let env = open(dir)?;
+//!         let (a, b) = (env.typed::<Str, OwnedType<u64>>("a", &())?, env.typed::<Str, OwnedType<u64>>("b", &())?);
+//!         let rtxn = env.rtx()?;
+//!         let counters = (
+//!             a.get(&rtxn, "n")?,
+//!             b.get(&rtxn, "n")?,
+//!         );
+//!         drop(rtxn);
+//!         env.prepare_for_closing().wait();
+//!         match counters {
+//!             (a, b) if a == b => Ok(()),
+//!             (a, b) => Err(format!("the counters disagree: {:?} and {:?}", a, b).into()),
+//!         }
+//!     },
+//! )?;
+//! assert_eq!(report.killed + report.completed, 20);
+//! # Ok(()) }
+//! ```
+
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, error, fmt, io, process, thread};
+
+/// The variable giving the directory of the workload to the child process.
+const CHILD_DIR_VAR: &str = "HEED_CRASHSIM_DIR";
+
+/// The exit code of a child process whose workload returned, a test harness that didn't
+/// find the test to run exits with 0.
+const COMPLETED_CODE: i32 = 0x5a;
+
+/// How many times the workload of a [`CrashSim`] was killed or returned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CrashReport {
+    pub killed: usize,
+    pub completed: usize,
+}
+
+/// An error of a [`CrashSim`], either from running the child process or from the invariants.
+#[derive(Debug)]
+pub enum CrashSimError<E> {
+    Io(io::Error),
+    /// The child process exited without running the test, its name is wrong.
+    NotRun(String),
+    /// The workload failed, the child process exited with this status.
+    Workload(ExitStatus),
+    /// The invariants don't hold after the run with this index.
+    Invariant {
+        run: usize,
+        error: E,
+    },
+}
+
+impl<E: fmt::Display> fmt::Display for CrashSimError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrashSimError::Io(error) => write!(f, "{}", error),
+            CrashSimError::NotRun(test) => {
+                write!(f, "the child process didn't run the {:?} test", test)
+            }
+            CrashSimError::Workload(status) => {
+                write!(f, "the workload failed, the child process {}", status)
+            }
+            CrashSimError::Invariant { run, error } => {
+                write!(f, "the invariants don't hold after run {}: {}", run, error)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for CrashSimError<E> {}
+
+impl<E> From<io::Error> for CrashSimError<E> {
+    fn from(error: io::Error) -> CrashSimError<E> {
+        CrashSimError::Io(error)
+    }
+}
+
+/// Runs a workload in child processes that are killed at random points,
+/// see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct CrashSim {
+    test: String,
+    runs: usize,
+    max_delay: Duration,
+    seed: u64,
+}
+
+impl CrashSim {
+    /// A simulation run by the `test` test, its name as given to the test harness
+    /// to run it alone, like `module::tests::name`.
+    pub fn new(test: &str) -> CrashSim {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        CrashSim {
+            test: test.to_string(),
+            runs: 10,
+            max_delay: Duration::from_millis(200),
+            seed: now.as_nanos() as u64,
+        }
+    }
+
+    /// The number of times the workload is run, 10 by default.
+    pub fn runs(&mut self, runs: usize) -> &mut Self {
+        self.runs = runs;
+        self
+    }
+
+    /// The workload is killed after a random delay up to this one, 200ms by default.
+    pub fn max_delay(&mut self, delay: Duration) -> &mut Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// The seed of the random delays, to reproduce a failing simulation.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Run `workload` on `dir` in child processes and `verify` the invariants of `dir`
+    /// after each of them is killed or returns. `verify` must close the environments
+    /// it opens, the next run opens them in another process.
+    ///
+    /// In the child process, runs `workload` and exits.
+    pub fn run<W, V, E>(
+        &self,
+        dir: &Path,
+        workload: W,
+        mut verify: V,
+    ) -> Result<CrashReport, CrashSimError<E>>
+    where
+        W: FnOnce(&Path),
+        V: FnMut(&Path) -> Result<(), E>,
+    {
+        if let Some(dir) = env::var_os(CHILD_DIR_VAR) {
+            workload(Path::new(&dir));
+            process::exit(COMPLETED_CODE);
+        }
+
+        let exe = env::current_exe()?;
+        let mut rng = self.seed | 1;
+        let mut report = CrashReport::default();
+        for run in 0..self.runs {
+            let max_nanos = self.max_delay.as_nanos() as u64;
+            let delay = Duration::from_nanos(xorshift(&mut rng) % max_nanos.max(1));
+
+            let mut child = Command::new(&exe)
+                .args([OsStr::new(&self.test), OsStr::new("--exact"), OsStr::new("--nocapture")])
+                .env(CHILD_DIR_VAR, dir)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+
+            let deadline = Instant::now() + delay;
+            let status = loop {
+                if let Some(status) = child.try_wait()? {
+                    break Some(status);
+                }
+                if Instant::now() >= deadline {
+                    break None;
+                }
+                thread::sleep(Duration::from_millis(1));
+            };
+
+            match status {
+                None => {
+                    child.kill()?;
+                    child.wait()?;
+                    report.killed += 1;
+                }
+                Some(status) if status.code() == Some(COMPLETED_CODE) => report.completed += 1,
+                Some(status) if status.success() => {
+                    return Err(CrashSimError::NotRun(self.test.clone()))
+                }
+                Some(status) => return Err(CrashSimError::Workload(status)),
+            }
+
+            verify(dir).map_err(|error| CrashSimError::Invariant { run, error })?;
+        }
+
+        Ok(report)
+    }
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{OwnedType, Str};
+
+    use super::*;
+    use crate::store::{Store, WritableStore};
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn tables_written_together_agree_after_crashes() {
+        let open = |dir: &Path| EnvOpenOptions::new().max_dbs(2).open(dir);
+        let dir = tempfile::tempdir().unwrap();
+
+        let report =
+            CrashSim::new("store::crashsim::tests::tables_written_together_agree_after_crashes")
+                .runs(5)
+                .max_delay(Duration::from_millis(100))
+                .run(
+                    dir.path(),
+                    |dir| {
+                        let env = open(dir).unwrap();
+                        let (a, b) = (
+                            env.typed::<Str, OwnedType<u64>>("a", &()).unwrap(),
+                            env.typed::<Str, OwnedType<u64>>("b", &()).unwrap(),
+                        );
+                        for _ in 0..1000 {
+                            let mut wtxn = env.wtx().unwrap();
+                            let n = a.get(&wtxn, "n").unwrap().unwrap_or(0);
+                            a.put(&mut wtxn, "n", &(n + 1)).unwrap();
+                            b.put(&mut wtxn, "n", &(n + 1)).unwrap();
+                            wtxn.commit().unwrap();
+                        }
+                    },
+                    |dir| {
+                        let env = open(dir)?;
+                        let (a, b) = (
+                            env.typed::<Str, OwnedType<u64>>("a", &())?,
+                            env.typed::<Str, OwnedType<u64>>("b", &())?,
+                        );
+                        let rtxn = env.rtx()?;
+                        let counters = (a.get(&rtxn, "n")?, b.get(&rtxn, "n")?);
+                        drop(rtxn);
+                        env.prepare_for_closing().wait();
+                        assert_eq!(counters.0, counters.1);
+                        Ok::<_, crate::Error>(())
+                    },
+                )
+                .unwrap();
+
+        assert_eq!(report.killed + report.completed, 5);
+    }
+
+    #[test]
+    fn misnamed_test_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let result =
+            CrashSim::new("no::such::test").runs(1).run(dir.path(), |_| (), |_| Ok::<_, ()>(()));
+        assert!(matches!(result, Err(CrashSimError::NotRun(_))));
+    }
+}
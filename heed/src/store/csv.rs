@@ -0,0 +1,293 @@
+//! CSV export and import of the tables whose keys and values decode to scalars or tuples of
+//! scalars, see [`Typed::dump_csv`] and [`Typed::load_csv`].
+//!
+//! The columns of the keys come first, named `key` or `key.0`, `key.1`..., followed by the
+//! columns of the values, named `value` or `value.0`, `value.1`... A unit value has no column.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let prices = env.typed::<Str, OwnedType<f64>>("prices", &Default::default())?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! let loaded = prices.load_csv(&mut wtxn, &b"key,value\napple,1.5\n\"pear, ripe\",2\n"[..])?;
+//! assert_eq!(loaded, 2);
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let mut csv = Vec::new();
+//! prices.dump_csv(&rtxn, &mut csv)?;
+//! assert_eq!(csv, b"key,value\napple,1.5\n\"pear, ripe\",2\n");
+//! # Ok(()) }
+//! ```
+
+use std::borrow::Borrow;
+use std::{error, fmt, io};
+
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::store::{ErrorOf, RtxOf, Store, Typed, WritableStore, WtxOf};
+
+/// A type that is written as a fixed number of CSV columns.
+pub trait CsvColumns: Sized {
+    /// The number of columns.
+    const COLUMNS: usize;
+
+    /// Push the columns of the value.
+    fn write_columns(&self, columns: &mut Vec<String>);
+
+    /// Read the value from its `COLUMNS` fields, or return the index
+    /// of the field that can't be read and the expected type.
+    fn read_columns(fields: &[&str]) -> Result<Self, (usize, &'static str)>;
+}
+
+macro_rules! impl_csv_scalar {
+    ($($t:ty),*) => {
+        $(
+            impl CsvColumns for $t {
+                const COLUMNS: usize = 1;
+
+                fn write_columns(&self, columns: &mut Vec<String>) {
+                    columns.push(self.to_string());
+                }
+
+                fn read_columns(fields: &[&str]) -> Result<Self, (usize, &'static str)> {
+                    fields[0].trim().parse().map_err(|_| (0, stringify!($t)))
+                }
+            }
+        )*
+    };
+}
+
+impl_csv_scalar!(bool, char, i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+impl CsvColumns for String {
+    const COLUMNS: usize = 1;
+
+    fn write_columns(&self, columns: &mut Vec<String>) {
+        columns.push(self.clone());
+    }
+
+    fn read_columns(fields: &[&str]) -> Result<Self, (usize, &'static str)> {
+        Ok(fields[0].to_string())
+    }
+}
+
+impl CsvColumns for () {
+    const COLUMNS: usize = 0;
+
+    fn write_columns(&self, _columns: &mut Vec<String>) {}
+
+    fn read_columns(_fields: &[&str]) -> Result<Self, (usize, &'static str)> {
+        Ok(())
+    }
+}
+
+macro_rules! impl_csv_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: CsvColumns),+> CsvColumns for ($($t,)+) {
+            const COLUMNS: usize = 0 $(+ $t::COLUMNS)+;
+
+            #[allow(non_snake_case)]
+            fn write_columns(&self, columns: &mut Vec<String>) {
+                let ($($t,)+) = self;
+                $($t.write_columns(columns);)+
+            }
+
+            // The offset is advanced after the last item too.
+            #[allow(unused_assignments)]
+            fn read_columns(fields: &[&str]) -> Result<Self, (usize, &'static str)> {
+                let mut offset = 0;
+                Ok(($({
+                    let fields = &fields[offset..offset + $t::COLUMNS];
+                    let item = $t::read_columns(fields).map_err(|(i, ty)| (offset + i, ty))?;
+                    offset += $t::COLUMNS;
+                    item
+                },)+))
+            }
+        }
+    };
+}
+
+impl_csv_tuple!(A);
+impl_csv_tuple!(A, B);
+impl_csv_tuple!(A, B, C);
+impl_csv_tuple!(A, B, C, D);
+impl_csv_tuple!(A, B, C, D, E);
+impl_csv_tuple!(A, B, C, D, E, F);
+
+/// An error of a CSV export or import.
+#[derive(Debug)]
+pub enum CsvError<E> {
+    Store(E),
+    Csv(::csv::Error),
+    /// A row doesn't have as many columns as the table.
+    Columns {
+        line: u64,
+        expected: usize,
+        found: usize,
+    },
+    /// A field can't be read as the type of its column.
+    Coercion {
+        line: u64,
+        column: String,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl<E: fmt::Display> fmt::Display for CsvError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Store(error) => write!(f, "{}", error),
+            CsvError::Csv(error) => write!(f, "{}", error),
+            CsvError::Columns { line, expected, found } => {
+                write!(f, "line {}: expected {} columns, found {}", line, expected, found)
+            }
+            CsvError::Coercion { line, column, value, expected } => write!(
+                f,
+                "line {}: the {:?} column value {:?} is not a valid {}",
+                line, column, value, expected
+            ),
+        }
+    }
+}
+
+impl<E: error::Error> error::Error for CsvError<E> {}
+
+impl<E> From<::csv::Error> for CsvError<E> {
+    fn from(error: ::csv::Error) -> CsvError<E> {
+        CsvError::Csv(error)
+    }
+}
+
+/// The names of the columns of a `prefix`, `prefix` itself if it has a single one.
+fn headers(prefix: &str, columns: usize, headers: &mut Vec<String>) {
+    match columns {
+        1 => headers.push(prefix.to_string()),
+        n => headers.extend((0..n).map(|i| format!("{}.{}", prefix, i))),
+    }
+}
+
+impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
+    /// Write a header and a row for every entry of the table, in key order,
+    /// returns the number of rows written.
+    pub fn dump_csv<W: io::Write>(
+        &self,
+        rtx: &RtxOf<S>,
+        writer: W,
+    ) -> Result<usize, CsvError<ErrorOf<S>>>
+    where
+        KC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC: BytesDecode,
+        KC::DItem: CsvColumns,
+        DC::DItem: CsvColumns,
+    {
+        let mut writer = ::csv::Writer::from_writer(writer);
+
+        let mut record = Vec::new();
+        headers("key", KC::DItem::COLUMNS, &mut record);
+        headers("value", DC::DItem::COLUMNS, &mut record);
+        writer.write_record(&record)?;
+
+        let mut rows = 0;
+        for entry in self.range(rtx, &(..)).map_err(CsvError::Store)? {
+            let (key, value) = entry.map_err(CsvError::Store)?;
+            record.clear();
+            key.write_columns(&mut record);
+            value.write_columns(&mut record);
+            writer.write_record(&record)?;
+            rows += 1;
+        }
+
+        writer.flush().map_err(::csv::Error::from)?;
+        Ok(rows)
+    }
+
+    /// Put an entry for every row following the header, replacing the entries with the same
+    /// keys, returns the number of rows read.
+    ///
+    /// The rows preceding a row in error are already put, the transaction
+    /// must be aborted to leave the table untouched.
+    pub fn load_csv<R: io::Read>(
+        &self,
+        wtx: &mut WtxOf<S>,
+        reader: R,
+    ) -> Result<usize, CsvError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+        KC: BytesDecode + for<'a> BytesEncode<'a>,
+        DC: BytesDecode + for<'a> BytesEncode<'a>,
+        KC::DItem: CsvColumns + for<'a> Borrow<<KC as BytesEncode<'a>>::EItem>,
+        DC::DItem: CsvColumns + for<'a> Borrow<<DC as BytesEncode<'a>>::EItem>,
+    {
+        let mut reader = ::csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+        let key_columns = KC::DItem::COLUMNS;
+        let expected = key_columns + DC::DItem::COLUMNS;
+
+        let header = reader.headers()?.clone();
+        if header.len() != expected {
+            return Err(CsvError::Columns { line: 1, expected, found: header.len() });
+        }
+
+        let mut rows = 0;
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            if record.len() != expected {
+                return Err(CsvError::Columns { line, expected, found: record.len() });
+            }
+
+            let fields: Vec<&str> = record.iter().collect();
+            let coercion = |(i, expected): (usize, &'static str)| CsvError::Coercion {
+                line,
+                column: header[i].to_string(),
+                value: fields[i].to_string(),
+                expected,
+            };
+            let key = KC::DItem::read_columns(&fields[..key_columns]).map_err(coercion)?;
+            let value = DC::DItem::read_columns(&fields[key_columns..])
+                .map_err(|(i, ty)| coercion((key_columns + i, ty)))?;
+
+            self.put(wtx, key.borrow(), value.borrow()).map_err(CsvError::Store)?;
+            rows += 1;
+        }
+
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tuple_columns() {
+        let mut columns = Vec::new();
+        (1u32, (String::from("a"), true), ()).write_columns(&mut columns);
+        assert_eq!(columns, ["1", "a", "true"]);
+        assert_eq!(<(u32, (String, bool), ())>::COLUMNS, 3);
+
+        let read = <(u32, (String, bool), ())>::read_columns(&["1", "a", "true"]);
+        assert_eq!(read, Ok((1, (String::from("a"), true), ())));
+        let read = <(u32, (String, bool))>::read_columns(&["1", "a", "yes"]);
+        assert_eq!(read, Err((2, "bool")));
+    }
+
+    #[test]
+    fn column_headers() {
+        let mut names = Vec::new();
+        headers("key", 1, &mut names);
+        headers("value", 2, &mut names);
+        headers("value", 0, &mut names);
+        assert_eq!(names, ["key", "value.0", "value.1"]);
+    }
+}
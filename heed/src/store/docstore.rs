@@ -0,0 +1,467 @@
+//! A document store on top of any [`Store`], documents are JSON values stored under
+//! increasing ids and can be queried by the value of their fields.
+//!
+//! Fields are designated by dotted paths, `"address.city"` is the `city` field of the
+//! `address` object and `"tags.0"` the first element of the `tags` array. The fields given
+//! when opening a [`DocStore`] are indexed, queries on them are index scans, queries on
+//! other fields scan every document.
+//!
+//! Only scalar values are indexed and compared: `null`, booleans, numbers and strings,
+//! sorted in this order, numbers by their `f64` value and strings by their bytes.
+//!
+//! ```
+//! # use std::fs;
+//! # use std::path::Path;
+//! # use heed::EnvOpenOptions;
+//! use heed::store::docstore::{DocStore, Query};
+//! use heed::store::{Store, WritableStore};
+//! use serde_json::json;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let people = DocStore::open(&env, "people", &["age"])?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! let alice = people.insert(&mut wtxn, &json!({ "name": "alice", "age": 32 }))?;
+//! let bob = people.insert(&mut wtxn, &json!({ "name": "bob", "age": 27 }))?;
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let found = people.query(&rtxn, &Query::range("age", json!(30)..))?;
+//! assert_eq!(found, vec![(alice, json!({ "name": "alice", "age": 32 }))]);
+//!
+//! let found = people.query(&rtxn, &Query::eq("name", json!("bob")))?;
+//! assert_eq!(found, vec![(bob, json!({ "name": "bob", "age": 27 }))]);
+//! # Ok(()) }
+//! ```
+
+use std::ops::{Bound, RangeBounds};
+
+use heed_types::{ByteSlice, SerdeJson, Unit};
+use serde_json::Value;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, Typed, WritableStore, WtxOf};
+
+/// The id given to a document when it is inserted.
+pub type DocId = u64;
+
+type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// A query on the value of a field of the documents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// The field is equal to the value.
+    Eq(String, Value),
+    /// The field is in the range of values.
+    Range(String, Bound<Value>, Bound<Value>),
+}
+
+impl Query {
+    pub fn eq(path: &str, value: Value) -> Query {
+        Query::Eq(path.to_owned(), value)
+    }
+
+    pub fn range<R: RangeBounds<Value>>(path: &str, range: R) -> Query {
+        Query::Range(path.to_owned(), range.start_bound().cloned(), range.end_bound().cloned())
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            Query::Eq(path, _) | Query::Range(path, _, _) => path,
+        }
+    }
+
+    /// The range of index keys matching this query, `None` if nothing can match.
+    fn key_range(&self) -> Option<KeyRange> {
+        let (start, end) = match self {
+            Query::Eq(_, value) => (Bound::Included(value), Bound::Included(value)),
+            Query::Range(_, start, end) => (start.as_ref(), end.as_ref()),
+        };
+
+        // The keys of a value are its encoding followed by the ids of the documents.
+        let start = match start {
+            Bound::Included(value) => Bound::Included(encode_value(value)?),
+            Bound::Excluded(value) => Bound::Excluded(index_key(&encode_value(value)?, DocId::MAX)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        let end = match end {
+            Bound::Included(value) => Bound::Included(index_key(&encode_value(value)?, DocId::MAX)),
+            Bound::Excluded(value) => Bound::Excluded(encode_value(value)?),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+
+        Some((start, end))
+    }
+}
+
+struct Index<'s, S: Store + 's> {
+    path: String,
+    pointer: String,
+    table: TableOf<'s, S>,
+}
+
+impl<'s, S: Store> Index<'s, S> {
+    fn key(&self, id: DocId, doc: &Value) -> Option<Vec<u8>> {
+        let value = encode_value(doc.pointer(&self.pointer)?)?;
+        Some(index_key(&value, id))
+    }
+
+    fn put(&self, wtx: &mut WtxOf<S>, id: DocId, doc: &Value) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        match self.key(id, doc) {
+            Some(key) => self.table.put::<ByteSlice, Unit>(wtx, &key, &()),
+            None => Ok(()),
+        }
+    }
+
+    fn delete(&self, wtx: &mut WtxOf<S>, id: DocId, doc: &Value) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        match self.key(id, doc) {
+            Some(key) => self.table.delete::<ByteSlice>(wtx, &key),
+            None => Ok(()),
+        }
+    }
+}
+
+/// JSON documents stored in the tables of a [`Store`], see the [module documentation](self).
+pub struct DocStore<'s, S: Store + 's> {
+    docs: Typed<'s, S, ByteSlice, SerdeJson<Value>>,
+    indexes: Vec<Index<'s, S>>,
+}
+
+impl<'s, S: Store> DocStore<'s, S> {
+    /// Open the documents named `name` in the store, indexing the given fields.
+    ///
+    /// The documents are stored in the `name` table, the index of each field in
+    /// a `name:index:path` table and the list of indexed fields in a `name:meta` table.
+    /// Fields that were not indexed yet are indexed right away, the indexes of fields
+    /// that are not given anymore are cleared.
+    pub fn open(store: &'s S, name: &str, indexed: &[&str]) -> Result<Self, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let cfg = S::Config::default();
+        let docs = store.typed(name, &cfg)?;
+        let meta = store.typed::<ByteSlice, Unit>(&format!("{}:meta", name), &cfg)?;
+
+        let mut indexes = Vec::with_capacity(indexed.len());
+        for path in indexed {
+            let table = store.table(&index_table_name(name, path), &cfg)?;
+            indexes.push(Index { path: path.to_string(), pointer: json_pointer(path), table });
+        }
+
+        let docstore = DocStore { docs, indexes };
+
+        let known = store.with_rtx(|rtx| {
+            let mut known = Vec::new();
+            for entry in meta.range(rtx, &..)? {
+                known.push(entry?.0);
+            }
+            Ok(known)
+        })?;
+
+        let mut stale = Vec::new();
+        for path in &known {
+            if !indexed.iter().any(|p| p.as_bytes() == &path[..]) {
+                let table = store.table(&index_table_name(name, path_str(path)), &cfg)?;
+                stale.push((path, table));
+            }
+        }
+
+        store.with_wtx(|wtx| {
+            for (path, table) in &stale {
+                table.clear(wtx)?;
+                meta.delete(wtx, path)?;
+            }
+
+            for index in &docstore.indexes {
+                if !known.iter().any(|path| &path[..] == index.path.as_bytes()) {
+                    docstore.build_index(wtx, index)?;
+                    meta.put(wtx, index.path.as_bytes(), &())?;
+                }
+            }
+
+            Ok(())
+        })?;
+
+        Ok(docstore)
+    }
+
+    fn build_index(&self, wtx: &mut WtxOf<S>, index: &Index<'s, S>) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        index.table.clear(wtx)?;
+
+        let mut docs = Vec::new();
+        for entry in self.docs.range(wtx, &..)? {
+            let (key, doc) = entry?;
+            docs.push((decode_id(&key), doc));
+        }
+
+        for (id, doc) in docs {
+            index.put(wtx, id, &doc)?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert a document under a new id, greater than the ids of all the documents.
+    pub fn insert(&self, wtx: &mut WtxOf<S>, doc: &Value) -> Result<DocId, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let id = match self.docs.rev_range(wtx, &..)?.next().transpose()? {
+            Some((key, _)) => decode_id(&key) + 1,
+            None => 0,
+        };
+
+        self.docs.put(wtx, &id.to_be_bytes(), doc)?;
+        for index in &self.indexes {
+            index.put(wtx, id, doc)?;
+        }
+
+        Ok(id)
+    }
+
+    pub fn get(&self, rtx: &RtxOf<S>, id: DocId) -> Result<Option<Value>, ErrorOf<S>> {
+        self.docs.get(rtx, &id.to_be_bytes())
+    }
+
+    /// Replace the document with the given id, returns `false` if there is no such document.
+    pub fn replace(&self, wtx: &mut WtxOf<S>, id: DocId, doc: &Value) -> Result<bool, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let old = match self.docs.get(wtx, &id.to_be_bytes())? {
+            Some(old) => old,
+            None => return Ok(false),
+        };
+
+        for index in &self.indexes {
+            index.delete(wtx, id, &old)?;
+            index.put(wtx, id, doc)?;
+        }
+        self.docs.put(wtx, &id.to_be_bytes(), doc)?;
+
+        Ok(true)
+    }
+
+    /// Remove the document with the given id and return it.
+    pub fn remove(&self, wtx: &mut WtxOf<S>, id: DocId) -> Result<Option<Value>, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let old = match self.docs.get(wtx, &id.to_be_bytes())? {
+            Some(old) => old,
+            None => return Ok(None),
+        };
+
+        for index in &self.indexes {
+            index.delete(wtx, id, &old)?;
+        }
+        self.docs.delete(wtx, &id.to_be_bytes())?;
+
+        Ok(Some(old))
+    }
+
+    /// The documents matching the query, ordered by the queried value then by id
+    /// when the field is indexed, by id otherwise.
+    pub fn query(&self, rtx: &RtxOf<S>, query: &Query) -> Result<Vec<(DocId, Value)>, ErrorOf<S>> {
+        let (start, end) = match query.key_range() {
+            Some(range) => range,
+            None => return Ok(Vec::new()),
+        };
+
+        let index = match self.indexes.iter().find(|index| index.path == query.path()) {
+            Some(index) => index,
+            None => return self.scan(rtx, query, (start, end)),
+        };
+
+        let range = (bound_as_slice(&start), bound_as_slice(&end));
+        let mut ids = Vec::new();
+        for entry in index.table.range::<ByteSlice, Unit, _>(rtx, &range)? {
+            let (key, ()) = entry?;
+            ids.push(decode_id(&key[key.len() - 8..]));
+        }
+
+        let mut docs = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(doc) = self.get(rtx, id)? {
+                docs.push((id, doc));
+            }
+        }
+
+        Ok(docs)
+    }
+
+    /// Answers a query on a field that isn't indexed by comparing the index keys
+    /// the documents would have, to give the same results as an index scan.
+    fn scan(
+        &self,
+        rtx: &RtxOf<S>,
+        query: &Query,
+        range: KeyRange,
+    ) -> Result<Vec<(DocId, Value)>, ErrorOf<S>> {
+        let pointer = json_pointer(query.path());
+
+        let mut docs = Vec::new();
+        for entry in self.docs.range(rtx, &..)? {
+            let (key, doc) = entry?;
+            let id = decode_id(&key);
+
+            if let Some(value) = doc.pointer(&pointer).and_then(encode_value) {
+                if range.contains(&index_key(&value, id)) {
+                    docs.push((id, doc));
+                }
+            }
+        }
+
+        Ok(docs)
+    }
+}
+
+/// Converts a dotted path into a JSON pointer.
+fn json_pointer(path: &str) -> String {
+    let mut pointer = String::with_capacity(path.len() + 1);
+    for part in path.split('.') {
+        pointer.push('/');
+        pointer.push_str(&part.replace('~', "~0").replace('/', "~1"));
+    }
+    pointer
+}
+
+/// The name of the index table of a field, the `index:` prefix keeps any field path
+/// from naming the `name:meta` table.
+fn index_table_name(name: &str, path: &str) -> String {
+    format!("{}:index:{}", name, path)
+}
+
+fn path_str(path: &[u8]) -> &str {
+    std::str::from_utf8(path).unwrap_or_default()
+}
+
+fn decode_id(bytes: &[u8]) -> DocId {
+    let mut id = [0; 8];
+    id.copy_from_slice(bytes);
+    DocId::from_be_bytes(id)
+}
+
+fn index_key(value: &[u8], id: DocId) -> Vec<u8> {
+    let mut key = Vec::with_capacity(value.len() + 8);
+    key.extend_from_slice(value);
+    key.extend_from_slice(&id.to_be_bytes());
+    key
+}
+
+fn bound_as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(bytes) => Bound::Included(bytes),
+        Bound::Excluded(bytes) => Bound::Excluded(bytes),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Encodes a scalar value into bytes that sort like the values, no encoded value
+/// is the prefix of another one so the document ids can be appended to them.
+fn encode_value(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Null => Some(vec![0]),
+        Value::Bool(b) => Some(vec![1, *b as u8]),
+        Value::Number(n) => {
+            // Adding zero turns -0.0 into 0.0.
+            let bits = (n.as_f64()? + 0.0).to_bits();
+            let bits = if bits >> 63 == 1 { !bits } else { bits | 1 << 63 };
+
+            let mut bytes = vec![2];
+            bytes.extend_from_slice(&bits.to_be_bytes());
+            Some(bytes)
+        }
+        Value::String(s) => {
+            // Zeros are escaped and the string is terminated by two zeros.
+            let mut bytes = Vec::with_capacity(s.len() + 3);
+            bytes.push(3);
+            for &byte in s.as_bytes() {
+                bytes.push(byte);
+                if byte == 0 {
+                    bytes.push(0xFF);
+                }
+            }
+            bytes.extend_from_slice(&[0, 0]);
+            Some(bytes)
+        }
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn encoded_values_sort_like_values() {
+        let values = vec![
+            json!(null),
+            json!(false),
+            json!(true),
+            json!(-1e300),
+            json!(-2),
+            json!(-0.5),
+            json!(0),
+            json!(0.5),
+            json!(3),
+            json!(u64::MAX),
+            json!(""),
+            json!("a"),
+            json!("a\u{0}"),
+            json!("a\u{0}b"),
+            json!("ab"),
+            json!("b"),
+        ];
+
+        let keys: Vec<_> = values.iter().map(|v| index_key(&encode_value(v).unwrap(), 7)).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+
+        assert_eq!(encode_value(&json!(-0.0)), encode_value(&json!(0)));
+        assert_eq!(encode_value(&json!([1])), None);
+    }
+
+    #[test]
+    fn dotted_paths_become_pointers() {
+        assert_eq!(json_pointer("address.city"), "/address/city");
+        assert_eq!(json_pointer("tags.0"), "/tags/0");
+        assert_eq!(json_pointer("a/b.c~d"), "/a~1b/c~0d");
+    }
+
+    #[test]
+    fn fields_can_be_named_like_the_meta_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(10).open(dir.path()).unwrap();
+        let docs = DocStore::open(&env, "docs", &["meta", "index"]).unwrap();
+
+        let mut wtxn = env.wtx().unwrap();
+        let id = docs.insert(&mut wtxn, &json!({ "meta": 1, "index": "a" })).unwrap();
+        wtxn.commit().unwrap();
+        drop(docs);
+
+        // The indexed fields are still known once reopened, the indexes are not rebuilt.
+        let docs = DocStore::open(&env, "docs", &["meta", "index"]).unwrap();
+        let rtxn = env.rtx().unwrap();
+        let doc = json!({ "meta": 1, "index": "a" });
+        assert_eq!(docs.query(&rtxn, &Query::eq("meta", json!(1))).unwrap(), [(id, doc.clone())]);
+        assert_eq!(docs.query(&rtxn, &Query::eq("index", json!("a"))).unwrap(), [(id, doc)]);
+    }
+}
@@ -0,0 +1,226 @@
+//! The entry of a key of a [`Typed`] table in a write transaction, occupied or vacant,
+//! like the entries of the maps of [`std::collections`].
+//!
+//! The value of the key is read once, with [`Table::get_for_update`], when the entry is
+//! created, the methods of the entry write or delete it in the transaction.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::entry::Entry;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let stock = env.typed::<Str, OwnedType<u32>>("stock", &())?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! assert_eq!(stock.entry(&mut wtxn, "apple")?.or_insert(3)?, 3);
+//! let apples = stock.entry(&mut wtxn, "apple")?;
+//! assert_eq!(apples.and_modify(|count| *count -= 1)?.or_insert(0)?, 2);
+//!
+//! if let Entry::Occupied(apples) = stock.entry(&mut wtxn, "apple")? {
+//!     assert_eq!(apples.remove()?, 2);
+//! }
+//! assert_eq!(stock.get(&wtxn, "apple")?, None);
+//! wtxn.commit()?;
+//! # Ok(()) }
+//! ```
+
+use std::mem;
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, Table, Typed, WritableStore, WtxOf};
+
+/// The entry of a key of a [`Typed`] table, see [`Typed::entry`].
+pub enum Entry<'t, 'e, 's, S: WritableStore + 's, KC, DC: BytesDecode> {
+    Occupied(OccupiedEntry<'t, 'e, 's, S, KC, DC>),
+    Vacant(VacantEntry<'t, 'e, 's, S, KC, DC>),
+}
+
+/// The entry of a key that has a value.
+pub struct OccupiedEntry<'t, 'e, 's, S: WritableStore + 's, KC, DC: BytesDecode> {
+    table: &'t Typed<'s, S, KC, DC>,
+    txn: &'t mut WtxOf<'e, S>,
+    key: Vec<u8>,
+    value: DC::DItem,
+}
+
+/// The entry of a key that has no value.
+pub struct VacantEntry<'t, 'e, 's, S: WritableStore + 's, KC, DC> {
+    table: &'t Typed<'s, S, KC, DC>,
+    txn: &'t mut WtxOf<'e, S>,
+    key: Vec<u8>,
+}
+
+impl<'s, S: WritableStore, KC, DC> Typed<'s, S, KC, DC> {
+    /// Reads the value of the key, with [`Table::get_for_update`], and returns
+    /// its entry to write or delete it.
+    pub fn entry<'t, 'e, 'a>(
+        &'t self,
+        txn: &'t mut WtxOf<'e, S>,
+        key: &'a KC::EItem,
+    ) -> Result<Entry<'t, 'e, 's, S, KC, DC>, ErrorOf<S>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        let value = self.dyndb.get_for_update::<KC, DC>(txn, key)?;
+        let key = KC::bytes_encode(key).expect("the key was encoded to be read").into_owned();
+        Ok(match value {
+            Some(value) => Entry::Occupied(OccupiedEntry { table: self, txn, key, value }),
+            None => Entry::Vacant(VacantEntry { table: self, txn, key }),
+        })
+    }
+}
+
+impl<'t, 'e, 's, S: WritableStore, KC, DC> Entry<'t, 'e, 's, S, KC, DC>
+where
+    DC: BytesDecode + for<'b> BytesEncode<'b, EItem = <DC as BytesDecode>::DItem>,
+{
+    /// The encoded key of the entry.
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Returns the value of the entry, writes and returns `default` if it is vacant.
+    pub fn or_insert(self, default: DC::DItem) -> Result<DC::DItem, ErrorOf<S>> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Returns the value of the entry, writes and returns the result
+    /// of `default` if it is vacant.
+    pub fn or_insert_with<F>(self, default: F) -> Result<DC::DItem, ErrorOf<S>>
+    where
+        F: FnOnce() -> DC::DItem,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_value()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Returns the value of the entry, writes and returns
+    /// the default value if it is vacant.
+    pub fn or_default(self) -> Result<DC::DItem, ErrorOf<S>>
+    where
+        DC::DItem: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+
+    /// Modifies the value of an occupied entry with `modify` and writes it.
+    pub fn and_modify<F>(self, modify: F) -> Result<Self, ErrorOf<S>>
+    where
+        F: FnOnce(&mut DC::DItem),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                modify(&mut entry.value);
+                entry.write()?;
+                Ok(Entry::Occupied(entry))
+            }
+            Entry::Vacant(entry) => Ok(Entry::Vacant(entry)),
+        }
+    }
+
+    /// Deletes the key of an occupied entry and returns its value.
+    pub fn remove(self) -> Result<Option<DC::DItem>, ErrorOf<S>> {
+        match self {
+            Entry::Occupied(entry) => entry.remove().map(Some),
+            Entry::Vacant(_) => Ok(None),
+        }
+    }
+}
+
+impl<'t, 'e, 's, S: WritableStore, KC, DC> OccupiedEntry<'t, 'e, 's, S, KC, DC>
+where
+    DC: BytesDecode + for<'b> BytesEncode<'b, EItem = <DC as BytesDecode>::DItem>,
+{
+    /// The encoded key of the entry.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// The value of the entry.
+    pub fn get(&self) -> &DC::DItem {
+        &self.value
+    }
+
+    /// Converts the entry into its value.
+    pub fn into_value(self) -> DC::DItem {
+        self.value
+    }
+
+    /// Writes the value of the entry and returns the previous one.
+    pub fn insert(&mut self, value: DC::DItem) -> Result<DC::DItem, ErrorOf<S>> {
+        let old = mem::replace(&mut self.value, value);
+        self.write()?;
+        Ok(old)
+    }
+
+    /// Deletes the key of the entry and returns its value.
+    pub fn remove(self) -> Result<DC::DItem, ErrorOf<S>> {
+        self.table.dyndb.delete::<ByteSlice>(self.txn, &self.key)?;
+        Ok(self.value)
+    }
+
+    fn write(&mut self) -> Result<(), ErrorOf<S>> {
+        self.table.dyndb.put::<ByteSlice, DC>(self.txn, &self.key, &self.value)
+    }
+}
+
+impl<'t, 'e, 's, S: WritableStore, KC, DC> VacantEntry<'t, 'e, 's, S, KC, DC>
+where
+    DC: BytesDecode + for<'b> BytesEncode<'b, EItem = <DC as BytesDecode>::DItem>,
+{
+    /// The encoded key of the entry.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Writes the value of the entry and returns it.
+    pub fn insert(self, value: DC::DItem) -> Result<DC::DItem, ErrorOf<S>> {
+        self.table.dyndb.put::<ByteSlice, DC>(self.txn, &self.key, &value)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{OwnedType, Str};
+
+    use super::*;
+    use crate::store::Store;
+
+    #[test]
+    fn occupied_entries_write_through() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<Str, OwnedType<u32>>("entries", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(table.entry(&mut wtx, "a").unwrap().remove().unwrap(), None);
+        assert_eq!(table.entry(&mut wtx, "a").unwrap().or_default().unwrap(), 0);
+
+        match table.entry(&mut wtx, "a").unwrap() {
+            Entry::Occupied(mut entry) => {
+                assert_eq!((entry.key(), *entry.get()), (&b"a"[..], 0));
+                assert_eq!(entry.insert(7).unwrap(), 0);
+            }
+            Entry::Vacant(_) => panic!("the entry was inserted"),
+        }
+        assert_eq!(table.get(&wtx, "a").unwrap(), Some(7));
+        assert_eq!(table.entry(&mut wtx, "a").unwrap().remove().unwrap(), Some(7));
+        assert_eq!(table.get(&wtx, "a").unwrap(), None);
+    }
+}
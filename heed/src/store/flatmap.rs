@@ -0,0 +1,350 @@
+//! Maps stored on top of any [`Store`] as one row per entry under a key prefix, rather than
+//! as a single serialized blob that must be rewritten on every change.
+//!
+//! The key of an entry is the prefix followed by the bincode encoding of the map key,
+//! its value is the bincode encoding of the map value. Many maps can share a table
+//! as long as no prefix is a prefix of another.
+//!
+//! [`FlatMap::save`] compares a whole map with the stored rows and only writes the entries
+//! that were inserted, changed or removed, [`FlatMap::diff`] does the comparison alone.
+//!
+//! ```
+//! # use std::collections::HashMap;
+//! # use heed::EnvOpenOptions;
+//! use heed::store::flatmap::FlatMap;
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let stock = FlatMap::<_, String, u32>::open(&env, "maps", b"stock/")?;
+//!
+//! let mut fruits = HashMap::new();
+//! fruits.insert("apple".to_string(), 3);
+//! fruits.insert("pear".to_string(), 5);
+//!
+//! let mut wtxn = env.wtx()?;
+//! let diff = stock.save(&mut wtxn, &fruits)?;
+//! assert_eq!(diff.inserted.len(), 2);
+//!
+//! fruits.insert("pear".to_string(), 4);
+//! fruits.remove("apple");
+//! let diff = stock.save(&mut wtxn, &fruits)?;
+//! assert_eq!(diff.updated, vec![(&"pear".to_string(), &4)]);
+//! assert_eq!(diff.removed, vec!["apple".to_string()]);
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! assert_eq!(stock.load(&rtxn)?, fruits);
+//! # Ok(()) }
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Bound;
+use std::{error, fmt, marker};
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::{ByteSlice, SerdeBincode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::iter::prefix_end;
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+/// An error of a [`FlatMap`], either from the store or from the bincode encoding.
+#[derive(Debug)]
+pub enum FlatMapError<E> {
+    Store(E),
+    /// A key or a value can't be serialized.
+    Encoding,
+    /// A stored row can't be deserialized as a key or a value.
+    Decoding,
+}
+
+impl<E: fmt::Display> fmt::Display for FlatMapError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlatMapError::Store(error) => write!(f, "{}", error),
+            FlatMapError::Encoding => f.write_str("error while encoding an entry of the map"),
+            FlatMapError::Decoding => f.write_str("error while decoding an entry of the map"),
+        }
+    }
+}
+
+impl<E: error::Error> error::Error for FlatMapError<E> {}
+
+/// The entries of a map that differ from the stored rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diff<'m, K, V> {
+    /// The entries of the map that are not stored.
+    pub inserted: Vec<(&'m K, &'m V)>,
+    /// The entries of the map whose stored value is different.
+    pub updated: Vec<(&'m K, &'m V)>,
+    /// The stored keys that are not in the map.
+    pub removed: Vec<K>,
+}
+
+impl<K, V> Diff<'_, K, V> {
+    /// Whether the map is identical to the stored rows.
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The full keys and the values of stored entries.
+type Rows = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The full keys to write, with their value, or to delete.
+type Writes = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
+/// A map of `K` to `V` stored as one row per entry under a prefix of a table of a [`Store`].
+///
+/// See the [module documentation](self).
+pub struct FlatMap<'s, S: Store + 's, K, V> {
+    table: TableOf<'s, S>,
+    prefix: Vec<u8>,
+    marker: marker::PhantomData<(K, V)>,
+}
+
+impl<'s, S: Store, K, V> FlatMap<'s, S, K, V>
+where
+    K: Serialize + DeserializeOwned + Eq + Hash + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    /// Open the map stored under `prefix` in the `name` table of the store.
+    pub fn open(store: &'s S, name: &str, prefix: &[u8]) -> Result<Self, ErrorOf<S>> {
+        let table = store.table(name, &S::Config::default())?;
+        Ok(FlatMap { table, prefix: prefix.to_vec(), marker: marker::PhantomData })
+    }
+
+    /// Read every entry of the map.
+    pub fn load(&self, rtx: &RtxOf<S>) -> Result<HashMap<K, V>, FlatMapError<ErrorOf<S>>> {
+        let mut map = HashMap::new();
+        for (key, value) in self.rows(rtx)? {
+            map.insert(self.decode_key(&key)?, decode(&value)?);
+        }
+        Ok(map)
+    }
+
+    /// The value of a single entry.
+    pub fn get(&self, rtx: &RtxOf<S>, key: &K) -> Result<Option<V>, FlatMapError<ErrorOf<S>>> {
+        let key = self.encode_key(key)?;
+        match self.table.get::<ByteSlice, ByteSlice>(rtx, &key).map_err(FlatMapError::Store)? {
+            Some(value) => decode(&value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Write a single entry, replacing its previous value if any.
+    pub fn insert(
+        &self,
+        wtx: &mut WtxOf<S>,
+        key: &K,
+        value: &V,
+    ) -> Result<(), FlatMapError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        let key = self.encode_key(key)?;
+        let value = encode(value)?;
+        self.table.put::<ByteSlice, ByteSlice>(wtx, &key, &value).map_err(FlatMapError::Store)
+    }
+
+    /// Delete a single entry, if any.
+    pub fn remove(&self, wtx: &mut WtxOf<S>, key: &K) -> Result<(), FlatMapError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        let key = self.encode_key(key)?;
+        self.table.delete::<ByteSlice>(wtx, &key).map_err(FlatMapError::Store)
+    }
+
+    /// The differences between `map` and the stored entries.
+    ///
+    /// Values are compared by their encoding, a value whose encoding isn't deterministic,
+    /// like a `HashMap`, may be reported as updated when it is not.
+    pub fn diff<'m>(
+        &self,
+        rtx: &RtxOf<S>,
+        map: &'m HashMap<K, V>,
+    ) -> Result<Diff<'m, K, V>, FlatMapError<ErrorOf<S>>> {
+        self.compare(rtx, map, &mut Vec::new())
+    }
+
+    /// Make the stored entries identical to `map`, only the differences are written,
+    /// returns them.
+    pub fn save<'m>(
+        &self,
+        wtx: &mut WtxOf<S>,
+        map: &'m HashMap<K, V>,
+    ) -> Result<Diff<'m, K, V>, FlatMapError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        let mut writes = Vec::new();
+        let diff = self.compare(wtx, map, &mut writes)?;
+        for (key, value) in writes {
+            match value {
+                Some(value) => self.table.put::<ByteSlice, ByteSlice>(wtx, &key, &value),
+                None => self.table.delete::<ByteSlice>(wtx, &key),
+            }
+            .map_err(FlatMapError::Store)?;
+        }
+        Ok(diff)
+    }
+
+    /// Delete every entry of the map, returns the number of deleted entries.
+    pub fn clear(&self, wtx: &mut WtxOf<S>) -> Result<usize, FlatMapError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        let rows = self.rows(wtx)?;
+        for (key, _) in &rows {
+            self.table.delete::<ByteSlice>(wtx, key).map_err(FlatMapError::Store)?;
+        }
+        Ok(rows.len())
+    }
+
+    /// The differences between `map` and the stored entries, the writes that
+    /// apply them are pushed to `writes`.
+    fn compare<'m>(
+        &self,
+        rtx: &RtxOf<S>,
+        map: &'m HashMap<K, V>,
+        writes: &mut Writes,
+    ) -> Result<Diff<'m, K, V>, FlatMapError<ErrorOf<S>>> {
+        let mut stored: HashMap<_, _> = self.rows(rtx)?.into_iter().collect();
+        let mut diff = Diff { inserted: Vec::new(), updated: Vec::new(), removed: Vec::new() };
+
+        for (key, value) in map {
+            let full_key = self.encode_key(key)?;
+            let encoded = encode(value)?;
+            match stored.remove(&full_key) {
+                Some(previous) if previous == encoded => continue,
+                Some(_) => diff.updated.push((key, value)),
+                None => diff.inserted.push((key, value)),
+            }
+            writes.push((full_key, Some(encoded)));
+        }
+
+        for (full_key, _) in stored {
+            diff.removed.push(self.decode_key(&full_key)?);
+            writes.push((full_key, None));
+        }
+
+        Ok(diff)
+    }
+
+    fn rows(&self, rtx: &RtxOf<S>) -> Result<Rows, FlatMapError<ErrorOf<S>>> {
+        let end = prefix_end(&self.prefix);
+        let end = match &end {
+            Some(end) => Bound::Excluded(&end[..]),
+            None => Bound::Unbounded,
+        };
+        let range = (Bound::Included(&self.prefix[..]), end);
+        let rows = self.table.range::<ByteSlice, ByteSlice, _>(rtx, &range);
+        rows.map_err(FlatMapError::Store)?.collect::<Result<_, _>>().map_err(FlatMapError::Store)
+    }
+
+    fn encode_key(&self, key: &K) -> Result<Vec<u8>, FlatMapError<ErrorOf<S>>> {
+        let mut full_key = self.prefix.clone();
+        full_key.extend_from_slice(&encode(key)?);
+        Ok(full_key)
+    }
+
+    fn decode_key(&self, full_key: &[u8]) -> Result<K, FlatMapError<ErrorOf<S>>> {
+        decode(&full_key[self.prefix.len()..])
+    }
+}
+
+fn encode<T: Serialize, E>(item: &T) -> Result<Vec<u8>, FlatMapError<E>> {
+    SerdeBincode::<T>::bytes_encode(item)
+        .map(|bytes| bytes.into_owned())
+        .ok_or(FlatMapError::Encoding)
+}
+
+fn decode<T: DeserializeOwned + 'static, E>(bytes: &[u8]) -> Result<T, FlatMapError<E>> {
+    SerdeBincode::<T>::bytes_decode(bytes).ok_or(FlatMapError::Decoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    fn map(entries: &[(&str, u32)]) -> HashMap<String, u32> {
+        entries.iter().map(|&(key, value)| (key.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn maps_sharing_a_table_stay_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let stock = FlatMap::<_, String, u32>::open(&env, "maps", b"stock/").unwrap();
+        let prices = FlatMap::<_, String, u32>::open(&env, "maps", b"prices/").unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        stock.save(&mut wtx, &map(&[("apple", 3), ("pear", 5)])).unwrap();
+        prices.save(&mut wtx, &map(&[("apple", 120)])).unwrap();
+        stock.insert(&mut wtx, &"kiwi".to_string(), &1).unwrap();
+        stock.remove(&mut wtx, &"pear".to_string()).unwrap();
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        assert_eq!(stock.load(&rtx).unwrap(), map(&[("apple", 3), ("kiwi", 1)]));
+        assert_eq!(prices.load(&rtx).unwrap(), map(&[("apple", 120)]));
+        assert_eq!(stock.get(&rtx, &"pear".to_string()).unwrap(), None);
+        assert_eq!(prices.get(&rtx, &"apple".to_string()).unwrap(), Some(120));
+        drop(rtx);
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(stock.clear(&mut wtx).unwrap(), 2);
+        assert!(stock.load(&wtx).unwrap().is_empty());
+        assert_eq!(prices.load(&wtx).unwrap(), map(&[("apple", 120)]));
+    }
+
+    #[test]
+    fn diffs_only_write_when_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let stock = FlatMap::<_, String, u32>::open(&env, "maps", b"stock/").unwrap();
+        let fruits = map(&[("apple", 3), ("pear", 5)]);
+
+        let mut wtx = env.wtx().unwrap();
+        stock.save(&mut wtx, &fruits).unwrap();
+        wtx.commit().unwrap();
+
+        let changed = map(&[("apple", 4), ("kiwi", 1)]);
+        let rtx = env.rtx().unwrap();
+        assert!(stock.diff(&rtx, &fruits).unwrap().is_empty());
+        let diff = stock.diff(&rtx, &changed).unwrap();
+        assert_eq!(diff.inserted, [(&"kiwi".to_string(), &1)]);
+        assert_eq!(diff.updated, [(&"apple".to_string(), &4)]);
+        assert_eq!(diff.removed, ["pear".to_string()]);
+        assert_eq!(stock.load(&rtx).unwrap(), fruits);
+        drop(rtx);
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(stock.save(&mut wtx, &changed).unwrap(), diff);
+        assert!(stock.save(&mut wtx, &changed).unwrap().is_empty());
+        assert_eq!(stock.load(&wtx).unwrap(), changed);
+    }
+
+    #[test]
+    fn undecodable_rows_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let stock = FlatMap::<_, String, u32>::open(&env, "maps", b"stock/").unwrap();
+
+        // The length of the key is larger than the row.
+        let mut wtx = env.wtx().unwrap();
+        let table = &stock.table;
+        Table::put::<ByteSlice, ByteSlice>(table, &mut wtx, b"stock/\xff", &[0; 4]).unwrap();
+        assert!(matches!(stock.load(&wtx), Err(FlatMapError::Decoding)));
+        assert!(matches!(stock.clear(&mut wtx), Ok(1)));
+    }
+}
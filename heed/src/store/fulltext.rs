@@ -0,0 +1,325 @@
+//! An inverted index on top of any [`Store`], finding the documents that contain words.
+//!
+//! Texts are split into tokens by a [`Tokenizer`], every `(token, document)` pair is a key
+//! of the postings table, so the documents containing a token are a range of keys. The
+//! index only knows the ids of the documents, the documents are stored by the caller,
+//! with the same write transaction if they live in the same store.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::fulltext::{FullText, SimpleTokenizer, TextQuery};
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let index = FullText::open(&env, "words", SimpleTokenizer)?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! index.add(&mut wtxn, 1, "The quick brown fox")?;
+//! index.add(&mut wtxn, 2, "The lazy dog")?;
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! assert_eq!(index.search(&rtxn, &TextQuery::all(&SimpleTokenizer, "the fox"))?, vec![1]);
+//! assert_eq!(index.search(&rtxn, &TextQuery::any(&SimpleTokenizer, "fox dog"))?, vec![1, 2]);
+//! # Ok(()) }
+//! ```
+
+use std::ops::Bound;
+
+use heed_types::{ByteSlice, Unit};
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+/// Splits texts into the tokens they are indexed and searched by.
+pub trait Tokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String>;
+}
+
+/// Splits texts on everything that isn't alphanumeric and lowercases the tokens.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SimpleTokenizer;
+
+impl Tokenizer for SimpleTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    }
+}
+
+/// A query on the tokens of the documents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextQuery {
+    /// The documents containing the token.
+    Token(String),
+    /// The documents matching all the queries, none if there are no queries.
+    And(Vec<TextQuery>),
+    /// The documents matching any of the queries.
+    Or(Vec<TextQuery>),
+}
+
+impl TextQuery {
+    /// The documents containing all the tokens of the text.
+    pub fn all<T: Tokenizer>(tokenizer: &T, text: &str) -> TextQuery {
+        TextQuery::And(tokenizer.tokenize(text).into_iter().map(TextQuery::Token).collect())
+    }
+
+    /// The documents containing any of the tokens of the text.
+    pub fn any<T: Tokenizer>(tokenizer: &T, text: &str) -> TextQuery {
+        TextQuery::Or(tokenizer.tokenize(text).into_iter().map(TextQuery::Token).collect())
+    }
+}
+
+/// The ids of matching documents, in increasing order.
+type Postings<'t, E> = Box<dyn Iterator<Item = Result<u64, E>> + 't>;
+
+/// An inverted index stored in a table of a [`Store`], see the [module documentation](self).
+pub struct FullText<'s, S: Store + 's, T> {
+    postings: TableOf<'s, S>,
+    tokenizer: T,
+}
+
+impl<'s, S: Store, T: Tokenizer> FullText<'s, S, T> {
+    /// Open the index stored in the `name` table of the store.
+    ///
+    /// The same tokenizer must be used every time the index is opened.
+    pub fn open(store: &'s S, name: &str, tokenizer: T) -> Result<Self, ErrorOf<S>> {
+        let postings = store.table(name, &S::Config::default())?;
+        Ok(FullText { postings, tokenizer })
+    }
+
+    pub fn tokenizer(&self) -> &T {
+        &self.tokenizer
+    }
+
+    /// Index the tokens of the text as being contained by the document.
+    pub fn add(&self, wtx: &mut WtxOf<S>, doc: u64, text: &str) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        for token in self.tokens(text) {
+            self.postings.put::<ByteSlice, Unit>(wtx, &posting_key(&token, doc), &())?;
+        }
+        Ok(())
+    }
+
+    /// Remove the document from the postings of the tokens of the text, the text
+    /// must be the one that was indexed for the document.
+    pub fn remove(&self, wtx: &mut WtxOf<S>, doc: u64, text: &str) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        for token in self.tokens(text) {
+            self.postings.delete::<ByteSlice>(wtx, &posting_key(&token, doc))?;
+        }
+        Ok(())
+    }
+
+    fn tokens(&self, text: &str) -> Vec<String> {
+        let mut tokens = self.tokenizer.tokenize(text);
+        tokens.sort_unstable();
+        tokens.dedup();
+        tokens
+    }
+
+    /// The ids of the documents matching the query, in increasing order.
+    pub fn search(&self, rtx: &RtxOf<S>, query: &TextQuery) -> Result<Vec<u64>, ErrorOf<S>> {
+        self.postings(rtx, query)?.collect()
+    }
+
+    /// The ids of the documents matching the query, in increasing order, read
+    /// lazily from the postings table.
+    pub fn postings<'t>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        query: &TextQuery,
+    ) -> Result<Postings<'t, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+    {
+        match query {
+            TextQuery::Token(token) => {
+                let start = posting_key(token, 0);
+                let end = posting_key(token, u64::MAX);
+                let range = (Bound::Included(&start[..]), Bound::Included(&end[..]));
+
+                let entries = self.postings.range::<ByteSlice, Unit, _>(rtx, &range)?;
+                Ok(Box::new(entries.map(|entry| entry.map(|(key, ())| posting_doc(&key)))))
+            }
+            TextQuery::And(queries) => {
+                let postings =
+                    queries.iter().map(|q| self.postings(rtx, q)).collect::<Result<_, _>>()?;
+                Ok(Box::new(Intersection { postings, next_min: Some(0) }))
+            }
+            TextQuery::Or(queries) => {
+                let postings =
+                    queries.iter().map(|q| self.postings(rtx, q)).collect::<Result<_, _>>()?;
+                Ok(Box::new(Union { postings, heads: None }))
+            }
+        }
+    }
+}
+
+/// The tokens are UTF-8 strings, they never contain `0xFF` and are separated from the ids by it.
+fn posting_key(token: &str, doc: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(token.len() + 9);
+    key.extend_from_slice(token.as_bytes());
+    key.push(0xFF);
+    key.extend_from_slice(&doc.to_be_bytes());
+    key
+}
+
+fn posting_doc(key: &[u8]) -> u64 {
+    let mut doc = [0; 8];
+    doc.copy_from_slice(&key[key.len() - 8..]);
+    u64::from_be_bytes(doc)
+}
+
+/// The ids found in all the postings.
+struct Intersection<'t, E> {
+    postings: Vec<Postings<'t, E>>,
+    /// The smallest id that can still be returned, `None` once exhausted.
+    next_min: Option<u64>,
+}
+
+impl<E> Iterator for Intersection<'_, E> {
+    type Item = Result<u64, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut target = self.next_min?;
+        if self.postings.is_empty() {
+            return None;
+        }
+
+        // Moves every postings in turn to the target, raising it when a postings skips it,
+        // until all the postings agree on it.
+        let mut agreeing = 0;
+        for i in (0..self.postings.len()).cycle() {
+            let id = loop {
+                match self.postings[i].next() {
+                    Some(Ok(id)) if id < target => continue,
+                    Some(Ok(id)) => break id,
+                    Some(Err(e)) => return Some(Err(e)),
+                    None => {
+                        self.next_min = None;
+                        return None;
+                    }
+                }
+            };
+
+            if id == target {
+                agreeing += 1;
+            } else {
+                target = id;
+                agreeing = 1;
+            }
+
+            if agreeing == self.postings.len() {
+                break;
+            }
+        }
+
+        self.next_min = target.checked_add(1);
+        Some(Ok(target))
+    }
+}
+
+/// The ids found in any of the postings.
+struct Union<'t, E> {
+    postings: Vec<Postings<'t, E>>,
+    /// The next id of every postings, read on the first call.
+    heads: Option<Vec<Option<u64>>>,
+}
+
+impl<E> Iterator for Union<'_, E> {
+    type Item = Result<u64, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.heads.is_none() {
+            let mut heads = Vec::with_capacity(self.postings.len());
+            for postings in &mut self.postings {
+                match postings.next().transpose() {
+                    Ok(head) => heads.push(head),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.heads = Some(heads);
+        }
+
+        let heads = self.heads.as_mut().unwrap();
+        let min = heads.iter().flatten().min().copied()?;
+
+        for (head, postings) in heads.iter_mut().zip(&mut self.postings) {
+            if *head == Some(min) {
+                match postings.next().transpose() {
+                    Ok(next) => *head = next,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+        }
+
+        Some(Ok(min))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn postings(ids: &'static [u64]) -> Postings<'static, ()> {
+        Box::new(ids.iter().copied().map(Ok))
+    }
+
+    #[test]
+    fn intersect_postings() {
+        let all = Intersection {
+            postings: vec![postings(&[1, 3, 5, 7, 9]), postings(&[2, 3, 7, 9]), postings(&[3, 9])],
+            next_min: Some(0),
+        };
+        assert_eq!(all.collect::<Result<Vec<_>, _>>(), Ok(vec![3, 9]));
+
+        let none =
+            Intersection { postings: vec![postings(&[1]), postings(&[])], next_min: Some(0) };
+        assert_eq!(none.collect::<Result<Vec<_>, _>>(), Ok(vec![]));
+    }
+
+    #[test]
+    fn unite_postings() {
+        let any = Union {
+            postings: vec![postings(&[1, 3, 5]), postings(&[]), postings(&[0, 3, u64::MAX])],
+            heads: None,
+        };
+        assert_eq!(any.collect::<Result<Vec<_>, _>>(), Ok(vec![0, 1, 3, 5, u64::MAX]));
+    }
+
+    #[test]
+    fn posting_keys_group_by_token() {
+        let mut keys = [
+            posting_key("ab", 1),
+            posting_key("a", u64::MAX),
+            posting_key("a", 2),
+            posting_key("b", 0),
+        ];
+        keys.sort();
+
+        // The separator sorts after any other byte, "ab" comes before "a" but never in between.
+        assert_eq!(
+            keys.iter().map(|key| posting_doc(key)).collect::<Vec<_>>(),
+            vec![1, 2, u64::MAX, 0]
+        );
+    }
+
+    #[test]
+    fn simple_tokenizer() {
+        assert_eq!(
+            SimpleTokenizer.tokenize("Hello, World! l'été"),
+            vec!["hello", "world", "l", "été"]
+        );
+    }
+}
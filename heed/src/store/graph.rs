@@ -0,0 +1,279 @@
+//! Directed graphs on top of any [`Store`], the edges between nodes stored as keys of a table.
+//!
+//! Every edge is keyed by its source followed by its destination, both big endian, the
+//! out-neighbors of a node are therefore a range of keys. A second table stores the reversed
+//! edges for the in-neighbors, both tables are updated in the same write transaction.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::graph::{Direction, Graph};
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let dependencies = Graph::open(&env, "dependencies")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! for &(src, dst) in &[(1, 2), (1, 3), (2, 4), (3, 4)] {
+//!     dependencies.add_edge(&mut wtxn, src, dst)?;
+//! }
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let needed: Result<Vec<_>, _> = dependencies.bfs(&rtxn, 1, Direction::Outgoing).collect();
+//! assert_eq!(needed?, vec![1, 2, 3, 4]);
+//! let dependents: Result<Vec<_>, _> = dependencies.dfs(&rtxn, 4, Direction::Incoming).collect();
+//! assert_eq!(dependents?, vec![4, 2, 1, 3]);
+//! # Ok(()) }
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+use std::iter;
+use std::ops::Bound;
+
+use heed_types::{ByteSlice, Unit};
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+/// A node of a graph.
+pub type NodeId = u64;
+
+/// Nodes of a graph, read lazily from a store.
+pub type Nodes<'t, E> = Box<dyn Iterator<Item = Result<NodeId, E>> + 't>;
+
+/// The edges a graph is walked along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// From the source of the edges to their destination.
+    Outgoing,
+    /// From the destination of the edges to their source.
+    Incoming,
+}
+
+/// A directed graph stored in tables of a [`Store`], see the [module documentation](self).
+pub struct Graph<'s, S: Store + 's> {
+    edges: TableOf<'s, S>,
+    reversed: TableOf<'s, S>,
+}
+
+impl<'s, S: Store> Graph<'s, S> {
+    /// Open the graph stored in the `name` table of the store, the reversed edges
+    /// are stored in the `name:reversed` table.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let cfg = S::Config::default();
+        let edges = store.table(name, &cfg)?;
+        let reversed = store.table(&format!("{}:reversed", name), &cfg)?;
+        Ok(Graph { edges, reversed })
+    }
+
+    /// Add an edge from `src` to `dst`, adding an existing edge does nothing.
+    pub fn add_edge(&self, wtx: &mut WtxOf<S>, src: NodeId, dst: NodeId) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        self.edges.put::<ByteSlice, Unit>(wtx, &edge_key(src, dst), &())?;
+        self.reversed.put::<ByteSlice, Unit>(wtx, &edge_key(dst, src), &())
+    }
+
+    /// Remove the edge from `src` to `dst`, if any.
+    pub fn remove_edge(
+        &self,
+        wtx: &mut WtxOf<S>,
+        src: NodeId,
+        dst: NodeId,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        self.edges.delete::<ByteSlice>(wtx, &edge_key(src, dst))?;
+        self.reversed.delete::<ByteSlice>(wtx, &edge_key(dst, src))
+    }
+
+    /// Remove all the edges from and to the node.
+    pub fn remove_node(&self, wtx: &mut WtxOf<S>, node: NodeId) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let outgoing =
+            self.neighbors(wtx, node, Direction::Outgoing)?.collect::<Result<Vec<_>, _>>()?;
+        let incoming =
+            self.neighbors(wtx, node, Direction::Incoming)?.collect::<Result<Vec<_>, _>>()?;
+
+        for dst in outgoing {
+            self.remove_edge(wtx, node, dst)?;
+        }
+        for src in incoming {
+            self.remove_edge(wtx, src, node)?;
+        }
+        Ok(())
+    }
+
+    pub fn has_edge(&self, rtx: &RtxOf<S>, src: NodeId, dst: NodeId) -> Result<bool, ErrorOf<S>> {
+        self.edges.get::<ByteSlice, Unit>(rtx, &edge_key(src, dst)).map(|edge| edge.is_some())
+    }
+
+    /// The nodes with an edge from the node, in increasing order.
+    pub fn out_neighbors<'t>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        node: NodeId,
+    ) -> Result<Nodes<'t, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+    {
+        self.neighbors(rtx, node, Direction::Outgoing)
+    }
+
+    /// The nodes with an edge to the node, in increasing order.
+    pub fn in_neighbors<'t>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        node: NodeId,
+    ) -> Result<Nodes<'t, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+    {
+        self.neighbors(rtx, node, Direction::Incoming)
+    }
+
+    /// The neighbors of the node along the edges in the given direction, in increasing order.
+    pub fn neighbors<'t>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        node: NodeId,
+        direction: Direction,
+    ) -> Result<Nodes<'t, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+    {
+        let table = match direction {
+            Direction::Outgoing => &self.edges,
+            Direction::Incoming => &self.reversed,
+        };
+
+        let start = edge_key(node, 0);
+        let end = edge_key(node, NodeId::MAX);
+        let range = (Bound::Included(&start[..]), Bound::Included(&end[..]));
+
+        let edges = table.range::<ByteSlice, Unit, _>(rtx, &range)?;
+        Ok(Box::new(edges.map(|edge| edge.map(|(key, ())| edge_dst(&key)))))
+    }
+
+    /// The nodes reachable from `start`, itself included, in breadth first order.
+    ///
+    /// The neighbors of a node are read when the node is returned.
+    pub fn bfs<'t>(
+        &'t self,
+        rtx: &'t RtxOf<S>,
+        start: NodeId,
+        direction: Direction,
+    ) -> Nodes<'t, ErrorOf<S>> {
+        traversal(start, Order::BreadthFirst, move |node| {
+            self.neighbors(rtx, node, direction)?.collect()
+        })
+    }
+
+    /// The nodes reachable from `start`, itself included, in depth first order.
+    ///
+    /// The neighbors of a node are read when the node is returned.
+    pub fn dfs<'t>(
+        &'t self,
+        rtx: &'t RtxOf<S>,
+        start: NodeId,
+        direction: Direction,
+    ) -> Nodes<'t, ErrorOf<S>> {
+        traversal(start, Order::DepthFirst, move |node| {
+            self.neighbors(rtx, node, direction)?.collect()
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Order {
+    BreadthFirst,
+    DepthFirst,
+}
+
+/// Walks the nodes reachable from `start`, every node is returned once, an error stops the walk.
+fn traversal<'t, E, F>(start: NodeId, order: Order, mut neighbors: F) -> Nodes<'t, E>
+where
+    F: FnMut(NodeId) -> Result<Vec<NodeId>, E> + 't,
+{
+    // The queue of a breadth first walk, the stack of a depth first one.
+    let mut pending = VecDeque::from(vec![start]);
+    let mut visited = HashSet::new();
+
+    Box::new(iter::from_fn(move || loop {
+        let node = match order {
+            Order::BreadthFirst => pending.pop_front()?,
+            Order::DepthFirst => pending.pop_back()?,
+        };
+        if !visited.insert(node) {
+            continue;
+        }
+
+        let next = match neighbors(node) {
+            Ok(next) => next.into_iter().filter(|n| !visited.contains(n)),
+            Err(e) => {
+                pending.clear();
+                return Some(Err(e));
+            }
+        };
+        match order {
+            Order::BreadthFirst => pending.extend(next),
+            // Pushed in reverse so that the smallest neighbor is visited first.
+            Order::DepthFirst => pending.extend(next.rev()),
+        }
+
+        return Some(Ok(node));
+    }))
+}
+
+fn edge_key(src: NodeId, dst: NodeId) -> [u8; 16] {
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&src.to_be_bytes());
+    key[8..].copy_from_slice(&dst.to_be_bytes());
+    key
+}
+
+fn edge_dst(key: &[u8]) -> NodeId {
+    let mut dst = [0; 8];
+    dst.copy_from_slice(&key[8..16]);
+    NodeId::from_be_bytes(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(order: Order, start: NodeId) -> Result<Vec<NodeId>, ()> {
+        // 1 -> 2 -> 4 -> 1, 1 -> 3 -> 4, 3 -> 5
+        let edges: &[(NodeId, NodeId)] = &[(1, 2), (1, 3), (2, 4), (3, 4), (3, 5), (4, 1)];
+        let neighbors =
+            |node| Ok(edges.iter().filter(|(src, _)| *src == node).map(|(_, dst)| *dst).collect());
+        traversal(start, order, neighbors).collect()
+    }
+
+    #[test]
+    fn walk_breadth_first() {
+        assert_eq!(walk(Order::BreadthFirst, 1), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(walk(Order::BreadthFirst, 5), Ok(vec![5]));
+    }
+
+    #[test]
+    fn walk_depth_first() {
+        assert_eq!(walk(Order::DepthFirst, 1), Ok(vec![1, 2, 4, 3, 5]));
+        assert_eq!(walk(Order::DepthFirst, 3), Ok(vec![3, 4, 1, 2, 5]));
+    }
+
+    #[test]
+    fn stop_walking_on_error() {
+        let neighbors = |node| if node == 2 { Err(()) } else { Ok(vec![node + 1]) };
+        let nodes: Vec<_> = traversal(0, Order::DepthFirst, neighbors).collect();
+        assert_eq!(nodes, vec![Ok(0), Ok(1), Err(())]);
+    }
+}
@@ -0,0 +1,230 @@
+//! A hybrid logical clock persisted in a table of any [`Store`], giving the events written
+//! in write transactions timestamps that increase across restarts.
+//!
+//! A [`Timestamp`] is the wall clock time, in milliseconds, followed by a logical counter
+//! that orders the events of the same millisecond, or the events that follow a step back of
+//! the wall clock. The last timestamp is stored in the write transaction that takes the next
+//! one, a transaction that is aborted gives its timestamp back. Timestamps received from
+//! other processes are merged with [`Clock::observe`], the timestamps taken after it are
+//! greater than the received one. A clock that reached the greatest timestamp, which only
+//! a received timestamp can make it do, fails with [`Exhausted`](ClockError::Exhausted).
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::hlc::{Clock, Timestamp};
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let clock = Clock::open(&env, "clock")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! let first = clock.now(&mut wtxn)?;
+//! let second = clock.now(&mut wtxn)?;
+//! assert!(first < second);
+//!
+//! // A timestamp from a process whose wall clock is ahead.
+//! let remote = Timestamp::new(second.physical() + 60_000, 7);
+//! assert!(clock.observe(&mut wtxn, remote)? > remote);
+//! assert!(clock.now(&mut wtxn)? > remote);
+//! wtxn.commit()?;
+//! # Ok(()) }
+//! ```
+
+use std::convert::TryInto;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{error, fmt};
+
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+const CLOCK_KEY: &[u8] = b"clock";
+const LOGICAL_BITS: u32 = 16;
+
+/// A timestamp of a hybrid logical clock, the milliseconds since the unix epoch
+/// on 48 bits followed by a logical counter on 16 bits.
+///
+/// The big endian bytes of [`Timestamp::to_u64`] sort like the timestamps.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(u64);
+
+impl Timestamp {
+    /// The timestamp of a millisecond and a logical counter, the milliseconds
+    /// are truncated to 48 bits.
+    pub fn new(physical: u64, logical: u16) -> Timestamp {
+        Timestamp(physical << LOGICAL_BITS | logical as u64)
+    }
+
+    pub fn from_u64(timestamp: u64) -> Timestamp {
+        Timestamp(timestamp)
+    }
+
+    pub fn to_u64(self) -> u64 {
+        self.0
+    }
+
+    /// The milliseconds since the unix epoch.
+    pub fn physical(self) -> u64 {
+        self.0 >> LOGICAL_BITS
+    }
+
+    /// The counter ordering the timestamps of the same millisecond.
+    pub fn logical(self) -> u16 {
+        self.0 as u16
+    }
+
+    /// The smallest timestamp greater than this one and than the wall clock, the
+    /// logical counter overflows into the milliseconds. `None` for the greatest timestamp.
+    fn tick(self, wall: u64) -> Option<Timestamp> {
+        if wall > self.physical() {
+            Some(Timestamp::new(wall, 0))
+        } else {
+            self.0.checked_add(1).map(Timestamp)
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.physical(), self.logical())
+    }
+}
+
+/// An error of [`Clock::now`] and [`Clock::observe`].
+#[derive(Debug)]
+pub enum ClockError<E> {
+    /// An error of the store.
+    Store(E),
+    /// The clock reached the greatest timestamp, there is no greater one to take.
+    Exhausted,
+}
+
+impl<E: fmt::Display> fmt::Display for ClockError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClockError::Store(error) => write!(f, "{}", error),
+            ClockError::Exhausted => f.write_str("the timestamps of the clock are exhausted"),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for ClockError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ClockError::Store(error) => Some(error),
+            ClockError::Exhausted => None,
+        }
+    }
+}
+
+impl<E> From<E> for ClockError<E> {
+    fn from(error: E) -> ClockError<E> {
+        ClockError::Store(error)
+    }
+}
+
+/// A hybrid logical clock stored in a table of a [`Store`].
+///
+/// See the [module documentation](self).
+pub struct Clock<'s, S: Store + 's> {
+    table: TableOf<'s, S>,
+}
+
+impl<'s, S: Store> Clock<'s, S> {
+    /// Open the clock stored in the `name` table of the store.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let table = store.table(name, &S::Config::default())?;
+        Ok(Clock { table })
+    }
+
+    /// The last timestamp taken, or observed, by the clock.
+    pub fn last(&self, txn: &RtxOf<S>) -> Result<Timestamp, ErrorOf<S>> {
+        let last = self.table.get::<ByteSlice, ByteSlice>(txn, CLOCK_KEY)?;
+        Ok(last.and_then(|last| decode(&last)).unwrap_or_default())
+    }
+
+    /// Takes a timestamp greater than the wall clock and than the
+    /// timestamps previously taken or observed.
+    pub fn now(&self, txn: &mut WtxOf<S>) -> Result<Timestamp, ClockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        self.advance(txn, Timestamp::default())
+    }
+
+    /// Takes a timestamp greater than a `remote` timestamp received from another process,
+    /// the wall clock and the timestamps previously taken or observed.
+    pub fn observe(
+        &self,
+        txn: &mut WtxOf<S>,
+        remote: Timestamp,
+    ) -> Result<Timestamp, ClockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        self.advance(txn, remote)
+    }
+
+    fn advance(
+        &self,
+        txn: &mut WtxOf<S>,
+        remote: Timestamp,
+    ) -> Result<Timestamp, ClockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        let last = self.table.get_for_update::<ByteSlice, ByteSlice>(txn, CLOCK_KEY)?;
+        let last = last.and_then(|last| decode(&last)).unwrap_or_default();
+        let now = last.max(remote).tick(wall_clock()).ok_or(ClockError::Exhausted)?;
+        self.table.raw_put(txn, CLOCK_KEY, &now.to_u64().to_be_bytes())?;
+        Ok(now)
+    }
+}
+
+fn decode(bytes: &[u8]) -> Option<Timestamp> {
+    bytes.try_into().ok().map(|bytes| Timestamp(u64::from_be_bytes(bytes)))
+}
+
+fn wall_clock() -> u64 {
+    // A wall clock before the epoch is as good as a clock stuck at the epoch.
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_follows_the_wall_clock_or_counts() {
+        let last = Timestamp::new(100, 3);
+        assert_eq!(last.tick(101), Some(Timestamp::new(101, 0)));
+        assert_eq!(last.tick(100), Some(Timestamp::new(100, 4)));
+        assert_eq!(last.tick(50), Some(Timestamp::new(100, 4)));
+        assert_eq!(Timestamp::new(100, u16::MAX).tick(50), Some(Timestamp::new(101, 0)));
+        assert_eq!(Timestamp::from_u64(u64::MAX).tick(50), None);
+    }
+
+    #[test]
+    fn the_greatest_remote_timestamp_exhausts_the_clock() {
+        use crate::store::Transaction;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let clock = Clock::open(&env, "clock").unwrap();
+
+        let mut wtxn = env.wtx().unwrap();
+        let remote = Timestamp::from_u64(u64::MAX);
+        assert!(matches!(clock.observe(&mut wtxn, remote), Err(ClockError::Exhausted)));
+        let now = clock.now(&mut wtxn).unwrap();
+        wtxn.commit().unwrap();
+
+        assert!(now < remote);
+        assert_eq!(clock.last(&env.rtx().unwrap()).unwrap(), now);
+    }
+}
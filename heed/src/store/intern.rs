@@ -0,0 +1,230 @@
+//! Compact ids for byte strings, to store long and repeated keys as ids in other tables.
+//!
+//! An [`Interner`] keeps two tables consistent: the ids of the byte strings in `name:ids`,
+//! and the byte strings of the ids in `name:bytes`. The ids are allocated from 0 by the
+//! [`Sequence`] of the interner and never reused, an interned byte string keeps its id.
+//!
+//! The committed ids are cached in memory. The ids interned by a write transaction are only
+//! cached once they are read after it committed: they are forgotten if it is aborted. The
+//! transactions without [`Extensions`](crate::Extensions) can't track them, an interner stops
+//! caching ids once one of them interned a byte string.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::intern::Interner;
+//! use heed::store::{Store, Transaction, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().max_dbs(10).open(dir.path())?;
+//! let urls = Interner::open(&env, "urls", 10_000)?;
+//!
+//! let mut wtx = env.wtx()?;
+//! let id = urls.intern(&mut wtx, b"https://example.com/a/long/path")?;
+//! assert_eq!(urls.intern(&mut wtx, b"https://example.com/a/long/path")?, id);
+//! wtx.commit()?;
+//!
+//! let rtx = env.rtx()?;
+//! assert_eq!(urls.resolve(&rtx, id)?.as_deref(), Some(&b"https://example.com/a/long/path"[..]));
+//! # Ok(()) }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use heed_types::{ByteSlice, Composite};
+
+use crate::store::sequence::{Sequence, SequenceError};
+use crate::store::{ErrorOf, RtxOf, Store, Transaction, Typed, WritableStore, WtxOf};
+
+/// Byte strings mapped to compact ids, see the [module](self).
+pub struct Interner<'s, S: Store> {
+    name: String,
+    ids: Typed<'s, S, ByteSlice, Composite<u64>>,
+    bytes: Typed<'s, S, Composite<u64>, ByteSlice>,
+    sequence: Sequence<'s, S>,
+    cache: Mutex<Cache>,
+    /// Set once a transaction without extensions interned a byte string.
+    untracked: AtomicBool,
+}
+
+/// The committed ids, cleared when it holds `capacity` of them.
+struct Cache {
+    ids: HashMap<Vec<u8>, u64>,
+    bytes: HashMap<u64, Vec<u8>>,
+    capacity: usize,
+}
+
+impl Cache {
+    fn insert(&mut self, bytes: &[u8], id: u64) {
+        if self.ids.len() >= self.capacity {
+            self.ids.clear();
+            self.bytes.clear();
+        }
+        if self.capacity > 0 {
+            self.ids.insert(bytes.to_vec(), id);
+            self.bytes.insert(id, bytes.to_vec());
+        }
+    }
+}
+
+/// The ids interned by a write transaction, by interner, they aren't cached before it commits.
+#[derive(Default)]
+struct Uncommitted(HashSet<(String, u64)>);
+
+impl<'s, S: Store> Interner<'s, S> {
+    /// Opens the tables of the interner `name`, creating them if needed, the
+    /// `capacity` is the number of ids cached.
+    pub fn open(store: &'s S, name: &str, capacity: usize) -> Result<Interner<'s, S>, S::Error> {
+        let ids = store.typed(format!("{}:ids", name), &S::Config::default())?;
+        let bytes = store.typed(format!("{}:bytes", name), &S::Config::default())?;
+        let sequence = Sequence::open(store, name)?;
+        let cache = Cache { ids: HashMap::new(), bytes: HashMap::new(), capacity };
+        Ok(Interner {
+            name: name.to_owned(),
+            ids,
+            bytes,
+            sequence,
+            cache: Mutex::new(cache),
+            untracked: AtomicBool::new(false),
+        })
+    }
+
+    /// The id of the byte string, `None` if it wasn't interned.
+    pub fn id(&self, txn: &RtxOf<S>, bytes: &[u8]) -> Result<Option<u64>, ErrorOf<S>> {
+        if let Some(&id) = self.cache.lock().unwrap().ids.get(bytes) {
+            return Ok(Some(id));
+        }
+        let id = self.ids.get(txn, bytes)?;
+        if let Some(id) = id {
+            self.cache_committed(txn, bytes, id);
+        }
+        Ok(id)
+    }
+
+    /// The byte string of the id, `None` if no byte string has this id.
+    pub fn resolve(&self, txn: &RtxOf<S>, id: u64) -> Result<Option<Vec<u8>>, ErrorOf<S>> {
+        if let Some(bytes) = self.cache.lock().unwrap().bytes.get(&id) {
+            return Ok(Some(bytes.clone()));
+        }
+        let bytes = self.bytes.get(txn, &id)?;
+        if let Some(bytes) = &bytes {
+            self.cache_committed(txn, bytes, id);
+        }
+        Ok(bytes)
+    }
+
+    /// Caches an id read by the transaction, unless it was interned by the transaction.
+    fn cache_committed(&self, txn: &RtxOf<S>, bytes: &[u8], id: u64) {
+        if self.untracked.load(Ordering::Acquire) {
+            return;
+        }
+        let uncommitted = txn.extensions().get::<Uncommitted>();
+        if !uncommitted.is_some_and(|ids| ids.0.contains(&(self.name.clone(), id))) {
+            self.cache.lock().unwrap().insert(bytes, id);
+        }
+    }
+
+    /// The number of cached ids.
+    pub fn cached(&self) -> usize {
+        self.cache.lock().unwrap().ids.len()
+    }
+}
+
+impl<'s, S: WritableStore> Interner<'s, S> {
+    /// The id of the byte string, a new one is allocated if it wasn't interned.
+    pub fn intern(
+        &self,
+        txn: &mut WtxOf<S>,
+        bytes: &[u8],
+    ) -> Result<u64, SequenceError<ErrorOf<S>>> {
+        if let Some(id) = self.id(txn, bytes)? {
+            return Ok(id);
+        }
+
+        let id = self.bytes.alloc_id(txn, &self.sequence)?;
+        self.bytes.put(txn, &id, bytes)?;
+        self.ids.put(txn, bytes, &id)?;
+        match txn.extensions_mut() {
+            Some(extensions) => {
+                let uncommitted = extensions.get_or_insert_with(Uncommitted::default);
+                uncommitted.0.insert((self.name.clone(), id));
+            }
+            None => self.untracked.store(true, Ordering::Release),
+        }
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn aborted_ids_arent_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(3).open(dir.path()).unwrap();
+        let interner = Interner::open(&env, "words", 10).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(interner.intern(&mut wtx, b"aborted").unwrap(), 0);
+        assert_eq!(interner.id(&wtx, b"aborted").unwrap(), Some(0));
+        drop(wtx);
+        assert_eq!(interner.cached(), 0);
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(interner.intern(&mut wtx, b"hello").unwrap(), 0);
+        assert_eq!(interner.intern(&mut wtx, b"world").unwrap(), 1);
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        assert_eq!(interner.id(&rtx, b"aborted").unwrap(), None);
+        assert_eq!(interner.resolve(&rtx, 1).unwrap().as_deref(), Some(&b"world"[..]));
+        assert_eq!(interner.id(&rtx, b"world").unwrap(), Some(1));
+        assert_eq!(interner.cached(), 1);
+    }
+
+    #[test]
+    fn full_caches_are_cleared() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(3).open(dir.path()).unwrap();
+        let interner = Interner::open(&env, "words", 2).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        for word in [&b"a"[..], b"b", b"c"] {
+            interner.intern(&mut wtx, word).unwrap();
+        }
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        assert_eq!(interner.id(&rtx, b"a").unwrap(), Some(0));
+        assert_eq!(interner.id(&rtx, b"b").unwrap(), Some(1));
+        assert_eq!(interner.cached(), 2);
+        // The third id doesn't fit, the cache is cleared before it is cached.
+        assert_eq!(interner.resolve(&rtx, 2).unwrap().as_deref(), Some(&b"c"[..]));
+        assert_eq!(interner.cached(), 1);
+    }
+
+    #[test]
+    fn ids_are_resolved_after_a_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(3).open(dir.path()).unwrap();
+        let interner = Interner::open(&env, "words", 1).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(interner.intern(&mut wtx, b"hello").unwrap(), 0);
+        assert_eq!(interner.intern(&mut wtx, b"world").unwrap(), 1);
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        assert_eq!(interner.resolve(&rtx, 0).unwrap().as_deref(), Some(&b"hello"[..]));
+        assert_eq!(interner.resolve(&rtx, 1).unwrap().as_deref(), Some(&b"world"[..]));
+        // Each resolution cleared the id cached by the previous one, they are read again.
+        assert_eq!(interner.resolve(&rtx, 0).unwrap().as_deref(), Some(&b"hello"[..]));
+        assert_eq!(interner.id(&rtx, b"world").unwrap(), Some(1));
+        assert_eq!(interner.resolve(&rtx, 2).unwrap(), None);
+        assert_eq!(interner.cached(), 1);
+    }
+}
@@ -0,0 +1,296 @@
+//! A lease on a [`Store`] electing the single process of a host that is allowed to write.
+//!
+//! The lease is a record of a dedicated table naming its holder, a token and an expiry. A
+//! process that holds the lease renews it before it expires, a process that crashed or stalls
+//! loses it once it expires and another one can take it over, with a greater token. Reading,
+//! checking and writing the record is guarded by an exclusive lock on a file, the check is
+//! therefore atomic across the processes sharing the store even when the store itself doesn't
+//! serialize its write transactions across processes.
+//!
+//! A lease doesn't prevent anything by itself, writers must check that they still
+//! hold it with [`WriterLock::is_held`] in the write transaction they are about to commit.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use std::time::Duration;
+//!
+//! use heed::store::lock::WriterLock;
+//! use heed::store::{Store, WritableStore};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let leader = WriterLock::open(&env, "leader", dir.path().join("leader.lock"))?;
+//! let standby = WriterLock::open(&env, "leader", dir.path().join("leader.lock"))?;
+//!
+//! let mut lease = leader.try_acquire(Duration::from_secs(10))?.expect("the lease is free");
+//! assert!(standby.try_acquire(Duration::from_secs(10))?.is_none());
+//!
+//! let wtxn = env.wtx()?;
+//! if leader.is_held(&wtxn, &lease)? {
+//!     // write while the lease is held
+//! }
+//! wtxn.commit()?;
+//!
+//! assert!(leader.renew(&mut lease, Duration::from_secs(10))?);
+//! leader.release(lease)?;
+//! assert!(standby.try_acquire(Duration::from_secs(10))?.is_some());
+//! # Ok(()) }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{error, fmt, io, process, thread};
+
+use fs2::FileExt;
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+const LEASE_KEY: &[u8] = b"lease";
+
+/// An error of a [`WriterLock`], either from the store or from the lock file.
+#[derive(Debug)]
+pub enum LockError<E> {
+    Store(E),
+    Io(io::Error),
+}
+
+impl<E: fmt::Display> fmt::Display for LockError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LockError::Store(error) => write!(f, "{}", error),
+            LockError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl<E: error::Error> error::Error for LockError<E> {}
+
+/// A lease taken by a holder, valid until it expires or is taken over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lease {
+    holder: u64,
+    token: u64,
+    /// Milliseconds since the UNIX epoch.
+    expires_at: u64,
+}
+
+impl Lease {
+    /// The id of the [`WriterLock`] holding the lease.
+    pub fn holder(&self) -> u64 {
+        self.holder
+    }
+
+    /// A number that is greater every time the lease changes of holder,
+    /// it can be stored along the writes to fence off previous holders.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    pub fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.expires_at)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= now_millis()
+    }
+
+    fn is_same(&self, other: &Lease) -> bool {
+        self.holder == other.holder && self.token == other.token
+    }
+
+    fn to_bytes(self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        bytes[..8].copy_from_slice(&self.holder.to_be_bytes());
+        bytes[8..16].copy_from_slice(&self.token.to_be_bytes());
+        bytes[16..].copy_from_slice(&self.expires_at.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Lease> {
+        if bytes.len() != 24 {
+            return None;
+        }
+        let mut words = bytes.chunks_exact(8).map(|word| {
+            let mut array = [0; 8];
+            array.copy_from_slice(word);
+            u64::from_be_bytes(array)
+        });
+        Some(Lease { holder: words.next()?, token: words.next()?, expires_at: words.next()? })
+    }
+}
+
+/// The lease of a table of a [`Store`], see the [module documentation](self).
+///
+/// Every `WriterLock` is a different holder, even in the same process.
+pub struct WriterLock<'s, S: Store + 's> {
+    store: &'s S,
+    leases: TableOf<'s, S>,
+    file: File,
+    holder: u64,
+}
+
+impl<'s, S: Store> WriterLock<'s, S> {
+    /// Open the lease stored in the `name` table of the store, guarded by the file at `path`.
+    ///
+    /// All the processes must use the same table and the same lock file, which is created
+    /// if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(
+        store: &'s S,
+        name: &str,
+        path: P,
+    ) -> Result<Self, LockError<ErrorOf<S>>> {
+        let leases = store.table(name, &S::Config::default()).map_err(LockError::Store)?;
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(LockError::Io)?;
+
+        Ok(WriterLock { store, leases, file, holder: new_holder() })
+    }
+
+    /// The id of this holder, unique on the host while the process lives.
+    pub fn holder(&self) -> u64 {
+        self.holder
+    }
+
+    /// The lease as last written, it may be expired.
+    pub fn current(&self, rtx: &RtxOf<S>) -> Result<Option<Lease>, ErrorOf<S>> {
+        let bytes = self.leases.get::<ByteSlice, ByteSlice>(rtx, LEASE_KEY)?;
+        Ok(bytes.and_then(|bytes| Lease::from_bytes(&bytes)))
+    }
+
+    /// Whether the lease is still held, to be checked in the write transaction
+    /// that is about to be committed.
+    pub fn is_held(&self, rtx: &RtxOf<S>, lease: &Lease) -> Result<bool, ErrorOf<S>> {
+        match self.current(rtx)? {
+            Some(current) => Ok(current.is_same(lease) && !current.is_expired()),
+            None => Ok(false),
+        }
+    }
+
+    /// Take the lease for `ttl` if it is free, expired or already held by this holder,
+    /// returns `None` if another holder has it.
+    ///
+    /// It opens its own write transaction, it must not be called while
+    /// a write transaction of the store is open on the same thread.
+    pub fn try_acquire(&self, ttl: Duration) -> Result<Option<Lease>, LockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        self.locked(|wtx| {
+            let token = match self.current(wtx)? {
+                Some(current) if current.holder == self.holder && !current.is_expired() => {
+                    current.token
+                }
+                Some(current) if !current.is_expired() => return Ok(None),
+                Some(current) => current.token + 1,
+                None => 1,
+            };
+
+            let lease = Lease { holder: self.holder, token, expires_at: expiry(ttl) };
+            self.leases.put::<ByteSlice, ByteSlice>(wtx, LEASE_KEY, &lease.to_bytes())?;
+            Ok(Some(lease))
+        })
+    }
+
+    /// Extend the lease for `ttl` from now, returns `false` if it was lost.
+    pub fn renew(&self, lease: &mut Lease, ttl: Duration) -> Result<bool, LockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        self.locked(|wtx| {
+            if !self.is_held(wtx, lease)? {
+                return Ok(false);
+            }
+
+            let renewed = Lease { expires_at: expiry(ttl), ..*lease };
+            self.leases.put::<ByteSlice, ByteSlice>(wtx, LEASE_KEY, &renewed.to_bytes())?;
+            *lease = renewed;
+            Ok(true)
+        })
+    }
+
+    /// Give the lease up so that another holder can take it right away.
+    pub fn release(&self, lease: Lease) -> Result<(), LockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        self.locked(|wtx| match self.current(wtx)? {
+            // The lease is expired rather than deleted, the next holder takes the next token.
+            Some(current) if current.is_same(&lease) => {
+                let released = Lease { expires_at: 0, ..lease };
+                self.leases.put::<ByteSlice, ByteSlice>(wtx, LEASE_KEY, &released.to_bytes())
+            }
+            _ => Ok(()),
+        })
+    }
+
+    /// Block until the lease can be taken, trying every `poll_interval`. This is the way
+    /// a standby process watches for the lease of a crashed or stalled holder to take over.
+    pub fn wait_for_takeover(
+        &self,
+        ttl: Duration,
+        poll_interval: Duration,
+    ) -> Result<Lease, LockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+    {
+        loop {
+            if let Some(lease) = self.try_acquire(ttl)? {
+                return Ok(lease);
+            }
+            thread::sleep(poll_interval);
+        }
+    }
+
+    /// Run `fun` in a write transaction while holding the lock file.
+    fn locked<R, F>(&self, fun: F) -> Result<R, LockError<ErrorOf<S>>>
+    where
+        S: WritableStore,
+        F: FnOnce(&mut WtxOf<S>) -> Result<R, ErrorOf<S>>,
+    {
+        FileExt::lock_exclusive(&self.file).map_err(LockError::Io)?;
+        let result = self.store.with_wtx(fun).map_err(LockError::Store);
+        let unlocked = FileExt::unlock(&self.file).map_err(LockError::Io);
+        result.and_then(|out| unlocked.map(|()| out))
+    }
+}
+
+/// The process id in the high bits, a counter of the holders of the process in the low ones.
+fn new_holder() -> u64 {
+    static HOLDERS: AtomicU32 = AtomicU32::new(0);
+    (u64::from(process::id()) << 32) | u64::from(HOLDERS.fetch_add(1, Ordering::Relaxed))
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |now| now.as_millis() as u64)
+}
+
+fn expiry(ttl: Duration) -> u64 {
+    now_millis().saturating_add(ttl.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_record_roundtrip() {
+        let lease =
+            Lease { holder: new_holder(), token: 7, expires_at: expiry(Duration::from_secs(0)) };
+        assert_eq!(Lease::from_bytes(&lease.to_bytes()), Some(lease));
+        assert_eq!(Lease::from_bytes(&lease.to_bytes()[..16]), None);
+        assert!(lease.is_expired());
+        assert_ne!(new_holder(), new_holder());
+    }
+}
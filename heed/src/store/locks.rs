@@ -0,0 +1,199 @@
+//! Advisory locks on ranges of keys, to serialize the logical operations of the tasks of a
+//! process on the same keys.
+//!
+//! A single write transaction is open at a time but the work of an operation is often staged
+//! outside of it, from values read in a read transaction: two tasks reading the same value
+//! and writing it back in turn lose the first write. A task holding a lock of a
+//! [`RangeLocks`] on the keys it reads and writes keeps the other tasks that lock an
+//! overlapping range waiting until it is done.
+//!
+//! The locks are advisory, they don't prevent any read or write of the store, and only work
+//! within a process: share the registry between the tasks, in an `Arc` for example, like the
+//! store. A task must not lock a range overlapping a range it holds, it would wait forever.
+//!
+//! ```
+//! use std::sync::Arc;
+//! use std::thread;
+//!
+//! use heed::store::locks::RangeLocks;
+//!
+//! let locks = Arc::new(RangeLocks::new());
+//!
+//! let alice = locks.lock(&(b"user:alice".to_vec()..=b"user:alice".to_vec()));
+//! assert!(locks.try_lock(&(b"user:a".to_vec()..b"user:b".to_vec())).is_none());
+//! assert!(locks.try_lock(&(b"user:bob".to_vec()..)).is_some());
+//!
+//! let other = Arc::clone(&locks);
+//! let waiting = thread::spawn(move || {
+//!     // waits until the lock of alice is released
+//!     let _all = other.lock(&(..));
+//! });
+//! drop(alice);
+//! waiting.join().unwrap();
+//! ```
+
+use std::ops::{Bound, RangeBounds};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A registry of the ranges of keys locked by the tasks of a process, see the
+/// [module documentation](self).
+#[derive(Default)]
+pub struct RangeLocks {
+    held: Mutex<Held>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct Held {
+    ranges: Vec<Locked>,
+    next_id: u64,
+}
+
+struct Locked {
+    id: u64,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+impl Held {
+    fn overlaps(&self, start: &Bound<Vec<u8>>, end: &Bound<Vec<u8>>) -> bool {
+        self.ranges
+            .iter()
+            .any(|locked| !ends_before(end, &locked.start) && !ends_before(&locked.end, start))
+    }
+
+    fn insert(&mut self, start: Bound<Vec<u8>>, end: Bound<Vec<u8>>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.ranges.push(Locked { id, start, end });
+        id
+    }
+}
+
+/// Whether the range ending at `end` ends before the range starting at `start` starts.
+fn ends_before(end: &Bound<Vec<u8>>, start: &Bound<Vec<u8>>) -> bool {
+    match (end, start) {
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => false,
+        (Bound::Included(end), Bound::Included(start)) => end < start,
+        (Bound::Included(end), Bound::Excluded(start))
+        | (Bound::Excluded(end), Bound::Included(start))
+        | (Bound::Excluded(end), Bound::Excluded(start)) => end <= start,
+    }
+}
+
+fn owned_bounds<R: RangeBounds<Vec<u8>>>(range: &R) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+impl RangeLocks {
+    pub fn new() -> RangeLocks {
+        RangeLocks::default()
+    }
+
+    /// Locks the range of keys, waits until no overlapping range is locked.
+    pub fn lock<R: RangeBounds<Vec<u8>>>(&self, range: &R) -> RangeGuard<'_> {
+        let (start, end) = owned_bounds(range);
+        let mut held = self.held.lock().unwrap();
+        while held.overlaps(&start, &end) {
+            held = self.released.wait(held).unwrap();
+        }
+        RangeGuard { locks: self, id: held.insert(start, end) }
+    }
+
+    /// Locks the range of keys, `None` if an overlapping range is locked.
+    pub fn try_lock<R: RangeBounds<Vec<u8>>>(&self, range: &R) -> Option<RangeGuard<'_>> {
+        let (start, end) = owned_bounds(range);
+        let mut held = self.held.lock().unwrap();
+        if held.overlaps(&start, &end) {
+            return None;
+        }
+        Some(RangeGuard { locks: self, id: held.insert(start, end) })
+    }
+
+    /// Locks the range of keys, `None` if an overlapping range is still locked after the timeout.
+    pub fn lock_timeout<R: RangeBounds<Vec<u8>>>(
+        &self,
+        range: &R,
+        timeout: Duration,
+    ) -> Option<RangeGuard<'_>> {
+        let (start, end) = owned_bounds(range);
+        // A timeout too long to be added to the clock waits without a deadline.
+        let deadline = Instant::now().checked_add(timeout);
+        let mut held = self.held.lock().unwrap();
+        while held.overlaps(&start, &end) {
+            held = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return None;
+                    }
+                    self.released.wait_timeout(held, deadline - now).unwrap().0
+                }
+                None => self.released.wait(held).unwrap(),
+            };
+        }
+        Some(RangeGuard { locks: self, id: held.insert(start, end) })
+    }
+
+    /// The number of locked ranges.
+    pub fn len(&self) -> usize {
+        self.held.lock().unwrap().ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A range of keys locked in a [`RangeLocks`], until it is dropped.
+#[must_use = "the range is unlocked when the guard is dropped"]
+pub struct RangeGuard<'l> {
+    locks: &'l RangeLocks,
+    id: u64,
+}
+
+impl Drop for RangeGuard<'_> {
+    fn drop(&mut self) {
+        let mut held = self.locks.held.lock().unwrap();
+        held.ranges.retain(|locked| locked.id != self.id);
+        drop(held);
+        self.locks.released.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_overlapping_ranges_conflict() {
+        let locks = RangeLocks::new();
+        let b = locks.lock(&(b"b".to_vec()..b"d".to_vec()));
+
+        assert!(locks.try_lock(&(b"a".to_vec()..b"b".to_vec())).is_some());
+        assert!(locks.try_lock(&(b"a".to_vec()..=b"b".to_vec())).is_none());
+        assert!(locks.try_lock(&(b"c".to_vec()..=b"c".to_vec())).is_none());
+        assert!(locks.try_lock(&(b"d".to_vec()..)).is_some());
+        assert!(locks.try_lock(&(..)).is_none());
+        assert!(locks.lock_timeout(&(..), Duration::from_millis(10)).is_none());
+
+        drop(b);
+        assert!(locks.is_empty());
+        assert!(locks.try_lock(&(..)).is_some());
+    }
+
+    #[test]
+    fn long_timeouts_wait_for_the_release() {
+        let locks = std::sync::Arc::new(RangeLocks::new());
+        let held = locks.lock(&(..));
+        let waiter = {
+            let locks = locks.clone();
+            std::thread::spawn(move || locks.lock_timeout(&(..), Duration::MAX).is_some())
+        };
+        std::thread::sleep(Duration::from_millis(20));
+        drop(held);
+        assert!(waiter.join().unwrap());
+        assert!(locks.is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! Append-only logs of any [`Store`], read by consumers that track their offset.
+//!
+//! The items of a [`Log`] are numbered by their offset, from 0, in the order they were
+//! appended. A consumer reads the items from its offset, stored with the log, and commits
+//! the offset of the next item to read in the same write transaction as its work. The items
+//! that every consumer read are deleted with [`Log::truncate_consumed`], the offsets of the
+//! deleted items are never reused.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::log::Log;
+//! use heed::store::{Store, Transaction, WritableStore};
+//! use heed::types::OwnedType;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().max_dbs(10).open(dir.path())?;
+//! let signups = Log::<_, OwnedType<u64>>::open(&env, "signups")?;
+//!
+//! let mut wtx = env.wtx()?;
+//! assert_eq!(signups.append(&mut wtx, &42)?, 0);
+//! assert_eq!(signups.append(&mut wtx, &7)?, 1);
+//! wtx.commit()?;
+//!
+//! let mut wtx = env.wtx()?;
+//! let offset = signups.consumer_offset(&wtx, "mailer")?;
+//! let unread: Result<Vec<_>, _> = signups.read_from(&wtx, offset)?.collect();
+//! assert_eq!(unread?, vec![(0, 42), (1, 7)]);
+//! signups.commit_offset(&mut wtx, "mailer", 2)?;
+//! assert_eq!(signups.truncate_consumed(&mut wtx)?, 2);
+//! wtx.commit()?;
+//! # Ok(()) }
+//! ```
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::{ByteSlice, Composite, DecodeIgnore, Str};
+
+use crate::store::{ErrorOf, RangeOf, RtxOf, Store, Table, Typed, WritableStore, WtxOf};
+
+const START_KEY: &[u8] = b"start";
+
+/// The items encoded by the `DC` codec appended to a table of a [`Store`], by offset.
+///
+/// See the [module documentation](self).
+pub struct Log<'s, S: Store + 's, DC> {
+    entries: Typed<'s, S, Composite<u64>, DC>,
+    offsets: Typed<'s, S, Str, Composite<u64>>,
+    meta: Typed<'s, S, ByteSlice, Composite<u64>>,
+}
+
+impl<'s, S: Store, DC> Log<'s, S, DC> {
+    /// Opens the log stored in the `name` table of the store, the offsets of its consumers
+    /// are stored in the `name:offsets` table and its first offset in the `name:meta` table.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let cfg = S::Config::default();
+        let entries = store.typed(name, &cfg)?;
+        let offsets = store.typed(format!("{}:offsets", name), &cfg)?;
+        let meta = store.typed(format!("{}:meta", name), &cfg)?;
+        Ok(Log { entries, offsets, meta })
+    }
+
+    /// The offset of the first item that wasn't truncated.
+    pub fn start_offset(&self, rtx: &RtxOf<S>) -> Result<u64, ErrorOf<S>> {
+        Ok(self.meta.get(rtx, START_KEY)?.unwrap_or(0))
+    }
+
+    /// The offset of the next item appended.
+    pub fn next_offset(&self, rtx: &RtxOf<S>) -> Result<u64, ErrorOf<S>> {
+        let mut last = self.entries.dyndb.rev_range::<Composite<u64>, DecodeIgnore, _>(rtx, &..)?;
+        match last.next().transpose()? {
+            Some((offset, ())) => Ok(offset + 1),
+            None => self.start_offset(rtx),
+        }
+    }
+
+    /// The items from the offset, with their offset, in the order they were appended.
+    pub fn read_from<'t>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        offset: u64,
+    ) -> Result<RangeOf<'s, 't, S, Composite<u64>, DC>, ErrorOf<S>>
+    where
+        DC: BytesDecode,
+    {
+        self.entries.range(rtx, &(offset..))
+    }
+
+    /// The offset of the next item the consumer reads, 0 if it never committed one.
+    pub fn consumer_offset(&self, rtx: &RtxOf<S>, consumer: &str) -> Result<u64, ErrorOf<S>> {
+        Ok(self.offsets.get(rtx, consumer)?.unwrap_or(0))
+    }
+}
+
+impl<'s, S: WritableStore, DC> Log<'s, S, DC> {
+    /// Appends the item to the log, returns its offset.
+    pub fn append(&self, wtx: &mut WtxOf<S>, item: &DC::DItem) -> Result<u64, ErrorOf<S>>
+    where
+        DC: BytesDecode + for<'a> BytesEncode<'a, EItem = <DC as BytesDecode>::DItem>,
+    {
+        let offset = self.next_offset(wtx)?;
+        self.entries.append(wtx, &offset, item)?;
+        Ok(offset)
+    }
+
+    /// Stores the offset of the next item the consumer reads.
+    pub fn commit_offset(
+        &self,
+        wtx: &mut WtxOf<S>,
+        consumer: &str,
+        offset: u64,
+    ) -> Result<(), ErrorOf<S>> {
+        self.offsets.put(wtx, consumer, &offset)
+    }
+
+    /// Forgets the consumer, its offset no longer holds back [`Log::truncate_consumed`].
+    pub fn remove_consumer(&self, wtx: &mut WtxOf<S>, consumer: &str) -> Result<(), ErrorOf<S>> {
+        self.offsets.delete(wtx, consumer)
+    }
+
+    /// Deletes the items before the offset, returns the number of deleted items. The
+    /// offsets of the deleted items aren't reused by the items appended after.
+    pub fn truncate_before(&self, wtx: &mut WtxOf<S>, offset: u64) -> Result<usize, ErrorOf<S>> {
+        let offset = offset.min(self.next_offset(wtx)?);
+        if offset <= self.start_offset(wtx)? {
+            return Ok(0);
+        }
+
+        let range =
+            self.entries.dyndb.range::<Composite<u64>, DecodeIgnore, _>(wtx, &(..offset))?;
+        let truncated: Vec<u64> =
+            range.map(|entry| entry.map(|(offset, ())| offset)).collect::<Result<_, _>>()?;
+        for offset in &truncated {
+            self.entries.delete(wtx, offset)?;
+        }
+        self.meta.put(wtx, START_KEY, &offset)?;
+        Ok(truncated.len())
+    }
+
+    /// Deletes the items read by every consumer, returns the number of deleted items.
+    pub fn truncate_consumed(&self, wtx: &mut WtxOf<S>) -> Result<usize, ErrorOf<S>> {
+        let mut consumed = None;
+        for entry in self.offsets.dyndb.range::<Str, Composite<u64>, _>(wtx, &..)? {
+            let (_, offset) = entry?;
+            consumed = Some(consumed.map_or(offset, |consumed: u64| consumed.min(offset)));
+        }
+        match consumed {
+            Some(offset) => self.truncate_before(wtx, offset),
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use heed_types::OwnedType;
+
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn truncated_offsets_arent_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(3).open(dir.path()).unwrap();
+        let log = Log::<_, OwnedType<u64>>::open(&env, "log").unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        for item in 0..3 {
+            log.append(&mut wtx, &item).unwrap();
+        }
+        log.commit_offset(&mut wtx, "slow", 1).unwrap();
+        log.commit_offset(&mut wtx, "fast", 3).unwrap();
+        assert_eq!(log.truncate_consumed(&mut wtx).unwrap(), 1);
+        log.remove_consumer(&mut wtx, "slow").unwrap();
+        assert_eq!(log.truncate_consumed(&mut wtx).unwrap(), 2);
+        wtx.commit().unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        assert_eq!(log.start_offset(&wtx).unwrap(), 3);
+        assert_eq!(log.append(&mut wtx, &3).unwrap(), 3);
+        assert_eq!(log.truncate_before(&mut wtx, 10).unwrap(), 1);
+        assert_eq!(log.append(&mut wtx, &4).unwrap(), 4);
+        let items: Result<Vec<_>, _> = log.read_from(&wtx, 0).unwrap().collect();
+        assert_eq!(items.unwrap(), vec![(4, 4)]);
+        wtx.commit().unwrap();
+    }
+}
@@ -1,44 +1,131 @@
 use std::ops::RangeBounds;
 
+use either::Either;
 use heed_traits::{BytesDecode, BytesEncode};
 
-use crate::store::{ErrorOf, RtxOf, Store, Table, Transaction, WtxOf};
-use crate::{Env, PolyDatabase, RoRange, RoRevRange, RoTxn, RwTxn};
+use crate::flags::Flags;
+use crate::mdb::error::mdb_result;
+use crate::mdb::ffi;
+use crate::store::{
+    Capabilities, ErrorOf, RtxOf, SizeHistogram, Store, Table, Transaction, WritableStore, WtxOf,
+};
+use crate::{Env, Extensions, Opened, PolyDatabase, RoCursor, RoRange, RoRevRange, RoTxn, RwTxn};
 
 impl Store for Env {
     type Error = crate::Error;
     type Rtx<'e> = RoTxn<'e>;
-    type Wtx<'e> = RwTxn<'e, 'e>;
     type Table<'store> = PolyDatabase;
     type Config = ();
 
-    fn table(&self, name: &str, _cfg: &Self::Config) -> Result<Self::Table<'_>, Self::Error> {
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        _cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
         let mut wtx = self.wtx()?;
-        let db = self.create_poly_database(&mut wtx, Some(name))?;
+        let db = self.create_poly_database(&mut wtx, Some(name.as_ref()))?;
         wtx.commit()?;
 
         Ok(db)
     }
 
+    fn open_or_create_table(
+        &self,
+        name: impl AsRef<str>,
+        _cfg: &Self::Config,
+    ) -> Result<Opened<Self::Table<'_>>, Self::Error> {
+        let mut wtx = self.wtx()?;
+        let db = self.open_or_create_poly_database(&mut wtx, Some(name.as_ref()))?;
+        wtx.commit()?;
+
+        Ok(db)
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let rtx = self.rtx()?;
+        let db = self.open_poly_database(&rtx, Some(name.as_ref()))?;
+        rtx.commit()?;
+
+        Ok(db)
+    }
+
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        self.database_exists(name.as_ref())
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        self.database_names()
+    }
+
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
         self.read_txn()
     }
 
+    fn capabilities(&self) -> Capabilities {
+        let flag = |flag| self.contains_flag(flag).unwrap_or(false);
+        let capabilities = Capabilities::APPEND_CHECK
+            | Capabilities::SNAPSHOT_READS
+            | Capabilities::NESTED_TXN
+            | Capabilities::DUPSORT
+            | Capabilities::PERSISTENT;
+
+        // Without syncs a system crash can corrupt the database.
+        #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+        let unsynced = flag(Flags::MdbNoSync);
+        #[cfg(all(feature = "mdbx", not(feature = "lmdb")))]
+        let unsynced = false;
+
+        if unsynced || (flag(Flags::MdbWriteMap) && flag(Flags::MdbMapAsync)) {
+            capabilities
+        } else {
+            capabilities | Capabilities::CRASH_SAFE
+        }
+    }
+}
+
+impl WritableStore for Env {
+    type Wtx<'e> = RwTxn<'e, 'e>;
+
     fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
         self.write_txn()
     }
+
+    fn create_table(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        _cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        self.create_poly_database(wtx, Some(name.as_ref()))
+    }
 }
 
 impl Transaction<Env> for RoTxn<'_> {
     fn commit(self) -> Result<(), ErrorOf<Env>> {
         RoTxn::commit(self)
     }
+
+    fn extensions(&self) -> &Extensions {
+        RoTxn::extensions(self)
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(RoTxn::extensions_mut(self))
+    }
 }
 
 impl Transaction<Env> for RwTxn<'_, '_> {
     fn commit(self) -> Result<(), ErrorOf<Env>> {
         RwTxn::commit(self)
     }
+
+    fn extensions(&self) -> &Extensions {
+        RoTxn::extensions(self)
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(RwTxn::extensions_mut(self))
+    }
 }
 
 impl<'store> Table<'store> for PolyDatabase {
@@ -88,6 +175,23 @@ impl<'store> Table<'store> for PolyDatabase {
         PolyDatabase::len(self, txn)
     }
 
+    fn is_empty<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<bool, ErrorOf<Self::Store>> {
+        PolyDatabase::is_empty(self, txn)
+    }
+
+    #[cfg(feature = "mdbx")]
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        PolyDatabase::estimate_range::<_, KC, _>(self, txn, range)
+    }
+
     fn put<'a, KC, DC>(
         &self,
         txn: &mut WtxOf<Self::Store>,
@@ -128,4 +232,50 @@ impl<'store> Table<'store> for PolyDatabase {
     fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
         PolyDatabase::clear(self, txn)
     }
+
+    fn raw_iter<'txn, E, F>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        mut visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
+
+        let mut cursor = RoCursor::new(txn, self.dbi).map_err(Either::Left)?;
+        while let Some((key, data)) = cursor.move_on_next().map_err(Either::Left)? {
+            visit(key, data).map_err(Either::Right)?;
+        }
+
+        Ok(())
+    }
+
+    /// Also counts the values stored in overflow pages, with LMDB.
+    fn size_histogram(
+        &self,
+        txn: &RtxOf<Self::Store>,
+    ) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        assert_eq!(self.env_ident, txn.env.env_mut_ptr() as usize);
+
+        let mut stat = std::mem::MaybeUninit::uninit();
+        unsafe { mdb_result(ffi::mdb_env_stat(txn.env.env_mut_ptr(), stat.as_mut_ptr()))? };
+        let page_size = unsafe { stat.assume_init() }.ms_psize as usize;
+
+        let mut histogram = SizeHistogram::default();
+        let mut overflow = Some((0, 0));
+        let mut cursor = RoCursor::new(txn, self.dbi)?;
+        while let Some((key, data)) = cursor.move_on_next()? {
+            histogram.record(key.len(), data.len());
+            overflow = match (overflow, ffi::overflow_pages(page_size, key.len(), data.len())) {
+                (Some((values, pages)), Some(0)) => Some((values, pages)),
+                (Some((values, pages)), Some(count)) => Some((values + 1, pages + count)),
+                _ => None,
+            };
+        }
+
+        histogram.overflow_values = overflow.map(|(values, _)| values);
+        histogram.overflow_pages = overflow.map(|(_, pages)| pages);
+        Ok(histogram)
+    }
 }
@@ -1,19 +1,53 @@
+pub mod checked;
+pub mod counters;
+#[cfg(feature = "crashsim")]
+pub mod crashsim;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "docstore")]
+pub mod docstore;
+pub mod entry;
+#[cfg(feature = "flatmap")]
+pub mod flatmap;
+pub mod fulltext;
+pub mod graph;
+pub mod hlc;
+pub mod intern;
+pub mod lock;
+pub mod locks;
+pub mod log;
 pub mod mdb;
 pub mod rck;
+pub mod readonly;
+pub mod schema;
+pub mod sequence;
+pub mod shadow;
+pub mod shard;
+pub mod split;
+pub mod spy;
+pub mod tiered;
+pub mod timeseries;
+pub mod truncatable;
 
+use std::borrow::Borrow;
+use std::convert::Infallible;
 use std::error::Error;
-use std::marker;
-use std::ops::{Deref, RangeBounds};
+use std::future::Future;
+use std::ops::{BitOr, Deref, RangeBounds};
+use std::pin::Pin;
+use std::{any, fmt, marker, mem, ptr};
 
 use either::Either;
 use heed_traits::{BytesDecode, BytesEncode};
-use heed_types::ByteSlice;
+use heed_types::{ByteSlice, DecodeIgnore};
+
+use crate::Opened;
 
 pub type TableOf<'s, S> = <S as Store>::Table<'s>;
 pub type ErrorOf<S> = <S as Store>::Error;
 
 pub type RtxOf<'e, S> = <S as Store>::Rtx<'e>;
-pub type WtxOf<'e, S> = <S as Store>::Wtx<'e>;
+pub type WtxOf<'e, S> = <S as WritableStore>::Wtx<'e>;
 
 pub type RangeOf<'e, 'r, S, KC, DC> = <<S as Store>::Table<'e> as Table<'e>>::Range<'r, KC, DC>;
 pub type RevRangeOf<'e, 'r, S, KC, DC> =
@@ -26,26 +60,76 @@ pub trait Store: Sized + Send + Sync + 'static {
     where
         Self: 'e;
 
-    type Wtx<'e>: Transaction<Self> + Deref<Target = Self::Rtx<'e>>
-    where
-        Self: 'e;
-
     type Table<'store>: Table<'store, Store = Self> + Send + Sync
     where
         Self: 'store;
 
     type Config: Default;
 
-    fn table(&self, name: &str, cfg: &Self::Config) -> Result<Self::Table<'_>, Self::Error>;
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error>;
     fn typed<KC, DC>(
         &self,
-        name: &str,
+        name: impl AsRef<str>,
         cfg: &Self::Config,
     ) -> Result<Typed<Self, KC, DC>, Self::Error> {
         Ok(Typed { dyndb: self.table(name, cfg)?, marker: Default::default() })
     }
+    /// Opens the table like [`Store::table`] and tells whether it was created, to initialize
+    /// it once. The default checks [`Store::table_exists`] first, a table created by another
+    /// process in between is seen as created.
+    fn open_or_create_table(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Opened<Self::Table<'_>>, Self::Error> {
+        let existed = self.table_exists(name.as_ref())?;
+        let table = self.table(name, cfg)?;
+        Ok(if existed { Opened::Existing(table) } else { Opened::Created(table) })
+    }
+    fn open_or_create_typed<KC, DC>(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Opened<Typed<Self, KC, DC>>, Self::Error> {
+        let opened = self.open_or_create_table(name, cfg)?;
+        Ok(opened.map(|dyndb| Typed { dyndb, marker: Default::default() }))
+    }
+    /// Opens the table, `None` when it doesn't exist. Unlike [`Store::table`] it doesn't
+    /// create it, tools reading a store can open its tables without changing its schema.
+    ///
+    /// The default opens the table with [`Store::table`] and the default configuration once
+    /// [`Store::table_exists`] found it, a table dropped in between is created again.
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        if self.table_exists(name.as_ref())? {
+            self.table(name, &Self::Config::default()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    /// Whether the table exists, unlike [`Store::table`] it doesn't create it. The default
+    /// looks for it among the [`Store::table_names`].
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        Ok(self.table_names()?.iter().any(|table| table == name.as_ref()))
+    }
+    /// The names of the tables of the store, in order. The unnamed database of LMDB and
+    /// the default column family of RocksDB aren't tables.
+    fn table_names(&self) -> Result<Vec<String>, Self::Error>;
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error>;
-    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error>;
+    /// The configuration of the tables of [`counters`], RocksDB gives them
+    /// a merge operator adding the deltas of [`Table::increment`]. It only applies to the
+    /// tables created with it, a reopened RocksDB store must be given it again, see the
+    /// [`counters`] module.
+    fn counters_config(&self) -> Self::Config {
+        Self::Config::default()
+    }
+    /// The guarantees this store gives, a store that doesn't override it claims none.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::empty()
+    }
     fn with_rtx<R>(
         &self,
         fun: impl FnOnce(&RtxOf<Self>) -> Result<R, Self::Error>,
@@ -56,6 +140,36 @@ pub trait Store: Sized + Send + Sync + 'static {
 
         Ok(out)
     }
+}
+
+/// A [`Store`] that can be written to, the stores given to the components that must not
+/// write can be wrapped in a [`ReadOnlyStore`](readonly::ReadOnlyStore) that doesn't implement it.
+pub trait WritableStore: Store {
+    type Wtx<'e>: Transaction<Self> + Deref<Target = Self::Rtx<'e>>
+    where
+        Self: 'e;
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error>;
+    /// Opens the table like [`Store::table`], creating it in the write transaction, its
+    /// creation is committed or discarded with the transaction. The default creates it
+    /// with [`Store::table`], for the stores whose tables aren't transactional.
+    fn create_table(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let _ = wtx;
+        self.table(name, cfg)
+    }
+    fn create_typed<KC, DC>(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Typed<Self, KC, DC>, Self::Error> {
+        Ok(Typed { dyndb: self.create_table(wtx, name, cfg)?, marker: Default::default() })
+    }
     fn with_wtx<R>(
         &self,
         fun: impl FnOnce(&mut WtxOf<Self>) -> Result<R, Self::Error>,
@@ -68,8 +182,167 @@ pub trait Store: Sized + Send + Sync + 'static {
     }
 }
 
+/// A set of guarantees a [`Store`] backend gives, see [`Store::capabilities`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// `append` returns an error when keys are not given in order.
+    pub const APPEND_CHECK: Capabilities = Capabilities(1 << 0);
+    /// Read transactions see a consistent snapshot, unaffected by concurrent commits.
+    pub const SNAPSHOT_READS: Capabilities = Capabilities(1 << 1);
+    /// Write transactions can be nested.
+    pub const NESTED_TXN: Capabilities = Capabilities(1 << 2);
+    /// The engine supports tables storing multiple values per key.
+    pub const DUPSORT: Capabilities = Capabilities(1 << 3);
+    /// The data outlives the process.
+    pub const PERSISTENT: Capabilities = Capabilities(1 << 4);
+    /// A crash never leaves a committed write transaction partially applied.
+    pub const CRASH_SAFE: Capabilities = Capabilities(1 << 5);
+
+    pub const fn empty() -> Capabilities {
+        Capabilities(0)
+    }
+
+    pub const fn union(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 | other.0)
+    }
+
+    /// The capabilities in both sets.
+    pub const fn intersection(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & other.0)
+    }
+
+    /// The capabilities of this set that aren't in `other`.
+    pub const fn difference(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & !other.0)
+    }
+
+    /// Whether all the capabilities of `other` are in this set.
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn supports_append_check(self) -> bool {
+        self.contains(Self::APPEND_CHECK)
+    }
+
+    pub const fn snapshot_reads(self) -> bool {
+        self.contains(Self::SNAPSHOT_READS)
+    }
+
+    pub const fn nested_txn(self) -> bool {
+        self.contains(Self::NESTED_TXN)
+    }
+
+    pub const fn dupsort(self) -> bool {
+        self.contains(Self::DUPSORT)
+    }
+
+    pub const fn persistent(self) -> bool {
+        self.contains(Self::PERSISTENT)
+    }
+
+    pub const fn crash_safe(self) -> bool {
+        self.contains(Self::CRASH_SAFE)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        self.union(rhs)
+    }
+}
+
+/// The distributions of the lengths of the keys and values of a table,
+/// see [`Table::size_histogram`].
+///
+/// The lengths are counted by buckets of powers of two, the bucket `i`
+/// counts the lengths within [`SizeHistogram::bucket_bounds(i)`](Self::bucket_bounds).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeHistogram {
+    pub entries: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+    pub max_key_len: usize,
+    pub max_value_len: usize,
+    /// The number of keys by bucket of length.
+    pub keys: Vec<u64>,
+    /// The number of values by bucket of length.
+    pub values: Vec<u64>,
+    /// The number of values stored in overflow pages, for the stores that report them.
+    pub overflow_values: Option<u64>,
+    /// The number of overflow pages used by the values, for the stores that report them.
+    pub overflow_pages: Option<u64>,
+}
+
+impl SizeHistogram {
+    /// The bucket counting a length.
+    pub fn bucket(len: usize) -> usize {
+        mem::size_of::<usize>() * 8 - len.leading_zeros() as usize
+    }
+
+    /// The smallest and largest lengths counted by a bucket.
+    pub fn bucket_bounds(bucket: usize) -> (usize, usize) {
+        match bucket {
+            0 => (0, 0),
+            i => {
+                let low = 1 << (i - 1);
+                (low, low + (low - 1))
+            }
+        }
+    }
+
+    /// Count an entry with a key and a value of these lengths.
+    pub fn record(&mut self, key_len: usize, value_len: usize) {
+        fn count(buckets: &mut Vec<u64>, len: usize) {
+            let bucket = SizeHistogram::bucket(len);
+            if buckets.len() <= bucket {
+                buckets.resize(bucket + 1, 0);
+            }
+            buckets[bucket] += 1;
+        }
+
+        self.entries += 1;
+        self.key_bytes += key_len as u64;
+        self.value_bytes += value_len as u64;
+        self.max_key_len = self.max_key_len.max(key_len);
+        self.max_value_len = self.max_value_len.max(value_len);
+        count(&mut self.keys, key_len);
+        count(&mut self.values, value_len);
+    }
+}
+
+/// A transaction of a store.
+///
+/// A transaction is aborted when it is dropped without being committed, the changes of a
+/// write transaction are discarded. The read transactions don't need to be committed, they
+/// end when dropped. The stores without transactions, like the raw RocksDB store, write the
+/// changes as they are made, committing or dropping their transactions doesn't change them.
 pub trait Transaction<S: Store>: Sized {
+    /// Commits the changes of the transaction, a read transaction is only ended.
     fn commit(self) -> Result<(), ErrorOf<S>>;
+
+    /// Aborts the transaction and discards its changes, like dropping it.
+    fn abort(self) {
+        drop(self)
+    }
+
+    /// The values attached to the transaction, which the wrappers layered on a store
+    /// use to share state until the transaction ends, see [`Extensions`](crate::Extensions).
+    ///
+    /// The default is always empty, the transactions holding values override both methods.
+    fn extensions(&self) -> &crate::Extensions {
+        crate::Extensions::none()
+    }
+
+    /// The values attached to the transaction, `None` when it can't hold any. The wrappers
+    /// attaching values to it, like [`Interner`](intern::Interner), do without them then.
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        None
+    }
 }
 
 pub trait Table<'store>: 'store {
@@ -94,6 +367,22 @@ pub trait Table<'store>: 'store {
         KC: BytesEncode<'a>,
         DC: BytesDecode;
 
+    /// Reads a key in a write transaction to write it back, stores whose transactions
+    /// don't isolate each other, like RocksDB, lock the key until the transaction ends
+    /// so that no other transaction writes it in between.
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        self.get::<KC, DC>(txn, key)
+    }
+
     fn range<'a, 'txn, KC, DC, R>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -116,6 +405,34 @@ pub trait Table<'store>: 'store {
 
     fn len<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>>;
 
+    fn is_empty<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<bool, ErrorOf<Self::Store>> {
+        Ok(self.len(txn)? == 0)
+    }
+
+    /// A cheap estimation of the number of entries in the table,
+    /// stores that can't estimate it return the exact [`len`](Table::len).
+    fn estimated_len<'txn>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+    ) -> Result<usize, ErrorOf<Self::Store>> {
+        self.len(txn)
+    }
+
+    /// The number of entries in the range, stores that can estimate it without reading
+    /// the entries, like MDBX, return an approximation.
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.range::<KC, DecodeIgnore, R>(txn, range)?
+            .try_fold(0, |len, entry| entry.map(|_| len + 1))
+    }
+
     fn put<'a, KC, DC>(
         &self,
         txn: &mut WtxOf<Self::Store>,
@@ -123,6 +440,7 @@ pub trait Table<'store>: 'store {
         data: &'a DC::EItem,
     ) -> Result<(), ErrorOf<Self::Store>>
     where
+        Self::Store: WritableStore,
         KC: BytesEncode<'a>,
         DC: BytesEncode<'a>;
 
@@ -133,6 +451,7 @@ pub trait Table<'store>: 'store {
         data: &'a DC::EItem,
     ) -> Result<(), ErrorOf<Self::Store>>
     where
+        Self::Store: WritableStore,
         KC: BytesEncode<'a>,
         DC: BytesEncode<'a>;
 
@@ -142,9 +461,118 @@ pub trait Table<'store>: 'store {
         key: &'a KC::EItem,
     ) -> Result<(), ErrorOf<Self::Store>>
     where
+        Self::Store: WritableStore,
         KC: BytesEncode<'a>;
 
-    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>>;
+    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore;
+
+    /// Calls `visit` with every entry of the table in key order, the slices are
+    /// borrowed from the store, nothing is decoded nor copied.
+    ///
+    /// The default copies every entry out of a [`Table::range`], the stores override it
+    /// to give the slices they read.
+    fn raw_iter<'txn, E, F>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        mut visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        for entry in self.range::<ByteSlice, ByteSlice, _>(txn, &..).map_err(Either::Left)? {
+            let (key, value) = entry.map_err(Either::Left)?;
+            visit(&key, &value).map_err(Either::Right)?;
+        }
+        Ok(())
+    }
+
+    /// Reads every entry of the table in key order, a byte of each of their pages, to load
+    /// them into the caches of the operating system and of the store after a cold start.
+    /// Returns the number of bytes of the keys and values.
+    fn warmup(&self, txn: &RtxOf<Self::Store>) -> Result<u64, ErrorOf<Self::Store>> {
+        // The smallest page of the stores, reading a byte of it faults it in as a whole.
+        const PAGE_SIZE: usize = 4096;
+
+        let mut bytes = 0;
+        let visited = self.raw_iter::<Infallible, _>(txn, |key, value| {
+            for slice in &[key, value] {
+                for byte in slice.iter().step_by(PAGE_SIZE) {
+                    // The read must not be optimized away.
+                    unsafe { ptr::read_volatile(byte) };
+                }
+            }
+            bytes += (key.len() + value.len()) as u64;
+            Ok(())
+        });
+
+        match visited {
+            Ok(()) => Ok(bytes),
+            Err(Either::Left(e)) => Err(e),
+            Err(Either::Right(never)) => match never {},
+        }
+    }
+
+    /// Reads every entry of the table and counts the lengths of their keys and values.
+    fn size_histogram(
+        &self,
+        txn: &RtxOf<Self::Store>,
+    ) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        let mut histogram = SizeHistogram::default();
+        let visited = self.raw_iter::<Infallible, _>(txn, |key, value| {
+            histogram.record(key.len(), value.len());
+            Ok(())
+        });
+
+        match visited {
+            Ok(()) => Ok(histogram),
+            Err(Either::Left(e)) => Err(e),
+            Err(Either::Right(never)) => match never {},
+        }
+    }
+
+    fn raw_put(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+    {
+        self.put::<ByteSlice, ByteSlice>(txn, key, data)
+    }
+
+    fn raw_append(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+    {
+        self.append::<ByteSlice, ByteSlice>(txn, key, data)
+    }
+
+    /// Adds `delta` to the counter stored as a big endian `i64` in the value of the key,
+    /// a missing counter is 0, and returns its new value. Stores with merge operators, like
+    /// RocksDB, merge the delta and require a table opened with [`Store::counters_config`].
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+    {
+        let old = self.get_for_update::<ByteSlice, ByteSlice>(txn, key)?;
+        let new = old.map_or(0, |old| counters::decode(&old)).wrapping_add(delta);
+        self.raw_put(txn, key, &new.to_be_bytes())?;
+        Ok(new)
+    }
 }
 
 pub struct Typed<'s, S: Store + 's, KC, DC> {
@@ -161,6 +589,19 @@ where
     }
 }
 
+impl<'s, S: Store, KC, DC> fmt::Debug for Typed<'s, S, KC, DC>
+where
+    S::Table<'s>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Typed")
+            .field("table", &self.dyndb)
+            .field("key", &any::type_name::<KC>())
+            .field("data", &any::type_name::<DC>())
+            .finish()
+    }
+}
+
 impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
     pub fn get<'a, 'txn>(
         &self,
@@ -200,10 +641,59 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         self.dyndb.rev_range::<KC, DC, R>(txn, range)
     }
 
-    pub fn len<'txn, T>(&self, txn: &'txn RtxOf<S>) -> Result<usize, ErrorOf<S>> {
+    pub fn len<'txn>(&self, txn: &'txn RtxOf<S>) -> Result<usize, ErrorOf<S>> {
         self.dyndb.len(txn)
     }
 
+    pub fn is_empty<'txn>(&self, txn: &'txn RtxOf<S>) -> Result<bool, ErrorOf<S>> {
+        self.dyndb.is_empty(txn)
+    }
+
+    pub fn estimated_len<'txn>(&self, txn: &'txn RtxOf<S>) -> Result<usize, ErrorOf<S>> {
+        self.dyndb.estimated_len(txn)
+    }
+
+    pub fn range_len<'a, 'txn, R>(
+        &self,
+        txn: &'txn RtxOf<S>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<S>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.dyndb.range_len::<KC, R>(txn, range)
+    }
+
+    pub fn size_histogram(&self, txn: &RtxOf<S>) -> Result<SizeHistogram, ErrorOf<S>> {
+        self.dyndb.size_histogram(txn)
+    }
+
+    pub fn warmup(&self, txn: &RtxOf<S>) -> Result<u64, ErrorOf<S>> {
+        self.dyndb.warmup(txn)
+    }
+
+    pub fn remap_types<KC2, DC2>(self) -> Typed<'s, S, KC2, DC2> {
+        Typed { dyndb: self.dyndb, marker: Default::default() }
+    }
+
+    /// Change the key codec type of this uniform database, specifying the new codec.
+    pub fn remap_key_type<KC2>(self) -> Typed<'s, S, KC2, DC> {
+        self.remap_types::<KC2, DC>()
+    }
+
+    /// Change the data codec type of this uniform database, specifying the new codec.
+    pub fn remap_data_type<DC2>(self) -> Typed<'s, S, KC, DC2> {
+        self.remap_types::<KC, DC2>()
+    }
+
+    // /// Wrap the data bytes into a lazy decoder.
+    // pub fn lazily_decode_data(self) -> Typed<S, KC, LazyDecode<DC>> {
+    //     self.remap_types::<KC, LazyDecode<DC>>()
+    // }
+}
+
+impl<'s, S: WritableStore, KC, DC> Typed<'s, S, KC, DC> {
     pub fn put<'a>(
         &self,
         txn: &mut WtxOf<S>,
@@ -240,57 +730,157 @@ impl<'s, S: Store, KC, DC> Typed<'s, S, KC, DC> {
         self.dyndb.clear(txn)
     }
 
-    pub fn remap_types<KC2, DC2>(self) -> Typed<'s, S, KC2, DC2> {
-        Typed { dyndb: self.dyndb, marker: Default::default() }
-    }
-
-    /// Change the key codec type of this uniform database, specifying the new codec.
-    pub fn remap_key_type<KC2>(self) -> Typed<'s, S, KC2, DC> {
-        self.remap_types::<KC2, DC>()
+    /// Reads the value of the key, passes it to `update` and writes the value it returns,
+    /// or deletes the key when it returns `None`. The key is read with
+    /// [`Table::get_for_update`], returns the new value.
+    pub fn update<'a, F>(
+        &self,
+        txn: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        update: F,
+    ) -> Result<Option<DC::DItem>, ErrorOf<S>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode + for<'b> BytesEncode<'b, EItem = <DC as BytesDecode>::DItem>,
+        F: FnOnce(Option<DC::DItem>) -> Option<DC::DItem>,
+    {
+        let old = self.dyndb.get_for_update::<KC, DC>(txn, key)?;
+        let existed = old.is_some();
+        let key = KC::bytes_encode(key).expect("the key was encoded to be read");
+        match update(old) {
+            Some(new) => {
+                self.dyndb.put::<ByteSlice, DC>(txn, &key, &new)?;
+                Ok(Some(new))
+            }
+            None => {
+                if existed {
+                    self.dyndb.delete::<ByteSlice>(txn, &key)?;
+                }
+                Ok(None)
+            }
+        }
     }
 
-    /// Change the data codec type of this uniform database, specifying the new codec.
-    pub fn remap_data_type<DC2>(self) -> Typed<'s, S, KC, DC2> {
-        self.remap_types::<KC, DC2>()
+    /// Appends the entries when the table is empty, returns whether it was. Seeds a table
+    /// with its defaults, like a configuration table when it is created, see
+    /// [`Store::open_or_create_typed`]. The keys must be given in increasing order.
+    pub fn seed_if_empty<'a, I>(&self, txn: &mut WtxOf<S>, entries: I) -> Result<bool, ErrorOf<S>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+        I: IntoIterator<Item = (&'a KC::EItem, &'a DC::EItem)>,
+    {
+        if !self.dyndb.is_empty(txn)? {
+            return Ok(false);
+        }
+        for (key, data) in entries {
+            self.dyndb.append::<KC, DC>(txn, key, data)?;
+        }
+        Ok(true)
     }
+}
 
-    // /// Wrap the data bytes into a lazy decoder.
-    // pub fn lazily_decode_data(self) -> Typed<S, KC, LazyDecode<DC>> {
-    //     self.remap_types::<KC, LazyDecode<DC>>()
-    // }
+/// The tables of a [`Tables`], borrowing their store for `'s`. Implemented by a marker
+/// type naming the tables, a struct of [`Typed`] tables or a tuple of them.
+pub trait TableSet<S: Store> {
+    type Tables<'s>;
 }
 
-pub struct Tables<S: Store, T> {
-    pub store: &'static S,
-    pub table: Option<T>,
+/// The tables `T` of a store, which it owns, the tables borrow the store for as long as they live.
+///
+/// The tables are only lent to the closures given to [`Tables::with`], which work with any
+/// borrow of the store, they can't keep the tables or the store once it is dropped.
+pub struct Tables<S: Store, T: TableSet<S>> {
+    store: ptr::NonNull<S>,
+    table: Option<T::Tables<'static>>,
 }
 
-impl<S: Store, T> Tables<S, T> {
+unsafe impl<S: Store, T: TableSet<S>> Send for Tables<S, T> where T::Tables<'static>: Send {}
+
+unsafe impl<S: Store, T: TableSet<S>> Sync for Tables<S, T> where T::Tables<'static>: Sync {}
+
+impl<S: Store, T: TableSet<S>> Tables<S, T> {
     pub fn new<F>(store: S, cfg: &S::Config, make: F) -> Result<Tables<S, T>, S::Error>
     where
-        F: FnOnce(&'static S, &S::Config) -> Result<T, S::Error>,
+        F: for<'s> FnOnce(&'s S, &S::Config) -> Result<T::Tables<'s>, S::Error>,
     {
-        let store = Box::new(store);
-        let store = Box::leak::<'static>(store) as &'static S;
-        let o = make(&store, cfg)?;
+        let mut tables: Tables<S, T> = Tables::owning(store);
+        tables.table = Some(make(tables.static_store(), cfg)?);
+        Ok(tables)
+    }
 
-        Ok(Tables { store, table: Some(o) })
+    /// Opens the tables like [`Tables::new`] with a `make` future, to open them from
+    /// asynchronous code. The configuration is given to `make`, the future it returns
+    /// only borrows the store.
+    pub async fn new_async<F>(store: S, cfg: &S::Config, make: F) -> Result<Tables<S, T>, S::Error>
+    where
+        F: for<'s> FnOnce(
+            &'s S,
+            &S::Config,
+        )
+            -> Pin<Box<dyn Future<Output = Result<T::Tables<'s>, S::Error>> + 's>>,
+    {
+        let mut tables: Tables<S, T> = Tables::owning(store);
+        tables.table = Some(make(tables.static_store(), cfg).await?);
+        Ok(tables)
+    }
+
+    /// Opens the tables like [`Tables::new`] in a write transaction, with
+    /// [`WritableStore::create_table`], then runs `bootstrap` in the same transaction to
+    /// seed the tables or check their version. The transaction is committed if both
+    /// succeed, a bootstrap that fails creates no table and writes nothing.
+    pub fn bootstrap<F, B>(
+        store: S,
+        cfg: &S::Config,
+        make: F,
+        bootstrap: B,
+    ) -> Result<Tables<S, T>, S::Error>
+    where
+        S: WritableStore,
+        F: for<'s> FnOnce(&'s S, &mut WtxOf<'s, S>, &S::Config) -> Result<T::Tables<'s>, S::Error>,
+        B: for<'s> FnOnce(&T::Tables<'s>, &mut WtxOf<'s, S>) -> Result<(), S::Error>,
+    {
+        let mut tables: Tables<S, T> = Tables::owning(store);
+        let store = tables.static_store();
+        // Declared after the tables, the transaction is dropped before the store is freed.
+        let mut wtx = store.wtx()?;
+        let table = make(store, &mut wtx, cfg)?;
+        bootstrap(&table, &mut wtx)?;
+        wtx.commit()?;
+        tables.table = Some(table);
+        Ok(tables)
     }
-}
 
-impl<S: Store, T> Deref for Tables<S, T> {
-    type Target = T;
+    pub fn store(&self) -> &S {
+        unsafe { self.store.as_ref() }
+    }
 
-    fn deref(&self) -> &Self::Target {
-        unsafe { self.table.as_ref().unwrap_unchecked() }
+    /// Lends the store and its tables to `fun`, which works with any borrow of the
+    /// store, and returns what it returns.
+    pub fn with<R>(&self, fun: impl for<'s> FnOnce(&'s S, &T::Tables<'s>) -> R) -> R {
+        let table = unsafe { self.table.as_ref().unwrap_unchecked() };
+        fun(self.static_store(), table)
+    }
+
+    /// Moves the store to the heap, it is freed when the tables are dropped, even when
+    /// they failed to open.
+    fn owning(store: S) -> Tables<S, T> {
+        let store = unsafe { ptr::NonNull::new_unchecked(Box::into_raw(Box::new(store))) };
+        Tables { store, table: None }
+    }
+
+    /// The store for the tables, which are dropped before it. It is only given to the
+    /// closures working with any borrow of the store, which can't keep it.
+    fn static_store(&self) -> &'static S {
+        unsafe { &*self.store.as_ptr() }
     }
 }
 
-impl<S: Store, T> Drop for Tables<S, T> {
+impl<S: Store, T: TableSet<S>> Drop for Tables<S, T> {
     fn drop(&mut self) {
         drop(self.table.take());
         unsafe {
-            drop(Box::from_raw(self.store as *const S as *mut S));
+            drop(Box::from_raw(self.store.as_ptr()));
         }
     }
 }
@@ -303,22 +893,57 @@ pub fn bytecopy<'tx, 'x, 'y, S1, S2, KC, DC>(
 ) -> Result<(), Either<S1::Error, S2::Error>>
 where
     S1: Store,
-    S2: Store,
-    <S1 as Store>::Table<'x>: Clone,
-    <S2 as Store>::Table<'y>: Clone,
-    KC: BytesDecode + for<'a> BytesEncode<'a>,
-    DC: BytesDecode + for<'a> BytesEncode<'a>,
+    S2: WritableStore,
 {
-    let t1 = t1.clone().remap_types::<ByteSlice, ByteSlice>();
-    let t2 = t2.clone().remap_types::<ByteSlice, ByteSlice>();
+    copy_table::<S1, S2>(rtx, wtx, &t1.dyndb, &t2.dyndb).map(drop)
+}
 
-    for item in t1.range(rtx, &..).map_err(Either::Left)? {
-        let (k, v) = item.map_err(Either::Left)?;
-        t2.append(wtx, &k, &v).map_err(Either::Right)?;
+/// Appends the entries of a table to a table of any other store, in key order, the bytes
+/// are copied without being decoded. Returns the number of copied entries.
+pub fn copy_table<'x, 'y, S1, S2>(
+    rtx: &RtxOf<S1>,
+    wtx: &mut WtxOf<S2>,
+    from: &TableOf<'x, S1>,
+    to: &TableOf<'y, S2>,
+) -> Result<usize, Either<S1::Error, S2::Error>>
+where
+    S1: Store,
+    S2: WritableStore,
+{
+    let mut copied = 0;
+    from.raw_iter(rtx, |key, data| {
+        copied += 1;
+        to.raw_append(wtx, key, data)
+    })?;
+    Ok(copied)
+}
+
+/// Copies the tables of a [`Store`] to any other store, implemented for every store.
+pub trait CopyTables: Store {
+    /// Copies the entries of the table `name` to the table of the same name of `other`,
+    /// created with `cfg` if needed, in a write transaction of `other`. Returns the number
+    /// of copied entries, a table that doesn't exist has none.
+    fn copy_table_to<S2: WritableStore>(
+        &self,
+        name: &str,
+        other: &S2,
+        cfg: &S2::Config,
+    ) -> Result<usize, Either<Self::Error, S2::Error>> {
+        let from = self.open_table(name).map_err(Either::Left)?;
+        let to = other.table(name, cfg).map_err(Either::Right)?;
+        let rtx = self.rtx().map_err(Either::Left)?;
+        let mut wtx = other.wtx().map_err(Either::Right)?;
+        let copied = match &from {
+            Some(from) => copy_table::<Self, S2>(&rtx, &mut wtx, from, &to)?,
+            None => 0,
+        };
+        wtx.commit().map_err(Either::Right)?;
+        Ok(copied)
     }
-    Ok(())
 }
 
+impl<S: Store> CopyTables for S {}
+
 #[inline(always)]
 pub fn refcopy<'tx, 'x, 'y, S1, S2, KC, DC>(
     rtx: &'tx RtxOf<S1>,
@@ -333,7 +958,7 @@ pub fn refcopy<'tx, 'x, 'y, S1, S2, KC, DC>(
 ) -> Result<(), Either<S1::Error, S2::Error>>
 where
     S1: Store,
-    S2: Store,
+    S2: WritableStore,
     KC: BytesDecode + for<'a> BytesEncode<'a>,
     DC: BytesDecode + for<'a> BytesEncode<'a>,
 {
@@ -352,14 +977,13 @@ pub fn readtx<S: Store, T>(
     s.with_rtx(fun)
 }
 
-pub fn writetx<S: Store, T>(
+pub fn writetx<S: WritableStore, T>(
     s: &S,
     fun: impl FnOnce(&mut WtxOf<S>) -> Result<T, ErrorOf<S>>,
 ) -> Result<T, ErrorOf<S>> {
     s.with_wtx(fun)
 }
 
-
 /// Run a query in paged mode (start from provided value), and on each iteration overwrite the value
 /// from within the method. If the value was not changed in 2 iterations, we consider the paged
 /// query done. Useful for progress reporting migrations that use low amount of memory.
@@ -377,3 +1001,379 @@ pub fn paged<T: Clone + PartialEq, F: FnMut(&mut T) -> Result<(), E>, E>(
         old = cur.clone();
     }
 }
+
+/// Deletes the entries of the range in chunks of at most `chunk_size` entries, each one in its
+/// own write transaction, calls `progress` with the number of entries deleted after each chunk
+/// and returns it.
+///
+/// A single transaction deleting a large range holds a dirty page for every page it changes,
+/// more than the store may allow. The deletion isn't atomic: when a chunk fails, the chunks
+/// before it remain deleted.
+///
+/// # Panics
+///
+/// When `chunk_size` is zero.
+pub fn delete_range_chunked<'a, 's, S, KC, DC, R>(
+    store: &S,
+    table: &Typed<'s, S, KC, DC>,
+    range: &'a R,
+    chunk_size: usize,
+    mut progress: impl FnMut(usize),
+) -> Result<usize, ErrorOf<S>>
+where
+    S: WritableStore,
+    KC: BytesDecode + for<'b> BytesEncode<'b>,
+    KC::DItem: for<'b> Borrow<<KC as BytesEncode<'b>>::EItem>,
+    R: RangeBounds<<KC as BytesEncode<'a>>::EItem>,
+{
+    assert!(chunk_size > 0, "the chunks must not be empty");
+    let mut deleted = 0;
+    paged(0, |deleted_before| {
+        let mut wtx = store.wtx()?;
+        // The deleted entries are gone, every chunk starts at the start of the range.
+        let keys = table
+            .dyndb
+            .range::<KC, DecodeIgnore, R>(&wtx, range)?
+            .take(chunk_size)
+            .map(|entry| entry.map(|(key, ())| key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+        for key in &keys {
+            table.dyndb.delete::<KC>(&mut wtx, key.borrow())?;
+        }
+        wtx.commit()?;
+
+        *deleted_before += keys.len();
+        deleted = *deleted_before;
+        progress(deleted);
+        Ok(())
+    })?;
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::ops::Bound;
+
+    use super::*;
+
+    #[test]
+    fn size_histogram_buckets() {
+        for &len in &[0, 1, 2, 3, 4, 1000, 4096, usize::MAX] {
+            let (low, high) = SizeHistogram::bucket_bounds(SizeHistogram::bucket(len));
+            assert!(low <= len && len <= high, "{} not in {}..={}", len, low, high);
+        }
+
+        let mut histogram = SizeHistogram::default();
+        histogram.record(0, 3);
+        histogram.record(4, 3);
+        assert_eq!(histogram.keys, [1, 0, 0, 1]);
+        assert_eq!(histogram.values, [0, 0, 2]);
+        assert_eq!((histogram.key_bytes, histogram.max_value_len), (4, 3));
+    }
+
+    #[test]
+    fn range_len_counts_the_entries_of_the_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        for key in &[b"a", b"b", b"c", b"d"] {
+            table.put(&mut wtx, &key[..], b"").unwrap();
+        }
+
+        let range = (Bound::Excluded(&b"a"[..]), Bound::Included(&b"c"[..]));
+        assert_eq!(table.range_len(&wtx, &range).unwrap(), 2);
+        assert_eq!(table.range_len(&wtx, &(..)).unwrap(), 4);
+    }
+
+    #[test]
+    fn delete_range_chunked_commits_every_chunk() {
+        use heed_types::Str;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<Str, ByteSlice>("entries", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        for key in 0..10 {
+            table.put(&mut wtx, &key.to_string(), b"").unwrap();
+        }
+        wtx.commit().unwrap();
+
+        let txn_id = env.last_txn_id().unwrap();
+        let mut progress = Vec::new();
+        let range = (Bound::Included("2"), Bound::Excluded("9"));
+        let deleted = delete_range_chunked(&env, &table, &range, 3, |n| progress.push(n)).unwrap();
+        assert_eq!((deleted, progress), (7, vec![3, 6, 7]));
+        assert_eq!(env.last_txn_id().unwrap(), txn_id + 3);
+
+        let rtx = env.rtx().unwrap();
+        let keys: Vec<_> = table.range(&rtx, &(..)).unwrap().map(|e| e.unwrap().0).collect();
+        assert_eq!(keys, ["0", "1", "9"]);
+    }
+
+    #[test]
+    fn warmup_reads_every_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        table.put(&mut wtx, b"small", b"value").unwrap();
+        table.put(&mut wtx, b"large", &[0; 10_000]).unwrap();
+        wtx.commit().unwrap();
+
+        env.prefetch().unwrap();
+        let rtx = env.rtx().unwrap();
+        assert_eq!(table.warmup(&rtx).unwrap(), 10 + 10_005);
+    }
+
+    #[test]
+    fn table_names_list_the_tables_without_creating_them() {
+        let dir = tempfile::tempdir().unwrap();
+        // The tables that aren't opened are opened for a moment in a free slot.
+        let env = crate::EnvOpenOptions::new().max_dbs(3).open(dir.path()).unwrap();
+        assert!(!env.table_exists("b").unwrap());
+        assert!(env.table_names().unwrap().is_empty());
+
+        env.table("b", &()).unwrap();
+        env.table("a", &()).unwrap();
+        // A key of the unnamed database that isn't a table.
+        let mut wtx = env.wtx().unwrap();
+        let unnamed = env.create_poly_database(&mut wtx, None).unwrap();
+        unnamed.put::<_, ByteSlice, ByteSlice>(&mut wtx, b"c", b"value").unwrap();
+        wtx.commit().unwrap();
+
+        assert!(env.table_exists("b").unwrap());
+        assert!(!env.table_exists("c").unwrap());
+        assert_eq!(env.table_names().unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn open_or_create_tells_the_created_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+
+        let opened = env.open_or_create_typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+        assert!(opened.is_created());
+        let opened = env.open_or_create_typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+        assert!(!opened.is_created());
+        assert_eq!(env.table_names().unwrap(), ["entries"]);
+    }
+
+    #[test]
+    fn seed_only_the_empty_tables() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<ByteSlice, ByteSlice>("config", &()).unwrap();
+        let defaults: [(&[u8], &[u8]); 2] = [(b"color", b"blue"), (b"size", b"10")];
+
+        let mut wtx = env.wtx().unwrap();
+        assert!(table.seed_if_empty(&mut wtx, defaults.iter().copied()).unwrap());
+        table.put(&mut wtx, b"size", b"12").unwrap();
+        assert!(!table.seed_if_empty(&mut wtx, defaults.iter().copied()).unwrap());
+        assert_eq!(table.get(&wtx, b"size").unwrap().as_deref(), Some(&b"12"[..]));
+        assert_eq!(table.len(&wtx).unwrap(), 2);
+    }
+
+    #[test]
+    fn failed_bootstraps_commit_nothing() {
+        struct Config;
+
+        impl TableSet<crate::Env> for Config {
+            type Tables<'s> = Typed<'s, crate::Env, ByteSlice, ByteSlice>;
+        }
+
+        fn make<'s>(
+            store: &'s crate::Env,
+            wtx: &mut WtxOf<'s, crate::Env>,
+            cfg: &(),
+        ) -> crate::Result<Typed<'s, crate::Env, ByteSlice, ByteSlice>> {
+            store.create_typed(wtx, "config", cfg)
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+
+        let failed = Tables::<_, Config>::bootstrap(env.clone(), &(), make, |config, wtx| {
+            config.put(wtx, b"version", b"2")?;
+            Err(crate::Error::Encoding)
+        });
+        assert!(failed.is_err());
+        assert!(!env.table_exists("config").unwrap());
+
+        let tables = Tables::<_, Config>::bootstrap(env, &(), make, |config, wtx| {
+            assert!(config.is_empty(wtx)?);
+            config.put(wtx, b"version", b"2")
+        })
+        .unwrap();
+        let version = tables.with(|store, config| {
+            let rtx = store.rtx().unwrap();
+            config.get(&rtx, b"version").unwrap().map(|version| version.to_vec())
+        });
+        assert_eq!(version.as_deref(), Some(&b"2"[..]));
+    }
+
+    #[test]
+    fn uncommitted_transactions_are_aborted() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        table.put(&mut wtx, b"aborted", b"").unwrap();
+        wtx.abort().unwrap();
+        let mut wtx = env.wtx().unwrap();
+        table.put(&mut wtx, b"dropped", b"").unwrap();
+        drop(wtx);
+
+        let rtx = env.rtx().unwrap();
+        assert!(table.is_empty(&rtx).unwrap());
+        assert_eq!(env.read_txns(), 1);
+        drop(rtx);
+        assert_eq!(env.read_txns(), 0);
+    }
+
+    #[test]
+    fn copy_a_table_to_another_store() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("from")).unwrap();
+        fs::create_dir_all(dir.path().join("to")).unwrap();
+        let from = crate::EnvOpenOptions::new().max_dbs(2).open(dir.path().join("from")).unwrap();
+        let to = crate::EnvOpenOptions::new().max_dbs(2).open(dir.path().join("to")).unwrap();
+
+        let table = from.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+        let mut wtx = from.wtx().unwrap();
+        table.put(&mut wtx, b"b", b"2").unwrap();
+        table.put(&mut wtx, b"a", b"1").unwrap();
+        wtx.commit().unwrap();
+
+        assert_eq!(from.copy_table_to("entries", &to, &()).unwrap(), 2);
+        assert_eq!(from.copy_table_to("missing", &to, &()).unwrap(), 0);
+
+        let copied = to.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+        let rtx = to.rtx().unwrap();
+        let entries: Result<Vec<_>, _> = copied.range(&rtx, &..).unwrap().collect();
+        assert_eq!(
+            entries.unwrap(),
+            [(b"a".to_vec(), b"1".to_vec()), (b"b".to_vec(), b"2".to_vec())]
+        );
+    }
+
+    #[test]
+    fn open_table_doesnt_create_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        assert!(env.open_table("entries").unwrap().is_none());
+        assert!(env.table_names().unwrap().is_empty());
+
+        let table = env.typed::<ByteSlice, ByteSlice>("entries", &()).unwrap();
+        let mut wtx = env.wtx().unwrap();
+        table.put(&mut wtx, b"key", b"value").unwrap();
+        wtx.commit().unwrap();
+
+        let opened = env.open_table("entries").unwrap().unwrap();
+        let rtx = env.rtx().unwrap();
+        assert_eq!(opened.len(&rtx).unwrap(), 1);
+    }
+
+    #[test]
+    fn update_writes_or_deletes_the_value() {
+        use heed_types::{OwnedType, Str};
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<Str, OwnedType<u64>>("counters", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        let increment = |count: Option<u64>| Some(count.unwrap_or(0) + 1);
+        assert_eq!(table.update(&mut wtx, "hits", increment).unwrap(), Some(1));
+        assert_eq!(table.update(&mut wtx, "hits", increment).unwrap(), Some(2));
+        assert_eq!(table.get(&wtx, "hits").unwrap(), Some(2));
+
+        let decrement = |count: Option<u64>| count.filter(|&c| c > 1).map(|c| c - 1);
+        assert_eq!(table.update(&mut wtx, "hits", decrement).unwrap(), Some(1));
+        assert_eq!(table.update(&mut wtx, "hits", decrement).unwrap(), None);
+        assert_eq!(table.get(&wtx, "hits").unwrap(), None);
+        assert_eq!(table.update(&mut wtx, "misses", decrement).unwrap(), None);
+    }
+
+    #[test]
+    fn prefix_range_bounds_the_composite_keys() {
+        use heed_types::{prefix_range, Composite, Unit};
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.typed::<Composite<(u8, String)>, Unit>("composite", &()).unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        for &(first, second) in &[(0xFE, "z"), (0xFF, "a"), (0xFF, "bb")] {
+            table.put(&mut wtx, &(first, second.to_string()), &()).unwrap();
+        }
+
+        let bytes = table.remap_key_type::<ByteSlice>();
+        let range = prefix_range::<(u8, String), _>(&0xFF);
+        let keys: Vec<_> = bytes.range(&wtx, &range).unwrap().map(|e| e.unwrap().0).collect();
+        let keys: Vec<(u8, String)> =
+            keys.iter().map(|key| Composite::bytes_decode(key).unwrap()).collect();
+        assert_eq!(keys, [(0xFF, "a".to_string()), (0xFF, "bb".to_string())]);
+    }
+
+    /// Checks every range between keys that extend each other, or end with 0 or 255,
+    /// against the keys it contains.
+    fn check_range_bounds<S: WritableStore>(store: &S) {
+        let keys: [&[u8]; 7] = [b"\0", b"a", b"a\0", b"aa", b"a\xFF", b"a\xFF\x01", b"b"];
+        let table = store.typed::<ByteSlice, ByteSlice>("bounds", &S::Config::default()).unwrap();
+        let mut wtx = store.wtx().unwrap();
+        for key in &keys {
+            table.put(&mut wtx, key, b"").unwrap();
+        }
+        wtx.commit().unwrap();
+
+        let rtx = store.rtx().unwrap();
+        let bounds = |key| vec![Bound::Included(key), Bound::Excluded(key), Bound::Unbounded];
+        for start in keys.iter().flat_map(|&key| bounds(key)) {
+            for end in keys.iter().flat_map(|&key| bounds(key)) {
+                let range = (start, end);
+                let expected: Vec<_> = keys
+                    .iter()
+                    .filter(|key| range.contains(*key))
+                    .map(|key| key.to_vec())
+                    .collect();
+                let found =
+                    table.range(&rtx, &range).unwrap().map(|entry| entry.unwrap().0.to_vec());
+                assert_eq!(found.collect::<Vec<_>>(), expected, "{:?}", range);
+
+                let found =
+                    table.rev_range(&rtx, &range).unwrap().map(|entry| entry.unwrap().0.to_vec());
+                let expected: Vec<_> = expected.into_iter().rev().collect();
+                assert_eq!(found.collect::<Vec<_>>(), expected, "rev {:?}", range);
+            }
+        }
+    }
+
+    #[test]
+    fn lmdb_ranges_translate_their_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        check_range_bounds(&env);
+    }
+
+    #[test]
+    fn rocksdb_ranges_translate_their_bounds() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        check_range_bounds(&rck::raw::DBType::open(&opts, dir.path()).unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let txn_opts = rocksdb::TransactionDBOptions::default();
+        let db = rck::rtx::DBType::open(&opts, &txn_opts, dir.path()).unwrap();
+        check_range_bounds(&db);
+    }
+}
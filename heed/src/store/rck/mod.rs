@@ -1,2 +1,387 @@
 pub mod raw;
 pub mod rtx;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::{error, fmt};
+
+use rocksdb::{
+    ColumnFamilyDescriptor, DBRecoveryMode, ErrorKind, MergeOperands, Options,
+    TransactionDBOptions, DB, DEFAULT_COLUMN_FAMILY_NAME,
+};
+
+use crate::store::{counters, Store};
+
+/// The errors of the RocksDB stores.
+#[derive(Debug)]
+pub enum RockError {
+    Rocks(rocksdb::Error),
+    /// A key appended to a table doesn't sort after its last key, appended keys must be
+    /// strictly increasing, see [`Table::append`](crate::store::Table::append).
+    UnsortedAppend {
+        table: String,
+        /// The last key of the table, that the appended key doesn't sort after.
+        last: Vec<u8>,
+        key: Vec<u8>,
+    },
+}
+
+impl fmt::Display for RockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RockError::Rocks(error) => write!(f, "{}", error),
+            RockError::UnsortedAppend { table, last, key } => write!(
+                f,
+                "key {:?} appended to the {:?} table doesn't sort after its last key {:?}",
+                key, table, last
+            ),
+        }
+    }
+}
+
+impl error::Error for RockError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            RockError::Rocks(error) => Some(error),
+            RockError::UnsortedAppend { .. } => None,
+        }
+    }
+}
+
+impl From<rocksdb::Error> for RockError {
+    fn from(error: rocksdb::Error) -> RockError {
+        RockError::Rocks(error)
+    }
+}
+
+/// The last key appended to each table during a write transaction, RocksDB has no
+/// notion of append so the ordering contract of [`Table::append`] is checked here.
+///
+/// [`Table::append`]: crate::store::Table::append
+#[derive(Default)]
+pub(crate) struct AppendedKeys(HashMap<String, Option<Vec<u8>>>);
+
+impl AppendedKeys {
+    /// Records `key` as the last key appended to `table`, or returns an
+    /// [`UnsortedAppend`](RockError::UnsortedAppend) error if it doesn't sort strictly after
+    /// the previously appended key, or after the `current_last` key of the table for the
+    /// first append to it.
+    pub(crate) fn push(
+        &mut self,
+        table: &str,
+        key: &[u8],
+        current_last: impl FnOnce() -> Result<Option<Vec<u8>>, RockError>,
+    ) -> Result<(), RockError> {
+        if !self.0.contains_key(table) {
+            self.0.insert(table.to_owned(), current_last()?);
+        }
+
+        match self.0.get_mut(table).unwrap() {
+            Some(last) if key <= last.as_slice() => Err(RockError::UnsortedAppend {
+                table: table.to_owned(),
+                last: last.clone(),
+                key: key.to_vec(),
+            }),
+            Some(last) => {
+                last.clear();
+                last.extend_from_slice(key);
+                Ok(())
+            }
+            last @ None => {
+                *last = Some(key.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    /// Forgets the last key appended to `table`, after it was written otherwise,
+    /// the next append reads its last key again.
+    pub(crate) fn forget(&mut self, table: &str) {
+        self.0.remove(table);
+    }
+}
+
+/// The options of the column families of counters, their merge operator adds the big
+/// endian `i64` deltas merged by [`Table::increment`](crate::store::Table::increment).
+pub(crate) fn counters_options() -> Options {
+    let mut opts = Options::default();
+    opts.set_merge_operator_associative("heed.counters", add_counters);
+    opts
+}
+
+/// The names of the tables among the column families, in order.
+pub(crate) fn table_names(mut families: Vec<String>) -> Vec<String> {
+    families.retain(|name| name != DEFAULT_COLUMN_FAMILY_NAME);
+    families.sort_unstable();
+    families
+}
+
+/// How [`OpenWithRecovery::open_with_recovery`] opens a RocksDB store after a crash.
+#[derive(Clone)]
+pub struct RecoveryConfig {
+    /// The options of the database and of its tables, the store is created if missing.
+    pub options: Options,
+    /// How the write-ahead log is replayed: `PointInTime`, the default, stops at the first
+    /// corrupted record and drops the tail of the log after it, `TolerateCorruptedTailRecords`
+    /// only drops an incomplete last record, `AbsoluteConsistency` fails on any corruption.
+    pub wal_recovery_mode: DBRecoveryMode,
+    /// Whether the database is repaired, and opened again, when its files are corrupted. The
+    /// repair keeps the data it can read from the files and can lose the rest, the other
+    /// errors, like a database locked by another process, are returned without a repair.
+    pub repair_on_failure: bool,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> RecoveryConfig {
+        RecoveryConfig {
+            options: Options::default(),
+            wal_recovery_mode: DBRecoveryMode::PointInTime,
+            repair_on_failure: false,
+        }
+    }
+}
+
+/// The RocksDB stores that can be opened after a crash, with every table of the database.
+pub trait OpenWithRecovery: Store + Sized {
+    /// Opens the store at the path with the tables it has, recovering it as configured.
+    ///
+    /// Every table is opened with the merge operator of the [`counters`], the counters
+    /// written before the crash can be read and incremented. The default configuration
+    /// opens the store like RocksDB does, it is the way to reopen a store with counters.
+    fn open_with_recovery<P: AsRef<Path>>(
+        path: P,
+        cfg: &RecoveryConfig,
+    ) -> Result<Self, Self::Error>;
+}
+
+impl OpenWithRecovery for raw::DBType {
+    fn open_with_recovery<P: AsRef<Path>>(
+        path: P,
+        cfg: &RecoveryConfig,
+    ) -> Result<Self, Self::Error> {
+        let db = open_with_recovery(path.as_ref(), cfg, |opts, cfs| {
+            raw::DBType::open_cf_descriptors(opts, path.as_ref(), cfs)
+        })?;
+        Ok(db)
+    }
+}
+
+impl OpenWithRecovery for rtx::DBType {
+    fn open_with_recovery<P: AsRef<Path>>(
+        path: P,
+        cfg: &RecoveryConfig,
+    ) -> Result<Self, Self::Error> {
+        let db = open_with_recovery(path.as_ref(), cfg, |opts, cfs| {
+            rtx::DBType::open_cf_descriptors(
+                opts,
+                &TransactionDBOptions::default(),
+                path.as_ref(),
+                cfs,
+            )
+        })?;
+        Ok(db)
+    }
+}
+
+fn open_with_recovery<D>(
+    path: &Path,
+    cfg: &RecoveryConfig,
+    open: impl Fn(&Options, Vec<ColumnFamilyDescriptor>) -> Result<D, rocksdb::Error>,
+) -> Result<D, rocksdb::Error> {
+    let mut options = cfg.options.clone();
+    options.create_if_missing(true);
+    options.set_wal_recovery_mode(cfg.wal_recovery_mode);
+
+    let open_all = || {
+        // a missing database has no column family yet
+        let names = match path.join("CURRENT").exists() {
+            true => DB::list_cf(&options, path)?,
+            false => Vec::new(),
+        };
+        let descriptors = names
+            .into_iter()
+            .map(|name| {
+                let mut table_options = cfg.options.clone();
+                table_options.set_merge_operator_associative("heed.counters", add_counters);
+                ColumnFamilyDescriptor::new(name, table_options)
+            })
+            .collect();
+        open(&options, descriptors)
+    };
+
+    match open_all() {
+        Err(e) if cfg.repair_on_failure && e.kind() == ErrorKind::Corruption => {
+            DB::repair(&options, path)?;
+            open_all()
+        }
+        result => result,
+    }
+}
+
+fn add_counters(_key: &[u8], value: Option<&[u8]>, deltas: &MergeOperands) -> Option<Vec<u8>> {
+    let sum = value.into_iter().chain(deltas).map(counters::decode).fold(0, i64::wrapping_add);
+    Some(sum.to_be_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::ByteSlice;
+
+    use super::*;
+    use crate::store::counters::Counters;
+    use crate::store::{Table, Transaction, WritableStore};
+
+    #[test]
+    fn appended_keys_in_order() {
+        let mut appended = AppendedKeys::default();
+        appended.push("table", b"b", || Ok(Some(b"a".to_vec()))).unwrap();
+        appended.push("table", b"c", || unreachable!()).unwrap();
+        appended.push("other", b"a", || Ok(None)).unwrap();
+    }
+
+    #[test]
+    fn appended_keys_out_of_order() {
+        let mut appended = AppendedKeys::default();
+        appended.push("table", b"b", || Ok(None)).unwrap();
+        match appended.push("table", b"b", || unreachable!()) {
+            Err(RockError::UnsortedAppend { table, last, key }) => {
+                assert_eq!((table.as_str(), &last[..], &key[..]), ("table", &b"b"[..], &b"b"[..]))
+            }
+            result => panic!("the key was appended out of order: {:?}", result),
+        }
+
+        // a key written otherwise is seen by the next append
+        appended.forget("table");
+        appended.push("table", b"a", || Ok(Some(b"c".to_vec()))).unwrap_err();
+    }
+
+    #[test]
+    fn reopened_with_every_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = RecoveryConfig::default();
+        {
+            let db = rtx::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+            let table = db.table("table", &Options::default()).unwrap();
+            let counters = Counters::open(&db, "counters").unwrap();
+            let mut wtx = db.wtx().unwrap();
+            table.raw_put(&mut wtx, b"key", b"value").unwrap();
+            counters.incr(&mut wtx, "hits", 2).unwrap();
+            wtx.commit().unwrap();
+        }
+
+        let db = rtx::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+        let table = db.open_table("table").unwrap().unwrap();
+        let counters = Counters::open(&db, "counters").unwrap();
+        let mut wtx = db.wtx().unwrap();
+        assert_eq!(counters.incr(&mut wtx, "hits", 3).unwrap(), 5);
+        assert_eq!(table.len(&wtx).unwrap(), 1);
+    }
+
+    #[test]
+    fn counters_are_merged_after_a_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = RecoveryConfig::default();
+        {
+            let db = raw::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+            let counters = Counters::open(&db, "counters").unwrap();
+            let mut wtx = db.wtx().unwrap();
+            counters.incr(&mut wtx, "hits", 2).unwrap();
+            wtx.commit().unwrap();
+        }
+
+        let db = raw::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+        let counters = Counters::open(&db, "counters").unwrap();
+        let mut wtx = db.wtx().unwrap();
+        assert_eq!(counters.incr(&mut wtx, "hits", -5).unwrap(), -3);
+        wtx.commit().unwrap();
+        assert_eq!(counters.get(&db.rtx().unwrap(), "hits").unwrap(), -3);
+    }
+
+    #[test]
+    fn corrupted_databases_are_repaired() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db =
+                raw::DBType::open_with_recovery(dir.path(), &RecoveryConfig::default()).unwrap();
+            let table = db.table("table", &Options::default()).unwrap();
+            let mut wtx = db.wtx().unwrap();
+            table.raw_put(&mut wtx, b"key", b"value").unwrap();
+            wtx.commit().unwrap();
+            db.flush_cf(&db.cf_handle("table").unwrap()).unwrap();
+        }
+
+        // the manifest lists the files of the tables, the repair finds them again
+        for entry in std::fs::read_dir(dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().unwrap().to_string_lossy().starts_with("MANIFEST-") {
+                std::fs::write(path, vec![0xab; 1024]).unwrap();
+            }
+        }
+
+        let cfg = RecoveryConfig::default();
+        let err = raw::DBType::open_with_recovery(dir.path(), &cfg).err().unwrap();
+        assert!(matches!(err, RockError::Rocks(ref e) if e.kind() == ErrorKind::Corruption));
+
+        let cfg = RecoveryConfig { repair_on_failure: true, ..RecoveryConfig::default() };
+        let db = raw::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+        let table = db.open_table("table").unwrap().unwrap();
+        assert_eq!(
+            table.get::<ByteSlice, ByteSlice>(&db.rtx().unwrap(), b"key").unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn locked_databases_are_not_repaired() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = RecoveryConfig { repair_on_failure: true, ..RecoveryConfig::default() };
+        let db = raw::DBType::open_with_recovery(dir.path(), &cfg).unwrap();
+        let table = db.table("table", &Options::default()).unwrap();
+        let mut wtx = db.wtx().unwrap();
+        table.raw_put(&mut wtx, b"key", b"value").unwrap();
+        wtx.commit().unwrap();
+
+        let err = raw::DBType::open_with_recovery(dir.path(), &cfg).err().unwrap();
+        assert!(matches!(err, RockError::Rocks(ref e) if e.kind() != ErrorKind::Corruption));
+        assert_eq!(
+            table.get::<ByteSlice, ByteSlice>(&db.rtx().unwrap(), b"key").unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn corrupted_logs_follow_the_recovery_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let db =
+                raw::DBType::open_with_recovery(dir.path(), &RecoveryConfig::default()).unwrap();
+            let table = db.table("table", &Options::default()).unwrap();
+            let mut wtx = db.wtx().unwrap();
+            table.raw_put(&mut wtx, b"key", b"value").unwrap();
+            wtx.commit().unwrap();
+        }
+
+        // the write is only in the log, garbage is appended after its record
+        for entry in std::fs::read_dir(dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() == Some("log".as_ref()) {
+                let mut log = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+                std::io::Write::write_all(&mut log, &[0xab; 1024]).unwrap();
+            }
+        }
+
+        let cfg = RecoveryConfig {
+            wal_recovery_mode: DBRecoveryMode::AbsoluteConsistency,
+            ..RecoveryConfig::default()
+        };
+        let err = raw::DBType::open_with_recovery(dir.path(), &cfg).err().unwrap();
+        assert!(matches!(err, RockError::Rocks(ref e) if e.kind() == ErrorKind::Corruption));
+
+        let db = raw::DBType::open_with_recovery(dir.path(), &RecoveryConfig::default()).unwrap();
+        let table = db.open_table("table").unwrap().unwrap();
+        assert_eq!(
+            table.get::<ByteSlice, ByteSlice>(&db.rtx().unwrap(), b"key").unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+}
@@ -1,49 +1,85 @@
 use std::collections::Bound;
 use std::marker::PhantomData;
 use std::ops::{Deref, RangeBounds};
+use std::fmt;
 use std::sync::Arc;
 
+use either::Either;
 use heed_traits::{BytesDecode, BytesEncode};
 use rocksdb::{
     BoundColumnFamily, DBIteratorWithThreadMode, DBWithThreadMode, Direction, ErrorKind,
-    IteratorMode, MultiThreaded, Options, ReadOptions,
+    IteratorMode, MultiThreaded, Options, ReadOptions, WriteBatch,
 };
 
-use crate::iter::advance_key;
-use crate::store::{ErrorOf, RtxOf, Store, Table, Transaction, WtxOf};
+use crate::store::counters;
+use crate::store::rck::{counters_options, table_names, AppendedKeys, RockError};
+use crate::store::{Capabilities, ErrorOf, RtxOf, Store, Table, Transaction, WritableStore, WtxOf};
+use crate::Extensions;
 
 pub type DBType = DBWithThreadMode<MultiThreaded>;
 
 impl Store for DBType {
-    type Error = rocksdb::Error;
+    type Error = RockError;
     type Rtx<'e> = RawTxn<'e>;
-    type Wtx<'e> = WRawTxn<'e>;
     type Table<'store> = RockTable<'store>;
     type Config = Options;
 
-    fn table(&self, name: &str, opts: &Self::Config) -> Result<Self::Table<'_>, Self::Error> {
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        opts: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
         match self.create_cf(name, opts) {
             Ok(..) => {}
             Err(e)
                 if e.kind() == ErrorKind::InvalidArgument
                     && e.to_string().contains("Column family already exists") => {}
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         };
         let cf = self.cf_handle(name).unwrap();
-        Ok(RockTable { cf })
+        Ok(RockTable { name: name.to_owned(), cf })
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let name = name.as_ref();
+        if !self.table_exists(name)? {
+            return Ok(None);
+        }
+        // A column family listed on disk is only missing its handle while it is dropped.
+        Ok(self.cf_handle(name).map(|cf| RockTable { name: name.to_owned(), cf }))
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(table_names(Self::list_cf(&Options::default(), self.path())?))
     }
 
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
-        Ok(RawTxn { db: self })
+        Ok(RawTxn { db: self, extensions: Extensions::new() })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Writes are applied one by one as they are made, a write transaction is not atomic.
+        Capabilities::PERSISTENT
     }
 
+    fn counters_config(&self) -> Self::Config {
+        counters_options()
+    }
+}
+
+impl WritableStore for DBType {
+    type Wtx<'e> = WRawTxn<'e>;
+
     fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
-        Ok(WRawTxn { rtx: RawTxn { db: self } })
+        let rtx = RawTxn { db: self, extensions: Extensions::new() };
+        Ok(WRawTxn { rtx, appended: AppendedKeys::default() })
     }
 }
 
 pub struct WRawTxn<'a> {
     rtx: RawTxn<'a>,
+    appended: AppendedKeys,
 }
 
 impl<'a> Deref for WRawTxn<'a> {
@@ -58,34 +94,74 @@ impl Transaction<DBType> for WRawTxn<'_> {
     fn commit(self) -> Result<(), ErrorOf<DBType>> {
         Ok(())
     }
+
+    fn extensions(&self) -> &Extensions {
+        &self.rtx.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(&mut self.rtx.extensions)
+    }
 }
 
 pub struct RawTxn<'a> {
     db: &'a DBType,
+    extensions: Extensions,
 }
 
 impl Transaction<DBType> for RawTxn<'_> {
     fn commit(self) -> Result<(), ErrorOf<DBType>> {
         Ok(())
     }
+
+    fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(&mut self.extensions)
+    }
 }
 
 #[derive(Clone)]
 pub struct RockTable<'store> {
+    name: String,
     cf: Arc<BoundColumnFamily<'store>>,
 }
 
+impl fmt::Debug for RockTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RockTable").field("name", &self.name).finish()
+    }
+}
+
 unsafe impl<'store> Send for RockTable<'store> {}
 
 unsafe impl<'store> Sync for RockTable<'store> {}
 
 pub struct Iter<'a, KC: BytesDecode, DC: BytesDecode> {
     it: DBIteratorWithThreadMode<'a, DBType>,
+    direction: Direction,
     _p: PhantomData<(KC, DC)>,
 }
 
+impl<'a, KC: BytesDecode, DC: BytesDecode> Iter<'a, KC, DC> {
+    /// Moves the iterator so that the next entry returned is the first one with a key
+    /// greater than or equal to `key`, or lower than or equal to it when iterating
+    /// in reverse, without recreating the iterator.
+    ///
+    /// The iterator never leaves the bounds of the range it was created with.
+    pub fn seek<'k>(&mut self, key: &'k KC::EItem)
+    where
+        KC: BytesEncode<'k>,
+    {
+        let key = KC::bytes_encode(key).unwrap();
+        self.it.set_mode(IteratorMode::From(&key, self.direction));
+    }
+}
+
 impl<'a, KC: BytesDecode, DC: BytesDecode> Iterator for Iter<'a, KC, DC> {
-    type Item = Result<(KC::DItem, DC::DItem), rocksdb::Error>;
+    type Item = Result<(KC::DItem, DC::DItem), RockError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.it.next()? {
@@ -97,7 +173,7 @@ impl<'a, KC: BytesDecode, DC: BytesDecode> Iterator for Iter<'a, KC, DC> {
                 )));
             }
             Err(e) => {
-                return Some(Err(e));
+                return Some(Err(e.into()));
             }
         }
     }
@@ -140,8 +216,9 @@ impl<'store> Table<'store> for RockTable<'store> {
 
         match range.end_bound() {
             Bound::Included(i) => {
+                // The smallest key greater than the end is the end followed by a zero.
                 let mut v = KC::bytes_encode(i).unwrap().to_vec();
-                crate::iter::advance_key(&mut v);
+                v.push(0);
                 opt.set_iterate_upper_bound(v);
             }
             Bound::Excluded(i) => {
@@ -150,21 +227,24 @@ impl<'store> Table<'store> for RockTable<'store> {
             _ => {}
         };
 
+        // The lower bound is also given to the options, seeking can't leave the range.
         let it = match range.start_bound() {
             Bound::Included(i) => {
                 let k = KC::bytes_encode(i).unwrap().to_vec();
+                opt.set_iterate_lower_bound(k.clone());
                 txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
             }
             Bound::Excluded(i) => {
                 let mut k = KC::bytes_encode(i).unwrap().to_vec();
-                advance_key(&mut k);
+                k.push(0);
+                opt.set_iterate_lower_bound(k.clone());
 
                 txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
             }
             Bound::Unbounded => txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::Start),
         };
 
-        Ok(Iter { it, _p: Default::default() })
+        Ok(Iter { it, direction: Direction::Forward, _p: Default::default() })
     }
 
     fn rev_range<'a, 'txn, KC, DC, R>(
@@ -184,30 +264,63 @@ impl<'store> Table<'store> for RockTable<'store> {
                 let v = KC::bytes_encode(i).unwrap().to_vec();
                 opt.set_iterate_lower_bound(v);
             }
-            Bound::Excluded(..) => {
-                panic!("Excluded lower bound");
+            Bound::Excluded(i) => {
+                let mut v = KC::bytes_encode(i).unwrap().to_vec();
+                v.push(0);
+                opt.set_iterate_lower_bound(v);
             }
             _ => {}
         };
 
+        // The upper bound is also given to the options, seeking can't leave the range.
         let it = match range.end_bound() {
             Bound::Included(i) => {
-                let k = KC::bytes_encode(i).unwrap();
+                let k = KC::bytes_encode(i).unwrap().to_vec();
+                let mut upper = k.clone();
+                upper.push(0);
+                opt.set_iterate_upper_bound(upper);
                 txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
             }
             Bound::Excluded(i) => {
-                let mut k = KC::bytes_encode(i).unwrap().to_vec();
-                crate::iter::retreat_key(&mut k);
+                // Seeking the end itself, the upper bound excludes it.
+                let k = KC::bytes_encode(i).unwrap().to_vec();
+                opt.set_iterate_upper_bound(k.clone());
                 txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
             }
             Bound::Unbounded => txn.db.iterator_cf_opt(&self.cf, opt, IteratorMode::End),
         };
 
-        Ok(Iter { it, _p: Default::default() })
+        Ok(Iter { it, direction: Direction::Reverse, _p: Default::default() })
     }
 
     fn len<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>> {
-        Ok(txn.db.iterator(IteratorMode::Start).count())
+        let mut it = txn.db.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+
+        let mut len = 0;
+        while it.valid() {
+            len += 1;
+            it.next();
+        }
+        it.status()?;
+
+        Ok(len)
+    }
+
+    fn estimated_len<'txn>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+    ) -> Result<usize, ErrorOf<Self::Store>> {
+        let estimate = txn.db.property_int_value_cf(&self.cf, "rocksdb.estimate-num-keys")?;
+        Ok(estimate.unwrap_or(0) as usize)
+    }
+
+    fn is_empty<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<bool, ErrorOf<Self::Store>> {
+        let mut it = txn.db.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+        it.status()?;
+
+        Ok(!it.valid())
     }
 
     fn put<'a, KC, DC>(
@@ -222,6 +335,7 @@ impl<'store> Table<'store> for RockTable<'store> {
     {
         let k = KC::bytes_encode(key).unwrap();
         let v = DC::bytes_encode(data).unwrap();
+        txn.appended.forget(&self.name);
         txn.rtx.db.put_cf(&self.cf, k, v)?;
 
         Ok(())
@@ -237,7 +351,18 @@ impl<'store> Table<'store> for RockTable<'store> {
         KC: BytesEncode<'a>,
         DC: BytesEncode<'a>,
     {
-        self.put::<KC, DC>(txn, key, data)
+        let k = KC::bytes_encode(key).unwrap();
+        let v = DC::bytes_encode(data).unwrap();
+        let db = txn.rtx.db;
+        txn.appended.push(&self.name, &k, || {
+            let mut it = db.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+            it.seek_to_last();
+            it.status()?;
+            Ok(it.key().map(<[u8]>::to_vec))
+        })?;
+        db.put_cf(&self.cf, k, v)?;
+
+        Ok(())
     }
 
     fn delete<'a, KC>(
@@ -249,13 +374,109 @@ impl<'store> Table<'store> for RockTable<'store> {
         KC: BytesEncode<'a>,
     {
         let k = KC::bytes_encode(key).unwrap();
+        txn.appended.forget(&self.name);
         txn.rtx.db.delete_cf(&self.cf, k)?;
         Ok(())
     }
 
     fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
-        txn.rtx.db.delete_range_cf(&self.cf, &[][..], &vec![0xFF; 512][..])?;
+        txn.appended.forget(&self.name);
+        // No key sorts above the last one, the range end is exclusive so we delete it apart.
+        let mut it = txn.rtx.db.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_last();
+        it.status()?;
+        let last = match it.key() {
+            Some(last) => last.to_vec(),
+            None => return Ok(()),
+        };
+        drop(it);
 
+        let mut batch = WriteBatch::default();
+        batch.delete_range_cf(&self.cf, &[][..], &last[..]);
+        batch.delete_cf(&self.cf, &last);
+        txn.rtx.db.write(batch)?;
         Ok(())
     }
+
+    fn raw_iter<'txn, E, F>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        mut visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        let mut it = txn.db.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+        while let Some((key, data)) = it.item() {
+            visit(key, data).map_err(Either::Right)?;
+            it.next();
+        }
+
+        it.status().map_err(|e| Either::Left(e.into()))
+    }
+
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>> {
+        txn.appended.forget(&self.name);
+        txn.rtx.db.merge_cf(&self.cf, key, delta.to_be_bytes())?;
+        let value = txn.rtx.db.get_pinned_cf_opt(&self.cf, key, &ReadOptions::default())?;
+        Ok(value.map_or(0, |value| counters::decode(&value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::ByteSlice;
+    use rocksdb::Options;
+    use tempfile::tempdir;
+
+    use super::DBType;
+    use crate::store::counters::Counters;
+    use crate::store::{Store, Table, Transaction, WritableStore};
+
+    #[test]
+    fn clear_removes_keys_above_any_sentinel() {
+        let dir = tempdir().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        let db = DBType::open(&opts, dir.path()).unwrap();
+        let table = db.table("table", &Options::default()).unwrap();
+
+        let keys = [vec![], vec![0x00], vec![0xFF; 512], vec![0xFF; 513], vec![0xFF; 4096]];
+        let mut wtx = db.wtx().unwrap();
+        for key in &keys {
+            table.put::<ByteSlice, ByteSlice>(&mut wtx, key, b"value").unwrap();
+        }
+        table.clear(&mut wtx).unwrap();
+        wtx.commit().unwrap();
+
+        let rtx = db.rtx().unwrap();
+        assert_eq!(table.len(&rtx).unwrap(), 0);
+    }
+
+    #[test]
+    fn counters_merge_the_deltas() {
+        let dir = tempdir().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        let db = DBType::open(&opts, dir.path()).unwrap();
+        let counters = Counters::open(&db, "counters").unwrap();
+
+        let mut wtx = db.wtx().unwrap();
+        assert_eq!(counters.incr(&mut wtx, "hits", 2).unwrap(), 2);
+        assert_eq!(counters.incr(&mut wtx, "hits", -5).unwrap(), -3);
+        wtx.commit().unwrap();
+
+        let rtx = db.rtx().unwrap();
+        assert_eq!(counters.get(&rtx, "hits").unwrap(), -3);
+        assert_eq!(
+            counters.snapshot(&rtx).unwrap().into_iter().collect::<Vec<_>>(),
+            [("hits".to_string(), -3)]
+        );
+    }
 }
@@ -1,50 +1,86 @@
 use std::collections::Bound;
 use std::marker::PhantomData;
 use std::ops::{Deref, RangeBounds};
+use std::fmt;
 use std::sync::Arc;
 
+use either::Either;
 use heed_traits::{BytesDecode, BytesEncode};
-use heed_types::{ByteSlice, Unit};
 use rocksdb::{
     BoundColumnFamily, DBIteratorWithThreadMode, Direction, ErrorKind, IteratorMode, MultiThreaded,
     Options, ReadOptions, TransactionDB,
 };
 
-use crate::iter::advance_key;
-use crate::store::{ErrorOf, RtxOf, Store, Table, Transaction, WtxOf};
+use crate::store::counters;
+use crate::store::rck::{counters_options, table_names, AppendedKeys, RockError};
+use crate::store::{Capabilities, ErrorOf, RtxOf, Store, Table, Transaction, WritableStore, WtxOf};
+use crate::Extensions;
 
 pub type DBType = TransactionDB<MultiThreaded>;
 
 impl Store for DBType {
-    type Error = rocksdb::Error;
+    type Error = RockError;
     type Rtx<'e> = RockTxn<'e>;
-    type Wtx<'e> = WRockTxn<'e>;
     type Table<'store> = RockTable<'store>;
     type Config = Options;
 
-    fn table(&self, name: &str, opts: &Self::Config) -> Result<Self::Table<'_>, Self::Error> {
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        opts: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
         match self.create_cf(name, opts) {
             Ok(..) => {}
             Err(e)
                 if e.kind() == ErrorKind::InvalidArgument
                     && e.to_string().contains("Column family already exists") => {}
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into()),
         };
         let cf = self.cf_handle(name).unwrap();
-        Ok(RockTable { cf })
+        Ok(RockTable { name: name.to_owned(), cf })
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let name = name.as_ref();
+        if !self.table_exists(name)? {
+            return Ok(None);
+        }
+        // A column family listed on disk is only missing its handle while it is dropped.
+        Ok(self.cf_handle(name).map(|cf| RockTable { name: name.to_owned(), cf }))
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        Ok(table_names(Self::list_cf(&Options::default(), self.path())?))
     }
 
     fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
-        Ok(RockTxn { tx: self.transaction() })
+        Ok(RockTxn { db: self, tx: self.transaction(), extensions: Extensions::new() })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        // Transactions are not given a snapshot, reads see the commits made after they began.
+        Capabilities::PERSISTENT | Capabilities::CRASH_SAFE
+    }
+
+    fn counters_config(&self) -> Self::Config {
+        counters_options()
     }
+}
+
+impl WritableStore for DBType {
+    type Wtx<'e> = WRockTxn<'e>;
 
     fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
-        Ok(WRockTxn { db: RockTxn { tx: self.transaction() } })
+        let db = RockTxn { db: self, tx: self.transaction(), extensions: Extensions::new() };
+        Ok(WRockTxn { db, appended: AppendedKeys::default() })
     }
 }
 
+#[must_use = "a write transaction is aborted as soon as it is dropped, its changes are discarded"]
 pub struct WRockTxn<'a> {
     db: RockTxn<'a>,
+    appended: AppendedKeys,
 }
 
 impl<'a> Deref for WRockTxn<'a> {
@@ -57,36 +93,80 @@ impl<'a> Deref for WRockTxn<'a> {
 
 impl Transaction<DBType> for WRockTxn<'_> {
     fn commit(self) -> Result<(), ErrorOf<DBType>> {
-        rocksdb::Transaction::commit(self.db.tx)
+        rocksdb::Transaction::commit(self.db.tx)?;
+        Ok(())
+    }
+
+    fn extensions(&self) -> &Extensions {
+        &self.db.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(&mut self.db.extensions)
     }
 }
 
+#[must_use = "a read transaction is aborted as soon as it is dropped"]
 pub struct RockTxn<'a> {
+    db: &'a DBType,
     tx: rocksdb::Transaction<'a, TransactionDB<MultiThreaded>>,
+    extensions: Extensions,
 }
 
 impl Transaction<DBType> for RockTxn<'_> {
     fn commit(self) -> Result<(), ErrorOf<DBType>> {
-        rocksdb::Transaction::commit(self.tx)
+        rocksdb::Transaction::commit(self.tx)?;
+        Ok(())
+    }
+
+    fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut Extensions> {
+        Some(&mut self.extensions)
     }
 }
 
 #[derive(Clone)]
 pub struct RockTable<'store> {
+    name: String,
     cf: Arc<BoundColumnFamily<'store>>,
 }
 
+impl fmt::Debug for RockTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RockTable").field("name", &self.name).finish()
+    }
+}
+
 unsafe impl<'store> Send for RockTable<'store> {}
 
 unsafe impl<'store> Sync for RockTable<'store> {}
 
 pub struct Iter<'a, KC: BytesDecode, DC: BytesDecode> {
     it: DBIteratorWithThreadMode<'a, rocksdb::Transaction<'a, DBType>>,
+    direction: Direction,
     _p: PhantomData<(KC, DC)>,
 }
 
+impl<'a, KC: BytesDecode, DC: BytesDecode> Iter<'a, KC, DC> {
+    /// Moves the iterator so that the next entry returned is the first one with a key
+    /// greater than or equal to `key`, or lower than or equal to it when iterating
+    /// in reverse, without recreating the iterator.
+    ///
+    /// The iterator never leaves the bounds of the range it was created with.
+    pub fn seek<'k>(&mut self, key: &'k KC::EItem)
+    where
+        KC: BytesEncode<'k>,
+    {
+        let key = KC::bytes_encode(key).unwrap();
+        self.it.set_mode(IteratorMode::From(&key, self.direction));
+    }
+}
+
 impl<'a, KC: BytesDecode, DC: BytesDecode> Iterator for Iter<'a, KC, DC> {
-    type Item = Result<(KC::DItem, DC::DItem), rocksdb::Error>;
+    type Item = Result<(KC::DItem, DC::DItem), RockError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.it.next()? {
@@ -98,7 +178,7 @@ impl<'a, KC: BytesDecode, DC: BytesDecode> Iterator for Iter<'a, KC, DC> {
                 )));
             }
             Err(e) => {
-                return Some(Err(e));
+                return Some(Err(e.into()));
             }
         }
     }
@@ -128,6 +208,21 @@ impl<'store> Table<'store> for RockTable<'store> {
         }))
     }
 
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        let key = KC::bytes_encode(key).unwrap();
+        let opts = ReadOptions::default();
+        let data = txn.tx.get_pinned_for_update_cf_opt(&self.cf, key, true, &opts)?;
+        Ok(data.and_then(|v| DC::bytes_decode(&v)))
+    }
+
     fn range<'a, 'txn, KC, DC, R>(
         &self,
         txn: &'txn RtxOf<Self::Store>,
@@ -142,8 +237,9 @@ impl<'store> Table<'store> for RockTable<'store> {
 
         match range.end_bound() {
             Bound::Included(i) => {
+                // The smallest key greater than the end is the end followed by a zero.
                 let mut v = KC::bytes_encode(i).unwrap().to_vec();
-                crate::iter::advance_key(&mut v);
+                v.push(0);
                 opt.set_iterate_upper_bound(v);
             }
             Bound::Excluded(i) => {
@@ -152,21 +248,24 @@ impl<'store> Table<'store> for RockTable<'store> {
             _ => {}
         };
 
+        // The lower bound is also given to the options, seeking can't leave the range.
         let it = match range.start_bound() {
             Bound::Included(i) => {
                 let k = KC::bytes_encode(i).unwrap().to_vec();
+                opt.set_iterate_lower_bound(k.clone());
                 txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
             }
             Bound::Excluded(i) => {
                 let mut k = KC::bytes_encode(i).unwrap().to_vec();
-                advance_key(&mut k);
+                k.push(0);
+                opt.set_iterate_lower_bound(k.clone());
 
                 txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Forward))
             }
             Bound::Unbounded => txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::Start),
         };
 
-        Ok(Iter { it, _p: Default::default() })
+        Ok(Iter { it, direction: Direction::Forward, _p: Default::default() })
     }
 
     fn rev_range<'a, 'txn, KC, DC, R>(
@@ -186,30 +285,63 @@ impl<'store> Table<'store> for RockTable<'store> {
                 let v = KC::bytes_encode(i).unwrap().to_vec();
                 opt.set_iterate_lower_bound(v);
             }
-            Bound::Excluded(..) => {
-                panic!("Excluded lower bound");
+            Bound::Excluded(i) => {
+                let mut v = KC::bytes_encode(i).unwrap().to_vec();
+                v.push(0);
+                opt.set_iterate_lower_bound(v);
             }
             _ => {}
         };
 
+        // The upper bound is also given to the options, seeking can't leave the range.
         let it = match range.end_bound() {
             Bound::Included(i) => {
-                let k = KC::bytes_encode(i).unwrap();
+                let k = KC::bytes_encode(i).unwrap().to_vec();
+                let mut upper = k.clone();
+                upper.push(0);
+                opt.set_iterate_upper_bound(upper);
                 txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
             }
             Bound::Excluded(i) => {
-                let mut k = KC::bytes_encode(i).unwrap().to_vec();
-                crate::iter::retreat_key(&mut k);
+                // Seeking the end itself, the upper bound excludes it.
+                let k = KC::bytes_encode(i).unwrap().to_vec();
+                opt.set_iterate_upper_bound(k.clone());
                 txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::From(&k, Direction::Reverse))
             }
             Bound::Unbounded => txn.tx.iterator_cf_opt(&self.cf, opt, IteratorMode::End),
         };
 
-        Ok(Iter { it, _p: Default::default() })
+        Ok(Iter { it, direction: Direction::Reverse, _p: Default::default() })
     }
 
     fn len<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<usize, ErrorOf<Self::Store>> {
-        Ok(txn.tx.iterator(IteratorMode::Start).count())
+        let mut it = txn.tx.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+
+        let mut len = 0;
+        while it.valid() {
+            len += 1;
+            it.next();
+        }
+        it.status()?;
+
+        Ok(len)
+    }
+
+    fn estimated_len<'txn>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+    ) -> Result<usize, ErrorOf<Self::Store>> {
+        let estimate = txn.db.property_int_value_cf(&self.cf, "rocksdb.estimate-num-keys")?;
+        Ok(estimate.unwrap_or(0) as usize)
+    }
+
+    fn is_empty<'txn>(&self, txn: &'txn RtxOf<Self::Store>) -> Result<bool, ErrorOf<Self::Store>> {
+        let mut it = txn.tx.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+        it.status()?;
+
+        Ok(!it.valid())
     }
 
     fn put<'a, KC, DC>(
@@ -224,6 +356,7 @@ impl<'store> Table<'store> for RockTable<'store> {
     {
         let k = KC::bytes_encode(key).unwrap();
         let v = DC::bytes_encode(data).unwrap();
+        txn.appended.forget(&self.name);
         txn.tx.put_cf(&self.cf, k, v)?;
 
         Ok(())
@@ -239,7 +372,18 @@ impl<'store> Table<'store> for RockTable<'store> {
         KC: BytesEncode<'a>,
         DC: BytesEncode<'a>,
     {
-        self.put::<KC, DC>(txn, key, data)
+        let k = KC::bytes_encode(key).unwrap();
+        let v = DC::bytes_encode(data).unwrap();
+        let tx = &txn.db.tx;
+        txn.appended.push(&self.name, &k, || {
+            let mut it = tx.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+            it.seek_to_last();
+            it.status()?;
+            Ok(it.key().map(<[u8]>::to_vec))
+        })?;
+        tx.put_cf(&self.cf, k, v)?;
+
+        Ok(())
     }
 
     fn delete<'a, KC>(
@@ -251,18 +395,106 @@ impl<'store> Table<'store> for RockTable<'store> {
         KC: BytesEncode<'a>,
     {
         let k = KC::bytes_encode(key).unwrap();
+        txn.appended.forget(&self.name);
         txn.tx.delete_cf(&self.cf, k)?;
         Ok(())
     }
 
     fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
-        let items =
-            self.range::<ByteSlice, Unit, _>(txn, &..).unwrap().collect::<Result<Vec<_>, _>>()?;
+        txn.appended.forget(&self.name);
+        // Transactions have no range deletion, we delete the keys by batches and refresh
+        // the iterator in between as it must not outlive the writes to the transaction.
+        const BATCH_SIZE: usize = 1024;
+
+        let mut keys: Vec<Vec<u8>> = Vec::with_capacity(BATCH_SIZE);
+        loop {
+            let mut it = txn.tx.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+            match keys.last() {
+                Some(last) => it.seek(last),
+                None => it.seek_to_first(),
+            }
 
-        for (k, _) in items {
-            self.delete::<ByteSlice>(txn, &k)?;
+            keys.clear();
+            while let Some(key) = it.key().filter(|_| keys.len() < BATCH_SIZE) {
+                keys.push(key.to_vec());
+                it.next();
+            }
+            it.status()?;
+            drop(it);
+
+            if keys.is_empty() {
+                return Ok(());
+            }
+            for key in &keys {
+                txn.tx.delete_cf(&self.cf, key)?;
+            }
         }
+    }
 
-        Ok(())
+    fn raw_iter<'txn, E, F>(
+        &self,
+        txn: &'txn RtxOf<Self::Store>,
+        mut visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        let mut it = txn.tx.raw_iterator_cf_opt(&self.cf, ReadOptions::default());
+        it.seek_to_first();
+        while let Some((key, data)) = it.item() {
+            visit(key, data).map_err(Either::Right)?;
+            it.next();
+        }
+
+        it.status().map_err(|e| Either::Left(e.into()))
+    }
+
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>> {
+        txn.appended.forget(&self.name);
+        txn.tx.merge_cf(&self.cf, key, delta.to_be_bytes())?;
+        let value = txn.tx.get_pinned_cf_opt(&self.cf, key, &ReadOptions::default())?;
+        Ok(value.map_or(0, |value| counters::decode(&value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::ByteSlice;
+    use rocksdb::{Options, TransactionDBOptions};
+    use tempfile::tempdir;
+
+    use super::DBType;
+    use crate::store::{Store, Table, Transaction, WritableStore};
+
+    #[test]
+    fn clear_deletes_every_batch() {
+        let dir = tempdir().unwrap();
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        let db = DBType::open(&opts, &TransactionDBOptions::default(), dir.path()).unwrap();
+        let table = db.table("table", &Options::default()).unwrap();
+        let other = db.table("other", &Options::default()).unwrap();
+
+        // More keys than a batch, the last batch isn't full.
+        let mut wtx = db.wtx().unwrap();
+        for i in 0u32..2500 {
+            table.put::<ByteSlice, ByteSlice>(&mut wtx, &i.to_be_bytes(), b"value").unwrap();
+        }
+        other.put::<ByteSlice, ByteSlice>(&mut wtx, b"kept", b"value").unwrap();
+        wtx.commit().unwrap();
+
+        let mut wtx = db.wtx().unwrap();
+        table.clear(&mut wtx).unwrap();
+        assert!(table.is_empty(&wtx).unwrap());
+        wtx.commit().unwrap();
+
+        let rtx = db.rtx().unwrap();
+        assert_eq!(table.len(&rtx).unwrap(), 0);
+        assert_eq!(other.len(&rtx).unwrap(), 1);
     }
 }
@@ -0,0 +1,350 @@
+//! A wrapper of a [`Store`] that can only be read, to give to the components that must
+//! not write: it doesn't implement [`WritableStore`], the write transactions and the
+//! writes of its tables don't type check.
+//!
+//! The tables aren't created either, [`Store::table`] opens them like [`Store::open_table`]
+//! and returns a [`MissingTable`](ReadOnlyError::MissingTable) error for a table that
+//! doesn't exist.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::readonly::{ReadOnlyError, ReadOnlyStore};
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! /// Only reads the balances, it can't be given a writable store by mistake.
+//! fn balance<S: Store>(
+//!     store: &ReadOnlyStore<S>,
+//!     account: &str,
+//! ) -> Result<u64, ReadOnlyError<S::Error>> {
+//!     let balances = store.typed::<Str, OwnedType<u64>>("balances", &Default::default())?;
+//!     let rtx = store.rtx()?;
+//!     Ok(balances.get(&rtx, account)?.unwrap_or(0))
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let balances = env.typed::<Str, OwnedType<u64>>("balances", &())?;
+//! let mut wtx = env.wtx()?;
+//! balances.put(&mut wtx, "alice", &10)?;
+//! wtx.commit()?;
+//!
+//! let store = ReadOnlyStore::new(env.clone());
+//! assert_eq!(balance(&store, "alice")?, 10);
+//! assert!(matches!(store.table("missing", &()), Err(ReadOnlyError::MissingTable(_))));
+//! # Ok(()) }
+//! ```
+//!
+//! ```compile_fail
+//! # use heed::EnvOpenOptions;
+//! use heed::store::readonly::ReadOnlyStore;
+//! use heed::store::WritableStore;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().open(dir.path())?;
+//! let store = ReadOnlyStore::new(env);
+//! let wtx = store.wtx()?;
+//! # Ok(()) }
+//! ```
+
+use std::ops::{Deref, RangeBounds};
+use std::{error, fmt};
+
+use either::Either;
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::store::{
+    Capabilities, ErrorOf, RangeOf, RevRangeOf, RtxOf, SizeHistogram, Store, Table, TableOf,
+    Transaction, WritableStore, WtxOf,
+};
+
+/// An error of a [`ReadOnlyStore`].
+#[derive(Debug)]
+pub enum ReadOnlyError<E> {
+    /// An error of the wrapped store.
+    Store(E),
+    /// The table opened doesn't exist, a read-only store doesn't create it.
+    MissingTable(String),
+}
+
+impl<E: fmt::Display> fmt::Display for ReadOnlyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadOnlyError::Store(error) => write!(f, "{}", error),
+            ReadOnlyError::MissingTable(name) => {
+                write!(f, "the {:?} table doesn't exist, a read-only store doesn't create it", name)
+            }
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for ReadOnlyError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ReadOnlyError::Store(error) => Some(error),
+            ReadOnlyError::MissingTable(_) => None,
+        }
+    }
+}
+
+/// A [`Store`] that can only be read, see the [module documentation](self).
+pub struct ReadOnlyStore<S>(S);
+
+impl<S: Store> ReadOnlyStore<S> {
+    pub fn new(store: S) -> ReadOnlyStore<S> {
+        ReadOnlyStore(store)
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+}
+
+impl<S: Store> Store for ReadOnlyStore<S> {
+    type Error = ReadOnlyError<S::Error>;
+    type Rtx<'e> = ReadOnlyTxn<'e, S>;
+    type Table<'store> = ReadOnlyTable<'store, S>;
+    type Config = S::Config;
+
+    /// Opens the table like [`Store::open_table`], the configuration is ignored.
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        _cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
+        self.open_table(name)?.ok_or_else(|| ReadOnlyError::MissingTable(name.to_owned()))
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let table = self.0.open_table(name).map_err(ReadOnlyError::Store)?;
+        Ok(table.map(ReadOnlyTable))
+    }
+
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        self.0.table_exists(name).map_err(ReadOnlyError::Store)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        self.0.table_names().map_err(ReadOnlyError::Store)
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        self.0.rtx().map(ReadOnlyTxn).map_err(ReadOnlyError::Store)
+    }
+
+    fn counters_config(&self) -> Self::Config {
+        self.0.counters_config()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.0.capabilities()
+    }
+}
+
+/// A read transaction of a [`ReadOnlyStore`], it dereferences to the
+/// read transaction of the wrapped store.
+pub struct ReadOnlyTxn<'e, S: Store + 'e>(RtxOf<'e, S>);
+
+impl<'e, S: Store> Deref for ReadOnlyTxn<'e, S> {
+    type Target = RtxOf<'e, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: Store> Transaction<ReadOnlyStore<S>> for ReadOnlyTxn<'_, S> {
+    fn commit(self) -> Result<(), ErrorOf<ReadOnlyStore<S>>> {
+        self.0.commit().map_err(ReadOnlyError::Store)
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        self.0.extensions()
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        self.0.extensions_mut()
+    }
+}
+
+/// A table of a [`ReadOnlyStore`].
+pub struct ReadOnlyTable<'s, S: Store + 's>(TableOf<'s, S>);
+
+impl<'s, S: Store> Clone for ReadOnlyTable<'s, S>
+where
+    TableOf<'s, S>: Clone,
+{
+    fn clone(&self) -> Self {
+        ReadOnlyTable(self.0.clone())
+    }
+}
+
+impl<'s, S: Store> fmt::Debug for ReadOnlyTable<'s, S>
+where
+    TableOf<'s, S>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("ReadOnlyTable").field(&self.0).finish()
+    }
+}
+
+/// An iterator of a [`ReadOnlyTable`], the errors of the wrapped store are
+/// [`ReadOnlyError::Store`] errors.
+pub struct ReadOnlyIter<I>(I);
+
+impl<I, T, E> Iterator for ReadOnlyIter<I>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    type Item = Result<T, ReadOnlyError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| entry.map_err(ReadOnlyError::Store))
+    }
+}
+
+impl<'s, S: Store> Table<'s> for ReadOnlyTable<'s, S> {
+    type Store = ReadOnlyStore<S>;
+
+    type Range<'e, KC: BytesDecode, DC: BytesDecode> = ReadOnlyIter<RangeOf<'s, 'e, S, KC, DC>>;
+
+    type RevRange<'e, KC: BytesDecode, DC: BytesDecode> =
+        ReadOnlyIter<RevRangeOf<'s, 'e, S, KC, DC>>;
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn ReadOnlyTxn<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        self.0.get::<KC, DC>(&txn.0, key).map_err(ReadOnlyError::Store)
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn ReadOnlyTxn<S>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.0.range::<KC, DC, R>(&txn.0, range).map(ReadOnlyIter).map_err(ReadOnlyError::Store)
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn ReadOnlyTxn<S>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        let range = self.0.rev_range::<KC, DC, R>(&txn.0, range);
+        range.map(ReadOnlyIter).map_err(ReadOnlyError::Store)
+    }
+
+    fn len(&self, txn: &ReadOnlyTxn<S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.0.len(&txn.0).map_err(ReadOnlyError::Store)
+    }
+
+    fn is_empty(&self, txn: &ReadOnlyTxn<S>) -> Result<bool, ErrorOf<Self::Store>> {
+        self.0.is_empty(&txn.0).map_err(ReadOnlyError::Store)
+    }
+
+    fn estimated_len(&self, txn: &ReadOnlyTxn<S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.0.estimated_len(&txn.0).map_err(ReadOnlyError::Store)
+    }
+
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn ReadOnlyTxn<S>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.0.range_len::<KC, R>(&txn.0, range).map_err(ReadOnlyError::Store)
+    }
+
+    fn raw_iter<E, F>(
+        &self,
+        txn: &ReadOnlyTxn<S>,
+        visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        self.0.raw_iter(&txn.0, visit).map_err(|e| e.map_left(ReadOnlyError::Store))
+    }
+
+    fn warmup(&self, txn: &ReadOnlyTxn<S>) -> Result<u64, ErrorOf<Self::Store>> {
+        self.0.warmup(&txn.0).map_err(ReadOnlyError::Store)
+    }
+
+    fn size_histogram(&self, txn: &ReadOnlyTxn<S>) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        self.0.size_histogram(&txn.0).map_err(ReadOnlyError::Store)
+    }
+
+    // A read-only store has no write transaction, the writes can't be called.
+
+    fn put<'a, KC, DC>(
+        &self,
+        _txn: &mut WtxOf<Self::Store>,
+        _key: &'a KC::EItem,
+        _data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        unreachable!("a read-only store is written to")
+    }
+
+    fn append<'a, KC, DC>(
+        &self,
+        _txn: &mut WtxOf<Self::Store>,
+        _key: &'a KC::EItem,
+        _data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        unreachable!("a read-only store is written to")
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        _txn: &mut WtxOf<Self::Store>,
+        _key: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+        KC: BytesEncode<'a>,
+    {
+        unreachable!("a read-only store is written to")
+    }
+
+    fn clear(&self, _txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>>
+    where
+        Self::Store: WritableStore,
+    {
+        unreachable!("a read-only store is written to")
+    }
+}
@@ -0,0 +1,185 @@
+//! A description of the tables of any [`Store`], for admin dashboards and for the detection
+//! of the drift between the schemas of two stores.
+//!
+//! [`describe`] lists the tables of a store with their number of entries and their size, and
+//! the names of their codecs when they were opened with a [`CodecRegistry`]. The description
+//! is serializable with the `serde` feature, to JSON for example.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::schema::{describe, CodecRegistry};
+//! use heed::store::{Transaction, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().max_dbs(10).open(dir.path())?;
+//! let registry = CodecRegistry::new();
+//! let ages = registry.typed::<_, Str, OwnedType<u32>>(&env, "ages", &())?;
+//!
+//! let mut wtx = env.wtx()?;
+//! ages.put(&mut wtx, "alice", &32)?;
+//! wtx.commit()?;
+//!
+//! let schema = describe(&env, &registry)?;
+//! assert_eq!(schema.tables[0].name, "ages");
+//! assert_eq!(schema.tables[0].key_codec.as_deref(), Some("heed_types::str::Str"));
+//! assert_eq!(schema.tables[0].entries, 1);
+//! # Ok(()) }
+//! ```
+
+use std::any;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::store::{Store, Table, Transaction, Typed};
+
+/// The names of the codecs the tables are opened with, recorded to [`describe`] them.
+#[derive(Debug, Default)]
+pub struct CodecRegistry {
+    codecs: Mutex<BTreeMap<String, (&'static str, &'static str)>>,
+}
+
+impl CodecRegistry {
+    pub fn new() -> CodecRegistry {
+        CodecRegistry::default()
+    }
+
+    /// Records the codecs of the keys and of the values of a table,
+    /// the ones recorded before for the table are replaced.
+    pub fn register<KC, DC>(&self, table: impl Into<String>) {
+        let codecs = (any::type_name::<KC>(), any::type_name::<DC>());
+        self.codecs.lock().unwrap().insert(table.into(), codecs);
+    }
+
+    /// Opens a table with [`Store::typed`] and records its codecs.
+    pub fn typed<'s, S: Store, KC, DC>(
+        &self,
+        store: &'s S,
+        name: impl AsRef<str>,
+        cfg: &S::Config,
+    ) -> Result<Typed<'s, S, KC, DC>, S::Error> {
+        let table = store.typed::<KC, DC>(name.as_ref(), cfg)?;
+        self.register::<KC, DC>(name.as_ref());
+        Ok(table)
+    }
+
+    /// The names of the codecs of the keys and of the values of a table, if recorded.
+    pub fn codecs(&self, table: &str) -> Option<(&'static str, &'static str)> {
+        self.codecs.lock().unwrap().get(table).copied()
+    }
+}
+
+/// The tables of a store, see [`describe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SchemaDescription {
+    /// The tables, by name.
+    pub tables: Vec<TableDescription>,
+}
+
+/// A table of a store, see [`describe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableDescription {
+    pub name: String,
+    /// The name of the codec of the keys, if it was recorded in the registry.
+    pub key_codec: Option<String>,
+    /// The name of the codec of the values, if it was recorded in the registry.
+    pub data_codec: Option<String>,
+    pub entries: u64,
+    pub key_bytes: u64,
+    pub value_bytes: u64,
+}
+
+impl SchemaDescription {
+    /// The table of this name.
+    pub fn table(&self, name: &str) -> Option<&TableDescription> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+
+    /// The names of the tables that aren't in both descriptions, or whose codecs differ,
+    /// ignoring the codecs that aren't recorded. The entries and sizes are ignored.
+    pub fn drift<'a>(&'a self, other: &'a SchemaDescription) -> Vec<&'a str> {
+        let differ = |a: &Option<String>, b: &Option<String>| a.is_some() && b.is_some() && a != b;
+        let mut names: Vec<_> = self
+            .tables
+            .iter()
+            .filter(|table| match other.table(&table.name) {
+                Some(o) => {
+                    differ(&table.key_codec, &o.key_codec)
+                        || differ(&table.data_codec, &o.data_codec)
+                }
+                None => true,
+            })
+            .chain(other.tables.iter().filter(|table| self.table(&table.name).is_none()))
+            .map(|table| table.name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+/// Describes the tables of the store, reading all their entries to count their sizes.
+pub fn describe<S: Store>(
+    store: &S,
+    registry: &CodecRegistry,
+) -> Result<SchemaDescription, S::Error> {
+    // The tables are opened before the read transaction, LMDB opens one for each.
+    let mut tables = Vec::new();
+    for name in store.table_names()? {
+        tables.extend(store.open_table(&name)?.map(|table| (name, table)));
+    }
+
+    let rtx = store.rtx()?;
+    let mut described = Vec::with_capacity(tables.len());
+    for (name, table) in tables {
+        let histogram = table.size_histogram(&rtx)?;
+        let codecs = registry.codecs(&name);
+        described.push(TableDescription {
+            key_codec: codecs.map(|(key, _)| key.to_owned()),
+            data_codec: codecs.map(|(_, data)| data.to_owned()),
+            name,
+            entries: histogram.entries,
+            key_bytes: histogram.key_bytes,
+            value_bytes: histogram.value_bytes,
+        });
+    }
+    rtx.commit()?;
+
+    Ok(SchemaDescription { tables: described })
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{ByteSlice, Str};
+
+    use super::*;
+    use crate::store::WritableStore;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn the_drift_ignores_the_unrecorded_codecs() {
+        let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let a = EnvOpenOptions::new().max_dbs(3).open(dir_a.path()).unwrap();
+        let b = EnvOpenOptions::new().max_dbs(3).open(dir_b.path()).unwrap();
+        let (registry_a, registry_b) = (CodecRegistry::new(), CodecRegistry::new());
+
+        let names = registry_a.typed::<_, Str, Str>(&a, "names", &()).unwrap();
+        registry_a.typed::<_, Str, Str>(&a, "blobs", &()).unwrap();
+        a.table("extra", &()).unwrap();
+        registry_b.typed::<_, Str, Str>(&b, "names", &()).unwrap();
+        registry_b.typed::<_, Str, ByteSlice>(&b, "blobs", &()).unwrap();
+
+        let mut wtx = a.wtx().unwrap();
+        names.put(&mut wtx, "a", "alice").unwrap();
+        wtx.commit().unwrap();
+
+        let (schema_a, schema_b) =
+            (describe(&a, &registry_a).unwrap(), describe(&b, &registry_b).unwrap());
+        let described = schema_a.table("names").unwrap();
+        assert_eq!((described.entries, described.key_bytes, described.value_bytes), (1, 1, 5));
+        assert_eq!(schema_a.table("extra").unwrap().key_codec, None);
+        assert_eq!(schema_a.drift(&schema_b), ["blobs", "extra"]);
+    }
+}
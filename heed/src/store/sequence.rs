@@ -0,0 +1,229 @@
+//! The ids allocated for the keys of a [`Typed`] table whose key codec implements
+//! [`FromSequence`], to put a value under a generated id.
+//!
+//! The ids are allocated from a [`Sequence`] persisted with the table, in the `name:sequence`
+//! table, the first one is 0. An allocated id is never allocated again, even when it was
+//! deleted or when no value was put under it, and the ids follow the greatest key of the
+//! table, put without being allocated. Allocating an id once the key codec can't number the
+//! next one returns an [`Exhausted`](SequenceError::Exhausted) error.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::sequence::Sequence;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{Composite, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let users = env.typed::<Composite<u64>, Str>("users", &())?;
+//! let ids = Sequence::open(&env, "users")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! let alice = users.alloc_id(&mut wtxn, &ids)?;
+//! users.put(&mut wtxn, &alice, "alice")?;
+//! let bob = users.alloc_id(&mut wtxn, &ids)?;
+//! users.put(&mut wtxn, &bob, "bob")?;
+//! assert_eq!((alice, bob), (0, 1));
+//!
+//! users.delete(&mut wtxn, &bob)?;
+//! assert_eq!(users.alloc_id(&mut wtxn, &ids)?, 2);
+//! assert_eq!(users.get(&wtxn, &0)?, Some("alice".to_string()));
+//! wtxn.commit()?;
+//! # Ok(()) }
+//! ```
+
+use std::convert::TryInto;
+use std::{error, fmt};
+
+use byteorder::BigEndian;
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::{ByteSlice, Composite, DecodeIgnore, OwnedType};
+use zerocopy::{U32, U64};
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, Typed, WritableStore, WtxOf};
+
+const LAST_KEY: &[u8] = b"last";
+
+/// A key codec whose keys are numbered like the integers, the ids allocated
+/// by [`Typed::alloc_id`].
+///
+/// The encoded keys must sort like their numbers, like big endian integers.
+pub trait FromSequence: BytesDecode {
+    /// The key numbered `sequence`, `None` when it doesn't fit in the key.
+    fn from_sequence(sequence: u64) -> Option<Self::DItem>;
+
+    /// The number of a key.
+    fn to_sequence(key: &Self::DItem) -> u64;
+}
+
+impl FromSequence for Composite<u64> {
+    fn from_sequence(sequence: u64) -> Option<u64> {
+        Some(sequence)
+    }
+
+    fn to_sequence(key: &u64) -> u64 {
+        *key
+    }
+}
+
+impl FromSequence for Composite<u32> {
+    fn from_sequence(sequence: u64) -> Option<u32> {
+        sequence.try_into().ok()
+    }
+
+    fn to_sequence(key: &u32) -> u64 {
+        u64::from(*key)
+    }
+}
+
+impl FromSequence for OwnedType<U64<BigEndian>> {
+    fn from_sequence(sequence: u64) -> Option<U64<BigEndian>> {
+        Some(U64::new(sequence))
+    }
+
+    fn to_sequence(key: &U64<BigEndian>) -> u64 {
+        key.get()
+    }
+}
+
+impl FromSequence for OwnedType<U32<BigEndian>> {
+    fn from_sequence(sequence: u64) -> Option<U32<BigEndian>> {
+        sequence.try_into().ok().map(U32::new)
+    }
+
+    fn to_sequence(key: &U32<BigEndian>) -> u64 {
+        u64::from(key.get())
+    }
+}
+
+/// An error of [`Typed::alloc_id`].
+#[derive(Debug)]
+pub enum SequenceError<E> {
+    /// An error of the store.
+    Store(E),
+    /// The key codec can't number the id following the last allocated one.
+    Exhausted,
+}
+
+impl<E: fmt::Display> fmt::Display for SequenceError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SequenceError::Store(error) => write!(f, "{}", error),
+            SequenceError::Exhausted => f.write_str("the ids of the table are exhausted"),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for SequenceError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SequenceError::Store(error) => Some(error),
+            SequenceError::Exhausted => None,
+        }
+    }
+}
+
+impl<E> From<E> for SequenceError<E> {
+    fn from(error: E) -> SequenceError<E> {
+        SequenceError::Store(error)
+    }
+}
+
+/// The persisted sequence of the ids allocated for the keys of a table.
+///
+/// See the [module documentation](self).
+pub struct Sequence<'s, S: Store + 's> {
+    meta: Typed<'s, S, ByteSlice, Composite<u64>>,
+}
+
+impl<'s, S: Store> Sequence<'s, S> {
+    /// Opens the sequence of the ids of the `name` table, stored in the `name:sequence` table.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let meta = store.typed(format!("{}:sequence", name), &S::Config::default())?;
+        Ok(Sequence { meta })
+    }
+
+    /// The last id allocated, `None` when none was.
+    pub fn last(&self, rtx: &RtxOf<S>) -> Result<Option<u64>, ErrorOf<S>> {
+        self.meta.get(rtx, LAST_KEY)
+    }
+}
+
+impl<'s, S: WritableStore, KC, DC> Typed<'s, S, KC, DC>
+where
+    KC: FromSequence + for<'a> BytesEncode<'a, EItem = <KC as BytesDecode>::DItem>,
+{
+    /// Allocates the id following the last one allocated from the sequence and the greatest
+    /// key of the table, 0 for the first one. The id isn't allocated again, even if no value
+    /// is put under it.
+    pub fn alloc_id(
+        &self,
+        txn: &mut WtxOf<S>,
+        sequence: &Sequence<S>,
+    ) -> Result<KC::DItem, SequenceError<ErrorOf<S>>> {
+        let last = self.dyndb.rev_range::<KC, DecodeIgnore, _>(txn, &..)?.next().transpose()?;
+        let last_key = last.map(|(key, ())| KC::to_sequence(&key));
+        let last = sequence.last(txn)?.max(last_key);
+
+        let id = match last {
+            Some(last) => last.checked_add(1).ok_or(SequenceError::Exhausted)?,
+            None => 0,
+        };
+        let key = KC::from_sequence(id).ok_or(SequenceError::Exhausted)?;
+        sequence.meta.put(txn, LAST_KEY, &id)?;
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::Unit;
+
+    use super::*;
+
+    #[test]
+    fn narrow_keys_dont_wrap() {
+        assert_eq!(Composite::<u32>::from_sequence(u64::from(u32::MAX)), Some(u32::MAX));
+        assert_eq!(Composite::<u32>::from_sequence(u64::from(u32::MAX) + 1), None);
+        assert_eq!(OwnedType::<U32<BigEndian>>::from_sequence(1 << 32), None);
+    }
+
+    #[test]
+    fn ids_are_never_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let table = env.typed::<Composite<u64>, Unit>("table", &()).unwrap();
+        let sequence = Sequence::open(&env, "table").unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        let first = table.alloc_id(&mut wtx, &sequence).unwrap();
+        table.put(&mut wtx, &first, &()).unwrap();
+        table.delete(&mut wtx, &first).unwrap();
+        assert_eq!(table.alloc_id(&mut wtx, &sequence).unwrap(), 1);
+        // an allocated id isn't given again, even without a value put under it
+        assert_eq!(table.alloc_id(&mut wtx, &sequence).unwrap(), 2);
+
+        // the keys put without being allocated are followed
+        table.put(&mut wtx, &10, &()).unwrap();
+        assert_eq!(table.alloc_id(&mut wtx, &sequence).unwrap(), 11);
+        assert_eq!(sequence.last(&wtx).unwrap(), Some(11));
+    }
+
+    #[test]
+    fn exhausted_ids_are_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let table = env.typed::<Composite<u32>, Unit>("table", &()).unwrap();
+        let sequence = Sequence::open(&env, "table").unwrap();
+
+        let mut wtx = env.wtx().unwrap();
+        table.put(&mut wtx, &(u32::MAX - 1), &()).unwrap();
+        assert_eq!(table.alloc_id(&mut wtx, &sequence).unwrap(), u32::MAX);
+        let exhausted = table.alloc_id(&mut wtx, &sequence);
+        assert!(matches!(exhausted, Err(SequenceError::Exhausted)));
+    }
+}
@@ -0,0 +1,343 @@
+//! Shadow writes of a table to a second table with a new value codec, to migrate the codec
+//! of its values progressively and check the new codec against the old one on the way.
+//!
+//! A [`ShadowedTable`] writes every value to the old table with the old codec and to the new
+//! table with the new codec, in the same write transaction, and reads the values of the old
+//! table. Its reads also read the new table, and record a [`Divergence`] when the values
+//! differ, the last one of each key, [`ShadowedTable::verify`] compares every entry of the
+//! tables. Once the new table was
+//! filled, by shadow writes or a copy, and no divergence is reported for long enough, the
+//! readers switch to the new table.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::shadow::ShadowedTable;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{Composite, OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! // The balances were stored in native endian, they are moving to a portable codec.
+//! let balances = ShadowedTable::<_, Str, OwnedType<u64>, Composite<u64>>::open(
+//!     &env,
+//!     "balances",
+//!     "balances.v2",
+//! )?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! balances.put(&mut wtxn, "alice", &42)?;
+//! assert_eq!(balances.get(&wtxn, "alice")?, Some(42));
+//! assert_eq!(balances.verify(&wtxn)?, 0);
+//! assert!(balances.divergences().is_empty());
+//! # Ok(()) }
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::{error, fmt};
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, Typed, WritableStore, WtxOf};
+
+/// How the entries of the old and the new table of a [`ShadowedTable`] differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// The key only has a value in the old table.
+    MissingInNew,
+    /// The key only has a value in the new table.
+    MissingInOld,
+    /// The key has different values in the tables, or one of them can't be decoded.
+    Differ,
+}
+
+/// An entry of the old and the new table of a [`ShadowedTable`] that differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The encoded key of the entry.
+    pub key: Vec<u8>,
+    pub kind: DivergenceKind,
+}
+
+/// An error of [`ShadowedTable::get`].
+#[derive(Debug)]
+pub enum ShadowError<E> {
+    /// An error of the store.
+    Store(E),
+    /// The key can't be encoded by the key codec.
+    Encoding,
+}
+
+impl<E: fmt::Display> fmt::Display for ShadowError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShadowError::Store(error) => write!(f, "{}", error),
+            ShadowError::Encoding => f.write_str("the key can't be encoded"),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for ShadowError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ShadowError::Store(error) => Some(error),
+            ShadowError::Encoding => None,
+        }
+    }
+}
+
+impl<E> From<E> for ShadowError<E> {
+    fn from(error: E) -> ShadowError<E> {
+        ShadowError::Store(error)
+    }
+}
+
+/// A table whose values are written to an old and a new table, see the
+/// [module documentation](self).
+pub struct ShadowedTable<'s, S: Store + 's, KC, OldC, NewC> {
+    old: Typed<'s, S, KC, OldC>,
+    new: Typed<'s, S, KC, NewC>,
+    /// The last divergence of each key.
+    divergences: Mutex<BTreeMap<Vec<u8>, DivergenceKind>>,
+}
+
+impl<'s, S: Store, KC, OldC, NewC> ShadowedTable<'s, S, KC, OldC, NewC>
+where
+    OldC: BytesDecode + for<'a> BytesEncode<'a, EItem = <OldC as BytesDecode>::DItem>,
+    NewC: BytesDecode<DItem = OldC::DItem>
+        + for<'a> BytesEncode<'a, EItem = <OldC as BytesDecode>::DItem>,
+    OldC::DItem: PartialEq,
+{
+    /// Opens the old table `old` and the new table `new`, creating them if needed.
+    pub fn open(store: &'s S, old: &str, new: &str) -> Result<Self, ErrorOf<S>> {
+        let cfg = S::Config::default();
+        let old = store.typed(old, &cfg)?;
+        let new = store.typed(new, &cfg)?;
+        Ok(ShadowedTable { old, new, divergences: Mutex::new(BTreeMap::new()) })
+    }
+
+    /// The value of the key in the old table, the value of the new table is compared to it.
+    pub fn get<'a>(
+        &self,
+        rtx: &RtxOf<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<OldC::DItem>, ShadowError<ErrorOf<S>>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        let key = KC::bytes_encode(key).ok_or(ShadowError::Encoding)?;
+        let old = self.old.dyndb.get::<ByteSlice, ByteSlice>(rtx, &key)?;
+        let new = self.new.dyndb.get::<ByteSlice, ByteSlice>(rtx, &key)?;
+        let kind = compare::<OldC, NewC>(old.as_deref(), new.as_deref());
+        if let Some(kind) = kind {
+            self.divergences.lock().unwrap().insert(key.into_owned(), kind);
+        }
+        Ok(old.and_then(|old| OldC::bytes_decode(&old)))
+    }
+
+    /// Compares every entry of the tables, records the divergences and returns their number.
+    pub fn verify(&self, rtx: &RtxOf<S>) -> Result<usize, ErrorOf<S>> {
+        let mut old = self.old.dyndb.range::<ByteSlice, ByteSlice, _>(rtx, &..)?;
+        let mut new = self.new.dyndb.range::<ByteSlice, ByteSlice, _>(rtx, &..)?;
+        let mut old_entry = old.next().transpose()?;
+        let mut new_entry = new.next().transpose()?;
+        let mut divergences = BTreeMap::new();
+        loop {
+            let (key, kind) = match (old_entry.take(), new_entry.take()) {
+                (None, None) => break,
+                (Some((key, old_value)), Some((new_key, new_value))) if key == new_key => {
+                    old_entry = old.next().transpose()?;
+                    new_entry = new.next().transpose()?;
+                    (key, compare::<OldC, NewC>(Some(&old_value), Some(&new_value)))
+                }
+                (Some((key, _)), Some(next_new)) if key < next_new.0 => {
+                    old_entry = old.next().transpose()?;
+                    new_entry = Some(next_new);
+                    (key, Some(DivergenceKind::MissingInNew))
+                }
+                (Some((key, _)), None) => {
+                    old_entry = old.next().transpose()?;
+                    (key, Some(DivergenceKind::MissingInNew))
+                }
+                (next_old, Some((key, _))) => {
+                    old_entry = next_old;
+                    new_entry = new.next().transpose()?;
+                    (key, Some(DivergenceKind::MissingInOld))
+                }
+            };
+            if let Some(kind) = kind {
+                divergences.insert(key, kind);
+            }
+        }
+
+        let count = divergences.len();
+        self.divergences.lock().unwrap().extend(divergences);
+        Ok(count)
+    }
+
+    /// The divergences recorded by the reads, the last one of each key in the order of
+    /// the keys.
+    pub fn divergences(&self) -> Vec<Divergence> {
+        let divergences = self.divergences.lock().unwrap();
+        divergences.iter().map(|(key, &kind)| Divergence { key: key.clone(), kind }).collect()
+    }
+
+    /// Returns the divergences recorded by the reads and forgets them.
+    pub fn take_divergences(&self) -> Vec<Divergence> {
+        let divergences = std::mem::take(&mut *self.divergences.lock().unwrap());
+        divergences.into_iter().map(|(key, kind)| Divergence { key, kind }).collect()
+    }
+
+    /// The old table, read until the migration is done.
+    pub fn old_table(&self) -> &Typed<'s, S, KC, OldC> {
+        &self.old
+    }
+
+    /// The new table, read once the migration is done.
+    pub fn new_table(&self) -> &Typed<'s, S, KC, NewC> {
+        &self.new
+    }
+}
+
+impl<'s, S: WritableStore, KC, OldC, NewC> ShadowedTable<'s, S, KC, OldC, NewC>
+where
+    OldC: BytesDecode + for<'a> BytesEncode<'a, EItem = <OldC as BytesDecode>::DItem>,
+    NewC: BytesDecode<DItem = OldC::DItem>
+        + for<'a> BytesEncode<'a, EItem = <OldC as BytesDecode>::DItem>,
+    OldC::DItem: PartialEq,
+{
+    /// Writes the value of the key to both tables.
+    pub fn put<'a>(
+        &self,
+        wtx: &mut WtxOf<S>,
+        key: &'a KC::EItem,
+        value: &'a OldC::DItem,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        self.old.put(wtx, key, value)?;
+        self.new.put(wtx, key, value)
+    }
+
+    /// Deletes the key from both tables.
+    pub fn delete<'a>(&self, wtx: &mut WtxOf<S>, key: &'a KC::EItem) -> Result<(), ErrorOf<S>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        self.old.delete(wtx, key)?;
+        self.new.delete(wtx, key)
+    }
+}
+
+/// How the encoded values of a key differ, `None` if they decode to the same value.
+fn compare<OldC, NewC>(old: Option<&[u8]>, new: Option<&[u8]>) -> Option<DivergenceKind>
+where
+    OldC: BytesDecode,
+    NewC: BytesDecode<DItem = OldC::DItem>,
+    OldC::DItem: PartialEq,
+{
+    match (old, new) {
+        (None, None) => None,
+        (Some(_), None) => Some(DivergenceKind::MissingInNew),
+        (None, Some(_)) => Some(DivergenceKind::MissingInOld),
+        (Some(old), Some(new)) => match (OldC::bytes_decode(old), NewC::bytes_decode(new)) {
+            (Some(old), Some(new)) if old == new => None,
+            _ => Some(DivergenceKind::Differ),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{Composite, OwnedType, Str};
+
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn verify_reports_the_divergences() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let table =
+            ShadowedTable::<_, Str, OwnedType<u64>, Composite<u64>>::open(&env, "old", "new")
+                .unwrap();
+        let old = env.typed::<Str, OwnedType<u64>>("old", &()).unwrap();
+        let new = env.typed::<Str, Composite<u64>>("new", &()).unwrap();
+
+        let mut wtxn = env.wtx().unwrap();
+        table.put(&mut wtxn, "alice", &1).unwrap();
+        table.put(&mut wtxn, "bob", &2).unwrap();
+        old.put(&mut wtxn, "carol", &3).unwrap();
+        new.put(&mut wtxn, "bob", &20).unwrap();
+        new.put(&mut wtxn, "dave", &4).unwrap();
+
+        assert_eq!(table.get(&wtxn, "alice").unwrap(), Some(1));
+        assert_eq!(table.get(&wtxn, "bob").unwrap(), Some(2));
+        assert_eq!(
+            table.take_divergences(),
+            [Divergence { key: b"bob".to_vec(), kind: DivergenceKind::Differ }]
+        );
+
+        assert_eq!(table.verify(&wtxn).unwrap(), 3);
+        let kinds: Vec<_> = table
+            .divergences()
+            .into_iter()
+            .map(|d| (String::from_utf8(d.key).unwrap(), d.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("bob".to_string(), DivergenceKind::Differ),
+                ("carol".to_string(), DivergenceKind::MissingInNew),
+                ("dave".to_string(), DivergenceKind::MissingInOld),
+            ]
+        );
+    }
+
+    /// A key codec that can't encode its keys.
+    struct Unencodable;
+
+    impl<'a> BytesEncode<'a> for Unencodable {
+        type EItem = str;
+
+        fn bytes_encode(_key: &'a str) -> Option<std::borrow::Cow<'a, [u8]>> {
+            None
+        }
+    }
+
+    #[test]
+    fn reads_record_a_divergence_per_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let table =
+            ShadowedTable::<_, Str, OwnedType<u64>, Composite<u64>>::open(&env, "old", "new")
+                .unwrap();
+        let new = env.typed::<Str, Composite<u64>>("new", &()).unwrap();
+        let unencodable = ShadowedTable::<_, Unencodable, OwnedType<u64>, Composite<u64>>::open(
+            &env, "old", "new",
+        )
+        .unwrap();
+
+        let mut wtxn = env.wtx().unwrap();
+        table.put(&mut wtxn, "alice", &1).unwrap();
+        new.put(&mut wtxn, "alice", &10).unwrap();
+        for _ in 0..3 {
+            assert_eq!(table.get(&wtxn, "alice").unwrap(), Some(1));
+        }
+        new.delete(&mut wtxn, "alice").unwrap();
+        assert_eq!(table.get(&wtxn, "alice").unwrap(), Some(1));
+        assert_eq!(
+            table.take_divergences(),
+            [Divergence { key: b"alice".to_vec(), kind: DivergenceKind::MissingInNew }]
+        );
+
+        assert!(matches!(unencodable.get(&wtxn, "alice"), Err(ShadowError::Encoding)));
+        assert!(unencodable.divergences().is_empty());
+    }
+}
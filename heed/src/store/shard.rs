@@ -0,0 +1,229 @@
+//! Splitting the keys of a table of any [`Store`] into chunks of roughly the same number of
+//! entries, to scan a table in parallel or to distribute the work on its entries to workers.
+//!
+//! The number of entries is the [`estimated_len`](Table::estimated_len) of the table. The
+//! split keys of the small tables are found by reading their keys, without decoding nor
+//! copying the values, the ones of the large tables by seeking interpolated keys.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::shard::{chunks, split_points};
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{ByteSlice, Unit};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let entries = env.typed::<ByteSlice, Unit>("entries", &())?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! for i in 0..100 {
+//!     entries.put(&mut wtxn, format!("key{:03}", i).as_bytes(), &())?;
+//! }
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let points = split_points(&rtxn, &env.table("entries", &())?, 4)?;
+//! assert_eq!(points, [b"key025", b"key050", b"key075"]);
+//! for chunk in chunks(&points) {
+//!     assert_eq!(entries.range_len(&rtxn, &chunk)?, 25);
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::iter;
+use std::ops::Bound;
+
+use either::Either;
+use heed_types::{ByteSlice, DecodeIgnore};
+
+use crate::store::{ErrorOf, RtxOf, Table};
+
+/// The range of the keys of a chunk, see [`chunks`].
+pub type Chunk<'p> = (Bound<&'p [u8]>, Bound<&'p [u8]>);
+
+/// The tables of at most this many entries are split by reading their keys, the larger
+/// ones by seeking keys interpolated between their first and last keys.
+const EXACT_SPLIT_LEN: usize = 4096;
+
+/// Returns at most `n - 1` increasing keys of the table that split it into `n` chunks of
+/// roughly the same number of entries, the first chunk ends before the first key, the
+/// next chunks start at a key and end before the next one.
+///
+/// Fewer keys are returned when the table has less than `n` entries, none when it is
+/// empty or `n` is less than 2. The chunks are only as even as the estimation of the
+/// number of entries is precise, the entries beyond the estimation end up in the last one.
+///
+/// The keys of the tables of more than a few thousand entries are not read one by one,
+/// the split keys are the first keys at or after `n - 1` evenly spaced points between the
+/// first and the last key, which takes `n` seeks. The chunks of these tables are only
+/// even when their keys are evenly distributed in the key space, like increasing ids.
+pub fn split_points<'s, T: Table<'s>>(
+    rtx: &RtxOf<T::Store>,
+    table: &T,
+    n: usize,
+) -> Result<Vec<Vec<u8>>, ErrorOf<T::Store>> {
+    let len = table.estimated_len(rtx)?;
+    if n < 2 || len == 0 {
+        Ok(Vec::new())
+    } else if len <= EXACT_SPLIT_LEN {
+        read_split_points(rtx, table, n, len)
+    } else {
+        interpolate_split_points(rtx, table, n)
+    }
+}
+
+fn read_split_points<'s, T: Table<'s>>(
+    rtx: &RtxOf<T::Store>,
+    table: &T,
+    n: usize,
+    len: usize,
+) -> Result<Vec<Vec<u8>>, ErrorOf<T::Store>> {
+    // The j-th split is the key at index j * len / n, skipping the splits that would
+    // fall on the same key as the previous one when there are fewer entries than chunks.
+    let mut points = Vec::with_capacity(n - 1);
+    let mut split = 1;
+    let mut index = 0;
+    let visited = table.raw_iter(rtx, |key, _| {
+        while split < n && split * len / n < index {
+            split += 1;
+        }
+        if split == n {
+            return Err(());
+        }
+        if index > 0 && split * len / n == index {
+            points.push(key.to_vec());
+            split += 1;
+        }
+        index += 1;
+        Ok(())
+    });
+
+    match visited {
+        Ok(()) | Err(Either::Right(())) => Ok(points),
+        Err(Either::Left(e)) => Err(e),
+    }
+}
+
+fn interpolate_split_points<'s, T: Table<'s>>(
+    rtx: &RtxOf<T::Store>,
+    table: &T,
+    n: usize,
+) -> Result<Vec<Vec<u8>>, ErrorOf<T::Store>> {
+    let first = match table.range::<ByteSlice, DecodeIgnore, _>(rtx, &..)?.next() {
+        Some(entry) => entry?.0.to_vec(),
+        None => return Ok(Vec::new()),
+    };
+    let last = match table.rev_range::<ByteSlice, DecodeIgnore, _>(rtx, &..)?.next() {
+        Some(entry) => entry?.0.to_vec(),
+        None => return Ok(Vec::new()),
+    };
+
+    // The keys are positioned by their first 16 bytes after the prefix they all share.
+    let prefix = first.iter().zip(&last).take_while(|(a, b)| a == b).count();
+    let (low, high) = (key_position(&first, prefix), key_position(&last, prefix));
+    let span = high - low;
+
+    let mut points: Vec<Vec<u8>> = Vec::with_capacity(n - 1);
+    for split in 1..n as u128 {
+        let n = n as u128;
+        let position = low + span / n * split + span % n * split / n;
+        let mut target = first[..prefix].to_vec();
+        target.extend_from_slice(&position.to_be_bytes());
+        while target.len() > prefix && target.last() == Some(&0) {
+            target.pop();
+        }
+
+        let range = (Bound::Included(&target[..]), Bound::Unbounded);
+        let key = match table.range::<ByteSlice, DecodeIgnore, _>(rtx, &range)?.next() {
+            Some(entry) => entry?.0.to_vec(),
+            None => break,
+        };
+        if key != first && points.last() != Some(&key) {
+            points.push(key);
+        }
+    }
+
+    Ok(points)
+}
+
+/// The first 16 bytes of the key after `prefix` bytes, as a big endian number.
+fn key_position(key: &[u8], prefix: usize) -> u128 {
+    let mut bytes = [0; 16];
+    let suffix = &key[prefix..];
+    let len = suffix.len().min(bytes.len());
+    bytes[..len].copy_from_slice(&suffix[..len]);
+    u128::from_be_bytes(bytes)
+}
+
+/// The ranges of the keys of the chunks delimited by the split `points`,
+/// see [`split_points`].
+pub fn chunks(points: &[Vec<u8>]) -> Vec<Chunk<'_>> {
+    let starts = iter::once(Bound::Unbounded).chain(points.iter().map(|p| Bound::Included(&p[..])));
+    let ends = points.iter().map(|p| Bound::Excluded(&p[..])).chain(iter::once(Bound::Unbounded));
+    starts.zip(ends).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::Unit;
+
+    use super::*;
+    use crate::store::{Store, WritableStore};
+
+    #[test]
+    fn split_points_even_the_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let typed = env.typed::<ByteSlice, Unit>("entries", &()).unwrap();
+        let table = env.table("entries", &()).unwrap();
+
+        assert!(split_points(&env.rtx().unwrap(), &table, 3).unwrap().is_empty());
+        let mut wtx = env.wtx().unwrap();
+        for i in 0u32..1000 {
+            typed.put(&mut wtx, &i.to_be_bytes(), &()).unwrap();
+        }
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        assert!(split_points(&rtx, &table, 1).unwrap().is_empty());
+        for &n in &[2, 3, 7, 999, 1000, 1500] {
+            let points = split_points(&rtx, &table, n).unwrap();
+            assert_eq!(points.len(), n.min(1000) - 1);
+            let counts: Vec<_> =
+                chunks(&points).iter().map(|chunk| typed.range_len(&rtx, chunk).unwrap()).collect();
+            assert_eq!(counts.iter().sum::<usize>(), 1000);
+            let (min, max) = (counts.iter().min().unwrap(), counts.iter().max().unwrap());
+            assert!(max - min <= 1, "{} chunks of {:?} entries", n, counts);
+        }
+    }
+
+    #[test]
+    fn split_points_of_large_tables_are_interpolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = crate::EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let typed = env.typed::<ByteSlice, Unit>("entries", &()).unwrap();
+        let table = env.table("entries", &()).unwrap();
+
+        // Every third id, so that the interpolated keys are not all in the table.
+        let mut wtx = env.wtx().unwrap();
+        for i in 0u64..30_000 {
+            typed.put(&mut wtx, &(i * 3).to_be_bytes(), &()).unwrap();
+        }
+        wtx.commit().unwrap();
+
+        let rtx = env.rtx().unwrap();
+        for &n in &[2, 7, 64] {
+            let points = split_points(&rtx, &table, n).unwrap();
+            assert_eq!(points.len(), n - 1);
+            let counts: Vec<_> =
+                chunks(&points).iter().map(|chunk| typed.range_len(&rtx, chunk).unwrap()).collect();
+            assert_eq!(counts.iter().sum::<usize>(), 30_000);
+            let (min, max) = (counts.iter().min().unwrap(), counts.iter().max().unwrap());
+            assert!(max - min <= 1, "{} chunks of {:?} entries", n, counts);
+        }
+    }
+}
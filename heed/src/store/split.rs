@@ -0,0 +1,598 @@
+//! A [`Store`] whose tables are stored in two backends, routed by their names, like the small
+//! tables read often in LMDB and the huge append-only tables in RocksDB.
+//!
+//! A [`SplitStore`] opens a transaction in both backends for each of its transactions,
+//! the operations on a table go to the transaction of the backend of the table.
+//!
+//! # Atomicity
+//!
+//! The writes to the tables of one backend are atomic, like in this backend, but the
+//! writes to the two backends are not: a write transaction commits the transaction of B,
+//! then the one of A, only if B committed. A commit that fails in B leaves both backends
+//! unchanged. A commit that fails in A, or a crash between the two commits, leaves the
+//! writes to B committed without the writes to A, the commit returns a
+//! [`SplitError::HalfCommitted`]. The backend whose commits can fail for other reasons than
+//! the machine, like the RocksDB transactions that conflict, is best given as B.
+//!
+//! In the same way, the two read transactions are not a snapshot of both backends taken at
+//! the same time. The store doesn't claim the [`CRASH_SAFE`](Capabilities::CRASH_SAFE) nor
+//! the [`SNAPSHOT_READS`](Capabilities::SNAPSHOT_READS) capabilities.
+//!
+//! The tables of a store must not depend on the writes to the tables of the other backend
+//! being in the same transaction, like an index and the table it indexes.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::split::SplitStore;
+//! use heed::store::{Store, Transaction, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let (hot_dir, archive_dir) = (tempfile::tempdir()?, tempfile::tempdir()?);
+//! # let hot = EnvOpenOptions::new().max_dbs(10).open(hot_dir.path())?;
+//! # let archive = EnvOpenOptions::new().max_dbs(10).open(archive_dir.path())?;
+//! // The events are stored in the archive, the other tables in the hot store.
+//! let store = SplitStore::new(hot, archive, |name| name.starts_with("events"));
+//! let users = store.typed::<Str, Str>("users", &Default::default())?;
+//! let events = store.typed::<OwnedType<u64>, Str>("events", &Default::default())?;
+//!
+//! let mut wtx = store.wtx()?;
+//! users.put(&mut wtx, "alice", "Alice")?;
+//! events.append(&mut wtx, &1, "alice signed up")?;
+//! wtx.commit()?;
+//!
+//! assert!(store.a().table_exists("users")? && !store.a().table_exists("events")?);
+//! assert_eq!(store.table_names()?, ["events", "users"]);
+//! # Ok(()) }
+//! ```
+
+use std::ops::{Deref, RangeBounds};
+use std::{error, fmt};
+
+use either::Either;
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::store::{
+    Capabilities, ErrorOf, RangeOf, RevRangeOf, RtxOf, SizeHistogram, Store, Table, TableOf,
+    Transaction, WritableStore, WtxOf,
+};
+
+/// An error of a [`SplitStore`].
+#[derive(Debug)]
+pub enum SplitError<A, B> {
+    /// An error of the backend A.
+    A(A),
+    /// An error of the backend B.
+    B(B),
+    /// The commit of a write transaction failed in A after it committed in B,
+    /// the writes to B are committed without the writes to A.
+    HalfCommitted(A),
+}
+
+impl<A: fmt::Display, B: fmt::Display> fmt::Display for SplitError<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitError::A(error) => write!(f, "{}", error),
+            SplitError::B(error) => write!(f, "{}", error),
+            SplitError::HalfCommitted(error) => {
+                write!(f, "the commit failed in A after it committed in B: {}", error)
+            }
+        }
+    }
+}
+
+impl<A, B> error::Error for SplitError<A, B>
+where
+    A: error::Error + 'static,
+    B: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SplitError::A(error) | SplitError::HalfCommitted(error) => Some(error),
+            SplitError::B(error) => Some(error),
+        }
+    }
+}
+
+/// A [`Store`] whose tables are stored in two backends, see the [module documentation](self).
+pub struct SplitStore<A, B> {
+    a: A,
+    b: B,
+    in_b: Box<dyn Fn(&str) -> bool + Send + Sync>,
+}
+
+impl<A: WritableStore, B: WritableStore> SplitStore<A, B> {
+    /// The tables whose names match `in_b` are stored in `b`, the others in `a`.
+    pub fn new<F>(a: A, b: B, in_b: F) -> SplitStore<A, B>
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        SplitStore { a, b, in_b: Box::new(in_b) }
+    }
+
+    pub fn a(&self) -> &A {
+        &self.a
+    }
+
+    pub fn b(&self) -> &B {
+        &self.b
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<A, B> fmt::Debug for SplitStore<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SplitStore").finish_non_exhaustive()
+    }
+}
+
+impl<A: WritableStore, B: WritableStore> Store for SplitStore<A, B> {
+    type Error = SplitError<A::Error, B::Error>;
+    type Rtx<'e> = SplitTxn<'e, A, B>;
+    type Table<'store> = SplitTable<'store, A, B>;
+    /// The configurations of the tables of A and of the tables of B.
+    type Config = (A::Config, B::Config);
+
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
+        if (self.in_b)(name) {
+            self.b.table(name, &cfg.1).map(SplitTable::B).map_err(SplitError::B)
+        } else {
+            self.a.table(name, &cfg.0).map(SplitTable::A).map_err(SplitError::A)
+        }
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let name = name.as_ref();
+        if (self.in_b)(name) {
+            let table = self.b.open_table(name).map_err(SplitError::B)?;
+            Ok(table.map(SplitTable::B))
+        } else {
+            let table = self.a.open_table(name).map_err(SplitError::A)?;
+            Ok(table.map(SplitTable::A))
+        }
+    }
+
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        let name = name.as_ref();
+        if (self.in_b)(name) {
+            self.b.table_exists(name).map_err(SplitError::B)
+        } else {
+            self.a.table_exists(name).map_err(SplitError::A)
+        }
+    }
+
+    /// The tables of A and of B, the tables of a backend that aren't routed to it are ignored.
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        let mut names = self.a.table_names().map_err(SplitError::A)?;
+        names.retain(|name| !(self.in_b)(name));
+        let b = self.b.table_names().map_err(SplitError::B)?;
+        names.extend(b.into_iter().filter(|name| (self.in_b)(name)));
+        names.sort_unstable();
+        Ok(names)
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        let a = self.a.rtx().map_err(SplitError::A)?;
+        let b = self.b.rtx().map_err(SplitError::B)?;
+        Ok(SplitTxn { txns: SplitTxns::Read(a, b), extensions: crate::Extensions::new() })
+    }
+
+    fn counters_config(&self) -> Self::Config {
+        (self.a.counters_config(), self.b.counters_config())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let across = Capabilities::CRASH_SAFE | Capabilities::SNAPSHOT_READS;
+        self.a.capabilities().intersection(self.b.capabilities()).difference(across)
+    }
+}
+
+impl<A: WritableStore, B: WritableStore> WritableStore for SplitStore<A, B> {
+    type Wtx<'e> = SplitWtx<'e, A, B>;
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
+        let a = self.a.wtx().map_err(SplitError::A)?;
+        let b = self.b.wtx().map_err(SplitError::B)?;
+        let txns = SplitTxns::Write(a, b);
+        Ok(SplitWtx(SplitTxn { txns, extensions: crate::Extensions::new() }))
+    }
+
+    fn create_table(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
+        if (self.in_b)(name) {
+            let table = self.b.create_table(wtx.b_mut(), name, &cfg.1);
+            table.map(SplitTable::B).map_err(SplitError::B)
+        } else {
+            let table = self.a.create_table(wtx.a_mut(), name, &cfg.0);
+            table.map(SplitTable::A).map_err(SplitError::A)
+        }
+    }
+}
+
+/// A read transaction of a [`SplitStore`], a transaction of each backend.
+pub struct SplitTxn<'e, A: WritableStore + 'e, B: WritableStore + 'e> {
+    txns: SplitTxns<'e, A, B>,
+    extensions: crate::Extensions,
+}
+
+/// The read transactions are the write transactions too, to be
+/// dereferenced from the write transactions.
+enum SplitTxns<'e, A: WritableStore + 'e, B: WritableStore + 'e> {
+    Read(RtxOf<'e, A>, RtxOf<'e, B>),
+    Write(WtxOf<'e, A>, WtxOf<'e, B>),
+}
+
+impl<'e, A: WritableStore, B: WritableStore> SplitTxn<'e, A, B> {
+    /// The transaction of the backend A.
+    pub fn a(&self) -> &RtxOf<'e, A> {
+        match &self.txns {
+            SplitTxns::Read(a, _) => a,
+            SplitTxns::Write(a, _) => a,
+        }
+    }
+
+    /// The transaction of the backend B.
+    pub fn b(&self) -> &RtxOf<'e, B> {
+        match &self.txns {
+            SplitTxns::Read(_, b) => b,
+            SplitTxns::Write(_, b) => b,
+        }
+    }
+}
+
+impl<A: WritableStore, B: WritableStore> Transaction<SplitStore<A, B>> for SplitTxn<'_, A, B> {
+    fn commit(self) -> Result<(), SplitError<A::Error, B::Error>> {
+        match self.txns {
+            SplitTxns::Read(a, b) => {
+                b.commit().map_err(SplitError::B)?;
+                a.commit().map_err(SplitError::A)
+            }
+            SplitTxns::Write(a, b) => {
+                b.commit().map_err(SplitError::B)?;
+                a.commit().map_err(SplitError::HalfCommitted)
+            }
+        }
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        &self.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        Some(&mut self.extensions)
+    }
+}
+
+/// A write transaction of a [`SplitStore`], it dereferences to a read transaction.
+///
+/// It is committed in B then in A, see the [module documentation](self).
+pub struct SplitWtx<'e, A: WritableStore + 'e, B: WritableStore + 'e>(SplitTxn<'e, A, B>);
+
+impl<'e, A: WritableStore, B: WritableStore> SplitWtx<'e, A, B> {
+    fn a_mut(&mut self) -> &mut WtxOf<'e, A> {
+        match &mut self.0.txns {
+            SplitTxns::Write(a, _) => a,
+            SplitTxns::Read(..) => unreachable!("a write transaction wraps read transactions"),
+        }
+    }
+
+    fn b_mut(&mut self) -> &mut WtxOf<'e, B> {
+        match &mut self.0.txns {
+            SplitTxns::Write(_, b) => b,
+            SplitTxns::Read(..) => unreachable!("a write transaction wraps read transactions"),
+        }
+    }
+}
+
+impl<'e, A: WritableStore, B: WritableStore> Deref for SplitWtx<'e, A, B> {
+    type Target = SplitTxn<'e, A, B>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<A: WritableStore, B: WritableStore> Transaction<SplitStore<A, B>> for SplitWtx<'_, A, B> {
+    fn commit(self) -> Result<(), SplitError<A::Error, B::Error>> {
+        self.0.commit()
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        self.0.extensions()
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        self.0.extensions_mut()
+    }
+}
+
+/// A table of a [`SplitStore`], in one of its backends.
+pub enum SplitTable<'s, A: WritableStore + 's, B: WritableStore + 's> {
+    A(TableOf<'s, A>),
+    B(TableOf<'s, B>),
+}
+
+impl<'s, A: WritableStore, B: WritableStore> Clone for SplitTable<'s, A, B>
+where
+    TableOf<'s, A>: Clone,
+    TableOf<'s, B>: Clone,
+{
+    fn clone(&self) -> Self {
+        match self {
+            SplitTable::A(table) => SplitTable::A(table.clone()),
+            SplitTable::B(table) => SplitTable::B(table.clone()),
+        }
+    }
+}
+
+impl<'s, A: WritableStore, B: WritableStore> fmt::Debug for SplitTable<'s, A, B>
+where
+    TableOf<'s, A>: fmt::Debug,
+    TableOf<'s, B>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitTable::A(table) => f.debug_tuple("A").field(table).finish(),
+            SplitTable::B(table) => f.debug_tuple("B").field(table).finish(),
+        }
+    }
+}
+
+/// An iterator of a [`SplitTable`], the iterator of its backend.
+pub enum SplitIter<IA, IB> {
+    A(IA),
+    B(IB),
+}
+
+impl<IA, IB, T, EA, EB> Iterator for SplitIter<IA, IB>
+where
+    IA: Iterator<Item = Result<T, EA>>,
+    IB: Iterator<Item = Result<T, EB>>,
+{
+    type Item = Result<T, SplitError<EA, EB>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SplitIter::A(iter) => iter.next().map(|entry| entry.map_err(SplitError::A)),
+            SplitIter::B(iter) => iter.next().map(|entry| entry.map_err(SplitError::B)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            SplitIter::A(iter) => iter.size_hint(),
+            SplitIter::B(iter) => iter.size_hint(),
+        }
+    }
+}
+
+/// Calls the same method on the table of either backend, with the transaction of its backend.
+macro_rules! route {
+    ($table:expr, $txn:ident => $a:expr, $b:expr, |$t:ident| $call:expr) => {
+        match $table {
+            SplitTable::A($t) => {
+                let $txn = $a;
+                $call.map_err(SplitError::A)
+            }
+            SplitTable::B($t) => {
+                let $txn = $b;
+                $call.map_err(SplitError::B)
+            }
+        }
+    };
+}
+
+impl<'s, A: WritableStore, B: WritableStore> Table<'s> for SplitTable<'s, A, B> {
+    type Store = SplitStore<A, B>;
+
+    type Range<'e, KC: BytesDecode, DC: BytesDecode> =
+        SplitIter<RangeOf<'s, 'e, A, KC, DC>, RangeOf<'s, 'e, B, KC, DC>>;
+
+    type RevRange<'e, KC: BytesDecode, DC: BytesDecode> =
+        SplitIter<RevRangeOf<'s, 'e, A, KC, DC>, RevRangeOf<'s, 'e, B, KC, DC>>;
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn SplitTxn<A, B>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        route!(self, txn => txn.a(), txn.b(), |t| t.get::<KC, DC>(txn, key))
+    }
+
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        let (a, b) = match &txn.0.txns {
+            SplitTxns::Write(a, b) => (a, b),
+            SplitTxns::Read(..) => unreachable!("a write transaction wraps read transactions"),
+        };
+        route!(self, txn => a, b, |t| t.get_for_update::<KC, DC>(txn, key))
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn SplitTxn<A, B>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        match self {
+            SplitTable::A(t) => {
+                t.range::<KC, DC, R>(txn.a(), range).map(SplitIter::A).map_err(SplitError::A)
+            }
+            SplitTable::B(t) => {
+                t.range::<KC, DC, R>(txn.b(), range).map(SplitIter::B).map_err(SplitError::B)
+            }
+        }
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn SplitTxn<A, B>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        match self {
+            SplitTable::A(t) => {
+                t.rev_range::<KC, DC, R>(txn.a(), range).map(SplitIter::A).map_err(SplitError::A)
+            }
+            SplitTable::B(t) => {
+                t.rev_range::<KC, DC, R>(txn.b(), range).map(SplitIter::B).map_err(SplitError::B)
+            }
+        }
+    }
+
+    fn len(&self, txn: &SplitTxn<A, B>) -> Result<usize, ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a(), txn.b(), |t| t.len(txn))
+    }
+
+    fn estimated_len(&self, txn: &SplitTxn<A, B>) -> Result<usize, ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a(), txn.b(), |t| t.estimated_len(txn))
+    }
+
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn SplitTxn<A, B>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        route!(self, txn => txn.a(), txn.b(), |t| t.range_len::<KC, R>(txn, range))
+    }
+
+    fn raw_iter<E, F>(
+        &self,
+        txn: &SplitTxn<A, B>,
+        visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        match self {
+            SplitTable::A(t) => t.raw_iter(txn.a(), visit).map_err(|e| e.map_left(SplitError::A)),
+            SplitTable::B(t) => t.raw_iter(txn.b(), visit).map_err(|e| e.map_left(SplitError::B)),
+        }
+    }
+
+    fn warmup(&self, txn: &SplitTxn<A, B>) -> Result<u64, ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a(), txn.b(), |t| t.warmup(txn))
+    }
+
+    fn size_histogram(&self, txn: &SplitTxn<A, B>) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a(), txn.b(), |t| t.size_histogram(txn))
+    }
+
+    fn put<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        route!(self, txn => txn.a_mut(), txn.b_mut(), |t| t.put::<KC, DC>(txn, key, data))
+    }
+
+    fn append<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        route!(self, txn => txn.a_mut(), txn.b_mut(), |t| t.append::<KC, DC>(txn, key, data))
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        route!(self, txn => txn.a_mut(), txn.b_mut(), |t| t.delete::<KC>(txn, key))
+    }
+
+    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a_mut(), txn.b_mut(), |t| t.clear(txn))
+    }
+
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>> {
+        route!(self, txn => txn.a_mut(), txn.b_mut(), |t| t.increment(txn, key, delta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{OwnedType, Str};
+
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn tables_are_routed_by_name() {
+        let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let a = EnvOpenOptions::new().max_dbs(2).open(dir_a.path()).unwrap();
+        let b = EnvOpenOptions::new().max_dbs(2).open(dir_b.path()).unwrap();
+        let store = SplitStore::new(a, b, |name| name.starts_with("log"));
+
+        let counts = store.typed::<Str, OwnedType<u64>>("counts", &Default::default()).unwrap();
+        let log = store.typed::<OwnedType<u64>, Str>("log", &Default::default()).unwrap();
+        let mut wtx = store.wtx().unwrap();
+        counts.put(&mut wtx, "a", &1).unwrap();
+        log.append(&mut wtx, &0, "a").unwrap();
+        wtx.commit().unwrap();
+
+        let rtx = store.rtx().unwrap();
+        assert_eq!(counts.get(&rtx, "a").unwrap(), Some(1));
+        assert_eq!(log.len(&rtx).unwrap(), 1);
+        drop(rtx);
+
+        assert_eq!(store.a().table_names().unwrap(), ["counts"]);
+        assert_eq!(store.b().table_names().unwrap(), ["log"]);
+        assert!(!store.capabilities().crash_safe());
+    }
+}
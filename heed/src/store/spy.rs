@@ -0,0 +1,593 @@
+//! A wrapper of a [`Store`] recording the operations on its tables, to check in the tests
+//! which tables and keys a component reads and writes, and failing one of them on purpose,
+//! to check how it recovers.
+//!
+//! Every operation of the tables of a [`SpyStore`], and every commit of its write
+//! transactions, is recorded as a [`SpyOp`] before it is given to the wrapped store.
+//! [`SpyStore::fail_at`] makes one of the next operations return a [`SpyError::Injected`]
+//! instead, the operation isn't given to the wrapped store.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::spy::{SpyError, SpyOpKind, SpyStore};
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::{OwnedType, Str};
+//!
+//! /// Moves the balance of an account, it must only touch the balances.
+//! fn transfer<S: WritableStore>(store: &S, from: &str, to: &str) -> Result<(), S::Error> {
+//!     let balances = store.typed::<Str, OwnedType<u64>>("balances", &Default::default())?;
+//!     store.with_wtx(|wtx| {
+//!         let amount = balances.get(wtx, from)?.unwrap_or(0);
+//!         balances.delete(wtx, from)?;
+//!         balances.put(wtx, to, &amount)
+//!     })
+//! }
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let store = SpyStore::new(env);
+//! transfer(&store, "alice", "bob")?;
+//! assert_eq!(store.touched_tables(), ["balances"]);
+//! let kinds: Vec<_> = store.take_ops().into_iter().map(|op| op.kind).collect();
+//! assert_eq!(kinds, [SpyOpKind::Get, SpyOpKind::Delete, SpyOpKind::Put, SpyOpKind::Commit]);
+//!
+//! // The put fails, the transaction is aborted.
+//! store.fail_at(2);
+//! assert!(matches!(transfer(&store, "bob", "carol"), Err(SpyError::Injected(_))));
+//! # Ok(()) }
+//! ```
+
+use std::ops::{Deref, RangeBounds};
+use std::sync::Mutex;
+use std::{error, fmt};
+
+use either::Either;
+use heed_traits::{BytesDecode, BytesEncode};
+
+use crate::store::{
+    Capabilities, ErrorOf, RangeOf, RevRangeOf, RtxOf, SizeHistogram, Store, Table, TableOf,
+    Transaction, WritableStore, WtxOf,
+};
+
+/// The kinds of operations recorded by a [`SpyStore`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpyOpKind {
+    Get,
+    GetForUpdate,
+    Range,
+    RevRange,
+    Len,
+    EstimatedLen,
+    RangeLen,
+    RawIter,
+    Warmup,
+    SizeHistogram,
+    Put,
+    Append,
+    Delete,
+    Clear,
+    Increment,
+    /// The commit of a write transaction.
+    Commit,
+}
+
+/// An operation recorded by a [`SpyStore`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpyOp {
+    /// The table of the operation, `None` for the commits.
+    pub table: Option<String>,
+    pub kind: SpyOpKind,
+    /// The encoded key of the operations on a key, `None` when it can't be encoded.
+    pub key: Option<Vec<u8>>,
+}
+
+/// An error of a [`SpyStore`].
+#[derive(Debug)]
+pub enum SpyError<E> {
+    /// An error of the wrapped store.
+    Store(E),
+    /// The failure injected by [`SpyStore::fail_at`], in place of this operation.
+    Injected(SpyOp),
+}
+
+impl<E: fmt::Display> fmt::Display for SpyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpyError::Store(error) => write!(f, "{}", error),
+            SpyError::Injected(SpyOp { table: Some(table), kind, .. }) => {
+                write!(f, "injected failure of a {:?} on the {:?} table", kind, table)
+            }
+            SpyError::Injected(SpyOp { table: None, kind, .. }) => {
+                write!(f, "injected failure of a {:?}", kind)
+            }
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for SpyError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            SpyError::Store(error) => Some(error),
+            SpyError::Injected(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Spy {
+    ops: Vec<SpyOp>,
+    /// The number of operations to let through before the injected failure.
+    fail_in: Option<usize>,
+}
+
+/// A [`Store`] recording the operations on its tables, see the [module documentation](self).
+pub struct SpyStore<S> {
+    store: S,
+    spy: Mutex<Spy>,
+}
+
+impl<S: WritableStore> SpyStore<S> {
+    pub fn new(store: S) -> SpyStore<S> {
+        SpyStore { store, spy: Mutex::default() }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.store
+    }
+
+    /// The operations recorded so far, in order.
+    pub fn ops(&self) -> Vec<SpyOp> {
+        self.spy.lock().unwrap().ops.clone()
+    }
+
+    /// Returns the operations recorded so far and forgets them.
+    pub fn take_ops(&self) -> Vec<SpyOp> {
+        std::mem::take(&mut self.spy.lock().unwrap().ops)
+    }
+
+    /// The names of the tables of the recorded operations, sorted and deduplicated.
+    pub fn touched_tables(&self) -> Vec<String> {
+        let spy = self.spy.lock().unwrap();
+        let mut tables: Vec<_> = spy.ops.iter().filter_map(|op| op.table.clone()).collect();
+        tables.sort_unstable();
+        tables.dedup();
+        tables
+    }
+
+    /// Makes the operation following the next `ops` operations fail with a
+    /// [`SpyError::Injected`], 0 makes the next one fail. Only this operation fails,
+    /// the following ones are given to the wrapped store.
+    pub fn fail_at(&self, ops: usize) {
+        self.spy.lock().unwrap().fail_in = Some(ops);
+    }
+}
+
+impl<S: WritableStore> fmt::Debug for SpyStore<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let spy = self.spy.lock().unwrap();
+        f.debug_struct("SpyStore")
+            .field("ops", &spy.ops.len())
+            .field("fail_in", &spy.fail_in)
+            .finish()
+    }
+}
+
+impl Spy {
+    /// Records an operation, returns the injected failure if it is its turn.
+    fn record<E>(
+        spy: &Mutex<Spy>,
+        table: Option<&str>,
+        kind: SpyOpKind,
+        key: Option<&[u8]>,
+    ) -> Result<(), SpyError<E>> {
+        let op = SpyOp { table: table.map(String::from), kind, key: key.map(<[u8]>::to_vec) };
+        let mut spy = spy.lock().unwrap();
+        spy.ops.push(op.clone());
+        match spy.fail_in {
+            Some(0) => {
+                spy.fail_in = None;
+                Err(SpyError::Injected(op))
+            }
+            Some(ops) => {
+                spy.fail_in = Some(ops - 1);
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: WritableStore> Store for SpyStore<S> {
+    type Error = SpyError<S::Error>;
+    type Rtx<'e> = SpyTxn<'e, S>;
+    type Table<'store> = SpyTable<'store, S>;
+    type Config = S::Config;
+
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let table = self.store.table(name.as_ref(), cfg).map_err(SpyError::Store)?;
+        Ok(SpyTable { table, name: name.as_ref().to_string(), spy: &self.spy })
+    }
+
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let table = self.store.open_table(name.as_ref()).map_err(SpyError::Store)?;
+        Ok(table.map(|table| SpyTable { table, name: name.as_ref().to_string(), spy: &self.spy }))
+    }
+
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        self.store.table_exists(name).map_err(SpyError::Store)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        self.store.table_names().map_err(SpyError::Store)
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        let rtx = self.store.rtx().map_err(SpyError::Store)?;
+        Ok(SpyTxn { txn: SpyTxnInner::Read(rtx), spy: &self.spy })
+    }
+
+    fn counters_config(&self) -> Self::Config {
+        self.store.counters_config()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.store.capabilities()
+    }
+}
+
+impl<S: WritableStore> WritableStore for SpyStore<S> {
+    type Wtx<'e> = SpyWtx<'e, S>;
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
+        let wtx = self.store.wtx().map_err(SpyError::Store)?;
+        Ok(SpyWtx(SpyTxn { txn: SpyTxnInner::Write(wtx), spy: &self.spy }))
+    }
+
+    fn create_table(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let table =
+            self.store.create_table(wtx.wtx(), name.as_ref(), cfg).map_err(SpyError::Store)?;
+        Ok(SpyTable { table, name: name.as_ref().to_string(), spy: &self.spy })
+    }
+}
+
+/// A read transaction of a [`SpyStore`].
+pub struct SpyTxn<'e, S: WritableStore + 'e> {
+    txn: SpyTxnInner<'e, S>,
+    spy: &'e Mutex<Spy>,
+}
+
+/// The read transactions are the write transactions too, to be
+/// dereferenced from the write transactions.
+enum SpyTxnInner<'e, S: WritableStore + 'e> {
+    Read(RtxOf<'e, S>),
+    Write(WtxOf<'e, S>),
+}
+
+impl<'e, S: WritableStore> SpyTxn<'e, S> {
+    fn rtx(&self) -> &RtxOf<'e, S> {
+        match &self.txn {
+            SpyTxnInner::Read(rtx) => rtx,
+            SpyTxnInner::Write(wtx) => wtx,
+        }
+    }
+}
+
+impl<S: WritableStore> Transaction<SpyStore<S>> for SpyTxn<'_, S> {
+    fn commit(self) -> Result<(), SpyError<S::Error>> {
+        match self.txn {
+            SpyTxnInner::Read(rtx) => rtx.commit().map_err(SpyError::Store),
+            SpyTxnInner::Write(wtx) => wtx.commit().map_err(SpyError::Store),
+        }
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        match &self.txn {
+            SpyTxnInner::Read(rtx) => rtx.extensions(),
+            SpyTxnInner::Write(wtx) => wtx.extensions(),
+        }
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        match &mut self.txn {
+            SpyTxnInner::Read(rtx) => rtx.extensions_mut(),
+            SpyTxnInner::Write(wtx) => wtx.extensions_mut(),
+        }
+    }
+}
+
+/// A write transaction of a [`SpyStore`], it dereferences to a read transaction.
+pub struct SpyWtx<'e, S: WritableStore + 'e>(SpyTxn<'e, S>);
+
+impl<'e, S: WritableStore> SpyWtx<'e, S> {
+    fn wtx(&mut self) -> &mut WtxOf<'e, S> {
+        match &mut self.0.txn {
+            SpyTxnInner::Write(wtx) => wtx,
+            SpyTxnInner::Read(_) => unreachable!("a write transaction wraps a read transaction"),
+        }
+    }
+}
+
+impl<'e, S: WritableStore> Deref for SpyWtx<'e, S> {
+    type Target = SpyTxn<'e, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<S: WritableStore> Transaction<SpyStore<S>> for SpyWtx<'_, S> {
+    fn commit(self) -> Result<(), SpyError<S::Error>> {
+        // An injected failure drops the transaction, it is aborted.
+        Spy::record(self.0.spy, None, SpyOpKind::Commit, None)?;
+        self.0.commit()
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        self.0.extensions()
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        self.0.extensions_mut()
+    }
+}
+
+/// A table of a [`SpyStore`].
+pub struct SpyTable<'s, S: WritableStore + 's> {
+    table: TableOf<'s, S>,
+    name: String,
+    spy: &'s Mutex<Spy>,
+}
+
+impl<'s, S: WritableStore> SpyTable<'s, S> {
+    fn record<E>(&self, kind: SpyOpKind, key: Option<&[u8]>) -> Result<(), SpyError<E>> {
+        Spy::record(self.spy, Some(&self.name), kind, key)
+    }
+}
+
+impl<'s, S: WritableStore> Clone for SpyTable<'s, S>
+where
+    TableOf<'s, S>: Clone,
+{
+    fn clone(&self) -> Self {
+        SpyTable { table: self.table.clone(), name: self.name.clone(), spy: self.spy }
+    }
+}
+
+impl<'s, S: WritableStore> fmt::Debug for SpyTable<'s, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SpyTable").field("name", &self.name).finish()
+    }
+}
+
+/// An iterator of a [`SpyTable`], it wraps the errors of the iterator of the wrapped store.
+pub struct SpyIter<I>(I);
+
+impl<I, T, E> Iterator for SpyIter<I>
+where
+    I: Iterator<Item = Result<T, E>>,
+{
+    type Item = Result<T, SpyError<E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| entry.map_err(SpyError::Store))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'s, S: WritableStore> Table<'s> for SpyTable<'s, S> {
+    type Store = SpyStore<S>;
+
+    type Range<'e, KC: BytesDecode, DC: BytesDecode> = SpyIter<RangeOf<'s, 'e, S, KC, DC>>;
+
+    type RevRange<'e, KC: BytesDecode, DC: BytesDecode> = SpyIter<RevRangeOf<'s, 'e, S, KC, DC>>;
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn SpyTxn<S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        self.record(SpyOpKind::Get, KC::bytes_encode(key).as_deref())?;
+        self.table.get::<KC, DC>(txn.rtx(), key).map_err(SpyError::Store)
+    }
+
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        self.record(SpyOpKind::GetForUpdate, KC::bytes_encode(key).as_deref())?;
+        let wtx = match &txn.0.txn {
+            SpyTxnInner::Write(wtx) => wtx,
+            SpyTxnInner::Read(_) => unreachable!("a write transaction wraps a read transaction"),
+        };
+        self.table.get_for_update::<KC, DC>(wtx, key).map_err(SpyError::Store)
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn SpyTxn<S>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.record(SpyOpKind::Range, None)?;
+        self.table.range::<KC, DC, R>(txn.rtx(), range).map(SpyIter).map_err(SpyError::Store)
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn SpyTxn<S>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.record(SpyOpKind::RevRange, None)?;
+        self.table.rev_range::<KC, DC, R>(txn.rtx(), range).map(SpyIter).map_err(SpyError::Store)
+    }
+
+    fn len(&self, txn: &SpyTxn<S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::Len, None)?;
+        self.table.len(txn.rtx()).map_err(SpyError::Store)
+    }
+
+    fn estimated_len(&self, txn: &SpyTxn<S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::EstimatedLen, None)?;
+        self.table.estimated_len(txn.rtx()).map_err(SpyError::Store)
+    }
+
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn SpyTxn<S>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.record(SpyOpKind::RangeLen, None)?;
+        self.table.range_len::<KC, R>(txn.rtx(), range).map_err(SpyError::Store)
+    }
+
+    fn raw_iter<E, F>(
+        &self,
+        txn: &SpyTxn<S>,
+        visit: F,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        self.record(SpyOpKind::RawIter, None).map_err(Either::Left)?;
+        self.table.raw_iter(txn.rtx(), visit).map_err(|e| e.map_left(SpyError::Store))
+    }
+
+    fn warmup(&self, txn: &SpyTxn<S>) -> Result<u64, ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::Warmup, None)?;
+        self.table.warmup(txn.rtx()).map_err(SpyError::Store)
+    }
+
+    fn size_histogram(&self, txn: &SpyTxn<S>) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::SizeHistogram, None)?;
+        self.table.size_histogram(txn.rtx()).map_err(SpyError::Store)
+    }
+
+    fn put<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        self.record(SpyOpKind::Put, KC::bytes_encode(key).as_deref())?;
+        self.table.put::<KC, DC>(txn.wtx(), key, data).map_err(SpyError::Store)
+    }
+
+    fn append<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        self.record(SpyOpKind::Append, KC::bytes_encode(key).as_deref())?;
+        self.table.append::<KC, DC>(txn.wtx(), key, data).map_err(SpyError::Store)
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        self.record(SpyOpKind::Delete, KC::bytes_encode(key).as_deref())?;
+        self.table.delete::<KC>(txn.wtx(), key).map_err(SpyError::Store)
+    }
+
+    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::Clear, None)?;
+        self.table.clear(txn.wtx()).map_err(SpyError::Store)
+    }
+
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>> {
+        self.record(SpyOpKind::Increment, Some(key))?;
+        self.table.increment(txn.wtx(), key, delta).map_err(SpyError::Store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{OwnedType, Str};
+
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn injected_failures_abort_the_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let store = SpyStore::new(env);
+        let a = store.typed::<Str, OwnedType<u32>>("a", &()).unwrap();
+        let b = store.typed::<Str, OwnedType<u32>>("b", &()).unwrap();
+
+        store.fail_at(1);
+        let mut wtx = store.wtx().unwrap();
+        a.put(&mut wtx, "key", &1).unwrap();
+        let failed = b.put(&mut wtx, "key", &2).unwrap_err();
+        let expected =
+            SpyOp { table: Some("b".into()), kind: SpyOpKind::Put, key: Some(b"key".to_vec()) };
+        assert!(matches!(failed, SpyError::Injected(op) if op == expected));
+        drop(wtx);
+
+        // The failure is injected once.
+        let mut wtx = store.wtx().unwrap();
+        b.put(&mut wtx, "key", &2).unwrap();
+        wtx.commit().unwrap();
+
+        let rtx = store.rtx().unwrap();
+        assert_eq!((a.get(&rtx, "key").unwrap(), b.get(&rtx, "key").unwrap()), (None, Some(2)));
+        assert_eq!(store.touched_tables(), ["a", "b"]);
+        assert_eq!(store.ops().len(), 6);
+    }
+}
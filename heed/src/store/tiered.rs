@@ -0,0 +1,694 @@
+//! A [`Store`] caching the reads of a slow store in a fast one, like an LMDB cache over a
+//! RocksDB archive.
+//!
+//! A [`TieredStore`] opens a transaction in both stores for each of its transactions. The
+//! values are read from the fast store first, the ones it misses are read from the slow store
+//! and written to the fast store when the read transaction ends, in a write transaction of
+//! the fast store. The writes go to both stores. The slow store is the reference: the ranges,
+//! the lengths and the other reads of whole tables are read from it.
+//!
+//! # Consistency
+//!
+//! A write transaction commits in the slow store, then in the fast one, only if the slow one
+//! committed. A commit that fails in the fast store returns a [`TieredError::HalfCommitted`],
+//! the fast store may then return the values the transaction overwrote, until it is cleared
+//! with [`TieredStore::clear_fast`]. So may a crash between the two commits, the fast store
+//! is best cleared when the store is opened after a crash.
+//!
+//! The values a read transaction misses are written to the fast store only if no write
+//! transaction of the tiered store committed since it started, and none is alive: they would
+//! be stale, or the write transaction of the fast store would wait for the one of the same
+//! thread. The two read transactions are not a snapshot of both stores taken at the same
+//! time, the store doesn't claim the [`CRASH_SAFE`](Capabilities::CRASH_SAFE) nor the
+//! [`SNAPSHOT_READS`](Capabilities::SNAPSHOT_READS) capabilities.
+//!
+//! The stores must only be written through the tiered store, and the fast store must
+//! serialize its write transactions, like LMDB.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::tiered::TieredStore;
+//! use heed::store::{Store, Table, Transaction, WritableStore};
+//! use heed::types::Str;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let (cache_dir, archive_dir) = (tempfile::tempdir()?, tempfile::tempdir()?);
+//! # let cache = EnvOpenOptions::new().max_dbs(10).open(cache_dir.path())?;
+//! # let archive = EnvOpenOptions::new().max_dbs(10).open(archive_dir.path())?;
+//! let store = TieredStore::new(cache, archive);
+//! let users = store.typed::<Str, Str>("users", &Default::default())?;
+//!
+//! let mut wtx = store.wtx()?;
+//! users.put(&mut wtx, "alice", "Alice")?;
+//! wtx.commit()?;
+//!
+//! // Once cleared, the fast store is filled by the reads.
+//! store.clear_fast()?;
+//! let rtx = store.rtx()?;
+//! assert_eq!(users.get(&rtx, "alice")?.as_deref(), Some("Alice"));
+//! rtx.commit()?;
+//!
+//! let cached = store.fast().open_table("users")?.unwrap();
+//! let value = Table::get::<Str, Str>(&cached, &store.fast().rtx()?, "alice")?;
+//! assert_eq!(value.as_deref(), Some("Alice"));
+//! # Ok(()) }
+//! ```
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+use std::ops::{Deref, RangeBounds};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::{error, fmt, mem};
+
+use either::Either;
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::ByteSlice;
+
+use crate::store::{
+    Capabilities, ErrorOf, RangeOf, RevRangeOf, RtxOf, SizeHistogram, Store, Table, TableOf,
+    Transaction, WritableStore, WtxOf,
+};
+
+/// An error of a [`TieredStore`].
+#[derive(Debug)]
+pub enum TieredError<F, S> {
+    /// An error of the fast store.
+    Fast(F),
+    /// An error of the slow store.
+    Slow(S),
+    /// The commit of a write transaction failed in the fast store after it committed in
+    /// the slow one, the fast store may return the values the transaction overwrote.
+    HalfCommitted(F),
+}
+
+impl<F: fmt::Display, S: fmt::Display> fmt::Display for TieredError<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TieredError::Fast(error) => write!(f, "{}", error),
+            TieredError::Slow(error) => write!(f, "{}", error),
+            TieredError::HalfCommitted(error) => {
+                write!(f, "the commit failed in the fast store after the slow one: {}", error)
+            }
+        }
+    }
+}
+
+impl<F, S> error::Error for TieredError<F, S>
+where
+    F: error::Error + 'static,
+    S: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TieredError::Fast(error) | TieredError::HalfCommitted(error) => Some(error),
+            TieredError::Slow(error) => Some(error),
+        }
+    }
+}
+
+/// A [`Store`] caching a slow store in a fast one, see the [module documentation](self).
+pub struct TieredStore<F, S> {
+    cache: Cache<F>,
+    slow: S,
+}
+
+/// The fast store and what its fills depend on.
+struct Cache<F> {
+    fast: F,
+    /// The number of write transactions committed in the slow store.
+    generation: AtomicU64,
+    /// The number of write transactions alive.
+    writers: AtomicUsize,
+}
+
+impl<F: WritableStore, S: WritableStore> TieredStore<F, S> {
+    pub fn new(fast: F, slow: S) -> TieredStore<F, S> {
+        let cache = Cache { fast, generation: AtomicU64::new(0), writers: AtomicUsize::new(0) };
+        TieredStore { cache, slow }
+    }
+
+    pub fn fast(&self) -> &F {
+        &self.cache.fast
+    }
+
+    pub fn slow(&self) -> &S {
+        &self.slow
+    }
+
+    pub fn into_inner(self) -> (F, S) {
+        (self.cache.fast, self.slow)
+    }
+
+    /// Clears the tables of the fast store, the values are read from the slow store again.
+    pub fn clear_fast(&self) -> Result<(), F::Error> {
+        let mut tables = Vec::new();
+        for name in self.cache.fast.table_names()? {
+            tables.extend(self.cache.fast.open_table(name)?);
+        }
+        let mut wtx = self.cache.fast.wtx()?;
+        for table in &tables {
+            table.clear(&mut wtx)?;
+        }
+        wtx.commit()
+    }
+}
+
+impl<F, S> fmt::Debug for TieredStore<F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TieredStore").finish_non_exhaustive()
+    }
+}
+
+impl<F: WritableStore, S: WritableStore> Store for TieredStore<F, S> {
+    type Error = TieredError<F::Error, S::Error>;
+    type Rtx<'e> = TieredTxn<'e, F, S>;
+    type Table<'store> = TieredTable<'store, F, S>;
+    /// The configurations of the tables of the fast store and of the slow store.
+    type Config = (F::Config, S::Config);
+
+    fn table(
+        &self,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
+        let fast = self.cache.fast.table(name, &cfg.0).map_err(TieredError::Fast)?;
+        let slow = self.slow.table(name, &cfg.1).map_err(TieredError::Slow)?;
+        Ok(TieredTable { fast, slow, name: name.to_owned() })
+    }
+
+    /// Opens the table of the slow store, and creates the table of the fast
+    /// store with the default configuration if it doesn't exist.
+    fn open_table(&self, name: impl AsRef<str>) -> Result<Option<Self::Table<'_>>, Self::Error> {
+        let name = name.as_ref();
+        let slow = match self.slow.open_table(name).map_err(TieredError::Slow)? {
+            Some(slow) => slow,
+            None => return Ok(None),
+        };
+        let fast = match self.cache.fast.open_table(name).map_err(TieredError::Fast)? {
+            Some(fast) => fast,
+            None => self.cache.fast.table(name, &Default::default()).map_err(TieredError::Fast)?,
+        };
+        Ok(Some(TieredTable { fast, slow, name: name.to_owned() }))
+    }
+
+    fn table_exists(&self, name: impl AsRef<str>) -> Result<bool, Self::Error> {
+        self.slow.table_exists(name).map_err(TieredError::Slow)
+    }
+
+    fn table_names(&self) -> Result<Vec<String>, Self::Error> {
+        self.slow.table_names().map_err(TieredError::Slow)
+    }
+
+    fn rtx(&self) -> Result<Self::Rtx<'_>, Self::Error> {
+        // Taken first, the fills can only be older than the transactions.
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+        let fast = self.cache.fast.rtx().map_err(TieredError::Fast)?;
+        let slow = self.slow.rtx().map_err(TieredError::Slow)?;
+        Ok(TieredTxn::new(&self.cache, generation, TieredTxns::Read(fast, slow)))
+    }
+
+    fn counters_config(&self) -> Self::Config {
+        (self.cache.fast.counters_config(), self.slow.counters_config())
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let across =
+            Capabilities::CRASH_SAFE | Capabilities::SNAPSHOT_READS | Capabilities::NESTED_TXN;
+        self.slow.capabilities().difference(across)
+    }
+}
+
+impl<F: WritableStore, S: WritableStore> WritableStore for TieredStore<F, S> {
+    type Wtx<'e> = TieredWtx<'e, F, S>;
+
+    fn wtx(&self) -> Result<Self::Wtx<'_>, Self::Error> {
+        let generation = self.cache.generation.load(Ordering::SeqCst);
+        let fast = self.cache.fast.wtx().map_err(TieredError::Fast)?;
+        let slow = self.slow.wtx().map_err(TieredError::Slow)?;
+        Ok(TieredWtx(TieredTxn::new(&self.cache, generation, TieredTxns::Write(fast, slow))))
+    }
+
+    fn create_table(
+        &self,
+        wtx: &mut WtxOf<Self>,
+        name: impl AsRef<str>,
+        cfg: &Self::Config,
+    ) -> Result<Self::Table<'_>, Self::Error> {
+        let name = name.as_ref();
+        let (fast_wtx, slow_wtx) = wtx.txns_mut();
+        let fast =
+            self.cache.fast.create_table(fast_wtx, name, &cfg.0).map_err(TieredError::Fast)?;
+        let slow = self.slow.create_table(slow_wtx, name, &cfg.1).map_err(TieredError::Slow)?;
+        Ok(TieredTable { fast, slow, name: name.to_owned() })
+    }
+}
+
+/// A read transaction of a [`TieredStore`], a transaction of each store.
+///
+/// The values it read from the slow store are written to the fast store when it
+/// is committed or dropped, see the [module documentation](self).
+pub struct TieredTxn<'e, F: WritableStore + 'e, S: WritableStore + 'e> {
+    /// Taken when the transaction is committed.
+    txns: Option<TieredTxns<'e, F, S>>,
+    cache: &'e Cache<F>,
+    /// The generation of the cache when the transaction started.
+    generation: u64,
+    /// The values missed by the fast store.
+    fills: RefCell<Fills>,
+    extensions: crate::Extensions,
+}
+
+/// The values read from the slow store, by table and key.
+type Fills = BTreeMap<String, BTreeMap<Vec<u8>, Vec<u8>>>;
+
+/// The read transactions are the write transactions too, to be
+/// dereferenced from the write transactions.
+enum TieredTxns<'e, F: WritableStore + 'e, S: WritableStore + 'e> {
+    Read(RtxOf<'e, F>, RtxOf<'e, S>),
+    Write(WtxOf<'e, F>, WtxOf<'e, S>),
+}
+
+impl<'e, F: WritableStore, S: WritableStore> TieredTxn<'e, F, S> {
+    fn new(cache: &'e Cache<F>, generation: u64, txns: TieredTxns<'e, F, S>) -> Self {
+        if let TieredTxns::Write(..) = txns {
+            cache.writers.fetch_add(1, Ordering::SeqCst);
+        }
+        TieredTxn {
+            txns: Some(txns),
+            cache,
+            generation,
+            fills: RefCell::default(),
+            extensions: crate::Extensions::new(),
+        }
+    }
+
+    fn txns(&self) -> &TieredTxns<'e, F, S> {
+        self.txns.as_ref().expect("the transaction is alive until it is committed")
+    }
+
+    /// The transaction of the fast store.
+    pub fn fast(&self) -> &RtxOf<'e, F> {
+        match self.txns() {
+            TieredTxns::Read(fast, _) => fast,
+            TieredTxns::Write(fast, _) => fast,
+        }
+    }
+
+    /// The transaction of the slow store.
+    pub fn slow(&self) -> &RtxOf<'e, S> {
+        match self.txns() {
+            TieredTxns::Read(_, slow) => slow,
+            TieredTxns::Write(_, slow) => slow,
+        }
+    }
+
+    /// Records a value missed by the fast store, the write transactions write it themselves.
+    fn miss(&self, table: &str, key: &[u8], value: &[u8]) {
+        if let TieredTxns::Read(..) = self.txns() {
+            let mut fills = self.fills.borrow_mut();
+            let fills = fills.entry(table.to_owned()).or_default();
+            fills.insert(key.to_vec(), value.to_vec());
+        }
+    }
+
+    /// Writes the values missed by the fast store to it, unless they may be stale.
+    ///
+    /// Called once the transactions are closed, a thread can't open the read
+    /// transaction of LMDB in a write transaction.
+    fn fill(&mut self) -> Result<(), F::Error> {
+        let fills = mem::take(self.fills.get_mut());
+        if fills.is_empty() || self.cache.writers.load(Ordering::SeqCst) != 0 {
+            return Ok(());
+        }
+
+        let mut tables = Vec::with_capacity(fills.len());
+        for (name, entries) in fills {
+            if let Some(table) = self.cache.fast.open_table(name)? {
+                tables.push((table, entries));
+            }
+        }
+        let mut wtx = self.cache.fast.wtx()?;
+        // The write transactions bump the generation before they commit in the fast store.
+        if self.cache.generation.load(Ordering::SeqCst) != self.generation {
+            return Ok(());
+        }
+        for (table, entries) in &tables {
+            for (key, value) in entries {
+                table.raw_put(&mut wtx, key, value)?;
+            }
+        }
+        wtx.commit()
+    }
+}
+
+impl<F: WritableStore, S: WritableStore> Drop for TieredTxn<'_, F, S> {
+    fn drop(&mut self) {
+        match self.txns.take() {
+            Some(TieredTxns::Read(fast, slow)) => {
+                drop((fast, slow));
+                // The fast store is a cache, the values are read from the slow store again.
+                let _ = self.fill();
+            }
+            Some(TieredTxns::Write(..)) => {
+                self.cache.writers.fetch_sub(1, Ordering::SeqCst);
+            }
+            None => {}
+        }
+    }
+}
+
+impl<F: WritableStore, S: WritableStore> Transaction<TieredStore<F, S>> for TieredTxn<'_, F, S> {
+    fn commit(mut self) -> Result<(), TieredError<F::Error, S::Error>> {
+        match self.txns.take() {
+            Some(TieredTxns::Read(fast, slow)) => {
+                slow.commit().map_err(TieredError::Slow)?;
+                fast.commit().map_err(TieredError::Fast)?;
+                self.fill().map_err(TieredError::Fast)
+            }
+            Some(TieredTxns::Write(fast, slow)) => {
+                let committed = slow.commit().map_err(TieredError::Slow).and_then(|()| {
+                    self.cache.generation.fetch_add(1, Ordering::SeqCst);
+                    fast.commit().map_err(TieredError::HalfCommitted)
+                });
+                self.cache.writers.fetch_sub(1, Ordering::SeqCst);
+                committed
+            }
+            None => unreachable!("the transaction is committed once"),
+        }
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        &self.extensions
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        Some(&mut self.extensions)
+    }
+}
+
+/// A write transaction of a [`TieredStore`], it dereferences to a read transaction.
+///
+/// It is committed in the slow store then in the fast one, see the [module documentation](self).
+pub struct TieredWtx<'e, F: WritableStore + 'e, S: WritableStore + 'e>(TieredTxn<'e, F, S>);
+
+impl<'e, F: WritableStore, S: WritableStore> TieredWtx<'e, F, S> {
+    fn txns_mut(&mut self) -> (&mut WtxOf<'e, F>, &mut WtxOf<'e, S>) {
+        match &mut self.0.txns {
+            Some(TieredTxns::Write(fast, slow)) => (fast, slow),
+            _ => unreachable!("a write transaction wraps read transactions"),
+        }
+    }
+}
+
+impl<'e, F: WritableStore, S: WritableStore> Deref for TieredWtx<'e, F, S> {
+    type Target = TieredTxn<'e, F, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<F: WritableStore, S: WritableStore> Transaction<TieredStore<F, S>> for TieredWtx<'_, F, S> {
+    fn commit(self) -> Result<(), TieredError<F::Error, S::Error>> {
+        self.0.commit()
+    }
+
+    fn extensions(&self) -> &crate::Extensions {
+        self.0.extensions()
+    }
+
+    fn extensions_mut(&mut self) -> Option<&mut crate::Extensions> {
+        self.0.extensions_mut()
+    }
+}
+
+/// A table of a [`TieredStore`], in both stores.
+pub struct TieredTable<'s, F: WritableStore + 's, S: WritableStore + 's> {
+    fast: TableOf<'s, F>,
+    slow: TableOf<'s, S>,
+    name: String,
+}
+
+impl<'s, F: WritableStore, S: WritableStore> fmt::Debug for TieredTable<'s, F, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TieredTable").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+/// An iterator of a [`TieredTable`], the iterator of the slow store.
+pub struct TieredIter<I, F> {
+    iter: I,
+    fast: PhantomData<fn() -> F>,
+}
+
+impl<I, F, S, T> Iterator for TieredIter<I, F>
+where
+    I: Iterator<Item = Result<T, S>>,
+{
+    type Item = Result<T, TieredError<F, S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|entry| entry.map_err(TieredError::Slow))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'s, F: WritableStore, S: WritableStore> Table<'s> for TieredTable<'s, F, S> {
+    type Store = TieredStore<F, S>;
+
+    type Range<'e, KC: BytesDecode, DC: BytesDecode> =
+        TieredIter<RangeOf<'s, 'e, S, KC, DC>, F::Error>;
+
+    type RevRange<'e, KC: BytesDecode, DC: BytesDecode> =
+        TieredIter<RevRangeOf<'s, 'e, S, KC, DC>, F::Error>;
+
+    fn get<'a, 'txn, KC, DC>(
+        &self,
+        txn: &'txn TieredTxn<F, S>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        if let Some(value) = self.fast.get::<KC, DC>(txn.fast(), key).map_err(TieredError::Fast)? {
+            return Ok(Some(value));
+        }
+        let bytes = match self.slow.get::<KC, ByteSlice>(txn.slow(), key) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Ok(None),
+            Err(error) => return Err(TieredError::Slow(error)),
+        };
+        match (KC::bytes_encode(key), DC::bytes_decode(&bytes)) {
+            (Some(key), Some(value)) => {
+                txn.miss(&self.name, &key, &bytes);
+                Ok(Some(value))
+            }
+            // The slow store returns the error of the value it can't decode.
+            _ => self.slow.get::<KC, DC>(txn.slow(), key).map_err(TieredError::Slow),
+        }
+    }
+
+    fn get_for_update<'a, KC, DC>(
+        &self,
+        txn: &WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<Option<DC::DItem>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesDecode,
+    {
+        let slow = match txn.0.txns() {
+            TieredTxns::Write(_, slow) => slow,
+            TieredTxns::Read(..) => unreachable!("a write transaction wraps read transactions"),
+        };
+        self.slow.get_for_update::<KC, DC>(slow, key).map_err(TieredError::Slow)
+    }
+
+    fn range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn TieredTxn<F, S>,
+        range: &'a R,
+    ) -> Result<Self::Range<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        let iter = self.slow.range::<KC, DC, R>(txn.slow(), range).map_err(TieredError::Slow)?;
+        Ok(TieredIter { iter, fast: PhantomData })
+    }
+
+    fn rev_range<'a, 'txn, KC, DC, R>(
+        &self,
+        txn: &'txn TieredTxn<F, S>,
+        range: &'a R,
+    ) -> Result<Self::RevRange<'txn, KC, DC>, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        DC: BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        let iter =
+            self.slow.rev_range::<KC, DC, R>(txn.slow(), range).map_err(TieredError::Slow)?;
+        Ok(TieredIter { iter, fast: PhantomData })
+    }
+
+    fn len(&self, txn: &TieredTxn<F, S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.slow.len(txn.slow()).map_err(TieredError::Slow)
+    }
+
+    fn estimated_len(&self, txn: &TieredTxn<F, S>) -> Result<usize, ErrorOf<Self::Store>> {
+        self.slow.estimated_len(txn.slow()).map_err(TieredError::Slow)
+    }
+
+    fn range_len<'a, 'txn, KC, R>(
+        &self,
+        txn: &'txn TieredTxn<F, S>,
+        range: &'a R,
+    ) -> Result<usize, ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a> + BytesDecode,
+        R: RangeBounds<KC::EItem>,
+    {
+        self.slow.range_len::<KC, R>(txn.slow(), range).map_err(TieredError::Slow)
+    }
+
+    fn raw_iter<E, V>(
+        &self,
+        txn: &TieredTxn<F, S>,
+        visit: V,
+    ) -> Result<(), Either<ErrorOf<Self::Store>, E>>
+    where
+        V: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        self.slow.raw_iter(txn.slow(), visit).map_err(|e| e.map_left(TieredError::Slow))
+    }
+
+    /// Warms the table of the fast store up.
+    fn warmup(&self, txn: &TieredTxn<F, S>) -> Result<u64, ErrorOf<Self::Store>> {
+        self.fast.warmup(txn.fast()).map_err(TieredError::Fast)
+    }
+
+    fn size_histogram(&self, txn: &TieredTxn<F, S>) -> Result<SizeHistogram, ErrorOf<Self::Store>> {
+        self.slow.size_histogram(txn.slow()).map_err(TieredError::Slow)
+    }
+
+    fn put<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        let (fast, slow) = txn.txns_mut();
+        self.slow.put::<KC, DC>(slow, key, data).map_err(TieredError::Slow)?;
+        self.fast.put::<KC, DC>(fast, key, data).map_err(TieredError::Fast)
+    }
+
+    /// Appends to the table of the slow store, the fast store only has some of the keys.
+    fn append<'a, KC, DC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+        data: &'a DC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+        DC: BytesEncode<'a>,
+    {
+        let (fast, slow) = txn.txns_mut();
+        self.slow.append::<KC, DC>(slow, key, data).map_err(TieredError::Slow)?;
+        self.fast.put::<KC, DC>(fast, key, data).map_err(TieredError::Fast)
+    }
+
+    fn delete<'a, KC>(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &'a KC::EItem,
+    ) -> Result<(), ErrorOf<Self::Store>>
+    where
+        KC: BytesEncode<'a>,
+    {
+        let (fast, slow) = txn.txns_mut();
+        self.slow.delete::<KC>(slow, key).map_err(TieredError::Slow)?;
+        self.fast.delete::<KC>(fast, key).map_err(TieredError::Fast)
+    }
+
+    fn clear(&self, txn: &mut WtxOf<Self::Store>) -> Result<(), ErrorOf<Self::Store>> {
+        let (fast, slow) = txn.txns_mut();
+        self.slow.clear(slow).map_err(TieredError::Slow)?;
+        self.fast.clear(fast).map_err(TieredError::Fast)
+    }
+
+    /// Increments the counter of the slow store, and removes it from the fast store:
+    /// the counters of RocksDB are merged, they are read from it again.
+    fn increment(
+        &self,
+        txn: &mut WtxOf<Self::Store>,
+        key: &[u8],
+        delta: i64,
+    ) -> Result<i64, ErrorOf<Self::Store>> {
+        let (fast, slow) = txn.txns_mut();
+        let value = self.slow.increment(slow, key, delta).map_err(TieredError::Slow)?;
+        self.fast.delete::<ByteSlice>(fast, key).map_err(TieredError::Fast)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::Str;
+
+    use super::*;
+    use crate::EnvOpenOptions;
+
+    #[test]
+    fn the_misses_fill_the_fast_store_unless_a_write_committed() {
+        let (dir_fast, dir_slow) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let fast = EnvOpenOptions::new().max_dbs(2).open(dir_fast.path()).unwrap();
+        let slow = EnvOpenOptions::new().max_dbs(2).open(dir_slow.path()).unwrap();
+        let store = TieredStore::new(fast, slow);
+        let names = store.typed::<Str, Str>("names", &Default::default()).unwrap();
+
+        let mut wtx = store.wtx().unwrap();
+        names.put(&mut wtx, "a", "alice").unwrap();
+        names.put(&mut wtx, "b", "bob").unwrap();
+        wtx.commit().unwrap();
+        store.clear_fast().unwrap();
+
+        let cached = |key| {
+            let table = store.fast().open_table("names").unwrap().unwrap();
+            Table::get::<Str, Str>(&table, &store.fast().rtx().unwrap(), key).unwrap()
+        };
+
+        // A write committed while the transaction read, its fills are dropped.
+        let rtx = store.rtx().unwrap();
+        assert_eq!(names.get(&rtx, "a").unwrap().as_deref(), Some("alice"));
+        let mut wtx = store.wtx().unwrap();
+        names.put(&mut wtx, "b", "barbara").unwrap();
+        wtx.commit().unwrap();
+        drop(rtx);
+        assert_eq!(cached("a"), None);
+
+        let rtx = store.rtx().unwrap();
+        assert_eq!(names.get(&rtx, "a").unwrap().as_deref(), Some("alice"));
+        assert_eq!(names.get(&rtx, "c").unwrap(), None);
+        drop(rtx);
+        assert_eq!(cached("a").as_deref(), Some("alice"));
+        assert_eq!(cached("b").as_deref(), Some("barbara"));
+
+        let rtx = store.rtx().unwrap();
+        assert_eq!(names.len(&rtx).unwrap(), 2);
+        drop(rtx);
+        assert!(!store.capabilities().crash_safe());
+    }
+}
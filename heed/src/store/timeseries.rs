@@ -0,0 +1,241 @@
+//! Time series on top of any [`Store`], points of many series stored in a single table.
+//!
+//! Every point is keyed by its series id followed by its timestamp, both big endian,
+//! the points of a series are therefore sorted by time and a time range of a series is
+//! a range of keys. The unit of the timestamps is up to the caller.
+//!
+//! Old points can be downsampled with [`TimeSeries::downsample`], it replaces the points of
+//! every bucket of time older than a timestamp by a single one. It rewrites a bounded number
+//! of buckets per call so that it can be run regularly, in short write transactions, by a
+//! background job.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::timeseries::TimeSeries;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::OwnedType;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let temperatures = TimeSeries::<_, OwnedType<f64>>::open(&env, "temperatures")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! for &(ts, value) in &[(0, 20.0), (30, 22.0), (60, 21.0), (90, 23.0), (120, 25.0)] {
+//!     temperatures.append_point(&mut wtxn, 1, ts, &value)?;
+//! }
+//!
+//! // Average the points older than 120 by buckets of 60.
+//! let done = temperatures.downsample(&mut wtxn, 1, 120, 60, 100, |_, points| {
+//!     points.iter().map(|(_, value)| value).sum::<f64>() / points.len() as f64
+//! })?;
+//! assert!(done);
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let points: Result<Vec<_>, _> = temperatures.range_query(&rtxn, 1, ..)?.collect();
+//! assert_eq!(points?, vec![(0, 21.0), (60, 22.0), (120, 25.0)]);
+//! # Ok(()) }
+//! ```
+
+use std::marker;
+use std::ops::{Bound, RangeBounds};
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::ByteSlice;
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+/// The timestamps and values of points of a series, ordered by timestamp.
+pub type Points<'t, T, E> = Box<dyn Iterator<Item = Result<(u64, T), E>> + 't>;
+
+/// The start of a bucket and its points.
+type Bucket<T> = (u64, Vec<(u64, T)>);
+
+/// Points of time series stored in a table of a [`Store`], their values encoded by the `V` codec.
+///
+/// See the [module documentation](self).
+pub struct TimeSeries<'s, S: Store + 's, V> {
+    points: TableOf<'s, S>,
+    marker: marker::PhantomData<V>,
+}
+
+impl<'s, S: Store, V> TimeSeries<'s, S, V>
+where
+    V: BytesDecode + for<'a> BytesEncode<'a, EItem = <V as BytesDecode>::DItem>,
+{
+    /// Open the time series stored in the `name` table of the store.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let points = store.table(name, &S::Config::default())?;
+        Ok(TimeSeries { points, marker: marker::PhantomData })
+    }
+
+    /// Write a point of the series, replacing the point with the same timestamp if any.
+    pub fn append_point(
+        &self,
+        wtx: &mut WtxOf<S>,
+        series: u64,
+        timestamp: u64,
+        value: &V::DItem,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        self.points.put::<ByteSlice, V>(wtx, &point_key(series, timestamp), value)
+    }
+
+    /// Delete the point of the series with the given timestamp, if any.
+    pub fn delete_point(
+        &self,
+        wtx: &mut WtxOf<S>,
+        series: u64,
+        timestamp: u64,
+    ) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        self.points.delete::<ByteSlice>(wtx, &point_key(series, timestamp))
+    }
+
+    /// The points of the series in the range of timestamps, ordered by timestamp.
+    pub fn range_query<'t, R>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        series: u64,
+        range: R,
+    ) -> Result<Points<'t, V::DItem, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+        V: 't,
+        R: RangeBounds<u64>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(&ts) => Bound::Included(point_key(series, ts)),
+            Bound::Excluded(&ts) => Bound::Excluded(point_key(series, ts)),
+            Bound::Unbounded => Bound::Included(point_key(series, 0)),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&ts) => Bound::Included(point_key(series, ts)),
+            Bound::Excluded(&ts) => Bound::Excluded(point_key(series, ts)),
+            Bound::Unbounded => Bound::Included(point_key(series, u64::MAX)),
+        };
+
+        let range = (bound_as_slice(&start), bound_as_slice(&end));
+        let points = self.points.range::<ByteSlice, V, _>(rtx, &range)?;
+        Ok(Box::new(points.map(|point| point.map(|(key, value)| (point_timestamp(&key), value)))))
+    }
+
+    /// Replace the points of each bucket of `bucket` timestamps that ends before `before`
+    /// by a single point at the start of the bucket, with the value returned by `merge`.
+    ///
+    /// Buckets that are already reduced to a single point at their start are left untouched,
+    /// at most `max_buckets` other buckets are rewritten. Returns `true` if all the buckets
+    /// before `before` are downsampled, `false` if it may have to be called again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket` or `max_buckets` is zero.
+    pub fn downsample<F>(
+        &self,
+        wtx: &mut WtxOf<S>,
+        series: u64,
+        before: u64,
+        bucket: u64,
+        max_buckets: usize,
+        mut merge: F,
+    ) -> Result<bool, ErrorOf<S>>
+    where
+        S: WritableStore,
+        F: FnMut(u64, Vec<(u64, V::DItem)>) -> V::DItem,
+    {
+        assert!(bucket > 0, "the size of the buckets must not be zero");
+        assert!(max_buckets > 0, "at least one bucket must be rewritten per call");
+        let cutoff = before - before % bucket;
+
+        // The buckets are complete, the range stops at a bucket boundary or at a new bucket.
+        let mut buckets: Vec<Bucket<V::DItem>> = Vec::new();
+        let mut done = true;
+        for point in self.range_query(wtx, series, ..cutoff)? {
+            let (timestamp, value) = point?;
+            let start = timestamp - timestamp % bucket;
+
+            match buckets.last_mut() {
+                Some((last, points)) if *last == start => points.push((timestamp, value)),
+                _ => {
+                    let reduced = match buckets.last() {
+                        Some((start, points)) => is_reduced(*start, points),
+                        None => false,
+                    };
+                    if reduced {
+                        buckets.pop();
+                    }
+                    if buckets.len() == max_buckets {
+                        done = false;
+                        break;
+                    }
+                    buckets.push((start, vec![(timestamp, value)]));
+                }
+            }
+        }
+
+        for (start, points) in buckets {
+            if is_reduced(start, &points) {
+                continue;
+            }
+
+            for (timestamp, _) in &points {
+                self.delete_point(wtx, series, *timestamp)?;
+            }
+            let value = merge(start, points);
+            self.append_point(wtx, series, start, &value)?;
+        }
+
+        Ok(done)
+    }
+}
+
+/// Whether a bucket only contains a point at its start.
+fn is_reduced<T>(start: u64, points: &[(u64, T)]) -> bool {
+    matches!(points, [(timestamp, _)] if *timestamp == start)
+}
+
+fn point_key(series: u64, timestamp: u64) -> [u8; 16] {
+    let mut key = [0; 16];
+    key[..8].copy_from_slice(&series.to_be_bytes());
+    key[8..].copy_from_slice(&timestamp.to_be_bytes());
+    key
+}
+
+fn point_timestamp(key: &[u8]) -> u64 {
+    let mut timestamp = [0; 8];
+    timestamp.copy_from_slice(&key[8..16]);
+    u64::from_be_bytes(timestamp)
+}
+
+fn bound_as_slice(bound: &Bound<[u8; 16]>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(bytes) => Bound::Included(bytes),
+        Bound::Excluded(bytes) => Bound::Excluded(bytes),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_sort_by_series_then_time() {
+        let mut keys =
+            [point_key(2, 0), point_key(1, u64::MAX), point_key(1, 256), point_key(1, 3)];
+        keys.sort();
+        assert_eq!(
+            keys,
+            [point_key(1, 3), point_key(1, 256), point_key(1, u64::MAX), point_key(2, 0)]
+        );
+        assert_eq!(point_timestamp(&keys[1]), 256);
+    }
+}
@@ -0,0 +1,208 @@
+//! Tables of any [`Store`] that can be truncated in constant time.
+//!
+//! Clearing a large table deletes every one of its entries, in a single write transaction,
+//! which is slow on stores like RocksDB that must read the keys to delete them. The keys of
+//! a [`Truncatable`] table are prefixed by an epoch, read from a meta table, only the entries
+//! of the current epoch are visible. Truncating the table is a single write, that increments
+//! the epoch, the entries of the previous epochs are deleted later on by
+//! [`Truncatable::collect_garbage`]. Each call deletes a limited number of them, the write
+//! transactions that collect the garbage stay short however many entries were truncated.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::store::truncatable::Truncatable;
+//! use heed::store::{Store, WritableStore};
+//! use heed::types::OwnedType;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new()
+//! #     .map_size(10 * 1024 * 1024) // 10MB
+//! #     .max_dbs(3000)
+//! #     .open(dir.path())?;
+//! let sessions = Truncatable::<_, OwnedType<u64>>::open(&env, "sessions")?;
+//!
+//! let mut wtxn = env.wtx()?;
+//! sessions.put(&mut wtxn, b"alice", &1)?;
+//! sessions.put(&mut wtxn, b"bob", &2)?;
+//! sessions.truncate(&mut wtxn)?;
+//! assert_eq!(sessions.get(&wtxn, b"alice")?, None);
+//! sessions.put(&mut wtxn, b"bob", &3)?;
+//! wtxn.commit()?;
+//!
+//! // Later on, in another transaction, delete the truncated entries.
+//! let mut wtxn = env.wtx()?;
+//! assert!(sessions.collect_garbage(&mut wtxn, 100)?);
+//! wtxn.commit()?;
+//!
+//! let rtxn = env.rtx()?;
+//! let entries: Result<Vec<_>, _> = sessions.range(&rtxn, &(..))?.collect();
+//! assert_eq!(entries?, vec![(b"bob".to_vec(), 3)]);
+//! # Ok(()) }
+//! ```
+
+use std::convert::TryInto;
+use std::marker;
+use std::ops::{Bound, RangeBounds};
+
+use heed_traits::{BytesDecode, BytesEncode};
+use heed_types::{ByteSlice, DecodeIgnore};
+
+use crate::store::{ErrorOf, RtxOf, Store, Table, TableOf, WritableStore, WtxOf};
+
+const EPOCH_KEY: &[u8] = b"epoch";
+
+/// The keys, without their epoch, and the values of entries, ordered by key.
+pub type Entries<'t, T, E> = Box<dyn Iterator<Item = Result<(Vec<u8>, T), E>> + 't>;
+
+/// A table of a [`Store`] whose values are encoded by the `V` codec, that can be truncated
+/// without deleting its entries.
+///
+/// See the [module documentation](self).
+pub struct Truncatable<'s, S: Store + 's, V> {
+    entries: TableOf<'s, S>,
+    meta: TableOf<'s, S>,
+    marker: marker::PhantomData<V>,
+}
+
+impl<'s, S: Store, V> Truncatable<'s, S, V>
+where
+    V: BytesDecode + for<'a> BytesEncode<'a, EItem = <V as BytesDecode>::DItem>,
+{
+    /// Open the table stored in the `name` table of the store, its epoch
+    /// is stored in the `name:epoch` table.
+    pub fn open(store: &'s S, name: &str) -> Result<Self, ErrorOf<S>> {
+        let cfg = S::Config::default();
+        let entries = store.table(name, &cfg)?;
+        let meta = store.table(&format!("{}:epoch", name), &cfg)?;
+        Ok(Truncatable { entries, meta, marker: marker::PhantomData })
+    }
+
+    /// The current epoch, the number of times the table was truncated.
+    pub fn epoch(&self, rtx: &RtxOf<S>) -> Result<u64, ErrorOf<S>> {
+        let epoch = self.meta.get::<ByteSlice, ByteSlice>(rtx, EPOCH_KEY)?;
+        Ok(epoch.and_then(|bytes| bytes[..].try_into().ok()).map_or(0, u64::from_be_bytes))
+    }
+
+    pub fn get(&self, rtx: &RtxOf<S>, key: &[u8]) -> Result<Option<V::DItem>, ErrorOf<S>> {
+        let key = entry_key(self.epoch(rtx)?, key);
+        self.entries.get::<ByteSlice, V>(rtx, &key)
+    }
+
+    /// Write an entry, replacing the entry with the same key if any.
+    pub fn put(&self, wtx: &mut WtxOf<S>, key: &[u8], value: &V::DItem) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let key = entry_key(self.epoch(wtx)?, key);
+        self.entries.put::<ByteSlice, V>(wtx, &key, value)
+    }
+
+    /// Delete the entry with the given key, if any.
+    pub fn delete(&self, wtx: &mut WtxOf<S>, key: &[u8]) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let key = entry_key(self.epoch(wtx)?, key);
+        self.entries.delete::<ByteSlice>(wtx, &key)
+    }
+
+    /// The entries of the current epoch in the range of keys, ordered by key.
+    pub fn range<'t, R>(
+        &self,
+        rtx: &'t RtxOf<S>,
+        range: &R,
+    ) -> Result<Entries<'t, V::DItem, ErrorOf<S>>, ErrorOf<S>>
+    where
+        's: 't,
+        V: 't,
+        R: RangeBounds<[u8]>,
+    {
+        let epoch = self.epoch(rtx)?;
+        let start = match range.start_bound() {
+            Bound::Included(key) => Bound::Included(entry_key(epoch, key)),
+            Bound::Excluded(key) => Bound::Excluded(entry_key(epoch, key)),
+            Bound::Unbounded => Bound::Included(entry_key(epoch, &[])),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(key) => Bound::Included(entry_key(epoch, key)),
+            Bound::Excluded(key) => Bound::Excluded(entry_key(epoch, key)),
+            Bound::Unbounded => Bound::Excluded(entry_key(epoch + 1, &[])),
+        };
+
+        let range = (bound_as_slice(&start), bound_as_slice(&end));
+        let entries = self.entries.range::<ByteSlice, V, _>(rtx, &range)?;
+        Ok(Box::new(entries.map(|entry| entry.map(|(key, value)| (key[8..].to_vec(), value)))))
+    }
+
+    /// Make every entry invisible by moving to the next epoch,
+    /// it doesn't depend on the number of entries.
+    pub fn truncate(&self, wtx: &mut WtxOf<S>) -> Result<(), ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        let epoch = self.epoch(wtx)? + 1;
+        self.meta.put::<ByteSlice, ByteSlice>(wtx, EPOCH_KEY, &epoch.to_be_bytes())
+    }
+
+    /// Delete at most `max_entries` entries of the previous epochs. Returns `true`
+    /// if they are all deleted, `false` if it may have to be called again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_entries` is zero.
+    pub fn collect_garbage(
+        &self,
+        wtx: &mut WtxOf<S>,
+        max_entries: usize,
+    ) -> Result<bool, ErrorOf<S>>
+    where
+        S: WritableStore,
+    {
+        assert!(max_entries > 0, "at least one entry must be deleted per call");
+        let current = entry_key(self.epoch(wtx)?, &[]);
+        let range = (Bound::Unbounded, Bound::Excluded(&current[..]));
+        let keys = self
+            .entries
+            .range::<ByteSlice, DecodeIgnore, _>(wtx, &range)?
+            .take(max_entries)
+            .map(|entry| entry.map(|(key, ())| key))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for key in &keys {
+            self.entries.delete::<ByteSlice>(wtx, key)?;
+        }
+        Ok(keys.len() < max_entries)
+    }
+}
+
+fn entry_key(epoch: u64, key: &[u8]) -> Vec<u8> {
+    let mut entry_key = Vec::with_capacity(8 + key.len());
+    entry_key.extend_from_slice(&epoch.to_be_bytes());
+    entry_key.extend_from_slice(key);
+    entry_key
+}
+
+fn bound_as_slice(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    match bound {
+        Bound::Included(bytes) => Bound::Included(bytes),
+        Bound::Excluded(bytes) => Bound::Excluded(bytes),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_sort_by_epoch_then_key() {
+        let mut keys =
+            [entry_key(1, b""), entry_key(0, b"zz"), entry_key(0, b"a"), entry_key(1, b"a")];
+        keys.sort();
+        assert_eq!(
+            keys,
+            [entry_key(0, b"a"), entry_key(0, b"zz"), entry_key(1, b""), entry_key(1, b"a")]
+        );
+    }
+}
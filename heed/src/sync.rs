@@ -0,0 +1,234 @@
+//! Group syncs of the environments opened without syncs.
+//!
+//! The commits of an environment opened without syncs, with the LMDB `MdbNoSync` flag or the
+//! MDBX `MDBX_SAFE_NOSYNC` mode, don't wait for the disk, a sync with [`Env::force_sync`]
+//! writes all of them at once. A [`SyncScheduler`] coalesces the syncs of the writers of an
+//! environment: it syncs the commits it is told about at most every `interval`, or once
+//! `max_commits` of them are waiting, from a thread of its own. A writer that needs its commit
+//! to be durable before answering waits with [`SyncScheduler::flush_and_wait`], the writers
+//! waiting together are acknowledged by the same sync.
+//!
+//! A failed sync of the thread is retried on its next tick, a failed sync of
+//! [`flush_and_wait`](SyncScheduler::flush_and_wait) is returned to the writer running it,
+//! the other waiting writers run a sync of their own.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use heed::sync::SyncScheduler;
+//! use heed::types::Str;
+//! use heed::EnvOpenOptions;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().open(dir.path())?;
+//! let scheduler = SyncScheduler::new(env.clone(), Duration::from_millis(100), 1000);
+//!
+//! let mut wtxn = env.write_txn()?;
+//! let db = env.create_database::<Str, Str>(&mut wtxn, None)?;
+//! db.put(&mut wtxn, "low", "value")?;
+//! scheduler.commit(wtxn)?;
+//!
+//! let mut wtxn = env.write_txn()?;
+//! db.put(&mut wtxn, "critical", "value")?;
+//! scheduler.commit(wtxn)?;
+//! scheduler.flush_and_wait()?;
+//! assert_eq!(scheduler.pending(), 0);
+//! # Ok(()) }
+//! ```
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{Env, Result, RwTxn};
+
+/// Coalesces the syncs of the commits of an environment, see the
+/// [module documentation](self).
+pub struct SyncScheduler {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    env: Env,
+    interval: Duration,
+    max_commits: u64,
+    state: Mutex<State>,
+    /// Wakes the thread, when enough commits are waiting or the scheduler is dropped.
+    wake: Condvar,
+    /// Wakes the writers waiting for a sync, when one is done.
+    synced: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// The number of commits the scheduler was told about.
+    committed: u64,
+    /// The number of commits written by the last successful sync.
+    synced: u64,
+    /// The number of successful syncs.
+    syncs: u64,
+    syncing: bool,
+    stopping: bool,
+}
+
+impl Shared {
+    /// Syncs the commits not synced yet, or waits for the running sync, until the first
+    /// `target` commits are synced.
+    fn sync_until<'s>(&'s self, mut state: MutexGuard<'s, State>, target: u64) -> Result<()> {
+        while state.synced < target {
+            if state.syncing {
+                state = self.synced.wait(state).unwrap();
+                continue;
+            }
+
+            state.syncing = true;
+            let covered = state.committed;
+            drop(state);
+            let result = self.env.force_sync();
+            state = self.state.lock().unwrap();
+            state.syncing = false;
+            if result.is_ok() {
+                state.synced = state.synced.max(covered);
+                state.syncs += 1;
+            }
+            self.synced.notify_all();
+            result?;
+        }
+
+        Ok(())
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut last_sync = Instant::now();
+        while !state.stopping {
+            let pending = state.committed - state.synced;
+            let elapsed = last_sync.elapsed();
+            if pending > 0 && (pending >= self.max_commits || elapsed >= self.interval) {
+                let target = state.committed;
+                let result = self.sync_until(state, target);
+                last_sync = Instant::now();
+                state = self.state.lock().unwrap();
+                if result.is_err() {
+                    // a failed sync is retried on the next tick
+                    state = self.wake.wait_timeout(state, self.interval).unwrap().0;
+                }
+            } else {
+                let timeout = self.interval.checked_sub(elapsed).unwrap_or(self.interval);
+                state = self.wake.wait_timeout(state, timeout).unwrap().0;
+            }
+        }
+    }
+}
+
+impl SyncScheduler {
+    /// Starts the thread syncing the commits of the environment, at most every `interval`,
+    /// or as soon as `max_commits` are waiting.
+    pub fn new(env: Env, interval: Duration, max_commits: u64) -> SyncScheduler {
+        let shared = Arc::new(Shared {
+            env,
+            interval,
+            max_commits: max_commits.max(1),
+            state: Mutex::default(),
+            wake: Condvar::new(),
+            synced: Condvar::new(),
+        });
+
+        let thread_shared = Arc::clone(&shared);
+        let thread = thread::Builder::new()
+            .name("heed-sync".to_string())
+            .spawn(move || thread_shared.run())
+            .expect("the sync thread was spawned");
+
+        SyncScheduler { shared, thread: Some(thread) }
+    }
+
+    /// Commits the transaction, its changes are synced with the next sync.
+    pub fn commit<'e, T>(&self, wtxn: RwTxn<'e, 'e, T>) -> Result<()> {
+        wtxn.commit()?;
+        self.committed();
+        Ok(())
+    }
+
+    /// Tells the scheduler about a commit of the environment, made without it.
+    pub fn committed(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.committed += 1;
+        if state.committed - state.synced >= self.shared.max_commits {
+            self.shared.wake.notify_one();
+        }
+    }
+
+    /// Syncs the commits the scheduler was told about, and returns once they are on the
+    /// disk. A sync already running, that doesn't cover all of them, is waited for first.
+    pub fn flush_and_wait(&self) -> Result<()> {
+        let state = self.shared.state.lock().unwrap();
+        let target = state.committed;
+        self.shared.sync_until(state, target)
+    }
+
+    /// The number of commits not synced yet.
+    pub fn pending(&self) -> u64 {
+        let state = self.shared.state.lock().unwrap();
+        state.committed - state.synced
+    }
+
+    /// The number of syncs made, each of them wrote one or more commits.
+    pub fn syncs(&self) -> u64 {
+        self.shared.state.lock().unwrap().syncs
+    }
+}
+
+impl Drop for SyncScheduler {
+    /// Stops the thread and syncs the commits not synced yet.
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopping = true;
+        self.shared.wake.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = self.flush_and_wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn commits_are_synced_in_groups() {
+        use super::*;
+        use crate::flags::Flags;
+        use crate::types::Str;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        unsafe { options.flag(Flags::MdbNoSync) };
+        let env = options.open(dir.path()).unwrap();
+        let scheduler = SyncScheduler::new(env.clone(), Duration::from_secs(3600), 3);
+
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        scheduler.commit(wtxn).unwrap();
+        for key in ["a", "b"].iter() {
+            let mut wtxn = env.write_txn().unwrap();
+            db.put(&mut wtxn, key, "value").unwrap();
+            scheduler.commit(wtxn).unwrap();
+        }
+
+        // the third commit wakes the thread, that syncs the three of them at once
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while scheduler.pending() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!((scheduler.pending(), scheduler.syncs()), (0, 1));
+
+        scheduler.committed();
+        scheduler.flush_and_wait().unwrap();
+        assert_eq!((scheduler.pending(), scheduler.syncs()), (0, 2));
+        scheduler.flush_and_wait().unwrap();
+        assert_eq!(scheduler.syncs(), 2);
+    }
+}
@@ -0,0 +1,311 @@
+//! Recommends the [`EnvOpenOptions`] of an environment from the data it stores.
+//!
+//! [`inspect`] reads every entry of the named databases, to count the lengths of their keys
+//! and values, and the freelist of the environment. The [`TuneReport`] it returns gives the
+//! recommended page size and map size, with the numbers they were chosen from:
+//!
+//! - The page size is the smallest one under which at most 1% of the values overflow their
+//!   leaf page, a node larger than about half a page is stored in overflow pages of its own.
+//!   LMDB can only use the page size of the system, it is the only one recommended.
+//! - The map size leaves as much room as the used pages, and can hold the data file.
+//!
+//! The `tune` example prints the report of the environment at a path:
+//! `cargo run --example tune -- path/to/env`.
+//!
+//! ```
+//! # use heed::EnvOpenOptions;
+//! use heed::types::{ByteSlice, Str};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let dir = tempfile::tempdir()?;
+//! # let env = EnvOpenOptions::new().max_dbs(10).open(dir.path())?;
+//! let mut wtxn = env.write_txn()?;
+//! let blobs = env.create_database::<Str, ByteSlice>(&mut wtxn, Some("blobs"))?;
+//! blobs.put(&mut wtxn, "small", &[0; 100])?;
+//! blobs.put(&mut wtxn, "large", &[0; 100_000])?;
+//! wtxn.commit()?;
+//!
+//! let report = heed::tune::inspect(&env)?;
+//! assert_eq!(report.entries(), 2);
+//! assert!(report.recommended_map_size >= 2 * report.used_size as usize);
+//! let options = report.options();
+//! # Ok(()) }
+//! ```
+//!
+//! [`prefix_stats`] counts the entries of a table of any store by prefix of their keys, to
+//! find the tenants or namespaces taking most of it.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt;
+
+use either::Either;
+
+use crate::store::{ErrorOf, RtxOf, SizeHistogram, Table};
+use crate::{Env, EnvOpenOptions, FreelistStats, Result, RoCursor};
+
+/// The largest page size the engines accept.
+const MAX_PAGE_SIZE: usize = 64 * 1024;
+
+/// The part of the values allowed to overflow under the recommended page size.
+const MAX_OVERFLOW_RATIO: f64 = 0.01;
+
+/// The room left for the data to grow in the recommended map size, relative to the used pages.
+const MAP_HEADROOM: u64 = 2;
+
+/// The usage of an environment and the options recommended for it, see [`inspect`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneReport {
+    /// The size of the pages of the environment.
+    pub page_size: usize,
+    /// The size of the map of the environment.
+    pub map_size: usize,
+    /// The size of the data file.
+    pub file_size: u64,
+    /// The size of the pages used by the databases, without the free pages.
+    pub used_size: u64,
+    pub freelist: FreelistStats,
+    /// The lengths of the keys and values of the named databases.
+    pub tables: BTreeMap<String, SizeHistogram>,
+    /// The number of values that would overflow their leaf page, by page size.
+    pub overflowing: BTreeMap<usize, u64>,
+    pub recommended_page_size: usize,
+    pub recommended_map_size: usize,
+}
+
+impl TuneReport {
+    /// The number of entries of the named databases.
+    pub fn entries(&self) -> u64 {
+        self.tables.values().map(|histogram| histogram.entries).sum()
+    }
+
+    /// The options with the recommended page size and map size, the others are the defaults.
+    pub fn options(&self) -> EnvOpenOptions {
+        let mut options = EnvOpenOptions::new();
+        options.map_size(self.recommended_map_size);
+        if self.recommended_page_size != EnvOpenOptions::recommended_page_size() {
+            options.page_size(self.recommended_page_size);
+        }
+        options
+    }
+}
+
+impl fmt::Display for TuneReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "page size: {} (recommended {})", self.page_size, self.recommended_page_size)?;
+        writeln!(f, "map size: {} (recommended {})", self.map_size, self.recommended_map_size)?;
+        writeln!(f, "file size: {}, used: {}", self.file_size, self.used_size)?;
+        writeln!(
+            f,
+            "free pages: {}, reclaimable: {}",
+            self.freelist.free_pages, self.freelist.reclaimable_pages
+        )?;
+        for (name, histogram) in &self.tables {
+            writeln!(
+                f,
+                "table {}: {} entries, {} key bytes, {} value bytes, largest value {}",
+                name,
+                histogram.entries,
+                histogram.key_bytes,
+                histogram.value_bytes,
+                histogram.max_value_len
+            )?;
+        }
+        for (page_size, values) in &self.overflowing {
+            writeln!(f, "overflowing values with {} byte pages: {}", page_size, values)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the databases and the freelist of the environment and recommends its options.
+///
+/// The named databases that aren't opened are opened for a moment, the environment
+/// must be opened with enough [`max_dbs`](EnvOpenOptions::max_dbs) for one more.
+pub fn inspect(env: &Env) -> Result<TuneReport> {
+    let freelist = env.freelist_stats()?;
+    let page_size = freelist.page_size as usize;
+    let used_size = env.non_free_pages_size()?;
+    let file_size = env.real_disk_size()?;
+
+    let candidates = page_sizes(page_size);
+    let mut overflowing: BTreeMap<_, _> = candidates.iter().map(|&size| (size, 0)).collect();
+    let mut histograms = BTreeMap::new();
+    let rtxn = env.read_txn()?;
+    env.for_each_named_database(&rtxn, |name, dbi| {
+        let mut histogram = SizeHistogram::default();
+        let mut cursor = RoCursor::new(&rtxn, dbi)?;
+        while let Some((key, value)) = cursor.move_on_next()? {
+            histogram.record(key.len(), value.len());
+            for (&page_size, count) in overflowing.iter_mut() {
+                *count += overflows(page_size, key.len(), value.len()) as u64;
+            }
+        }
+        histograms.insert(name.to_owned(), histogram);
+        Ok(())
+    })?;
+    rtxn.commit()?;
+
+    let entries: u64 = histograms.values().map(|histogram| histogram.entries).sum();
+    let max_overflowing = (entries as f64 * MAX_OVERFLOW_RATIO) as u64;
+    let recommended_page_size = overflowing
+        .iter()
+        .find(|(_, &count)| count <= max_overflowing)
+        .map_or(*candidates.last().unwrap(), |(&size, _)| size);
+
+    let needed = (used_size * MAP_HEADROOM).max(file_size) as usize;
+    let recommended_map_size = (needed / recommended_page_size + 1) * recommended_page_size;
+
+    Ok(TuneReport {
+        page_size,
+        map_size: env.map_size()?,
+        file_size,
+        used_size,
+        freelist,
+        tables: histograms,
+        overflowing,
+        recommended_page_size,
+        recommended_map_size,
+    })
+}
+
+/// The entries of a table whose keys start with a prefix, see [`prefix_stats`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PrefixStats {
+    pub entries: u64,
+    /// The bytes of the keys and values of the entries.
+    pub bytes: u64,
+}
+
+/// The entries of a table by prefix of their keys, see [`prefix_stats`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PrefixReport {
+    /// The length of the prefixes, the keys shorter than it are their own prefix.
+    pub depth: usize,
+    pub prefixes: BTreeMap<Vec<u8>, PrefixStats>,
+}
+
+impl PrefixReport {
+    /// The `k` prefixes with the most entries, from the largest.
+    pub fn top_by_entries(&self, k: usize) -> Vec<(&[u8], &PrefixStats)> {
+        self.top(k, |stats| stats.entries)
+    }
+
+    /// The `k` prefixes with the most bytes, from the largest.
+    pub fn top_by_bytes(&self, k: usize) -> Vec<(&[u8], &PrefixStats)> {
+        self.top(k, |stats| stats.bytes)
+    }
+
+    fn top(&self, k: usize, weight: impl Fn(&PrefixStats) -> u64) -> Vec<(&[u8], &PrefixStats)> {
+        let mut prefixes: Vec<_> =
+            self.prefixes.iter().map(|(prefix, stats)| (prefix.as_slice(), stats)).collect();
+        // the sort is stable, the prefixes of the same weight stay in key order
+        prefixes.sort_by_key(|(_, stats)| Reverse(weight(stats)));
+        prefixes.truncate(k);
+        prefixes
+    }
+}
+
+/// Reads every entry of the table and counts them by the first `depth` bytes of their keys.
+pub fn prefix_stats<'s, T: Table<'s>>(
+    rtx: &RtxOf<T::Store>,
+    table: &T,
+    depth: usize,
+) -> std::result::Result<PrefixReport, ErrorOf<T::Store>> {
+    let mut prefixes: BTreeMap<Vec<u8>, PrefixStats> = BTreeMap::new();
+    let visited = table.raw_iter::<Infallible, _>(rtx, |key, value| {
+        let prefix = &key[..key.len().min(depth)];
+        let stats = match prefixes.get_mut(prefix) {
+            Some(stats) => stats,
+            None => prefixes.entry(prefix.to_vec()).or_default(),
+        };
+        stats.entries += 1;
+        stats.bytes += (key.len() + value.len()) as u64;
+        Ok(())
+    });
+
+    match visited {
+        Ok(()) => Ok(PrefixReport { depth, prefixes }),
+        Err(Either::Left(e)) => Err(e),
+        Err(Either::Right(never)) => match never {},
+    }
+}
+
+/// The page sizes the engine accepts, from the smallest, given the page size of the environment.
+fn page_sizes(page_size: usize) -> Vec<usize> {
+    if cfg!(feature = "mdbx") {
+        let system = EnvOpenOptions::recommended_page_size().min(page_size);
+        let mut sizes = vec![system];
+        while sizes[sizes.len() - 1] < MAX_PAGE_SIZE {
+            sizes.push(sizes[sizes.len() - 1] * 2);
+        }
+        sizes
+    } else {
+        vec![EnvOpenOptions::recommended_page_size()]
+    }
+}
+
+/// Whether an entry is stored in overflow pages: the leaf pages hold at least two
+/// nodes, made of a header of 8 bytes, the key and the value, after a header of 16 bytes.
+fn overflows(page_size: usize, key_len: usize, value_len: usize) -> bool {
+    8 + key_len + value_len > (page_size - 16) / 2
+}
+
+#[cfg(test)]
+mod tests {
+    use heed_types::{ByteSlice, OwnedType};
+
+    use super::*;
+
+    #[test]
+    fn the_large_values_overflow() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(2).open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, ByteSlice>(&mut wtxn, Some("db")).unwrap();
+        for i in 0..10 {
+            let len = if i == 0 { 10_000 } else { 10 };
+            db.put(&mut wtxn, &i, &vec![0; len]).unwrap();
+        }
+        wtxn.commit().unwrap();
+
+        let report = inspect(&env).unwrap();
+        assert_eq!(report.tables["db"].entries, 10);
+        assert_eq!(report.overflowing[&report.page_size], 1);
+        assert_eq!(report.recommended_map_size % report.recommended_page_size, 0);
+        assert!(report.recommended_map_size as u64 > report.file_size);
+    }
+
+    #[test]
+    fn the_heaviest_prefixes_come_first() {
+        use crate::store::Store;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().max_dbs(1).open(dir.path()).unwrap();
+        let table = env.table("users", &()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        for key in ["a:1", "a:2", "a:3", "b:1", "c"].iter() {
+            table.raw_put(&mut wtxn, key.as_bytes(), b"").unwrap();
+        }
+        table.raw_put(&mut wtxn, b"b:2", &[0; 100]).unwrap();
+
+        wtxn.commit().unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        let report = prefix_stats(&rtxn, &table, 2).unwrap();
+        assert_eq!(report.prefixes.len(), 3);
+        let entries: Vec<_> = report
+            .top_by_entries(2)
+            .into_iter()
+            .map(|(prefix, stats)| (prefix, stats.entries))
+            .collect();
+        assert_eq!(entries, [(&b"a:"[..], 3), (&b"b:"[..], 2)]);
+        let bytes: Vec<_> = report
+            .top_by_bytes(5)
+            .into_iter()
+            .map(|(prefix, stats)| (prefix, stats.bytes))
+            .collect();
+        assert_eq!(bytes, [(&b"b:"[..], 106), (&b"a:"[..], 9), (&b"c"[..], 1)]);
+    }
+}
@@ -1,36 +1,83 @@
+#[cfg(feature = "read-txn-no-tls")]
+use std::mem;
+use std::mem::ManuallyDrop;
 use std::ops::Deref;
-use std::{marker, ptr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "read-txn-no-tls")]
+use std::sync::Mutex;
+use std::{fmt, marker, ptr};
 
 use crate::mdb::error::mdb_result;
 use crate::mdb::ffi;
-use crate::{Env, Result};
+use crate::mdb::op::MdbOp;
+use crate::{Env, Extensions, Result};
 
+/// A read transaction, it is aborted when dropped, committing it isn't needed.
+#[must_use = "a read transaction is aborted as soon as it is dropped"]
 pub struct RoTxn<'e, T = ()> {
     pub(crate) txn: *mut ffi::MDB_txn,
     pub(crate) env: &'e Env,
+    /// The flags the transaction began with.
+    flags: u32,
+    /// Bumped when the transaction ends, the cursors check it didn't change since they opened.
+    pub(crate) generation: Arc<AtomicU64>,
+    extensions: Extensions,
     _phantom: marker::PhantomData<T>,
 }
 
 impl<'e, T> RoTxn<'e, T> {
     pub(crate) fn new(env: &'e Env) -> Result<RoTxn<'e, T>> {
-        let mut txn: *mut ffi::MDB_txn = ptr::null_mut();
+        let flags = ffi::MDB_RDONLY;
+        let txn = env.begin_txn(ptr::null_mut(), flags)?;
 
-        unsafe {
-            mdb_result(ffi::mdb_txn_begin(
-                env.env_mut_ptr(),
-                ptr::null_mut(),
-                ffi::MDB_RDONLY,
-                &mut txn,
-            ))?
-        };
+        Ok(RoTxn {
+            txn,
+            env,
+            flags,
+            generation: Arc::default(),
+            extensions: Extensions::new(),
+            _phantom: marker::PhantomData,
+        })
+    }
+
+    /// The id of this transaction, read transactions have the id of the snapshot they
+    /// see, write transactions the id they will commit with.
+    pub fn id(&self) -> u64 {
+        ffi::txn_id(self.txn)
+    }
 
-        Ok(RoTxn { txn, env, _phantom: marker::PhantomData })
+    /// Whether this transaction reads or writes.
+    pub fn kind(&self) -> TxnKind {
+        if self.flags & ffi::MDB_RDONLY == 0 {
+            TxnKind::Write
+        } else {
+            TxnKind::Read
+        }
+    }
+
+    /// The values attached to this transaction, see [`Extensions`].
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
     }
 
     pub fn commit(mut self) -> Result<()> {
         let result = unsafe { mdb_result(ffi::mdb_txn_commit(self.txn)) };
         self.txn = ptr::null_mut();
-        result.map_err(Into::into)
+        MdbOp::new("mdb_txn_commit").txn(self.kind()).result(result)
+    }
+
+    /// Commits this write transaction without waiting for the disk, while the caller holds
+    /// the [`commit_lock`](Env::commit_lock) of the environment.
+    pub(crate) fn commit_without_sync(mut self) -> Result<()> {
+        let result =
+            unsafe { mdb_result(ffi::txn_commit_without_sync(self.env.env_mut_ptr(), self.txn)) };
+        self.txn = ptr::null_mut();
+        MdbOp::new("mdb_txn_commit").txn(self.kind()).result(result)
     }
 
     pub fn abort(mut self) -> Result<()> {
@@ -45,12 +92,187 @@ impl<T> Drop for RoTxn<'_, T> {
         if !self.txn.is_null() {
             let _ = abort_txn(self.txn);
         }
+        // Committed, aborted or dropped, the cursors opened on it can't be used anymore.
+        self.generation.fetch_add(1, Ordering::Release);
+        self.env.end_txn(self.flags);
     }
 }
 
 #[cfg(feature = "read-txn-no-tls")]
 unsafe impl<T> Send for RoTxn<'_, T> {}
 
+/// A read transaction that owns a clone of its environment instead of borrowing it.
+///
+/// It isn't tied to the lifetime of an `Env` and can be stored or moved into spawned tasks
+/// along with the iterators created from it. It can only be sent to other threads when the
+/// `read-txn-no-tls` feature is enabled, like the `RoTxn`.
+#[must_use = "a read transaction is aborted as soon as it is dropped"]
+pub struct OwnedRoTxn<T = ()> {
+    // Dropped by hand, the transaction must be aborted before the environment is released.
+    txn: ManuallyDrop<RoTxn<'static, T>>,
+    env: *mut Env,
+}
+
+impl<T> OwnedRoTxn<T> {
+    pub(crate) fn new(env: Env) -> Result<OwnedRoTxn<T>> {
+        let env = Box::into_raw(Box::new(env));
+
+        // The environment lives on the heap, at the same place, until this transaction is dropped.
+        match RoTxn::new(unsafe { &*env }) {
+            Ok(txn) => Ok(OwnedRoTxn { txn: ManuallyDrop::new(txn), env }),
+            Err(e) => {
+                drop(unsafe { Box::from_raw(env) });
+                Err(e)
+            }
+        }
+    }
+
+    /// The environment this transaction reads from.
+    pub fn env(&self) -> &Env {
+        unsafe { &*self.env }
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        let result = unsafe { mdb_result(ffi::mdb_txn_commit(self.txn.txn)) };
+        self.txn.txn = ptr::null_mut();
+        MdbOp::new("mdb_txn_commit").txn(TxnKind::Read).result(result)
+    }
+
+    pub fn abort(mut self) -> Result<()> {
+        let result = abort_txn(self.txn.txn);
+        self.txn.txn = ptr::null_mut();
+        result
+    }
+}
+
+impl<T> Deref for OwnedRoTxn<T> {
+    type Target = RoTxn<'static, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+impl<T> Drop for OwnedRoTxn<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.txn);
+            drop(Box::from_raw(self.env));
+        }
+    }
+}
+
+#[cfg(feature = "read-txn-no-tls")]
+unsafe impl<T> Send for OwnedRoTxn<T> {}
+
+/// A pool of read transactions, the transactions it hands out are reset instead of aborted
+/// when dropped and renewed when handed out again, they keep their reader slot and their
+/// memory between the requests of a server.
+///
+/// It keeps at most `capacity` idle transactions, the others are aborted. An idle
+/// transaction holds a reader slot but no snapshot, it doesn't keep pages from being reused.
+/// It needs the `read-txn-no-tls` feature: the reader slots are tied to the transactions
+/// instead of the threads, the pooled transactions can be used on any thread.
+#[cfg(feature = "read-txn-no-tls")]
+pub struct RoTxnPool {
+    env: Env,
+    idle: Mutex<Vec<IdleTxn>>,
+    capacity: usize,
+}
+
+/// A reset transaction, it isn't tied to a thread.
+#[cfg(feature = "read-txn-no-tls")]
+struct IdleTxn(*mut ffi::MDB_txn);
+
+#[cfg(feature = "read-txn-no-tls")]
+unsafe impl Send for IdleTxn {}
+
+#[cfg(feature = "read-txn-no-tls")]
+impl RoTxnPool {
+    pub fn new(env: Env, capacity: usize) -> RoTxnPool {
+        RoTxnPool { env, idle: Mutex::new(Vec::with_capacity(capacity)), capacity }
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// The number of idle transactions, ready to be renewed.
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// A read transaction on the last snapshot, an idle one renewed
+    /// or a new one when none is idle.
+    pub fn read_txn(&self) -> Result<PooledRoTxn<'_>> {
+        let idle = self.idle.lock().unwrap().pop();
+        let txn = match idle {
+            Some(IdleTxn(txn)) => match self.env.renew_txn(txn) {
+                Ok(()) => RoTxn {
+                    txn,
+                    env: &self.env,
+                    flags: ffi::MDB_RDONLY,
+                    generation: Arc::default(),
+                    extensions: Extensions::new(),
+                    _phantom: marker::PhantomData,
+                },
+                Err(crate::Error::DatabaseClosing) => {
+                    let _ = abort_txn(txn);
+                    return Err(crate::Error::DatabaseClosing);
+                }
+                // The map may have been resized, a new transaction adopts the new size.
+                Err(_) => {
+                    let _ = abort_txn(txn);
+                    RoTxn::new(&self.env)?
+                }
+            },
+            None => RoTxn::new(&self.env)?,
+        };
+        Ok(PooledRoTxn { txn, pool: self })
+    }
+}
+
+#[cfg(feature = "read-txn-no-tls")]
+impl Drop for RoTxnPool {
+    fn drop(&mut self) {
+        for IdleTxn(txn) in self.idle.get_mut().unwrap().drain(..) {
+            let _ = abort_txn(txn);
+        }
+    }
+}
+
+/// A read transaction of a [`RoTxnPool`], returned to the pool when dropped.
+#[cfg(feature = "read-txn-no-tls")]
+#[must_use = "a pooled read transaction is returned to the pool as soon as it is dropped"]
+pub struct PooledRoTxn<'p> {
+    txn: RoTxn<'p>,
+    pool: &'p RoTxnPool,
+}
+
+#[cfg(feature = "read-txn-no-tls")]
+impl<'p> Deref for PooledRoTxn<'p> {
+    type Target = RoTxn<'p>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.txn
+    }
+}
+
+#[cfg(feature = "read-txn-no-tls")]
+impl Drop for PooledRoTxn<'_> {
+    fn drop(&mut self) {
+        // The transaction ends as if it was aborted, its cursors can't be used anymore.
+        let txn = mem::replace(&mut self.txn.txn, ptr::null_mut());
+        let mut idle = self.pool.idle.lock().unwrap();
+        if idle.len() < self.pool.capacity {
+            unsafe { ffi::mdb_txn_reset(txn) };
+            idle.push(IdleTxn(txn));
+        } else {
+            let _ = abort_txn(txn);
+        }
+    }
+}
+
 #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
 fn abort_txn(txn: *mut ffi::MDB_txn) -> Result<()> {
     // Asserts that the transaction hasn't been already committed.
@@ -64,9 +286,37 @@ fn abort_txn(txn: *mut ffi::MDB_txn) -> Result<()> {
     assert!(!txn.is_null());
 
     let ret = unsafe { ffi::mdb_txn_abort(txn) };
-    mdb_result(ret).map_err(Into::into)
+    MdbOp::new("mdb_txn_abort").result(mdb_result(ret))
+}
+
+/// Whether a transaction reads or writes, see [`RoTxn::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TxnKind {
+    Read,
+    Write,
+}
+
+impl fmt::Display for TxnKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxnKind::Read => f.write_str("read"),
+            TxnKind::Write => f.write_str("write"),
+        }
+    }
+}
+
+/// Whether a commit waits for its changes to reach the disk, see [`RwTxn::commit_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Durability {
+    /// The commit doesn't wait for the disk, a system crash can lose it.
+    Async,
+    /// The commit returns once its changes reached the disk.
+    Sync,
 }
 
+/// A write transaction, it is aborted when dropped without being committed and its changes
+/// are discarded.
+#[must_use = "a write transaction is aborted as soon as it is dropped, its changes are discarded"]
 pub struct RwTxn<'e, 'p, T = ()> {
     pub(crate) txn: RoTxn<'e, T>,
     _parent: marker::PhantomData<&'p mut ()>,
@@ -74,12 +324,17 @@ pub struct RwTxn<'e, 'p, T = ()> {
 
 impl<'e, T> RwTxn<'e, 'e, T> {
     pub(crate) fn new(env: &'e Env) -> Result<RwTxn<'e, 'e, T>> {
-        let mut txn: *mut ffi::MDB_txn = ptr::null_mut();
-
-        unsafe { mdb_result(ffi::mdb_txn_begin(env.env_mut_ptr(), ptr::null_mut(), 0, &mut txn))? };
+        let txn = env.begin_txn(ptr::null_mut(), 0)?;
 
         Ok(RwTxn {
-            txn: RoTxn { txn, env, _phantom: marker::PhantomData },
+            txn: RoTxn {
+                txn,
+                env,
+                flags: 0,
+                generation: Arc::default(),
+                extensions: Extensions::new(),
+                _phantom: marker::PhantomData,
+            },
             _parent: marker::PhantomData,
         })
     }
@@ -88,26 +343,114 @@ impl<'e, T> RwTxn<'e, 'e, T> {
         env: &'e Env,
         parent: &'p mut RwTxn<T>,
     ) -> Result<RwTxn<'e, 'p, T>> {
-        let mut txn: *mut ffi::MDB_txn = ptr::null_mut();
-        let parent_ptr: *mut ffi::MDB_txn = parent.txn.txn;
-
-        unsafe { mdb_result(ffi::mdb_txn_begin(env.env_mut_ptr(), parent_ptr, 0, &mut txn))? };
+        let txn = env.begin_txn(parent.txn.txn, 0)?;
 
         Ok(RwTxn {
-            txn: RoTxn { txn, env, _phantom: marker::PhantomData },
+            txn: RoTxn {
+                txn,
+                env,
+                flags: 0,
+                generation: Arc::default(),
+                extensions: Extensions::new(),
+                _phantom: marker::PhantomData,
+            },
             _parent: marker::PhantomData,
         })
     }
 
     pub fn commit(self) -> Result<()> {
+        let _commit = self.txn.env.commit_lock();
         self.txn.commit()
     }
 
+    /// Commits this transaction, with a durability chosen for it.
+    ///
+    /// An [`Async`](Durability::Async) commit doesn't wait for the disk, the environment
+    /// skips the sync of this commit only. A [`Sync`](Durability::Sync) commit of an
+    /// environment opened without syncs, with [`Flags::MdbNoSync`](crate::flags::Flags::MdbNoSync)
+    /// with LMDB or the `MDBX_SAFE_NOSYNC` mode with MDBX, syncs it, and the commits before
+    /// it with it. The other commits of the environment follow its flags.
+    pub fn commit_with(self, durability: Durability) -> Result<()> {
+        let env = self.txn.env;
+        let commit = env.commit_lock();
+        match durability {
+            Durability::Async => self.txn.commit_without_sync(),
+            Durability::Sync => {
+                let flags = env.raw_flags()?;
+                self.txn.commit()?;
+                drop(commit);
+                if flags & ffi::NON_DURABLE_FLAGS != 0 {
+                    env.force_sync()?;
+                }
+                Ok(())
+            }
+        }
+    }
+
     pub fn abort(self) -> Result<()> {
         self.txn.abort()
     }
 }
 
+impl<'e, 'p, T> RwTxn<'e, 'p, T> {
+    /// The values attached to this transaction, they are shared with
+    /// the read transaction it dereferences to.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.txn.extensions
+    }
+
+    /// The space used by this transaction and its limits, a long write transaction can
+    /// check it to commit before failing with [`Error::TxnFull`](crate::Error::TxnFull).
+    #[cfg(feature = "mdbx")]
+    pub fn info(&self) -> Result<TxnInfo> {
+        let info = ffi::txn_info(self.txn.txn)?;
+        Ok(TxnInfo {
+            used: info.txn_space_used,
+            dirty: info.txn_space_dirty,
+            retired: info.txn_space_retired,
+            leftover: info.txn_space_leftover,
+            limit_soft: info.txn_space_limit_soft,
+            limit_hard: info.txn_space_limit_hard,
+        })
+    }
+}
+
+/// The space used by a write transaction and its limits, in bytes, see [`RwTxn::info`].
+#[cfg(feature = "mdbx")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxnInfo {
+    /// The size of the database as seen by the transaction.
+    pub used: u64,
+    /// The size of the pages modified by the transaction.
+    pub dirty: u64,
+    /// The size of the pages freed by the transaction.
+    pub retired: u64,
+    /// The space left before the transaction reaches its hard limit.
+    pub leftover: u64,
+    /// Beyond this size the dirty pages start being spilled to disk.
+    pub limit_soft: u64,
+    /// Beyond this size the transaction fails with `TxnFull`.
+    pub limit_hard: u64,
+}
+
+#[cfg(feature = "mdbx")]
+impl TxnInfo {
+    /// The part of the hard limit the dirty pages take, from 0 to 1.
+    pub fn dirty_ratio(&self) -> f64 {
+        if self.limit_hard == 0 {
+            0.0
+        } else {
+            self.dirty as f64 / self.limit_hard as f64
+        }
+    }
+
+    /// Whether the transaction should be committed, and the work continued in another one,
+    /// because its dirty pages take more than `ratio` of its hard limit.
+    pub fn should_commit(&self, ratio: f64) -> bool {
+        self.dirty_ratio() >= ratio
+    }
+}
+
 impl<'e, 'p, T> Deref for RwTxn<'e, 'p, T> {
     type Target = RoTxn<'e, T>;
 
@@ -115,3 +458,127 @@ impl<'e, 'p, T> Deref for RwTxn<'e, 'p, T> {
         &self.txn
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn commits_choose_their_durability() {
+        use super::*;
+        use crate::flags::Flags;
+        use crate::types::Str;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        unsafe { options.flag(Flags::MdbNoSync) };
+        let env = options.open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        wtxn.commit_with(Durability::Sync).unwrap();
+
+        let mut wtxn = env.write_txn().unwrap();
+        db.put(&mut wtxn, "low", "value").unwrap();
+        wtxn.commit_with(Durability::Async).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        db.put(&mut wtxn, "critical", "value").unwrap();
+        wtxn.commit_with(Durability::Sync).unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(db.len(&rtxn).unwrap(), 2);
+    }
+
+    #[test]
+    #[cfg(all(feature = "lmdb", not(feature = "mdbx")))]
+    fn async_commits_skip_their_own_sync() {
+        use super::*;
+        use crate::flags::Flags;
+        use crate::types::{OwnedType, Str};
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let flags = env.raw_flags().unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<OwnedType<u32>, Str>(&mut wtxn, None).unwrap();
+        wtxn.commit().unwrap();
+
+        // The other commits run meanwhile and keep syncing.
+        let writer = {
+            let env = env.clone();
+            std::thread::spawn(move || {
+                for i in 0..100 {
+                    let mut wtxn = env.write_txn().unwrap();
+                    db.put(&mut wtxn, &i, "synced").unwrap();
+                    wtxn.commit().unwrap();
+                }
+            })
+        };
+        for i in 100..200 {
+            let mut wtxn = env.write_txn().unwrap();
+            db.put(&mut wtxn, &i, "async").unwrap();
+            wtxn.commit_with(Durability::Async).unwrap();
+            assert_eq!(env.raw_flags().unwrap(), flags);
+        }
+        writer.join().unwrap();
+        assert_eq!(db.len(&env.read_txn().unwrap()).unwrap(), 200);
+
+        // An environment opened without syncs keeps them skipped.
+        let dir = tempfile::tempdir().unwrap();
+        let mut options = EnvOpenOptions::new();
+        unsafe { options.flag(Flags::MdbNoSync) };
+        let env = options.open(dir.path()).unwrap();
+        let wtxn = env.write_txn().unwrap();
+        wtxn.commit_with(Durability::Async).unwrap();
+        assert!(env.contains_flag(Flags::MdbNoSync).unwrap());
+        let wtxn = env.write_txn().unwrap();
+        wtxn.commit_with(Durability::Sync).unwrap();
+        assert!(env.contains_flag(Flags::MdbNoSync).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "read-txn-no-tls")]
+    fn pooled_txns_are_renewed_on_the_last_snapshot() {
+        use super::*;
+        use crate::types::Str;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let mut wtxn = env.write_txn().unwrap();
+        let db = env.create_database::<Str, Str>(&mut wtxn, None).unwrap();
+        wtxn.commit().unwrap();
+
+        let pool = RoTxnPool::new(env.clone(), 1);
+        let (first, second) = (pool.read_txn().unwrap(), pool.read_txn().unwrap());
+        assert_eq!(env.read_txns(), 2);
+        drop((first, second));
+        assert_eq!((pool.idle(), env.read_txns()), (1, 0));
+
+        let mut wtxn = env.write_txn().unwrap();
+        db.put(&mut wtxn, "hello", "world").unwrap();
+        wtxn.commit().unwrap();
+
+        let rtxn = pool.read_txn().unwrap();
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(db.get(&rtxn, "hello").unwrap().as_deref(), Some("world"));
+    }
+
+    #[test]
+    #[cfg(feature = "read-txn-no-tls")]
+    fn pooled_txns_of_a_closing_env_arent_renewed() {
+        use super::*;
+        use crate::EnvOpenOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let env = EnvOpenOptions::new().open(dir.path()).unwrap();
+        let pool = RoTxnPool::new(env.clone(), 1);
+        drop(pool.read_txn().unwrap());
+        assert_eq!(pool.idle(), 1);
+
+        let _closing = env.prepare_for_closing();
+        assert!(matches!(pool.read_txn(), Err(crate::Error::DatabaseClosing)));
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(pool.env().read_txns(), 0);
+    }
+}